@@ -103,6 +103,28 @@ fn u128_from_bits(c: &mut Criterion, n: usize) {
     });
 }
 
+fn bench_batch_inv(c: &mut Criterion) {
+    let p = 109;
+    let rng = &mut rand::thread_rng();
+    let xs = (0..1000).map(|_| rng.gen_range(1, p)).collect::<Vec<u16>>();
+    let xs_ = xs.clone();
+    c.bench_function("util::batch_inv (1000)", move |b| {
+        b.iter(|| {
+            let invs = util::batch_inv(&xs, p).unwrap();
+            criterion::black_box(invs);
+        });
+    });
+    c.bench_function("util::inv (1000)", move |b| {
+        b.iter(|| {
+            let invs = xs_
+                .iter()
+                .map(|&x| util::inv(x as i128, p as i128))
+                .collect::<Vec<_>>();
+            criterion::black_box(invs);
+        });
+    });
+}
+
 fn bench_base_q_add_eq(c: &mut Criterion) {
     base_q_add_eq(c, 2);
     base_q_add_eq(c, 3);
@@ -137,7 +159,7 @@ criterion_group! {
     config = Criterion::default().warm_up_time(Duration::from_millis(100));
     targets = bench_tweak, bench_tweak2, bench_output_tweak, bench_base_q_add_eq,
               bench_as_base_q_u128, bench_from_base_q, bench_u128_to_bits,
-              bench_u128_from_bits,
+              bench_u128_from_bits, bench_batch_inv,
 }
 
 criterion_main!(util);
@@ -0,0 +1,113 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+// Oblivious AES-128 encryption over TCP: the garbler holds a block of
+// plaintext and the evaluator a key, and the evaluator learns the ciphertext
+// while the garbler learns nothing about the key. The circuit takes the
+// plaintext as the garbler's inputs and the key as the evaluator's, and
+// outputs the ciphertext, each as 128 bits, most significant bit of each byte
+// first. Plaintexts and keys are given in hex. Run the garbler and the
+// evaluator in separate shells, from this directory:
+//
+//     cargo run --example aes -- --role garbler --input 00112233445566778899aabbccddeeff
+//     cargo run --example aes -- --role evaluator --input 000102030405060708090a0b0c0d0e0f
+
+mod common;
+
+use common::Role;
+use fancy_garbling::{
+    circuit::Circuit,
+    twopac::semihonest::{Evaluator, Garbler},
+    FancyInput,
+};
+use ocelot::ot::{AlszReceiver as OtReceiver, AlszSender as OtSender};
+use scuttlebutt::{AbstractChannel, AesRng};
+
+const USAGE: &str =
+    "usage: aes --role <garbler|evaluator> --input <16 bytes in hex> [--address <host:port>]";
+
+fn parse_hex(s: &str) -> Vec<u8> {
+    if s.len() != 32 {
+        common::usage(USAGE);
+    }
+    (0..16)
+        .map(|i| {
+            u8::from_str_radix(&s[2 * i..2 * i + 2], 16).unwrap_or_else(|_| common::usage(USAGE))
+        })
+        .collect()
+}
+
+/// The bits of `bytes`, most significant bit of each byte first.
+fn to_bits(bytes: &[u8]) -> Vec<u16> {
+    bytes
+        .iter()
+        .flat_map(|b| (0..8).rev().map(move |i| u16::from((b >> i) & 1)))
+        .collect()
+}
+
+/// The bytes of `bits`, most significant bit of each byte first.
+fn from_bits(bits: &[u16]) -> Vec<u8> {
+    bits.chunks(8)
+        .map(|bs| bs.iter().fold(0, |b, &x| (b << 1) | x as u8))
+        .collect()
+}
+
+/// Garble the encryption of `plaintext` under the evaluator's key.
+fn garble<C: AbstractChannel>(circ: &Circuit, channel: C, plaintext: &[u8]) {
+    let mut gb = Garbler::<C, AesRng, OtSender>::new(channel, AesRng::new()).unwrap();
+    let xs = gb.encode_many(&to_bits(plaintext), &[2; 128]).unwrap();
+    let ys = gb.receive_many(&[2; 128]).unwrap();
+    circ.eval(&mut gb, &xs, &ys).unwrap();
+}
+
+/// Evaluate the encryption of the garbler's plaintext under `key`, returning
+/// the ciphertext.
+fn evaluate<C: AbstractChannel>(circ: &Circuit, channel: C, key: &[u8]) -> Vec<u8> {
+    let mut ev = Evaluator::<C, AesRng, OtReceiver>::new(channel, AesRng::new()).unwrap();
+    let xs = ev.receive_many(&[2; 128]).unwrap();
+    let ys = ev.encode_many(&to_bits(key), &[2; 128]).unwrap();
+    let outputs = circ.eval(&mut ev, &xs, &ys).unwrap().unwrap();
+    from_bits(&outputs)
+}
+
+fn main() {
+    let (role, address, input) = common::parse_args(USAGE);
+    let input = parse_hex(&input);
+    let circ = Circuit::parse("circuits/AES-non-expanded.txt").unwrap();
+    let channel = common::connect(role, &address);
+
+    if role == Role::Garbler {
+        garble(&circ, channel, &input);
+        println!("Garbled the encryption");
+    } else {
+        let ciphertext = evaluate(&circ, channel, &input);
+        let hex = ciphertext
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+        println!("Ciphertext: {}", hex);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use scuttlebutt::unix_channel_pair;
+
+    #[test]
+    fn fips_197_vector() {
+        // The example vector of FIPS-197, Appendix C.1.
+        let plaintext = parse_hex("00112233445566778899aabbccddeeff");
+        let key = parse_hex("000102030405060708090a0b0c0d0e0f");
+        let circ = Circuit::parse("circuits/AES-non-expanded.txt").unwrap();
+        let circ_ = circ.clone();
+        let (sender, receiver) = unix_channel_pair();
+        let handle = std::thread::spawn(move || garble(&circ_, sender, &plaintext));
+        let ciphertext = evaluate(&circ, receiver, &key);
+        handle.join().unwrap();
+        assert_eq!(ciphertext, parse_hex("69c4e0d86a7b0430d8cdb78070b4c55a"));
+    }
+}
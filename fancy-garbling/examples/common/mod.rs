@@ -0,0 +1,72 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+// Command-line parsing and TCP setup shared by the two-party examples.
+
+use scuttlebutt::Channel;
+use std::{
+    io::{BufReader, BufWriter},
+    net::{TcpListener, TcpStream},
+};
+
+/// A channel over a TCP stream, buffered in both directions.
+pub type TcpChannel = Channel<BufReader<TcpStream>, BufWriter<TcpStream>>;
+
+/// The party an example runs as.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    Garbler,
+    Evaluator,
+}
+
+/// Print `text` and exit.
+pub fn usage(text: &str) -> ! {
+    eprintln!("{}", text);
+    std::process::exit(1)
+}
+
+/// Parse `--role`, `--address`, and `--input` from the command line, printing
+/// the usage `text` and exiting if they are malformed.
+pub fn parse_args(text: &str) -> (Role, String, String) {
+    let mut role = None;
+    let mut address = "127.0.0.1:7000".to_string();
+    let mut input = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let value = args.next().unwrap_or_else(|| usage(text));
+        match (flag.as_str(), value.as_str()) {
+            ("--role", "garbler") => role = Some(Role::Garbler),
+            ("--role", "evaluator") => role = Some(Role::Evaluator),
+            ("--address", _) => address = value,
+            ("--input", _) => input = Some(value),
+            _ => usage(text),
+        }
+    }
+    match (role, input) {
+        (Some(role), Some(input)) => (role, address, input),
+        _ => usage(text),
+    }
+}
+
+/// The garbler listens on `address` and the evaluator connects to it.
+pub fn connect(role: Role, address: &str) -> TcpChannel {
+    let stream = match role {
+        Role::Garbler => {
+            println!("Waiting for the evaluator on {}", address);
+            TcpListener::bind(address).unwrap().accept().unwrap().0
+        }
+        Role::Evaluator => loop {
+            match TcpStream::connect(address) {
+                Ok(stream) => break stream,
+                Err(_) => std::thread::sleep(std::time::Duration::from_millis(100)),
+            }
+        },
+    };
+    Channel::new(
+        BufReader::new(stream.try_clone().unwrap()),
+        BufWriter::new(stream),
+    )
+}
@@ -0,0 +1,81 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+// Private dot product over TCP: each party holds a vector of integers, and
+// both learn their dot product and nothing else. The vectors are encoded as
+// CRT bundles, on which multiplication by another bundle is cheap. Run the
+// garbler and the evaluator in separate shells:
+//
+//     cargo run --example dot_product -- --role garbler --input 1,2,3,4
+//     cargo run --example dot_product -- --role evaluator --input 5,6,7,8
+
+mod common;
+
+use common::{Role, TcpChannel};
+use fancy_garbling::{
+    twopac::semihonest::{Evaluator, Garbler},
+    util,
+    CrtBundle,
+    CrtGadgets,
+    Fancy,
+    FancyInput,
+    FancyReveal,
+};
+use ocelot::ot::{AlszReceiver as OtReceiver, AlszSender as OtSender};
+use scuttlebutt::{AbstractChannel, AesRng};
+
+const USAGE: &str =
+    "usage: dot_product --role <garbler|evaluator> --input <u32,u32,...> [--address <host:port>]";
+
+/// Reveal to both parties the dot product of `xs` and `ys`.
+fn dot_product<F: Fancy + FancyReveal>(
+    f: &mut F,
+    xs: &[CrtBundle<F::Item>],
+    ys: &[CrtBundle<F::Item>],
+) -> Result<u128, F::Error> {
+    let mut z = f.crt_mul(&xs[0], &ys[0])?;
+    for (x, y) in xs.iter().zip(ys.iter()).skip(1) {
+        let xy = f.crt_mul(x, y)?;
+        z = f.crt_add(&z, &xy)?;
+    }
+    f.crt_reveal(&z)
+}
+
+fn main() {
+    let (role, address, input) = common::parse_args(USAGE);
+    let values = input
+        .split(',')
+        .map(|x| x.trim().parse::<u32>().map(u128::from))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap_or_else(|_| common::usage(USAGE));
+    let mut channel = common::connect(role, &address);
+    let rng = AesRng::new();
+
+    // The vector length is public, so the parties check that it agrees.
+    channel.write_usize(values.len()).unwrap();
+    channel.flush().unwrap();
+    let n = channel.read_usize().unwrap();
+    if n != values.len() || n == 0 {
+        eprintln!("the vectors must have the same, nonzero, length");
+        std::process::exit(1);
+    }
+
+    // Enough room for a sum of `n` products of `u32`s.
+    let nbits = 64 + 64 - (n as u64).leading_zeros();
+    let q = util::modulus_with_width(nbits);
+    let result = if role == Role::Garbler {
+        let mut gb = Garbler::<TcpChannel, AesRng, OtSender>::new(channel, rng).unwrap();
+        let xs = gb.crt_encode_many(&values, q).unwrap();
+        let ys = gb.crt_receive_many(n, q).unwrap();
+        dot_product(&mut gb, &xs, &ys).unwrap()
+    } else {
+        let mut ev = Evaluator::<TcpChannel, AesRng, OtReceiver>::new(channel, rng).unwrap();
+        let xs = ev.crt_receive_many(n, q).unwrap();
+        let ys = ev.crt_encode_many(&values, q).unwrap();
+        dot_product(&mut ev, &xs, &ys).unwrap()
+    };
+    println!("Dot product: {}", result);
+}
@@ -0,0 +1,69 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+// Yao's millionaires' problem over TCP: two parties learn which of them is
+// richer without revealing their wealth. Run the garbler and the evaluator in
+// separate shells:
+//
+//     cargo run --example millionaires -- --role garbler --input 1000000
+//     cargo run --example millionaires -- --role evaluator --input 2500000
+
+mod common;
+
+use common::{Role, TcpChannel};
+use fancy_garbling::{
+    twopac::semihonest::{Evaluator, Garbler},
+    BinaryBundle,
+    BinaryGadgets,
+    Fancy,
+    FancyInput,
+    FancyReveal,
+};
+use ocelot::ot::{AlszReceiver as OtReceiver, AlszSender as OtSender};
+use scuttlebutt::AesRng;
+
+const USAGE: &str =
+    "usage: millionaires --role <garbler|evaluator> --input <wealth> [--address <host:port>]";
+
+/// Reveal to both parties whether the garbler's wealth is greater than the
+/// evaluator's.
+fn garbler_is_richer<F: Fancy + FancyReveal>(
+    f: &mut F,
+    garbler_wealth: &BinaryBundle<F::Item>,
+    evaluator_wealth: &BinaryBundle<F::Item>,
+) -> Result<bool, F::Error> {
+    let z = f.bin_lt(evaluator_wealth, garbler_wealth)?;
+    Ok(f.reveal(&z)? == 1)
+}
+
+fn main() {
+    let (role, address, input) = common::parse_args(USAGE);
+    let wealth = input
+        .parse::<u64>()
+        .unwrap_or_else(|_| common::usage(USAGE));
+    let channel = common::connect(role, &address);
+    let rng = AesRng::new();
+
+    // Wealth is unsigned, so use one bit more than a `u64` to compare it as a
+    // non-negative two's complement number.
+    let nbits = 65;
+    let richer = if role == Role::Garbler {
+        let mut gb = Garbler::<TcpChannel, AesRng, OtSender>::new(channel, rng).unwrap();
+        let x = gb.bin_encode(u128::from(wealth), nbits).unwrap();
+        let y = gb.bin_receive(nbits).unwrap();
+        garbler_is_richer(&mut gb, &x, &y).unwrap()
+    } else {
+        let mut ev = Evaluator::<TcpChannel, AesRng, OtReceiver>::new(channel, rng).unwrap();
+        let x = ev.bin_receive(nbits).unwrap();
+        let y = ev.bin_encode(u128::from(wealth), nbits).unwrap();
+        garbler_is_richer(&mut ev, &x, &y).unwrap()
+    };
+    if richer {
+        println!("The garbler is richer");
+    } else {
+        println!("The garbler is not richer");
+    }
+}
@@ -0,0 +1,62 @@
+/*
+ * This file is part of `fancy-garbling`.
+ * Copyright © 2019 Galois, Inc.
+ * See LICENSE for licensing information.
+ *
+ * C interface to `fancy-garbling`. Build the library with the `capi` feature,
+ * e.g. `cargo rustc --release --features capi --crate-type cdylib`.
+ *
+ * 128-bit integers are passed as (lo, hi) pairs of 64-bit halves. Functions
+ * returning int32_t return 0 on success and -1 on failure, including invalid
+ * arguments and internal errors; functions returning a pointer return NULL.
+ * Wire-labels and garbled circuits are exchanged as arrays of 16-byte blocks.
+ */
+
+#ifndef FANCY_GARBLING_H
+#define FANCY_GARBLING_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct Circuit Circuit;
+typedef struct Encoder Encoder;
+typedef struct GarbledCircuit GarbledCircuit;
+
+/* numbers */
+int32_t fg_as_base_q(uint64_t x_lo, uint64_t x_hi, uint16_t q, uint16_t *out, size_t n);
+int32_t fg_from_base_q(const uint16_t *ds, size_t n, uint16_t q, uint64_t *out_lo, uint64_t *out_hi);
+int32_t fg_crt(uint64_t x_lo, uint64_t x_hi, const uint16_t *ps, size_t n, uint16_t *out);
+int32_t fg_crt_inv(const uint16_t *xs, const uint16_t *ps, size_t n, uint64_t *out_lo, uint64_t *out_hi);
+
+/* circuits */
+Circuit *fg_circuit_parse(const char *path);
+void fg_circuit_free(Circuit *circ);
+size_t fg_circuit_num_garbler_inputs(const Circuit *circ);
+size_t fg_circuit_num_evaluator_inputs(const Circuit *circ);
+size_t fg_circuit_num_outputs(const Circuit *circ);
+int32_t fg_circuit_eval_plain(const Circuit *circ, const uint16_t *gb_inputs,
+                              const uint16_t *ev_inputs, uint16_t *outputs);
+
+/* garbling and evaluation */
+int32_t fg_garble(const Circuit *circ, Encoder **encoder, GarbledCircuit **gc);
+void fg_encoder_free(Encoder *encoder);
+void fg_garbled_circuit_free(GarbledCircuit *gc);
+size_t fg_garbled_circuit_size(const GarbledCircuit *gc);
+void fg_garbled_circuit_write(const GarbledCircuit *gc, uint8_t *out);
+GarbledCircuit *fg_garbled_circuit_read(const uint8_t *bytes, size_t nblocks);
+int32_t fg_encode_garbler_inputs(const Encoder *encoder, const uint16_t *inputs, size_t n,
+                                 uint8_t *out);
+int32_t fg_encode_evaluator_inputs(const Encoder *encoder, const uint16_t *inputs, size_t n,
+                                   uint8_t *out);
+int32_t fg_evaluate(const Circuit *circ, const GarbledCircuit *gc, const uint8_t *gb_inputs,
+                    const uint8_t *ev_inputs, uint16_t *outputs);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* FANCY_GARBLING_H */
@@ -0,0 +1,227 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+// A command-line tool for garbling, evaluating, inspecting, and converting
+// circuits, which may be Bristol files or circuits written by `convert`:
+//
+//     swanky garble circuit.txt --garbler 1,0 --evaluator 0,1 --gc gc.bin --wires wires.bin
+//     swanky evaluate circuit.txt --gc gc.bin --wires wires.bin
+//     swanky stats circuit.txt --json
+//     swanky convert circuit.txt circuit.bin --to internal
+
+use fancy_garbling::{
+    circuit::Circuit,
+    classic::{garble, GarbledCircuit},
+    format::{read_wires, write_wires, CIRCUIT_MAGIC},
+};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+};
+
+const USAGE: &str = "usage:
+    swanky garble <circuit> --gc <file> --wires <file> [--garbler <x,..>] [--evaluator <x,..>]
+    swanky evaluate <circuit> --gc <file> --wires <file>
+    swanky stats <circuit> [--json]
+    swanky convert <input> <output> --to <bristol|internal>";
+
+/// The positional arguments and flags of a subcommand.
+struct Args {
+    positional: Vec<String>,
+    flags: HashMap<String, String>,
+}
+
+impl Args {
+    /// Split `args` into positional arguments and flags, where `switches` are
+    /// the flags that take no value.
+    fn parse(mut args: impl Iterator<Item = String>, switches: &[&str]) -> Result<Self, String> {
+        let mut positional = Vec::new();
+        let mut flags = HashMap::new();
+        while let Some(arg) = args.next() {
+            if !arg.starts_with("--") {
+                positional.push(arg);
+            } else if switches.contains(&arg.as_str()) {
+                flags.insert(arg, String::new());
+            } else {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("missing value for {}", arg))?;
+                flags.insert(arg, value);
+            }
+        }
+        Ok(Args { positional, flags })
+    }
+
+    fn flag(&self, name: &str) -> Result<&str, String> {
+        self.flags
+            .get(name)
+            .map(String::as_str)
+            .ok_or_else(|| format!("missing {}", name))
+    }
+
+    fn positional(&self, n: usize) -> Result<&[String], String> {
+        if self.positional.len() != n {
+            return Err(format!(
+                "expected {} arguments, got {}",
+                n,
+                self.positional.len()
+            ));
+        }
+        Ok(&self.positional)
+    }
+}
+
+fn open(path: &str) -> Result<BufReader<File>, String> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|e| format!("{}: {}", path, e))
+}
+
+fn create(path: &str) -> Result<BufWriter<File>, String> {
+    File::create(path)
+        .map(BufWriter::new)
+        .map_err(|e| format!("{}: {}", path, e))
+}
+
+/// Load a circuit written by `convert --to internal`, or else a Bristol
+/// circuit.
+fn load_circuit(path: &str) -> Result<Circuit, String> {
+    let mut bytes = Vec::new();
+    open(path)?
+        .read_to_end(&mut bytes)
+        .map_err(|e| format!("{}: {}", path, e))?;
+    if bytes.starts_with(&CIRCUIT_MAGIC) {
+        Circuit::read_from(&mut bytes.as_slice()).map_err(|e| format!("{}: {}", path, e))
+    } else {
+        Circuit::parse_reader(bytes.as_slice()).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+/// Parse comma-separated inputs, checking them against the input moduli.
+fn parse_inputs(s: Option<&String>, moduli: &[u16], who: &str) -> Result<Vec<u16>, String> {
+    let xs = match s.map(String::as_str) {
+        None | Some("") => Vec::new(),
+        Some(s) => s
+            .split(',')
+            .map(|x| x.trim().parse::<u16>())
+            .collect::<Result<Vec<u16>, _>>()
+            .map_err(|e| format!("{} inputs: {}", who, e))?,
+    };
+    if xs.len() != moduli.len() {
+        return Err(format!(
+            "expected {} {} inputs, got {}",
+            moduli.len(),
+            who,
+            xs.len()
+        ));
+    }
+    if let Some(i) = (0..xs.len()).find(|&i| xs[i] >= moduli[i]) {
+        return Err(format!(
+            "{} input {} is {}, not below its modulus {}",
+            who, i, xs[i], moduli[i]
+        ));
+    }
+    Ok(xs)
+}
+
+fn garble_cmd(args: Args) -> Result<(), String> {
+    let c = load_circuit(&args.positional(1)?[0])?;
+    let gb_moduli = (0..c.num_garbler_inputs())
+        .map(|i| c.garbler_input_mod(i))
+        .collect::<Vec<u16>>();
+    let ev_moduli = (0..c.num_evaluator_inputs())
+        .map(|i| c.evaluator_input_mod(i))
+        .collect::<Vec<u16>>();
+    let gb = parse_inputs(args.flags.get("--garbler"), &gb_moduli, "garbler")?;
+    let ev = parse_inputs(args.flags.get("--evaluator"), &ev_moduli, "evaluator")?;
+
+    let (en, gc) = garble(&c).map_err(|e| e.to_string())?;
+    let path = args.flag("--gc")?;
+    let mut w = create(path)?;
+    gc.write_to(&mut w)
+        .map_err(|e| format!("{}: {}", path, e))?;
+    w.flush().map_err(|e| format!("{}: {}", path, e))?;
+
+    let mut wires = en.encode_garbler_inputs(&gb);
+    wires.extend(en.encode_evaluator_inputs(&ev));
+    let path = args.flag("--wires")?;
+    let mut w = create(path)?;
+    write_wires(&wires, &mut w).map_err(|e| format!("{}: {}", path, e))?;
+    w.flush().map_err(|e| format!("{}: {}", path, e))
+}
+
+fn evaluate_cmd(args: Args) -> Result<(), String> {
+    let c = load_circuit(&args.positional(1)?[0])?;
+    let path = args.flag("--wires")?;
+    let mut wires = read_wires(&mut open(path)?).map_err(|e| format!("{}: {}", path, e))?;
+    let n = c.num_garbler_inputs();
+    if wires.len() != n + c.num_evaluator_inputs() {
+        return Err(format!(
+            "{}: expected {} wires, got {}",
+            path,
+            n + c.num_evaluator_inputs(),
+            wires.len()
+        ));
+    }
+    let ev = wires.split_off(n);
+    // Stream the garbled circuit rather than loading it, as it may be large.
+    let path = args.flag("--gc")?;
+    let outputs = GarbledCircuit::eval_from(&mut open(path)?, &c, &wires, &ev)
+        .map_err(|e| format!("{}: {}", path, e))?;
+    let outputs = outputs.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+    println!("{}", outputs.join(","));
+    Ok(())
+}
+
+fn stats_cmd(args: Args) -> Result<(), String> {
+    let c = load_circuit(&args.positional(1)?[0])?;
+    let stats = c.stats().map_err(|e| e.to_string())?;
+    if args.flags.contains_key("--json") {
+        println!("{}", stats.to_json());
+    } else {
+        print!("{}", stats);
+    }
+    Ok(())
+}
+
+fn convert_cmd(args: Args) -> Result<(), String> {
+    let paths = args.positional(2)?;
+    let c = load_circuit(&paths[0])?;
+    let path = &paths[1];
+    let mut w = create(path)?;
+    match args.flag("--to")? {
+        "bristol" => c
+            .write_bristol(&mut w)
+            .map_err(|e| format!("{}: {}", path, e))?,
+        "internal" => c.write_to(&mut w).map_err(|e| format!("{}: {}", path, e))?,
+        to => return Err(format!("unknown format {}", to)),
+    }
+    w.flush().map_err(|e| format!("{}: {}", path, e))
+}
+
+fn run() -> Result<(), String> {
+    let mut args = std::env::args().skip(1);
+    let cmd = args.next().ok_or_else(|| USAGE.to_string())?;
+    match cmd.as_str() {
+        "garble" => garble_cmd(Args::parse(args, &[])?),
+        "evaluate" => evaluate_cmd(Args::parse(args, &[])?),
+        "stats" => stats_cmd(Args::parse(args, &["--json"])?),
+        "convert" => convert_cmd(Args::parse(args, &[])?),
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+fn main() {
+    if let Err(e) = run() {
+        eprintln!("swanky: {}", e);
+        std::process::exit(1);
+    }
+}
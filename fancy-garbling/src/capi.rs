@@ -0,0 +1,457 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! C interface to `fancy-garbling`, enabled by the `capi` feature.
+//!
+//! The corresponding C header is `include/fancy_garbling.h`. Since C has no
+//! 128-bit integer type, `u128` values are passed as a pair of `u64`s holding
+//! the low and high halves. Functions returning `i32` return `0` on success and
+//! `-1` on failure, and functions returning a handle return null on failure.
+//! Panics are caught at the boundary and reported as failures. Wire-labels and
+//! garbled circuits cross the boundary as arrays of 16-byte blocks, so that
+//! they can be moved by the caller's own networking stack.
+//!
+//! All pointer arguments must be non-null and valid for the lengths given
+//! alongside them; handles must have been produced by this module and must not
+//! be used after being freed.
+
+#![allow(clippy::missing_safety_doc)]
+
+use crate::{
+    circuit::Circuit,
+    classic::{self, Encoder, GarbledCircuit},
+    util,
+    Wire,
+};
+use scuttlebutt::Block;
+use std::{
+    ffi::CStr,
+    os::raw::c_char,
+    panic::{catch_unwind, AssertUnwindSafe},
+    slice,
+};
+
+fn split(x: u128, lo: *mut u64, hi: *mut u64) {
+    unsafe {
+        *lo = x as u64;
+        *hi = (x >> 64) as u64;
+    }
+}
+
+fn join(lo: u64, hi: u64) -> u128 {
+    ((hi as u128) << 64) | lo as u128
+}
+
+/// Run `f`, returning `-1` if it panics, since unwinding across the C
+/// boundary is undefined behavior.
+fn guard<F: FnOnce() -> i32>(f: F) -> i32 {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or(-1)
+}
+
+/// Run `f`, returning null if it panics.
+fn guard_ptr<T, F: FnOnce() -> *mut T>(f: F) -> *mut T {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or(std::ptr::null_mut())
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// numbers
+
+/// Write the `n` base-`q` digits of `x` to `out`. Fails if `x` does not fit in
+/// `n` digits.
+#[no_mangle]
+pub unsafe extern "C" fn fg_as_base_q(
+    x_lo: u64,
+    x_hi: u64,
+    q: u16,
+    out: *mut u16,
+    n: usize,
+) -> i32 {
+    guard(|| {
+        if q < 2 {
+            return -1;
+        }
+        let x = join(x_lo, x_hi);
+        let ms = vec![q; n];
+        let ds = util::as_mixed_radix(x, &ms);
+        if util::from_mixed_radix(&ds, &ms) != x {
+            return -1;
+        }
+        slice::from_raw_parts_mut(out, n).copy_from_slice(&ds);
+        0
+    })
+}
+
+/// Convert the `n` little-endian base-`q` digits in `ds` into an integer.
+#[no_mangle]
+pub unsafe extern "C" fn fg_from_base_q(
+    ds: *const u16,
+    n: usize,
+    q: u16,
+    out_lo: *mut u64,
+    out_hi: *mut u64,
+) -> i32 {
+    guard(|| {
+        let ds = slice::from_raw_parts(ds, n);
+        let mut x = 0u128;
+        for &d in ds.iter().rev() {
+            if d >= q {
+                return -1;
+            }
+            x = match x.checked_mul(q as u128) {
+                Some(x) => x + d as u128,
+                None => return -1,
+            };
+        }
+        split(x, out_lo, out_hi);
+        0
+    })
+}
+
+/// Write the CRT representation of `x` with respect to the `n` primes `ps` to
+/// `out`.
+#[no_mangle]
+pub unsafe extern "C" fn fg_crt(
+    x_lo: u64,
+    x_hi: u64,
+    ps: *const u16,
+    n: usize,
+    out: *mut u16,
+) -> i32 {
+    guard(|| {
+        let ps = slice::from_raw_parts(ps, n);
+        if ps.contains(&0) {
+            return -1;
+        }
+        let xs = util::crt(join(x_lo, x_hi), ps);
+        slice::from_raw_parts_mut(out, n).copy_from_slice(&xs);
+        0
+    })
+}
+
+/// Reconstruct an integer from the `n` residues `xs` with respect to the
+/// primes `ps`.
+#[no_mangle]
+pub unsafe extern "C" fn fg_crt_inv(
+    xs: *const u16,
+    ps: *const u16,
+    n: usize,
+    out_lo: *mut u64,
+    out_hi: *mut u64,
+) -> i32 {
+    guard(|| {
+        let xs = slice::from_raw_parts(xs, n);
+        let ps = slice::from_raw_parts(ps, n);
+        if ps.iter().any(|&p| p < 2) {
+            return -1;
+        }
+        match util::try_crt_inv(xs, ps) {
+            Ok(x) => {
+                split(x, out_lo, out_hi);
+                0
+            }
+            Err(_) => -1,
+        }
+    })
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// circuits
+
+/// Parse the circuit file at `path`, returning null on failure.
+#[no_mangle]
+pub unsafe extern "C" fn fg_circuit_parse(path: *const c_char) -> *mut Circuit {
+    guard_ptr(|| {
+        let path = match CStr::from_ptr(path).to_str() {
+            Ok(path) => path,
+            Err(_) => return std::ptr::null_mut(),
+        };
+        match Circuit::parse(path) {
+            Ok(c) => Box::into_raw(Box::new(c)),
+            Err(_) => std::ptr::null_mut(),
+        }
+    })
+}
+
+/// Free a circuit.
+#[no_mangle]
+pub unsafe extern "C" fn fg_circuit_free(circ: *mut Circuit) {
+    if !circ.is_null() {
+        drop(Box::from_raw(circ));
+    }
+}
+
+/// The number of garbler inputs of a circuit.
+#[no_mangle]
+pub unsafe extern "C" fn fg_circuit_num_garbler_inputs(circ: *const Circuit) -> usize {
+    (*circ).num_garbler_inputs()
+}
+
+/// The number of evaluator inputs of a circuit.
+#[no_mangle]
+pub unsafe extern "C" fn fg_circuit_num_evaluator_inputs(circ: *const Circuit) -> usize {
+    (*circ).num_evaluator_inputs()
+}
+
+/// The number of outputs of a circuit.
+#[no_mangle]
+pub unsafe extern "C" fn fg_circuit_num_outputs(circ: *const Circuit) -> usize {
+    (*circ).noutputs()
+}
+
+/// Evaluate a circuit in plaintext, writing its outputs to `outputs`.
+#[no_mangle]
+pub unsafe extern "C" fn fg_circuit_eval_plain(
+    circ: *const Circuit,
+    gb_inputs: *const u16,
+    ev_inputs: *const u16,
+    outputs: *mut u16,
+) -> i32 {
+    guard(|| {
+        let circ = &*circ;
+        let gb = slice::from_raw_parts(gb_inputs, circ.num_garbler_inputs());
+        let ev = slice::from_raw_parts(ev_inputs, circ.num_evaluator_inputs());
+        match circ.eval_plain(gb, ev) {
+            Ok(out) => {
+                slice::from_raw_parts_mut(outputs, circ.noutputs()).copy_from_slice(&out);
+                0
+            }
+            Err(_) => -1,
+        }
+    })
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// garbling and evaluation
+
+/// Garble a circuit, storing handles to the encoder and the garbled circuit in
+/// `encoder` and `gc`.
+#[no_mangle]
+pub unsafe extern "C" fn fg_garble(
+    circ: *const Circuit,
+    encoder: *mut *mut Encoder,
+    gc: *mut *mut GarbledCircuit,
+) -> i32 {
+    guard(|| match classic::garble(&*circ) {
+        Ok((en, ev)) => {
+            *encoder = Box::into_raw(Box::new(en));
+            *gc = Box::into_raw(Box::new(ev));
+            0
+        }
+        Err(_) => -1,
+    })
+}
+
+/// Free an encoder.
+#[no_mangle]
+pub unsafe extern "C" fn fg_encoder_free(encoder: *mut Encoder) {
+    if !encoder.is_null() {
+        drop(Box::from_raw(encoder));
+    }
+}
+
+/// Free a garbled circuit.
+#[no_mangle]
+pub unsafe extern "C" fn fg_garbled_circuit_free(gc: *mut GarbledCircuit) {
+    if !gc.is_null() {
+        drop(Box::from_raw(gc));
+    }
+}
+
+/// The number of 16-byte blocks in a garbled circuit.
+#[no_mangle]
+pub unsafe extern "C" fn fg_garbled_circuit_size(gc: *const GarbledCircuit) -> usize {
+    (*gc).size()
+}
+
+/// Write the blocks of a garbled circuit to `out`, which must hold
+/// `16 * fg_garbled_circuit_size(gc)` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn fg_garbled_circuit_write(gc: *const GarbledCircuit, out: *mut u8) {
+    let gc = &*gc;
+    let out = slice::from_raw_parts_mut(out, 16 * gc.size());
+    for (chunk, block) in out.chunks_mut(16).zip(gc.blocks().iter()) {
+        chunk.copy_from_slice(block.as_ref());
+    }
+}
+
+/// Read a garbled circuit from the `16 * nblocks` bytes in `bytes`.
+#[no_mangle]
+pub unsafe extern "C" fn fg_garbled_circuit_read(
+    bytes: *const u8,
+    nblocks: usize,
+) -> *mut GarbledCircuit {
+    guard_ptr(|| {
+        let bytes = slice::from_raw_parts(bytes, 16 * nblocks);
+        let blocks = bytes.chunks(16).map(block_from_bytes).collect();
+        Box::into_raw(Box::new(GarbledCircuit::new(blocks)))
+    })
+}
+
+fn block_from_bytes(bytes: &[u8]) -> Block {
+    let mut b = [0u8; 16];
+    b.copy_from_slice(bytes);
+    Block::from(b)
+}
+
+fn write_wires(wires: &[Wire], out: &mut [u8]) {
+    for (chunk, w) in out.chunks_mut(16).zip(wires.iter()) {
+        chunk.copy_from_slice(w.as_block().as_ref());
+    }
+}
+
+/// Encode the garbler's inputs, writing one 16-byte wire-label per input to
+/// `out`.
+#[no_mangle]
+pub unsafe extern "C" fn fg_encode_garbler_inputs(
+    encoder: *const Encoder,
+    inputs: *const u16,
+    n: usize,
+    out: *mut u8,
+) -> i32 {
+    guard(|| {
+        let encoder = &*encoder;
+        if n != encoder.num_garbler_inputs() {
+            return -1;
+        }
+        let wires = encoder.encode_garbler_inputs(slice::from_raw_parts(inputs, n));
+        write_wires(&wires, slice::from_raw_parts_mut(out, 16 * n));
+        0
+    })
+}
+
+/// Encode the evaluator's inputs, writing one 16-byte wire-label per input to
+/// `out`.
+#[no_mangle]
+pub unsafe extern "C" fn fg_encode_evaluator_inputs(
+    encoder: *const Encoder,
+    inputs: *const u16,
+    n: usize,
+    out: *mut u8,
+) -> i32 {
+    guard(|| {
+        let encoder = &*encoder;
+        if n != encoder.num_evaluator_inputs() {
+            return -1;
+        }
+        let wires = encoder.encode_evaluator_inputs(slice::from_raw_parts(inputs, n));
+        write_wires(&wires, slice::from_raw_parts_mut(out, 16 * n));
+        0
+    })
+}
+
+/// Evaluate a garbled circuit on encoded garbler and evaluator inputs, given
+/// as 16-byte wire-labels, writing the decoded outputs to `outputs`.
+#[no_mangle]
+pub unsafe extern "C" fn fg_evaluate(
+    circ: *const Circuit,
+    gc: *const GarbledCircuit,
+    gb_inputs: *const u8,
+    ev_inputs: *const u8,
+    outputs: *mut u16,
+) -> i32 {
+    guard(|| {
+        let circ = &*circ;
+        let ngb = circ.num_garbler_inputs();
+        let nev = circ.num_evaluator_inputs();
+        // The labels come from the caller, so they are checked as they are
+        // unpacked.
+        let gb = slice::from_raw_parts(gb_inputs, 16 * ngb)
+            .chunks(16)
+            .enumerate()
+            .map(|(i, b)| Wire::try_from_block(block_from_bytes(b), circ.garbler_input_mod(i)))
+            .collect::<Result<Vec<_>, _>>();
+        let ev = slice::from_raw_parts(ev_inputs, 16 * nev)
+            .chunks(16)
+            .enumerate()
+            .map(|(i, b)| Wire::try_from_block(block_from_bytes(b), circ.evaluator_input_mod(i)))
+            .collect::<Result<Vec<_>, _>>();
+        let (gb, ev) = match (gb, ev) {
+            (Ok(gb), Ok(ev)) => (gb, ev),
+            _ => return -1,
+        };
+        match (*gc).eval(circ, &gb, &ev) {
+            Ok(out) => {
+                slice::from_raw_parts_mut(outputs, circ.noutputs()).copy_from_slice(&out);
+                0
+            }
+            Err(_) => -1,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{circuit::CircuitBuilder, fancy::Fancy};
+
+    #[test]
+    fn as_base_q_rejects_short_output() {
+        let mut out = [0u16; 3];
+        unsafe {
+            assert_eq!(fg_as_base_q(26, 0, 3, out.as_mut_ptr(), 3), 0);
+            assert_eq!(out, [2, 2, 2]);
+            assert_eq!(fg_as_base_q(27, 0, 3, out.as_mut_ptr(), 3), -1);
+        }
+    }
+
+    #[test]
+    fn crt_inv_rejects_bad_input() {
+        let (mut lo, mut hi) = (0, 0);
+        unsafe {
+            let ps = [3, 5, 7];
+            assert_eq!(
+                fg_crt_inv([2, 3, 2].as_ptr(), ps.as_ptr(), 3, &mut lo, &mut hi),
+                0
+            );
+            assert_eq!(join(lo, hi), 23);
+            // A residue above its modulus, and moduli that are not coprime.
+            assert_eq!(
+                fg_crt_inv([3, 3, 2].as_ptr(), ps.as_ptr(), 3, &mut lo, &mut hi),
+                -1
+            );
+            assert_eq!(
+                fg_crt_inv([1, 1].as_ptr(), [3, 6].as_ptr(), 2, &mut lo, &mut hi),
+                -1
+            );
+        }
+    }
+
+    #[test]
+    fn evaluate_rejects_invalid_labels() {
+        let mut b = CircuitBuilder::new();
+        let x = b.garbler_input(3);
+        let y = b.evaluator_input(3);
+        let z = b.add(&x, &y).unwrap();
+        b.output(&z).unwrap();
+        let c = b.finish();
+        let (en, gc) = classic::garble(&c).unwrap();
+        let mut gb = [0u8; 16];
+        let mut ev = [0u8; 16];
+        let mut out = [0u16];
+        unsafe {
+            assert_eq!(
+                fg_encode_garbler_inputs(&en, [1].as_ptr(), 1, gb.as_mut_ptr()),
+                0
+            );
+            assert_eq!(
+                fg_encode_evaluator_inputs(&en, [2].as_ptr(), 1, ev.as_mut_ptr()),
+                0
+            );
+            assert_eq!(
+                fg_evaluate(&c, &gc, gb.as_ptr(), ev.as_ptr(), out.as_mut_ptr()),
+                0
+            );
+            assert_eq!(out, [0]);
+            // Set both bits of the first mod-3 digit.
+            gb[0] |= 1;
+            gb[8] |= 1;
+            assert_eq!(
+                fg_evaluate(&c, &gc, gb.as_ptr(), ev.as_ptr(), out.as_mut_ptr()),
+                -1
+            );
+        }
+    }
+}
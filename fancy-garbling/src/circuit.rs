@@ -11,6 +11,8 @@ use crate::{
     dummy::{Dummy, DummyVal},
     errors::{CircuitBuilderError, DummyError, FancyError},
     fancy::{BinaryBundle, CrtBundle, Fancy, FancyInput, HasModulus},
+    informer::{Informer, InformerStats},
+    validate,
 };
 use itertools::Itertools;
 use std::collections::HashMap;
@@ -131,26 +133,39 @@ impl Circuit {
     }
 
     /// Evaluate the circuit using fancy object `f`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(ngates = self.gates.len()))
+    )]
     pub fn eval<F: Fancy>(
         &self,
         f: &mut F,
         garbler_inputs: &[F::Item],
         evaluator_inputs: &[F::Item],
     ) -> Result<Option<Vec<u16>>, F::Error> {
+        if garbler_inputs.len() != self.num_garbler_inputs() {
+            return Err(F::Error::from(FancyError::InvalidArgNum {
+                got: garbler_inputs.len(),
+                needed: self.num_garbler_inputs(),
+            }));
+        }
+        if evaluator_inputs.len() != self.num_evaluator_inputs() {
+            return Err(F::Error::from(FancyError::InvalidArgNum {
+                got: evaluator_inputs.len(),
+                needed: self.num_evaluator_inputs(),
+            }));
+        }
+        if validate::ENABLED {
+            let moduli = |refs: &[CircuitRef]| refs.iter().map(|r| r.modulus()).collect_vec();
+            validate::moduli(garbler_inputs, &moduli(&self.garbler_input_refs));
+            validate::moduli(evaluator_inputs, &moduli(&self.evaluator_input_refs));
+        }
         let mut cache: Vec<Option<F::Item>> = vec![None; self.gates.len()];
         for (i, gate) in self.gates.iter().enumerate() {
             let q = self.modulus(i);
             let (zref_, val) = match *gate {
                 Gate::GarblerInput { id } => (None, garbler_inputs[id].clone()),
-                Gate::EvaluatorInput { id } => {
-                    assert!(
-                        id < evaluator_inputs.len(),
-                        "id={} ev_inps.len()={}",
-                        id,
-                        evaluator_inputs.len()
-                    );
-                    (None, evaluator_inputs[id].clone())
-                }
+                Gate::EvaluatorInput { id } => (None, evaluator_inputs[id].clone()),
                 Gate::Constant { val } => (None, f.constant(val, q)?),
                 Gate::Add { xref, yref, out } => (
                     out,
@@ -242,27 +257,33 @@ impl Circuit {
         let gb = garbler_inputs
             .iter()
             .zip(self.garbler_input_refs.iter())
-            .map(|(x, r)| DummyVal::new(*x, r.modulus()))
+            .map(|(x, r)| {
+                validate::digits(&[*x], r.modulus());
+                DummyVal::new(*x, r.modulus())
+            })
             .collect_vec();
         let ev = evaluator_inputs
             .iter()
             .zip(self.evaluator_input_refs.iter())
-            .map(|(x, r)| DummyVal::new(*x, r.modulus()))
+            .map(|(x, r)| {
+                validate::digits(&[*x], r.modulus());
+                DummyVal::new(*x, r.modulus())
+            })
             .collect_vec();
 
         let outputs = self.eval(&mut dummy, &gb, &ev)?;
         Ok(outputs.expect("dummy will always return Some(u16) output"))
     }
 
-    /// Print circuit info.
-    pub fn print_info(&self) -> Result<(), DummyError> {
-        let mut informer = crate::informer::Informer::new(Dummy::new());
+    /// Count the inputs, outputs, gates, and ciphertexts of the circuit.
+    pub fn stats(&self) -> Result<InformerStats, DummyError> {
+        let mut informer = Informer::new(Dummy::new());
 
         // encode inputs as InformerVals
         let gb = self
             .garbler_input_refs
             .iter()
-            .map(|r| informer.receive(r.modulus()))
+            .map(|r| informer.encode(0, r.modulus()))
             .collect::<Result<Vec<DummyVal>, DummyError>>()?;
         let ev = self
             .evaluator_input_refs
@@ -271,7 +292,12 @@ impl Circuit {
             .collect::<Result<Vec<DummyVal>, DummyError>>()?;
 
         let _outputs = self.eval(&mut informer, &gb, &ev)?;
-        println!("{}", informer.stats());
+        Ok(informer.stats())
+    }
+
+    /// Print circuit info.
+    pub fn print_info(&self) -> Result<(), DummyError> {
+        println!("{}", self.stats()?);
         Ok(())
     }
 
@@ -578,8 +604,8 @@ mod plaintext {
         b.output(&z).unwrap();
         let c = b.finish();
         for _ in 0..16 {
-            let x = rng.gen_u16() % q;
-            let y = rng.gen_u16() % q;
+            let x = rng.gen_mod(q);
+            let y = rng.gen_mod(q);
             let out = c.eval_plain(&[x], &[y]).unwrap();
             assert_eq!(out[0], x * y % q);
         }
@@ -634,7 +660,7 @@ mod plaintext {
         let mut rng = thread_rng();
 
         let q = rng.gen_modulus();
-        let c = rng.gen_u16() % q;
+        let c = rng.gen_mod(q);
 
         let x = b.evaluator_input(q);
         let y = b.constant(c, q).unwrap();
@@ -644,7 +670,7 @@ mod plaintext {
         let circ = b.finish();
 
         for _ in 0..64 {
-            let x = rng.gen_u16() % q;
+            let x = rng.gen_mod(q);
             let z = circ.eval_plain(&[], &[x]).unwrap();
             assert_eq!(z[0], (x + c) % q);
         }
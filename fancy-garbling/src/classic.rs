@@ -12,6 +12,7 @@ use crate::{
     errors::{EvaluatorError, GarblerError},
     fancy::HasModulus,
     garble::{Evaluator, Garbler},
+    validate,
     wire::Wire,
 };
 use itertools::Itertools;
@@ -38,6 +39,11 @@ impl GarbledCircuit {
         self.blocks.len()
     }
 
+    /// The garbled rows and constant wires in the garbled circuit.
+    pub fn blocks(&self) -> &[Block] {
+        &self.blocks
+    }
+
     /// Evaluate the garbled circuit.
     pub fn eval(
         &self,
@@ -54,13 +60,22 @@ impl GarbledCircuit {
 
 /// Garble a circuit without streaming.
 pub fn garble(c: &Circuit) -> Result<(Encoder, GarbledCircuit), GarblerError> {
+    garble_with_rng(c, AesRng::new())
+}
+
+/// Garble a circuit without streaming, drawing all randomness from `rng`. With
+/// a seeded `rng` this is deterministic, which is useful for reproducing runs
+/// and for known-answer tests.
+pub fn garble_with_rng(
+    c: &Circuit,
+    rng: AesRng,
+) -> Result<(Encoder, GarbledCircuit), GarblerError> {
     let channel = Channel::new(
         GarbledReader::new(&[]),
         GarbledWriter::new(Some(c.num_nonfree_gates)),
     );
     let channel_ = channel.clone();
 
-    let rng = AesRng::new();
     let mut garbler = Garbler::new(channel_, rng);
 
     // get input wires, ignoring encoded values
@@ -94,6 +109,89 @@ pub fn garble(c: &Circuit) -> Result<(Encoder, GarbledCircuit), GarblerError> {
     Ok((en, gc))
 }
 
+/// Garbles many instances of one circuit, for running the same computation
+/// many times.
+///
+/// A garbled circuit must never be evaluated on more than one set of inputs,
+/// so every instance gets fresh wire-labels and deltas. A session amortizes
+/// the rest: every instance shares one copy of the circuit, and a batch is
+/// garbled across several threads. Instance `i` of the session draws its randomness
+/// from stream `i` of the session's RNG, so the garbled circuits depend only
+/// on the seed and not on how many threads produced them.
+pub struct Session<'a> {
+    circuit: &'a Circuit,
+    rng: AesRng,
+    ninstances: u64,
+    nthreads: usize,
+}
+
+impl<'a> Session<'a> {
+    /// Make a new session garbling `c` with randomness from `rng`, using as
+    /// many threads as there are CPUs.
+    pub fn new(c: &'a Circuit, rng: AesRng) -> Self {
+        let nthreads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        Session {
+            circuit: c,
+            rng,
+            ninstances: 0,
+            nthreads,
+        }
+    }
+
+    /// Garble batches with `n` threads.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is zero.
+    pub fn threads(mut self, n: usize) -> Self {
+        assert!(n > 0, "a session needs at least one thread");
+        self.nthreads = n;
+        self
+    }
+
+    /// The number of instances garbled so far.
+    pub fn ninstances(&self) -> u64 {
+        self.ninstances
+    }
+
+    /// Garble the next instance.
+    pub fn garble(&mut self) -> Result<(Encoder, GarbledCircuit), GarblerError> {
+        let rng = self.rng.stream(self.ninstances);
+        self.ninstances += 1;
+        garble_with_rng(self.circuit, rng)
+    }
+
+    /// Garble the next `n` instances, in order.
+    pub fn garble_batch(
+        &mut self,
+        n: usize,
+    ) -> Result<Vec<(Encoder, GarbledCircuit)>, GarblerError> {
+        let rngs = (0..n as u64)
+            .map(|i| self.rng.stream(self.ninstances + i))
+            .collect_vec();
+        self.ninstances += n as u64;
+        let c = self.circuit;
+        let chunk = std::cmp::max(1, (n + self.nthreads - 1) / self.nthreads);
+        std::thread::scope(|s| {
+            let handles = rngs
+                .chunks(chunk)
+                .map(|rngs| {
+                    s.spawn(move || {
+                        rngs.iter()
+                            .map(|rng| garble_with_rng(c, rng.clone()))
+                            .collect::<Result<Vec<_>, _>>()
+                    })
+                })
+                .collect_vec();
+            let mut instances = Vec::with_capacity(n);
+            for h in handles {
+                instances.extend(h.join().expect("garbling thread panicked")?);
+            }
+            Ok(instances)
+        })
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 // Encoder
 
@@ -135,6 +233,7 @@ impl Encoder {
     pub fn encode_garbler_input(&self, x: u16, id: usize) -> Wire {
         let X = &self.garbler_inputs[id];
         let q = X.modulus();
+        validate::digits(&[x], q);
         X.plus(&self.deltas[&q].cmul(x))
     }
 
@@ -142,6 +241,7 @@ impl Encoder {
     pub fn encode_evaluator_input(&self, x: u16, id: usize) -> Wire {
         let X = &self.evaluator_inputs[id];
         let q = X.modulus();
+        validate::digits(&[x], q);
         X.plus(&self.deltas[&q].cmul(x))
     }
 
@@ -186,6 +286,12 @@ impl GarbledReader {
 impl std::io::Read for GarbledReader {
     fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
         assert_eq!(buf.len() % 16, 0);
+        if self.blocks.len() - self.index < buf.len() / 16 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "garbled circuit is too short",
+            ));
+        }
         for data in buf.chunks_mut(16) {
             let block: [u8; 16] = self.blocks[self.index].into();
             for (a, b) in data.iter_mut().zip(block.iter()) {
@@ -235,3 +341,37 @@ impl std::io::Write for GarbledWriter {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{circuit::CircuitBuilder, fancy::Fancy};
+    use rand::SeedableRng;
+
+    #[test]
+    fn session_batches() {
+        let mut b = CircuitBuilder::new();
+        let x = b.garbler_input(7);
+        let y = b.evaluator_input(7);
+        let z = b.mul(&x, &y).unwrap();
+        b.output(&z).unwrap();
+        let c = b.finish();
+
+        let seed = rand::random::<Block>();
+        let mut one = Session::new(&c, AesRng::from_seed(seed)).threads(1);
+        let mut many = Session::new(&c, AesRng::from_seed(seed)).threads(3);
+        let mut instances = vec![one.garble().unwrap()];
+        instances.extend(one.garble_batch(6).unwrap());
+        assert_eq!(one.ninstances(), 7);
+        let instances_ = many.garble_batch(7).unwrap();
+        for (i, ((en, gc), (_, gc_))) in instances.iter().zip(instances_.iter()).enumerate() {
+            // Threading does not change the result, but every instance is fresh.
+            assert_eq!(gc.blocks(), gc_.blocks());
+            assert_ne!(gc.blocks(), instances[(i + 1) % 7].1.blocks());
+            let x = i as u16 % 7;
+            let gb = en.encode_garbler_inputs(&[x]);
+            let ev = en.encode_evaluator_inputs(&[3]);
+            assert_eq!(gc.eval(&c, &gb, &ev).unwrap(), vec![x * 3 % 7]);
+        }
+    }
+}
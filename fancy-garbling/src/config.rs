@@ -0,0 +1,146 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Process-wide defaults for the convenience constructors.
+//!
+//! Functions such as `util::modulus_with_width` choose primes without being
+//! told which ones may be used. They consult the `Config` installed with
+//! `set`, which must happen once, before anything reads it, typically at the
+//! start of `main`:
+//!
+//! ```
+//! use fancy_garbling::{config::{self, Config, WidthPolicy}, util::{self, PrimeSet}};
+//!
+//! config::set(
+//!     Config::default()
+//!         .with_primes(PrimeSet::skip_2())
+//!         .with_width_policy(WidthPolicy::MaxFactors(8)),
+//! )
+//! .unwrap();
+//! assert!(!util::primes_with_width(32).contains(&2));
+//! ```
+//!
+//! Without a call to `set`, the defaults reproduce the built-in `PRIMES` and
+//! the smallest-primes choice of `util::base_primes_with_width`. Wire-labels
+//! are always the 128 bits of a `Block`, so their length is not configurable.
+
+use crate::{
+    errors::NumberError,
+    util::{self, ModulusBuilder, PrimeSet},
+};
+use std::sync::OnceLock;
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// How to choose the primes of a CRT modulus wide enough for a given number of
+/// bits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum WidthPolicy {
+    /// The smallest primes whose product is wide enough, as chosen by
+    /// `util::base_primes_with_width`.
+    Smallest,
+    /// At most the given number of primes, as chosen by
+    /// `ModulusBuilder::max_factors`.
+    MaxFactors(usize),
+    /// Primes summing to at most the given number of residues, as chosen by
+    /// `ModulusBuilder::digit_budget`.
+    DigitBudget(usize),
+}
+
+/// The defaults used by the convenience constructors.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    primes: PrimeSet,
+    width_policy: WidthPolicy,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            primes: PrimeSet::default(),
+            width_policy: WidthPolicy::Smallest,
+        }
+    }
+}
+
+impl Config {
+    /// Use `primes` as the default CRT basis.
+    pub fn with_primes(mut self, primes: PrimeSet) -> Self {
+        self.primes = primes;
+        self
+    }
+
+    /// Choose moduli of a given width by `policy`.
+    pub fn with_width_policy(mut self, policy: WidthPolicy) -> Self {
+        self.width_policy = policy;
+        self
+    }
+
+    /// The default CRT basis.
+    pub fn primes(&self) -> &PrimeSet {
+        &self.primes
+    }
+
+    /// The policy for choosing moduli of a given width.
+    pub fn width_policy(&self) -> WidthPolicy {
+        self.width_policy
+    }
+
+    /// Choose the factors of a CRT modulus supporting at least `nbits`-bit
+    /// integers from the default basis, by the width policy.
+    pub fn primes_with_width(&self, nbits: u32) -> Result<Vec<u16>, NumberError> {
+        let builder = ModulusBuilder::new(nbits).from_primes(self.primes.primes());
+        match self.width_policy {
+            WidthPolicy::Smallest => util::try_base_primes_with_width(nbits, self.primes.primes()),
+            WidthPolicy::MaxFactors(n) => builder.max_factors(n).primes(),
+            WidthPolicy::DigitBudget(n) => builder.digit_budget(n).primes(),
+        }
+    }
+}
+
+/// Install `config` as the process-wide configuration. Returns it back if a
+/// configuration is already in place, either from an earlier call or because
+/// `get` has already installed the default.
+pub fn set(config: Config) -> Result<(), Config> {
+    CONFIG.set(config)
+}
+
+/// The process-wide configuration, installing the default if `set` has not
+/// been called.
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::PRIMES;
+
+    #[test]
+    fn policies() {
+        let c = Config::default();
+        for n in 1..100 {
+            assert_eq!(
+                c.primes_with_width(n).unwrap(),
+                util::base_primes_with_width(n, &PRIMES)
+            );
+        }
+        let odd = c.clone().with_primes(PrimeSet::skip_2());
+        assert_eq!(odd.primes_with_width(8).unwrap(), vec![3, 5, 7, 11]);
+        let few = odd.clone().with_width_policy(WidthPolicy::MaxFactors(2));
+        let ps = few.primes_with_width(12).unwrap();
+        assert!(ps.len() <= 2 && util::product(&ps) >> 12 > 0);
+        let budget = odd.with_width_policy(WidthPolicy::DigitBudget(10));
+        assert!(budget.primes_with_width(64).is_err());
+    }
+
+    #[test]
+    fn set_once() {
+        // Whichever test runs first installs the default.
+        assert_eq!(get(), &Config::default());
+        assert!(set(Config::default()).is_err());
+    }
+}
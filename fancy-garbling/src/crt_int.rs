@@ -0,0 +1,261 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Plaintext integers in CRT representation.
+//!
+//! A `CrtInt` is the plaintext counterpart of a `CrtBundle`: its arithmetic
+//! works on each residue separately, just like the gadgets on each wire, so it
+//! serves as a reference when testing gadgets and for computing locally on
+//! values that are later garbled.
+
+use crate::{errors::NumberError, pretty, util};
+use std::{
+    cmp::Ordering,
+    fmt,
+    ops::{Add, Mul, Neg, Sub},
+};
+
+/// An integer mod the product of the primes `ps`, held as its residues.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct CrtInt {
+    ps: Vec<u16>,
+    residues: Vec<u16>,
+}
+
+impl CrtInt {
+    /// Create an integer from its residues mod the primes `ps`, returning an
+    /// error if their numbers differ, a prime is below 2, or a residue is not
+    /// below its prime.
+    pub fn new(ps: Vec<u16>, residues: Vec<u16>) -> Result<Self, NumberError> {
+        if let Some(&p) = ps.iter().find(|&&p| p < 2) {
+            return Err(NumberError::InvalidModulus(p as u128));
+        }
+        if ps.len() != residues.len() || residues.iter().zip(ps.iter()).any(|(x, p)| x >= p) {
+            return Err(NumberError::Overflow);
+        }
+        Ok(CrtInt { ps, residues })
+    }
+
+    /// The residues of `x` mod the primes `ps`.
+    pub fn from_u128(x: u128, ps: &[u16]) -> Self {
+        CrtInt {
+            ps: ps.to_vec(),
+            residues: util::crt(x, ps),
+        }
+    }
+
+    /// The residues of the signed `x` mod the primes `ps`, as in
+    /// `util::crt_signed`.
+    pub fn from_i128(x: i128, ps: &[u16]) -> Self {
+        CrtInt {
+            ps: ps.to_vec(),
+            residues: util::crt_signed(x, ps),
+        }
+    }
+
+    /// The value below the product of the primes that this represents.
+    pub fn to_u128(&self) -> u128 {
+        util::crt_inv(&self.residues, &self.ps)
+    }
+
+    /// The value in the symmetric range around zero that this represents, as
+    /// in `util::crt_inv_signed`.
+    pub fn to_i128(&self) -> i128 {
+        util::crt_inv_signed(&self.residues, &self.ps)
+    }
+
+    /// The primes.
+    pub fn primes(&self) -> &[u16] {
+        &self.ps
+    }
+
+    /// The residues, one per prime.
+    pub fn residues(&self) -> &[u16] {
+        &self.residues
+    }
+
+    /// Whether `other` uses the same primes, so that the two can be combined.
+    pub fn is_compatible(&self, other: &CrtInt) -> bool {
+        self.ps == other.ps
+    }
+
+    /// Add `other`, or return `None` if it uses different primes.
+    pub fn checked_add(&self, other: &CrtInt) -> Option<CrtInt> {
+        self.zip_with(other, |x, y, p| (x + y) % p)
+    }
+
+    /// Subtract `other`, or return `None` if it uses different primes.
+    pub fn checked_sub(&self, other: &CrtInt) -> Option<CrtInt> {
+        self.zip_with(other, |x, y, p| (x + p - y) % p)
+    }
+
+    /// Multiply by `other`, or return `None` if it uses different primes.
+    pub fn checked_mul(&self, other: &CrtInt) -> Option<CrtInt> {
+        self.zip_with(other, |x, y, p| x * y % p)
+    }
+
+    fn zip_with(&self, other: &CrtInt, f: impl Fn(u32, u32, u32) -> u32) -> Option<CrtInt> {
+        if !self.is_compatible(other) {
+            return None;
+        }
+        let residues = self
+            .residues
+            .iter()
+            .zip(other.residues.iter())
+            .zip(self.ps.iter())
+            .map(|((&x, &y), &p)| f(x as u32, y as u32, p as u32) as u16)
+            .collect();
+        Some(CrtInt {
+            ps: self.ps.clone(),
+            residues,
+        })
+    }
+}
+
+macro_rules! binop {
+    ($trait:ident, $method:ident, $checked:ident) => {
+        impl<'a> $trait for &'a CrtInt {
+            type Output = CrtInt;
+
+            /// # Panics
+            ///
+            /// Panics if the operands use different primes.
+            fn $method(self, other: &CrtInt) -> CrtInt {
+                self.$checked(other).expect("CRT primes differ")
+            }
+        }
+
+        impl $trait for CrtInt {
+            type Output = CrtInt;
+
+            /// # Panics
+            ///
+            /// Panics if the operands use different primes.
+            fn $method(self, other: CrtInt) -> CrtInt {
+                (&self).$method(&other)
+            }
+        }
+    };
+}
+
+binop!(Add, add, checked_add);
+binop!(Sub, sub, checked_sub);
+binop!(Mul, mul, checked_mul);
+
+impl Neg for &CrtInt {
+    type Output = CrtInt;
+
+    fn neg(self) -> CrtInt {
+        let residues = self
+            .residues
+            .iter()
+            .zip(self.ps.iter())
+            .map(|(&x, &p)| (p - x) % p)
+            .collect();
+        CrtInt {
+            ps: self.ps.clone(),
+            residues,
+        }
+    }
+}
+
+impl Neg for CrtInt {
+    type Output = CrtInt;
+
+    fn neg(self) -> CrtInt {
+        -&self
+    }
+}
+
+/// Compares the value below the product of the primes that this represents with a
+/// constant.
+impl PartialEq<u128> for CrtInt {
+    fn eq(&self, other: &u128) -> bool {
+        self.to_u128() == *other
+    }
+}
+
+/// Compares the value below the product of the primes that this represents with a
+/// constant.
+impl PartialOrd<u128> for CrtInt {
+    fn partial_cmp(&self, other: &u128) -> Option<Ordering> {
+        Some(self.to_u128().cmp(other))
+    }
+}
+
+impl fmt::Debug for CrtInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CrtInt({})", pretty::crt(&self.residues, &self.ps))
+    }
+}
+
+impl fmt::Display for CrtInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&pretty::crt(&self.residues, &self.ps), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::{RngExt, PRIMES};
+    use rand::thread_rng;
+
+    #[test]
+    fn arithmetic() {
+        let mut rng = thread_rng();
+        let ps = &PRIMES[..10];
+        let q = util::product(ps);
+        for _ in 0..128 {
+            let x = rng.gen_u128() % q;
+            let y = rng.gen_u128() % q;
+            let (a, b) = (CrtInt::from_u128(x, ps), CrtInt::from_u128(y, ps));
+            assert_eq!(a.to_u128(), x);
+            assert_eq!((&a + &b).to_u128(), (x + y) % q);
+            assert_eq!((&a - &b).to_u128(), (x + q - y) % q);
+            assert_eq!((&a * &b).to_u128(), x * y % q);
+            assert_eq!((-a).to_u128(), (q - x) % q);
+        }
+        let a = CrtInt::from_i128(-5, ps);
+        let b = CrtInt::from_i128(3, ps);
+        assert_eq!((a.clone() * b.clone()).to_i128(), -15);
+        assert_eq!((a + b).to_i128(), -2);
+    }
+
+    #[test]
+    fn comparisons() {
+        let mut rng = thread_rng();
+        let ps = &PRIMES[..5];
+        let q = util::product(ps);
+        for _ in 0..128 {
+            let x = rng.gen_u128() % q;
+            let c = rng.gen_u128() % q;
+            let a = CrtInt::from_u128(x, ps);
+            assert!(a == x);
+            assert_eq!(a == c, x == c);
+            assert_eq!(a < c, x < c);
+            assert_eq!(a >= c, x >= c);
+        }
+        // Comparisons are of the value mod the product of the primes.
+        assert!(CrtInt::from_u128(q + 1, ps) == 1);
+        assert!(CrtInt::from_i128(-1, ps) > 1);
+    }
+
+    #[test]
+    fn compatibility() {
+        let a = CrtInt::from_u128(5, &[2, 3, 5]);
+        let b = CrtInt::from_u128(5, &[2, 3, 7]);
+        assert!(!a.is_compatible(&b));
+        assert_eq!(a.checked_add(&b), None);
+        assert!(CrtInt::new(vec![2, 3], vec![1, 3]).is_err());
+        assert!(CrtInt::new(vec![2, 3], vec![1]).is_err());
+        let c = CrtInt::new(vec![2, 3, 5, 7], vec![0, 2, 1, 2]).unwrap();
+        assert_eq!(
+            format!("{:?}", c),
+            "CrtInt(86 = {0 mod 2, 2 mod 3, 1 mod 5, 2 mod 7})"
+        );
+    }
+}
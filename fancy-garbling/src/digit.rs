@@ -0,0 +1,272 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Base-`q` and CRT conversions generic over the width of the modulus.
+//!
+//! The functions in `util` fix digits and moduli to `u16`, which is what wires
+//! use and what they are optimized for. The versions here work for any
+//! `Digit` type, so that plaintext computations can use moduli beyond `u16`,
+//! such as the NTT-friendly prime `786433 = 3 * 2^18 + 1`, or `u8` digits,
+//! which halve the memory of binary and other small-modulus digit vectors:
+//!
+//! ```
+//! use fancy_garbling::digit;
+//!
+//! let ds = digit::as_base_q(1 << 40, 786433u32, 3);
+//! assert_eq!(digit::from_base_q(&ds, 786433), 1 << 40);
+//! ```
+//!
+//! Intermediate values are computed in `u128`, so carries never overflow even
+//! for `u64` digits. A value that does not fit in a `u128` panics, as sums of
+//! a few wide digits can get there much sooner than with `u16`.
+
+use crate::{errors::NumberError, util};
+use std::{fmt, hash::Hash};
+
+/// An unsigned integer type usable as a digit or modulus.
+pub trait Digit:
+    Copy + Eq + Ord + Hash + fmt::Debug + fmt::Display + Send + Sync + 'static
+{
+    /// The number of bits of the type.
+    const BITS: u32;
+
+    /// Widen to a `u128`.
+    fn to_u128(self) -> u128;
+
+    /// Narrow from a `u128`, which must fit.
+    fn from_u128(x: u128) -> Self;
+}
+
+macro_rules! digit {
+    ($($t:ty),*) => {
+        $(impl Digit for $t {
+            const BITS: u32 = <$t>::max_value().count_ones();
+
+            #[inline]
+            fn to_u128(self) -> u128 {
+                self as u128
+            }
+
+            #[inline]
+            fn from_u128(x: u128) -> Self {
+                debug_assert!(x <= <$t>::max_value() as u128, "{} does not fit", x);
+                x as $t
+            }
+        })*
+    };
+}
+
+digit!(u8, u16, u32, u64);
+
+/// Convert `x` into base `q`, building a vector of length `n`. Digits beyond the `n`th are
+/// dropped, as in `util::as_base_q`.
+pub fn as_base_q<D: Digit>(x: u128, q: D, n: usize) -> Vec<D> {
+    as_mixed_radix(x, &vec![q; n])
+}
+
+/// Convert `x` into base `q`, building a vector of length `n`, returning an error if `x`
+/// does not fit in `n` digits.
+pub fn try_as_base_q<D: Digit>(x: u128, q: D, n: usize) -> Result<Vec<D>, NumberError> {
+    try_as_mixed_radix(x, &vec![q; n])
+}
+
+/// Convert `x` into mixed radix form using the provided `radii`.
+pub fn as_mixed_radix<D: Digit>(x: u128, radii: &[D]) -> Vec<D> {
+    let mut x = x;
+    radii
+        .iter()
+        .map(|&m| {
+            let m = m.to_u128();
+            let d = x % m;
+            x /= m;
+            D::from_u128(d)
+        })
+        .collect()
+}
+
+/// Convert `x` into mixed radix form using the provided `radii`, returning an error if `x`
+/// does not fit, or if a radix is below 2.
+pub fn try_as_mixed_radix<D: Digit>(x: u128, radii: &[D]) -> Result<Vec<D>, NumberError> {
+    if let Some(&m) = radii.iter().find(|m| m.to_u128() < 2) {
+        return Err(NumberError::InvalidModulus(m.to_u128()));
+    }
+    let ds = as_mixed_radix(x, radii);
+    if from_mixed_radix_checked(&ds, radii) != Some(x) {
+        return Err(NumberError::Overflow);
+    }
+    Ok(ds)
+}
+
+/// Convert little-endian base `q` digits into a `u128`.
+///
+/// # Panics
+///
+/// Panics if the value does not fit in a `u128`.
+pub fn from_base_q<D: Digit>(ds: &[D], q: D) -> u128 {
+    from_mixed_radix(ds, &vec![q; ds.len()])
+}
+
+/// Convert little-endian mixed radix digits into a `u128`.
+///
+/// # Panics
+///
+/// Panics if the value does not fit in a `u128`.
+pub fn from_mixed_radix<D: Digit>(digits: &[D], radii: &[D]) -> u128 {
+    debug_assert!(
+        digits.iter().zip(radii.iter()).all(|(d, q)| d < q),
+        "digits {:?} are not below radii {:?}",
+        digits,
+        radii
+    );
+    from_mixed_radix_checked(digits, radii).expect("value does not fit in a u128")
+}
+
+fn from_mixed_radix_checked<D: Digit>(digits: &[D], radii: &[D]) -> Option<u128> {
+    digits
+        .iter()
+        .zip(radii.iter())
+        .rev()
+        .try_fold(0u128, |x, (&d, &q)| {
+            x.checked_mul(q.to_u128())?.checked_add(d.to_u128())
+        })
+}
+
+/// Add a base `q` slice `ys` into `xs`, dropping the final carry.
+pub fn base_q_add_eq<D: Digit>(xs: &mut [D], ys: &[D], q: D) {
+    debug_assert!(xs.len() >= ys.len(), "xs is shorter than ys");
+    let q = q.to_u128();
+    let mut c = 0;
+    for (i, x) in xs.iter_mut().enumerate() {
+        let y = ys.get(i).map_or(0, |y| y.to_u128());
+        if i >= ys.len() && c == 0 {
+            break;
+        }
+        // Each digit is below `2^64`, so the sum fits.
+        let s = x.to_u128() + y + c;
+        c = (s >= q) as u128;
+        *x = D::from_u128(s - c * q);
+    }
+}
+
+/// Add base `q` slices `xs` and `ys`, which must have the same length, into a new vector,
+/// dropping the final carry.
+pub fn base_q_add<D: Digit>(xs: &[D], ys: &[D], q: D) -> Vec<D> {
+    debug_assert_eq!(xs.len(), ys.len(), "digit counts differ");
+    let mut zs = xs.to_vec();
+    base_q_add_eq(&mut zs, ys, q);
+    zs
+}
+
+/// Determine how many `mod q` digits fit into a `u128`, as `util::digits_per_u128` does.
+pub fn digits_per_u128<D: Digit>(q: D) -> usize {
+    debug_assert!(q.to_u128() >= 2, "invalid modulus {}", q);
+    let bits = 128 - (q.to_u128() - 1).leading_zeros() as usize;
+    128 / bits
+}
+
+/// Compute the CRT representation of `x` with respect to the primes `ps`.
+pub fn crt<D: Digit>(x: u128, ps: &[D]) -> Vec<D> {
+    ps.iter().map(|&p| D::from_u128(x % p.to_u128())).collect()
+}
+
+/// Compute the value `x` given a list of CRT primes and residues.
+///
+/// This uses Garner's algorithm, whose intermediate values never exceed the
+/// result, so it works whenever the product of `ps` fits in a `u128`.
+///
+/// # Panics
+///
+/// Panics if the product of `ps` does not fit in a `u128`, or if `ps` are not
+/// coprime.
+pub fn crt_inv<D: Digit>(xs: &[D], ps: &[D]) -> u128 {
+    let (mut x, mut m) = (0u128, 1u128);
+    for (&a, &p) in xs.iter().zip(ps.iter()) {
+        let (a, p) = (a.to_u128(), p.to_u128());
+        // `m mod p` and its inverse are below `p < 2^64`, so their product fits.
+        let minv = util::try_inv((m % p) as i128, p as i128).expect("CRT moduli are not coprime");
+        let t = (a + p - x % p) % p * minv as u128 % p;
+        let m_ = m
+            .checked_mul(p)
+            .expect("CRT modulus does not fit in a u128");
+        x += t * m;
+        m = m_;
+    }
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::{RngExt, PRIMES};
+    use rand::thread_rng;
+
+    #[test]
+    fn matches_u16() {
+        let mut rng = thread_rng();
+        for _ in 0..128 {
+            let q = 2 + rng.gen_mod(300);
+            let n = util::digits_per_u128(q);
+            assert_eq!(digits_per_u128(q), n);
+            let x = rng.gen_u128() % (q as u128).pow(n as u32 - 1);
+            let y = rng.gen_u128() % (q as u128).pow(n as u32 - 1);
+            assert_eq!(as_base_q(x, q, n), util::as_base_q(x, q, n));
+            let mut xs = as_base_q(x, q, n);
+            base_q_add_eq(&mut xs, &as_base_q(y, q, n), q);
+            assert_eq!(from_base_q(&xs, q), x + y);
+            let ps = &PRIMES[..8];
+            assert_eq!(crt(x, ps), util::crt(x, ps));
+            let z = x % util::product(ps);
+            assert_eq!(crt_inv(&crt(z, ps), ps), z);
+        }
+    }
+
+    #[test]
+    fn wide_moduli() {
+        let mut rng = thread_rng();
+        // A 20-bit NTT-friendly prime and the 61-bit Mersenne prime.
+        let q = 786433u32;
+        let ps = [786433u64, (1 << 61) - 1];
+        assert_eq!(digits_per_u128(q), 6);
+        assert_eq!(digits_per_u128((1u64 << 61) - 1), 2);
+        for _ in 0..128 {
+            let x = rng.gen_u128() >> 12;
+            let mut ds = as_base_q(x, q, 6);
+            assert!(ds.iter().all(|&d| d < q));
+            assert_eq!(from_base_q(&ds, q), x);
+            base_q_add_eq(&mut ds, &[q - 1], q);
+            assert_eq!(from_base_q(&ds, q), x + q as u128 - 1);
+            let x = rng.gen_u128() % (786433 * ((1 << 61) - 1));
+            assert_eq!(crt_inv(&crt(x, &ps), &ps), x);
+        }
+        assert_eq!(try_as_base_q(q as u128, q, 1), Err(NumberError::Overflow));
+        assert_eq!(try_as_base_q(q as u128, q, 2), Ok(vec![0, 1]));
+        assert!(try_as_mixed_radix(5, &[1u32]).is_err());
+    }
+
+    #[test]
+    fn narrow_digits() {
+        let mut rng = thread_rng();
+        assert_eq!(digits_per_u128(2u8), 128);
+        assert_eq!(digits_per_u128(255u8), 16);
+        for _ in 0..128 {
+            let (x, y) = (rng.gen_u128() >> 1, rng.gen_u128() >> 1);
+            let xs = as_base_q(x, 2u8, 128);
+            let ys = as_base_q(y, 2u8, 128);
+            assert_eq!(
+                xs.iter().map(|&d| d as u16).collect::<Vec<_>>(),
+                util::u128_to_bits(x, 128)
+            );
+            assert_eq!(from_base_q(&base_q_add(&xs, &ys, 2), 2), x + y);
+            let q = 3 + rng.gen_u16() as u8 % 253;
+            let n = digits_per_u128(q) - 1;
+            let zs = as_base_q(x, q, n);
+            let x = x % (q as u128).pow(n as u32);
+            assert_eq!(from_base_q(&zs, q), x);
+            let rs = crt(x, &[251u8, 241]);
+            assert_eq!(vec![rs[0] as u16, rs[1] as u16], util::crt(x, &[251, 241]));
+        }
+    }
+}
@@ -0,0 +1,350 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! A small textual language for `Fancy` computations, compiled to a compact
+//! bytecode and interpreted against any backend at runtime, so that services
+//! can accept new computations without being recompiled.
+//!
+//! A program has one statement per line, and `#` starts a comment. Each
+//! statement but `output` defines a new name:
+//!
+//! ```text
+//! x = garbler_input 7
+//! y = evaluator_input 7
+//! c = constant 3 7
+//! s = add x y
+//! t = sub s c
+//! u = cmul t 2
+//! v = mul u y
+//! b = proj v 2 0,1,0,1,0,1,0
+//! output b
+//! ```
+//!
+//! `proj x q tt` projects `x` to modulus `q` with the comma-separated truth
+//! table `tt`. Inputs are numbered in the order they are declared, and the
+//! moduli of every statement are checked when the program is parsed:
+//!
+//! ```
+//! use fancy_garbling::{dsl::Program, dummy::Dummy, FancyInput};
+//!
+//! let p: Program = "x = garbler_input 5\ny = mul x x\noutput y".parse().unwrap();
+//! let mut f = Dummy::new();
+//! let x = f.encode(3, 5).unwrap();
+//! assert_eq!(p.run(&mut f, &[x], &[]).unwrap(), Some(vec![4]));
+//! ```
+
+use crate::{
+    circuit::{Circuit, CircuitBuilder},
+    errors::{CircuitBuilderError, DslError, FancyError},
+    fancy::Fancy,
+};
+use std::{collections::HashMap, str::FromStr};
+
+/// An instruction, whose arguments are registers: each instruction but
+/// `Output` writes the next one.
+#[derive(Clone, Debug, PartialEq)]
+enum Instr {
+    GarblerInput,
+    EvaluatorInput,
+    Constant(u16, u16),
+    Add(usize, usize),
+    Sub(usize, usize),
+    Cmul(usize, u16),
+    Mul(usize, usize),
+    Proj(usize, u16, Vec<u16>),
+    Output(usize),
+}
+
+/// A parsed program.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Program {
+    instrs: Vec<Instr>,
+    // The modulus of each register.
+    moduli: Vec<u16>,
+    garbler_input_moduli: Vec<u16>,
+    evaluator_input_moduli: Vec<u16>,
+    noutputs: usize,
+}
+
+impl Program {
+    /// The moduli of the garbler inputs, in order.
+    pub fn garbler_input_moduli(&self) -> &[u16] {
+        &self.garbler_input_moduli
+    }
+
+    /// The moduli of the evaluator inputs, in order.
+    pub fn evaluator_input_moduli(&self) -> &[u16] {
+        &self.evaluator_input_moduli
+    }
+
+    /// The number of outputs.
+    pub fn num_outputs(&self) -> usize {
+        self.noutputs
+    }
+
+    /// Run the program with `f` on the given input wires, returning the
+    /// outputs as `Circuit::eval` does.
+    pub fn run<F: Fancy>(
+        &self,
+        f: &mut F,
+        garbler_inputs: &[F::Item],
+        evaluator_inputs: &[F::Item],
+    ) -> Result<Option<Vec<u16>>, F::Error> {
+        if garbler_inputs.len() != self.garbler_input_moduli.len() {
+            return Err(F::Error::from(FancyError::InvalidArgNum {
+                got: garbler_inputs.len(),
+                needed: self.garbler_input_moduli.len(),
+            }));
+        }
+        if evaluator_inputs.len() != self.evaluator_input_moduli.len() {
+            return Err(F::Error::from(FancyError::InvalidArgNum {
+                got: evaluator_inputs.len(),
+                needed: self.evaluator_input_moduli.len(),
+            }));
+        }
+        let mut gb = garbler_inputs.iter();
+        let mut ev = evaluator_inputs.iter();
+        let mut regs: Vec<F::Item> = Vec::with_capacity(self.moduli.len());
+        let mut outputs = Vec::with_capacity(self.noutputs);
+        for instr in self.instrs.iter() {
+            let z = match *instr {
+                // The counts were checked above.
+                Instr::GarblerInput => gb.next().unwrap().clone(),
+                Instr::EvaluatorInput => ev.next().unwrap().clone(),
+                Instr::Constant(x, q) => f.constant(x, q)?,
+                Instr::Add(x, y) => f.add(&regs[x], &regs[y])?,
+                Instr::Sub(x, y) => f.sub(&regs[x], &regs[y])?,
+                Instr::Cmul(x, c) => f.cmul(&regs[x], c)?,
+                Instr::Mul(x, y) => f.mul(&regs[x], &regs[y])?,
+                Instr::Proj(x, q, ref tt) => f.proj(&regs[x], q, Some(tt.clone()))?,
+                Instr::Output(x) => {
+                    outputs.push(f.output(&regs[x])?);
+                    continue;
+                }
+            };
+            regs.push(z);
+        }
+        Ok(outputs.into_iter().collect())
+    }
+
+    /// Compile the program into a `Circuit`.
+    pub fn to_circuit(&self) -> Result<Circuit, CircuitBuilderError> {
+        let mut b = CircuitBuilder::new();
+        let gb = b.garbler_inputs(&self.garbler_input_moduli);
+        let ev = b.evaluator_inputs(&self.evaluator_input_moduli);
+        self.run(&mut b, &gb, &ev)?;
+        Ok(b.finish())
+    }
+}
+
+impl FromStr for Program {
+    type Err = DslError;
+
+    fn from_str(s: &str) -> Result<Self, DslError> {
+        let mut p = Program {
+            instrs: Vec::new(),
+            moduli: Vec::new(),
+            garbler_input_moduli: Vec::new(),
+            evaluator_input_moduli: Vec::new(),
+            noutputs: 0,
+        };
+        let mut names = HashMap::new();
+        for (i, line) in s.lines().enumerate() {
+            let line_no = i + 1;
+            let code = line.split('#').next().unwrap_or("");
+            let tokens = code.split_whitespace().collect::<Vec<&str>>();
+            let syntax = |reason: &str| DslError::Syntax {
+                line: line_no,
+                reason: reason.to_string(),
+            };
+            let modulus = |reason: String| DslError::Modulus {
+                line: line_no,
+                reason,
+            };
+            let reg = |name: &str| {
+                names.get(name).copied().ok_or_else(|| DslError::Undefined {
+                    line: line_no,
+                    name: name.to_string(),
+                })
+            };
+            let int = |tok: &str| {
+                tok.parse::<u16>()
+                    .map_err(|_| syntax(&format!("expected an integer, got {}", tok)))
+            };
+            let q_arg = |tok: &str| {
+                let q = int(tok)?;
+                if q < 2 {
+                    return Err(modulus(format!("invalid modulus {}", q)));
+                }
+                Ok(q)
+            };
+            let (name, op, args) = match tokens.as_slice() {
+                [] => continue,
+                ["output", x] => {
+                    p.instrs.push(Instr::Output(reg(x)?));
+                    p.noutputs += 1;
+                    continue;
+                }
+                [name, "=", op, args @ ..] => (*name, *op, args),
+                _ => return Err(syntax("expected `name = op args` or `output name`")),
+            };
+            let arity = |n: usize| {
+                if args.len() == n {
+                    Ok(())
+                } else {
+                    Err(syntax(&format!("{} takes {} arguments", op, n)))
+                }
+            };
+            let same = |x: usize, y: usize| {
+                if p.moduli[x] == p.moduli[y] {
+                    Ok(())
+                } else {
+                    Err(modulus(format!(
+                        "{} of unequal moduli {} and {}",
+                        op, p.moduli[x], p.moduli[y]
+                    )))
+                }
+            };
+            let (instr, q) = match op {
+                "garbler_input" | "evaluator_input" => {
+                    arity(1)?;
+                    let q = q_arg(args[0])?;
+                    if op == "garbler_input" {
+                        p.garbler_input_moduli.push(q);
+                        (Instr::GarblerInput, q)
+                    } else {
+                        p.evaluator_input_moduli.push(q);
+                        (Instr::EvaluatorInput, q)
+                    }
+                }
+                "constant" => {
+                    arity(2)?;
+                    let (x, q) = (int(args[0])?, q_arg(args[1])?);
+                    if x >= q {
+                        return Err(modulus(format!("constant {} is not below {}", x, q)));
+                    }
+                    (Instr::Constant(x, q), q)
+                }
+                "add" | "sub" => {
+                    arity(2)?;
+                    let (x, y) = (reg(args[0])?, reg(args[1])?);
+                    same(x, y)?;
+                    let instr = if op == "add" {
+                        Instr::Add(x, y)
+                    } else {
+                        Instr::Sub(x, y)
+                    };
+                    (instr, p.moduli[x])
+                }
+                "cmul" => {
+                    arity(2)?;
+                    let x = reg(args[0])?;
+                    (Instr::Cmul(x, int(args[1])?), p.moduli[x])
+                }
+                "mul" => {
+                    arity(2)?;
+                    let (x, y) = (reg(args[0])?, reg(args[1])?);
+                    (Instr::Mul(x, y), std::cmp::max(p.moduli[x], p.moduli[y]))
+                }
+                "proj" => {
+                    arity(3)?;
+                    let (x, q) = (reg(args[0])?, q_arg(args[1])?);
+                    let tt = args[2]
+                        .split(',')
+                        .map(int)
+                        .collect::<Result<Vec<u16>, DslError>>()?;
+                    if tt.len() != p.moduli[x] as usize {
+                        return Err(modulus(format!(
+                            "truth table has {} entries, but the modulus is {}",
+                            tt.len(),
+                            p.moduli[x]
+                        )));
+                    }
+                    if let Some(y) = tt.iter().find(|&&y| y >= q) {
+                        return Err(modulus(format!(
+                            "truth table entry {} is not below {}",
+                            y, q
+                        )));
+                    }
+                    (Instr::Proj(x, q, tt), q)
+                }
+                op => return Err(syntax(&format!("unknown operation {}", op))),
+            };
+            if names.insert(name.to_string(), p.moduli.len()).is_some() {
+                return Err(syntax(&format!("{} is already defined", name)));
+            }
+            p.instrs.push(instr);
+            p.moduli.push(q);
+        }
+        Ok(p)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{classic::garble, dummy::Dummy, fancy::FancyInput};
+
+    const PROGRAM: &str = "
+        # The parity of (x + y - 3) * 2 * y mod 7.
+        x = garbler_input 7
+        y = evaluator_input 7
+        c = constant 3 7
+        s = add x y
+        t = sub s c
+        u = cmul t 2
+        v = mul u y
+        b = proj v 2 0,1,0,1,0,1,0
+        output b
+        output v
+    ";
+
+    #[test]
+    fn run() {
+        let p: Program = PROGRAM.parse().unwrap();
+        assert_eq!(p.garbler_input_moduli(), &[7]);
+        assert_eq!(p.evaluator_input_moduli(), &[7]);
+        assert_eq!(p.num_outputs(), 2);
+        let c = p.to_circuit().unwrap();
+        let (en, gc) = garble(&c).unwrap();
+        for x in 0..7 {
+            for y in 0..7 {
+                let v = (x + y + 4) * 2 * y % 7;
+                let mut f = Dummy::new();
+                let xs = f.encode_many(&[x, y], &[7, 7]).unwrap();
+                let outputs = p.run(&mut f, &xs[..1], &xs[1..]).unwrap();
+                assert_eq!(outputs, Some(vec![v % 2, v]));
+                let gb = en.encode_garbler_inputs(&[x]);
+                let ev = en.encode_evaluator_inputs(&[y]);
+                assert_eq!(gc.eval(&c, &gb, &ev).unwrap(), vec![v % 2, v]);
+            }
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let line = |s: &str| match s.parse::<Program>() {
+            Err(DslError::Syntax { line, .. })
+            | Err(DslError::Undefined { line, .. })
+            | Err(DslError::Modulus { line, .. }) => line,
+            Ok(_) => panic!("{:?} parsed", s),
+        };
+        assert_eq!(line("x = garbler_input 7\ny = add x z"), 2);
+        assert_eq!(line("x = garbler_input 7\nx = cmul x 2"), 2);
+        assert_eq!(line("x = garbler_input 1"), 1);
+        assert_eq!(
+            line("x = garbler_input 3\ny = garbler_input 5\nz = add x y"),
+            3
+        );
+        assert_eq!(line("x = garbler_input 3\n\ny = proj x 2 0,1"), 3);
+        assert_eq!(line("x = garbler_input 3\ny = proj x 2 0,1,2"), 2);
+        assert_eq!(line("x = garbler_input 3 4"), 1);
+        assert_eq!(line("x = div 3 4"), 1);
+        assert_eq!(line("output"), 1);
+        let p: Program = "x = garbler_input 3".parse().unwrap();
+        assert!(p.run(&mut Dummy::new(), &[], &[]).is_err());
+    }
+}
@@ -11,8 +11,11 @@
 
 use crate::{
     errors::{DummyError, FancyError},
-    fancy::{Fancy, FancyInput, FancyReveal, HasModulus},
+    fancy::{BinaryBundle, Fancy, FancyInput, FancyReveal, HasModulus, RevealPolicy},
+    util,
+    validate,
 };
+use std::convert::TryFrom;
 
 /// Simple struct that performs the fancy computation over `u16`.
 pub struct Dummy {}
@@ -42,6 +45,45 @@ impl DummyVal {
     }
 }
 
+/// The bits of `bytes`, least significant bit of the first byte first, in the
+/// order of `util::bytes_to_bits`.
+impl<const N: usize> From<[u8; N]> for BinaryBundle<DummyVal> {
+    fn from(bytes: [u8; N]) -> Self {
+        let bits = util::bytes_to_bits(&bytes);
+        BinaryBundle::new(bits.into_iter().map(|b| DummyVal::new(b, 2)).collect())
+    }
+}
+
+/// The inverse of `From<[u8; N]>`, failing unless the bundle has exactly
+/// `8 * N` values mod 2, each 0 or 1.
+impl<const N: usize> TryFrom<&BinaryBundle<DummyVal>> for [u8; N] {
+    type Error = FancyError;
+
+    fn try_from(bundle: &BinaryBundle<DummyVal>) -> Result<Self, FancyError> {
+        if bundle.size() != 8 * N {
+            return Err(FancyError::InvalidArgNum {
+                got: bundle.size(),
+                needed: 8 * N,
+            });
+        }
+        for x in bundle.iter() {
+            if x.modulus != 2 {
+                return Err(FancyError::InvalidArgMod {
+                    got: x.modulus,
+                    needed: 2,
+                });
+            }
+            if x.val > 1 {
+                return Err(FancyError::ArgNotBinary);
+            }
+        }
+        let bits = bundle.iter().map(DummyVal::val).collect::<Vec<u16>>();
+        let mut bytes = [0; N];
+        bytes.copy_from_slice(&util::bytes_from_bits(&bits));
+        Ok(bytes)
+    }
+}
+
 impl Dummy {
     /// Create a new Dummy.
     pub fn new() -> Dummy {
@@ -55,6 +97,7 @@ impl FancyInput for Dummy {
 
     /// Encode a single dummy value.
     fn encode(&mut self, value: u16, modulus: u16) -> Result<DummyVal, DummyError> {
+        validate::digits(&[value], modulus);
         Ok(DummyVal::new(value, modulus))
     }
 
@@ -66,7 +109,10 @@ impl FancyInput for Dummy {
         Ok(xs
             .iter()
             .zip(moduli.iter())
-            .map(|(x, q)| DummyVal::new(*x, *q))
+            .map(|(x, q)| {
+                validate::digits(&[*x], *q);
+                DummyVal::new(*x, *q)
+            })
             .collect())
     }
 
@@ -141,13 +187,17 @@ impl FancyReveal for Dummy {
     fn reveal(&mut self, x: &DummyVal) -> Result<u16, DummyError> {
         Ok(x.val)
     }
+
+    fn reveal_to(&mut self, x: &DummyVal, _: RevealPolicy) -> Result<Option<u16>, DummyError> {
+        Ok(Some(x.val))
+    }
 }
 
 #[cfg(test)]
 mod bundle {
     use super::*;
     use crate::{
-        fancy::{BinaryGadgets, Bundle, BundleGadgets, CrtGadgets},
+        fancy::{BinaryGadgets, Bundle, BundleGadgets, CrtGadgets, ShiftOp},
         util::{self, RngExt},
     };
     use itertools::Itertools;
@@ -155,6 +205,28 @@ mod bundle {
 
     const NITERS: usize = 1 << 10;
 
+    #[test]
+    fn bytes_round_trip() {
+        let bytes: [u8; 5] = rand::random();
+        let bundle = BinaryBundle::from(bytes);
+        let bits = bundle.iter().map(DummyVal::val).collect_vec();
+        assert_eq!(bits, util::bytes_to_bits(&bytes));
+        assert_eq!(<[u8; 5]>::try_from(&bundle).unwrap(), bytes);
+        match <[u8; 4]>::try_from(&bundle) {
+            Err(FancyError::InvalidArgNum {
+                got: 40,
+                needed: 32,
+            }) => {}
+            r => panic!("expected an argument count error, got {:?}", r),
+        }
+        let mut vals = bundle.wires().to_vec();
+        vals[3] = DummyVal::new(2, 3);
+        match <[u8; 5]>::try_from(&BinaryBundle::new(vals)) {
+            Err(FancyError::InvalidArgMod { got: 3, needed: 2 }) => {}
+            r => panic!("expected a modulus error, got {:?}", r),
+        }
+    }
+
     #[test]
     fn test_addition() {
         let mut rng = thread_rng();
@@ -370,7 +442,8 @@ mod bundle {
     fn test_relu() {
         let mut rng = thread_rng();
         for _ in 0..NITERS {
-            let q = crate::util::modulus_with_nprimes(4 + rng.gen_usize() % 7); // exact relu supports up to 11 primes
+            // Exact relu supports up to 11 primes.
+            let q = crate::util::modulus_with_nprimes(4 + rng.gen_usize() % 7);
             let x = rng.gen_u128() % q;
             let mut d = Dummy::new();
             let out;
@@ -387,6 +460,24 @@ mod bundle {
         }
     }
 
+    #[test] // bundle sign against its plaintext reference
+    fn test_fractional_sign() {
+        // The mixed radix moduli the gadget uses for 4 primes at "100%".
+        let q = util::modulus_with_nprimes(4);
+        let ps = util::factor(q);
+        for x in 0..q {
+            let mut d = Dummy::new();
+            let out;
+            {
+                let x = d.crt_encode(x, q).unwrap();
+                let z = d.crt_sign(&x, "100%").unwrap();
+                out = d.output(&z).unwrap().unwrap();
+            }
+            let (negative, _) = util::crt_fractional_sign(&ps, &util::crt(x, &ps), &[3, 26]);
+            assert_eq!(out, negative as u16);
+        }
+    }
+
     #[test]
     fn test_mask() {
         let mut rng = thread_rng();
@@ -435,6 +526,49 @@ mod bundle {
         }
     }
 
+    #[test]
+    fn binary_shifts() {
+        let mut rng = thread_rng();
+        let nbits = 12;
+        let mask = (1 << nbits) - 1;
+        let ops = [
+            ShiftOp::Shl,
+            ShiftOp::Shr,
+            ShiftOp::Sar,
+            ShiftOp::Rotl,
+            ShiftOp::Rotr,
+        ];
+        for _ in 0..64 {
+            let x = rng.gen_u128() & mask;
+            // Amounts of 5 bits, including some beyond the width.
+            let n = rng.gen_usize() % 32;
+            let sign = if x >> (nbits - 1) == 1 { mask } else { 0 };
+            for &op in ops.iter() {
+                let n_ = n as u32;
+                let should_be = match op {
+                    ShiftOp::Shl => x.checked_shl(n_).unwrap_or(0) & mask,
+                    ShiftOp::Shr => x.checked_shr(n_).unwrap_or(0),
+                    ShiftOp::Sar if n >= nbits => sign,
+                    ShiftOp::Sar => (x >> n) | (sign << (nbits - n) & mask),
+                    ShiftOp::Rotl => (x << (n % nbits) | x >> (nbits - n % nbits)) & mask,
+                    ShiftOp::Rotr => (x >> (n % nbits) | x << (nbits - n % nbits)) & mask,
+                };
+                let mut d = Dummy::new();
+                let (out, out_private);
+                {
+                    let x = d.bin_encode(x, nbits).unwrap();
+                    let amt = d.bin_encode(n as u128, 5).unwrap();
+                    let z = d.bin_shift(&x, op, n).unwrap();
+                    out = d.bin_output(&z).unwrap().unwrap();
+                    let z = d.bin_barrel_shift(&x, op, &amt).unwrap();
+                    out_private = d.bin_output(&z).unwrap().unwrap();
+                }
+                assert_eq!(out, should_be, "{:?} x={} n={}", op, x, n);
+                assert_eq!(out_private, should_be, "{:?} x={} n={}", op, x, n);
+            }
+        }
+    }
+
     #[test]
     fn binary_demux() {
         let mut rng = thread_rng();
@@ -39,6 +39,8 @@ pub enum FancyError {
     InvalidTruthTable,
     /// Uninitialized value encountered.
     UninitializedValue,
+    /// A number-theoretic error has occurred.
+    NumberError(NumberError),
 }
 
 /// Errors from the dummy fancy object.
@@ -82,6 +84,8 @@ pub enum GarblerError {
     DeltaRequired,
     /// Encoding error.
     EncodingError,
+    /// A wire-label forwarded by the evaluator does not encode any value.
+    DecodingFailed,
     /// A fancy error has occurred.
     FancyError(FancyError),
 }
@@ -119,10 +123,17 @@ impl Display for FancyError {
             FancyError::UninitializedValue => {
                 "uninitialized value in circuit. is the circuit topologically sorted?".fmt(f)
             }
+            FancyError::NumberError(e) => write!(f, "number error: {}", e),
         }
     }
 }
 
+impl From<NumberError> for FancyError {
+    fn from(e: NumberError) -> Self {
+        FancyError::NumberError(e)
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 // Dummy error
 
@@ -198,6 +209,7 @@ impl Display for GarblerError {
             GarblerError::EncodingError => {
                 "encoding failed: unequal length input values and moduli".fmt(f)
             }
+            GarblerError::DecodingFailed => "decoding failed".fmt(f),
             GarblerError::FancyError(e) => write!(f, "{}", e),
         }
     }
@@ -256,6 +268,9 @@ pub enum CircuitParserError {
     ParseLineError(String),
     /// An error occurred parsing a gate type.
     ParseGateError(String),
+    /// The circuit cannot be written in the Bristol format, for the given
+    /// reason.
+    Unsupported(String),
 }
 
 impl Display for CircuitParserError {
@@ -266,6 +281,7 @@ impl Display for CircuitParserError {
             CircuitParserError::ParseIntError => write!(f, "unable to parse integer"),
             CircuitParserError::ParseLineError(s) => write!(f, "unable to parse line '{}'", s),
             CircuitParserError::ParseGateError(s) => write!(f, "unable to parse gate '{}'", s),
+            CircuitParserError::Unsupported(s) => write!(f, "cannot write as Bristol: {}", s),
         }
     }
 }
@@ -288,6 +304,178 @@ impl From<std::num::ParseIntError> for CircuitParserError {
     }
 }
 
+/// Errors emitted when parsing a program written in the language of `dsl`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DslError {
+    /// A line could not be parsed.
+    Syntax {
+        /// The line, counting from 1.
+        line: usize,
+        /// What is wrong with it.
+        reason: String,
+    },
+    /// A line refers to a name that has not been defined.
+    Undefined {
+        /// The line, counting from 1.
+        line: usize,
+        /// The undefined name.
+        name: String,
+    },
+    /// The moduli of a line's arguments do not suit its operation.
+    Modulus {
+        /// The line, counting from 1.
+        line: usize,
+        /// What is wrong with them.
+        reason: String,
+    },
+}
+
+impl Display for DslError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            DslError::Syntax { line, reason } => write!(f, "line {}: {}", line, reason),
+            DslError::Undefined { line, name } => {
+                write!(f, "line {}: undefined name {}", line, name)
+            }
+            DslError::Modulus { line, reason } => write!(f, "line {}: {}", line, reason),
+        }
+    }
+}
+
+impl std::error::Error for DslError {}
+
+/// Errors from the number-theoretic functions in `util`, which arise from
+/// invalid or untrusted input rather than from bugs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NumberError {
+    /// The number is not a product of distinct supported primes.
+    NotFactorable(u128),
+    /// The supported primes are not enough for the given number of bits.
+    NotEnoughPrimes(u32),
+    /// An arithmetic overflow occurred.
+    Overflow,
+    /// The modulus is not supported, e.g., because it is below 2.
+    InvalidModulus(u128),
+    /// The number has no inverse modulo the given modulus.
+    NotInvertible {
+        /// The number to invert.
+        x: i128,
+        /// The modulus.
+        modulus: i128,
+    },
+    /// No choice satisfies the given constraints, for the given reason.
+    Unsatisfiable(String),
+    /// A digit is not below its modulus.
+    InvalidDigit {
+        /// The digit.
+        digit: u16,
+        /// The modulus.
+        modulus: u16,
+    },
+    /// The number of digits does not match the number of moduli.
+    DigitCount {
+        /// The number of digits.
+        got: usize,
+        /// The number of moduli.
+        needed: usize,
+    },
+    /// The tables of a prime are inconsistent, for the given reason.
+    InconsistentTables {
+        /// The prime.
+        p: u16,
+        /// The first inconsistency found.
+        reason: String,
+    },
+}
+
+impl Display for NumberError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            NumberError::NotFactorable(x) => {
+                write!(f, "{} is not a product of distinct supported primes", x)
+            }
+            NumberError::NotEnoughPrimes(n) => write!(f, "not enough primes for {} bits", n),
+            NumberError::Overflow => "arithmetic overflow".fmt(f),
+            NumberError::InvalidModulus(q) => write!(f, "invalid modulus {}", q),
+            NumberError::NotInvertible { x, modulus } => {
+                write!(f, "{} is not invertible mod {}", x, modulus)
+            }
+            NumberError::Unsatisfiable(reason) => write!(f, "unsatisfiable: {}", reason),
+            NumberError::InvalidDigit { digit, modulus } => {
+                write!(f, "digit {} is not below modulus {}", digit, modulus)
+            }
+            NumberError::DigitCount { got, needed } => {
+                write!(f, "got {} digits, but {} moduli", got, needed)
+            }
+            NumberError::InconsistentTables { p, reason } => {
+                write!(f, "inconsistent tables for the prime {}: {}", p, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for NumberError {}
+
+/// Errors emitted when reading or writing the binary format in `format`.
+#[derive(Debug)]
+pub enum FormatError {
+    /// An I/O error occurred.
+    IoError(std::io::Error),
+    /// The data does not start with the expected magic bytes.
+    BadMagic([u8; 4]),
+    /// The data uses a major version this library cannot read.
+    UnsupportedVersion {
+        /// Major version of the data.
+        major: u8,
+        /// Minor version of the data.
+        minor: u8,
+    },
+    /// The stored checksum does not match the data.
+    ChecksumMismatch {
+        /// Checksum stored alongside the data.
+        stored: u32,
+        /// Checksum computed over the data.
+        computed: u32,
+    },
+    /// The data is malformed.
+    Malformed(String),
+    /// Evaluating a garbled circuit read from the data failed.
+    EvaluatorError(EvaluatorError),
+}
+
+impl Display for FormatError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            FormatError::IoError(e) => write!(f, "io error: {}", e),
+            FormatError::BadMagic(m) => write!(f, "bad magic bytes {:?}", m),
+            FormatError::UnsupportedVersion { major, minor } => {
+                write!(f, "unsupported format version {}.{}", major, minor)
+            }
+            FormatError::ChecksumMismatch { stored, computed } => write!(
+                f,
+                "checksum mismatch: stored {:#010x}, computed {:#010x}",
+                stored, computed
+            ),
+            FormatError::Malformed(s) => write!(f, "malformed data: {}", s),
+            FormatError::EvaluatorError(e) => write!(f, "evaluator error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+impl From<std::io::Error> for FormatError {
+    fn from(e: std::io::Error) -> FormatError {
+        FormatError::IoError(e)
+    }
+}
+
+impl From<EvaluatorError> for FormatError {
+    fn from(e: EvaluatorError) -> FormatError {
+        FormatError::EvaluatorError(e)
+    }
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 // 2PC errors
 
@@ -361,3 +549,38 @@ impl From<TwopacError> for EvaluatorError {
         EvaluatorError::CommunicationError(e.to_string())
     }
 }
+
+////////////////////////////////////////////////////////////////////////////////
+// 3PC errors
+
+/// Errors produced by `threepac`.
+#[derive(Debug)]
+pub enum ThreepacError {
+    /// An I/O error has occurred.
+    IoError(std::io::Error),
+    /// Processing the computation produced an error.
+    FancyError(FancyError),
+}
+
+impl std::error::Error for ThreepacError {}
+
+impl From<std::io::Error> for ThreepacError {
+    fn from(e: std::io::Error) -> ThreepacError {
+        ThreepacError::IoError(e)
+    }
+}
+
+impl From<FancyError> for ThreepacError {
+    fn from(e: FancyError) -> ThreepacError {
+        ThreepacError::FancyError(e)
+    }
+}
+
+impl std::fmt::Display for ThreepacError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ThreepacError::IoError(e) => write!(f, "IO error: {}", e),
+            ThreepacError::FancyError(e) => write!(f, "fancy error: {}", e),
+        }
+    }
+}
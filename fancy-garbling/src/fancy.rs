@@ -17,11 +17,11 @@ mod bundle;
 mod crt;
 mod input;
 mod reveal;
-pub use binary::{BinaryBundle, BinaryGadgets};
+pub use binary::{BinaryBundle, BinaryGadgets, ShiftOp};
 pub use bundle::{Bundle, BundleGadgets};
 pub use crt::{CrtBundle, CrtGadgets};
 pub use input::FancyInput;
-pub use reveal::FancyReveal;
+pub use reveal::{FancyReveal, RevealPolicy};
 
 /// An object that has some modulus. Basic object of `Fancy` computations.
 pub trait HasModulus {
@@ -18,6 +18,7 @@ use std::ops::Deref;
 
 /// Bundle which is explicitly binary representation.
 #[derive(Clone)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
 pub struct BinaryBundle<W>(Bundle<W>);
 
 impl<W: Clone + HasModulus> BinaryBundle<W> {
@@ -47,6 +48,23 @@ impl<W: Clone + HasModulus> From<Bundle<W>> for BinaryBundle<W> {
     }
 }
 
+/// A shift or rotation of the bits of a binary bundle, for `bin_shift` and
+/// `bin_barrel_shift`. Bundles are little-endian, so a left shift moves bits
+/// toward the most significant end.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ShiftOp {
+    /// Logical left shift, filling with zeros.
+    Shl,
+    /// Logical right shift, filling with zeros.
+    Shr,
+    /// Arithmetic right shift, filling with copies of the sign bit.
+    Sar,
+    /// Left rotation.
+    Rotl,
+    /// Right rotation.
+    Rotr,
+}
+
 impl<F: Fancy> BinaryGadgets for F {}
 
 /// Extension trait for `Fancy` providing gadgets that operate over bundles of mod2 wires.
@@ -265,6 +283,70 @@ pub trait BinaryGadgets: Fancy + BundleGadgets {
             })
     }
 
+    /// Shift or rotate the bits of `x` by the constant `n`, which only rearranges
+    /// wires. Shifting by at least the width of `x` gives all zeros, or all copies of
+    /// the sign bit for `ShiftOp::Sar`.
+    fn bin_shift(
+        &mut self,
+        x: &BinaryBundle<Self::Item>,
+        op: ShiftOp,
+        n: usize,
+    ) -> Result<BinaryBundle<Self::Item>, Self::Error> {
+        let mut ws = x.wires().to_vec();
+        let k = ws.len();
+        if k == 0 {
+            return Ok(x.clone());
+        }
+        let m = n.min(k);
+        match op {
+            ShiftOp::Rotl => ws.rotate_right(n % k),
+            ShiftOp::Rotr => ws.rotate_left(n % k),
+            ShiftOp::Shl => {
+                let zero = self.constant(0, 2)?;
+                ws.truncate(k - m);
+                ws = std::iter::repeat(zero).take(m).chain(ws).collect();
+            }
+            ShiftOp::Shr | ShiftOp::Sar => {
+                let fill = if op == ShiftOp::Sar {
+                    ws[k - 1].clone()
+                } else {
+                    self.constant(0, 2)?
+                };
+                ws.drain(..m);
+                ws.extend(std::iter::repeat(fill).take(m));
+            }
+        }
+        Ok(BinaryBundle::new(ws))
+    }
+
+    /// Shift or rotate the bits of `x` by the private amount `amt`, using a barrel
+    /// shifter: the `i`th bit of `amt` selects whether to shift by `2^i`, at a cost of
+    /// one multiplexer per bit of `x` for each bit of `amt`. As with `bin_shift`,
+    /// amounts of at least the width of `x` shift every bit out.
+    fn bin_barrel_shift(
+        &mut self,
+        x: &BinaryBundle<Self::Item>,
+        op: ShiftOp,
+        amt: &BinaryBundle<Self::Item>,
+    ) -> Result<BinaryBundle<Self::Item>, Self::Error> {
+        let k = x.size();
+        let rotate = op == ShiftOp::Rotl || op == ShiftOp::Rotr;
+        // The shift of each stage, `2^i`, reduced mod `k` for rotations and capped at
+        // `k` for shifts so that it never overflows.
+        let mut step = if rotate { 1 % k.max(1) } else { 1 };
+        let mut z = x.clone();
+        for b in amt.wires().iter() {
+            let shifted = self.bin_shift(&z, op, step)?;
+            z = self.multiplex(b, &z, &shifted).map(BinaryBundle)?;
+            step = if rotate {
+                2 * step % k.max(1)
+            } else {
+                (2 * step).min(k.max(1))
+            };
+        }
+        Ok(z)
+    }
+
     /// Compute the absolute value of a binary bundle.
     fn bin_abs(
         &mut self,
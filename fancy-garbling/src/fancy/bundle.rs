@@ -12,7 +12,8 @@ use itertools::Itertools;
 use std::ops::Index;
 
 /// A collection of wires, useful for the garbled gadgets defined by `BundleGadgets`.
-#[derive(Clone)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
 pub struct Bundle<W>(Vec<W>);
 
 impl<W: Clone + HasModulus> Bundle<W> {
@@ -81,6 +82,36 @@ impl<W: Clone + HasModulus> Index<usize> for Bundle<W> {
     }
 }
 
+impl<W: Clone + HasModulus> From<Vec<W>> for Bundle<W> {
+    fn from(ws: Vec<W>) -> Bundle<W> {
+        Bundle(ws)
+    }
+}
+
+impl<W: Clone + HasModulus> From<Bundle<W>> for Vec<W> {
+    fn from(b: Bundle<W>) -> Vec<W> {
+        b.0
+    }
+}
+
+impl<W: Clone + HasModulus> IntoIterator for Bundle<W> {
+    type Item = W;
+    type IntoIter = std::vec::IntoIter<W>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a, W: Clone + HasModulus> IntoIterator for &'a Bundle<W> {
+    type Item = &'a W;
+    type IntoIter = std::slice::Iter<'a, W>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
 impl<F: Fancy> BundleGadgets for F {}
 
 /// Extension trait for Fancy which provides Bundle constructions which are not
@@ -11,12 +11,14 @@ use crate::{
     errors::FancyError,
     fancy::bundle::{Bundle, BundleGadgets},
     util,
+    validate,
 };
 use itertools::Itertools;
 use std::ops::Deref;
 
 /// Bundle which is explicitly CRT-representation.
 #[derive(Clone)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
 pub struct CrtBundle<W>(Bundle<W>);
 
 impl<W: Clone + HasModulus> CrtBundle<W> {
@@ -61,17 +63,16 @@ pub trait CrtGadgets: Fancy + BundleGadgets {
         x: u128,
         q: u128,
     ) -> Result<CrtBundle<Self::Item>, Self::Error> {
-        let ps = util::factor(q);
+        let ps = util::try_factor(q).map_err(FancyError::from)?;
         let xs = ps.iter().map(|&p| (x % p as u128) as u16).collect_vec();
         self.constant_bundle(&xs, &ps).map(CrtBundle)
     }
 
     /// Output a CRT bundle and interpret it mod Q.
     fn crt_output(&mut self, x: &CrtBundle<Self::Item>) -> Result<Option<u128>, Self::Error> {
-        let q = x.composite_modulus();
-        Ok(self
-            .output_bundle(x)?
-            .map(|xs| util::crt_inv_factor(&xs, q)))
+        validate::check_crt_bundle(x)?;
+        let ps = x.moduli();
+        Ok(self.output_bundle(x)?.map(|xs| util::crt_inv(&xs, &ps)))
     }
 
     /// Output a slice of CRT bundles and interpret the outputs mod Q.
@@ -186,6 +187,7 @@ pub trait CrtGadgets: Fancy + BundleGadgets {
         let M = util::product(ms);
 
         let mut ds = Vec::new();
+        let mut digits = vec![0; ndigits];
 
         for wire in bun.wires().iter() {
             let p = wire.modulus();
@@ -195,7 +197,7 @@ pub trait CrtGadgets: Fancy + BundleGadgets {
             for x in 0..p {
                 let crt_coef = util::inv(((q / p as u128) % p as u128) as i128, p as i128);
                 let y = (M as f64 * x as f64 * crt_coef as f64 / p as f64).round() as u128 % M;
-                let digits = util::as_mixed_radix(y, ms);
+                util::as_mixed_radix_into(y, ms, &mut digits);
                 for i in 0..ndigits {
                     tabs[i].push(digits[i]);
                 }
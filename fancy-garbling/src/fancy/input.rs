@@ -111,14 +111,14 @@ pub trait FancyInput {
         value: u128,
         modulus: u128,
     ) -> Result<CrtBundle<Self::Item>, Self::Error> {
-        let qs = util::factor(modulus);
-        let xs = util::crt(value, &qs);
+        let qs = util::try_factor(modulus).map_err(FancyError::from)?;
+        let xs = util::DigitArray::crt(value, &qs);
         self.encode_bundle(&xs, &qs).map(CrtBundle::from)
     }
 
     /// Receive an CRT input bundle.
     fn crt_receive(&mut self, modulus: u128) -> Result<CrtBundle<Self::Item>, Self::Error> {
-        let qs = util::factor(modulus);
+        let qs = util::try_factor(modulus).map_err(FancyError::from)?;
         self.receive_bundle(&qs).map(CrtBundle::from)
     }
 
@@ -128,13 +128,12 @@ pub trait FancyInput {
         values: &[u128],
         modulus: u128,
     ) -> Result<Vec<CrtBundle<Self::Item>>, Self::Error> {
-        let mods = util::factor(modulus);
+        let mods = util::try_factor(modulus).map_err(FancyError::from)?;
         let nmods = mods.len();
-        let xs = values
-            .iter()
-            .map(|x| util::crt(*x, &mods))
-            .flatten()
-            .collect_vec();
+        let mut xs = vec![0; values.len() * nmods];
+        for (&x, ds) in values.iter().zip(xs.chunks_mut(nmods.max(1))) {
+            util::crt_into(x, &mods, ds);
+        }
         let qs = itertools::repeat_n(mods, values.len())
             .flatten()
             .collect_vec();
@@ -154,7 +153,7 @@ pub trait FancyInput {
         n: usize,
         modulus: u128,
     ) -> Result<Vec<CrtBundle<Self::Item>>, Self::Error> {
-        let mods = util::factor(modulus);
+        let mods = util::try_factor(modulus).map_err(FancyError::from)?;
         let nmods = mods.len();
         let qs = itertools::repeat_n(mods, n).flatten().collect_vec();
         let mut wires = self.receive_many(&qs)?;
@@ -5,7 +5,18 @@
 // See LICENSE for licensing information.
 
 use super::*;
-use crate::util;
+use crate::{util, validate};
+
+/// Which parties learn an output revealed by `FancyReveal::reveal_to`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RevealPolicy {
+    /// Only the garbler learns the output.
+    Garbler,
+    /// Only the evaluator learns the output.
+    Evaluator,
+    /// Both parties learn the output, as with `FancyReveal::reveal`.
+    Both,
+}
 
 /// Trait to describe Fancy objects which can reveal outputs to both parties. For many
 /// simple Fancy objects in this library such as Dummy, this is simply output. For Garbler
@@ -15,6 +26,38 @@ pub trait FancyReveal: Fancy {
     /// Reveal the contents of `x` to all parties.
     fn reveal(&mut self, x: &Self::Item) -> Result<u16, Self::Error>;
 
+    /// Reveal the contents of `x` to the parties given by `policy`. Parties
+    /// that learn it get `Some` value, and the others get `None`.
+    ///
+    /// By default only `RevealPolicy::Both` is supported, as `reveal`, and
+    /// any other policy is an error.
+    fn reveal_to(
+        &mut self,
+        x: &Self::Item,
+        policy: RevealPolicy,
+    ) -> Result<Option<u16>, Self::Error> {
+        match policy {
+            RevealPolicy::Both => self.reveal(x).map(Some),
+            _ => Err(Self::Error::from(FancyError::InvalidArg(format!(
+                "reveal policy {:?} is not supported",
+                policy
+            )))),
+        }
+    }
+
+    /// Reveal a slice of items to the parties given by `policy`.
+    fn reveal_many_to(
+        &mut self,
+        xs: &[Self::Item],
+        policy: RevealPolicy,
+    ) -> Result<Option<Vec<u16>>, Self::Error> {
+        let mut zs = Vec::with_capacity(xs.len());
+        for x in xs.iter() {
+            zs.push(self.reveal_to(x, policy)?);
+        }
+        Ok(zs.into_iter().collect())
+    }
+
     /// Reveal a slice of items to all parties.
     fn reveal_many(&mut self, xs: &[Self::Item]) -> Result<Vec<u16>, Self::Error> {
         let mut zs = Vec::with_capacity(xs.len());
@@ -43,9 +86,9 @@ pub trait FancyReveal: Fancy {
 
     /// Reveal a CRT bundle to all parties.
     fn crt_reveal(&mut self, x: &CrtBundle<Self::Item>) -> Result<u128, Self::Error> {
-        let q = x.composite_modulus();
+        validate::check_crt_bundle(x)?;
         let xs = self.reveal_many(x.wires())?;
-        Ok(util::crt_inv_factor(&xs, q))
+        Ok(util::crt_inv(&xs, &x.moduli()))
     }
 
     /// Reveal many CRT bundles to all parties.
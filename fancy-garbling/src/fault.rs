@@ -0,0 +1,185 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Fault injection for garbled data.
+//!
+//! These functions corrupt garbled circuits and encoded inputs using the
+//! probabilities of a `scuttlebutt::channel::FaultConfig`, treating each block
+//! as a message. Together with `FaultChannel` for streaming protocols, they
+//! check that the evaluator fails safely under corruption: it returns an error
+//! or the correct outputs, but never panics or accepts wrong outputs.
+
+use crate::{
+    circuit::Circuit,
+    classic::{garble_with_rng, GarbledCircuit},
+    fancy::HasModulus,
+    wire::Wire,
+};
+use rand::Rng;
+use scuttlebutt::{channel::FaultConfig, AesRng, Block};
+
+/// Corrupt a sequence of blocks, returning the corrupted blocks and the
+/// number of faults injected. Each block may have a bit flipped, be
+/// duplicated, or be swapped with the block after it, and the sequence may be
+/// truncated at any block.
+pub fn corrupt_blocks(
+    blocks: &[Block],
+    config: &FaultConfig,
+    rng: &mut AesRng,
+) -> (Vec<Block>, usize) {
+    let mut out = Vec::with_capacity(blocks.len());
+    let mut nfaults = 0;
+    let mut held: Option<Block> = None;
+    for block in blocks.iter() {
+        if rng.gen_bool(config.truncate) {
+            nfaults += 1;
+            break;
+        }
+        let mut bytes: [u8; 16] = (*block).into();
+        if rng.gen_bool(config.flip) {
+            let i = rng.gen_range(0, 128);
+            bytes[i / 8] ^= 1 << (i % 8);
+            nfaults += 1;
+        }
+        let block = Block::from(bytes);
+        if rng.gen_bool(config.duplicate) {
+            out.push(block);
+            nfaults += 1;
+        }
+        match held.take() {
+            Some(prev) => {
+                out.push(block);
+                out.push(prev);
+            }
+            None if rng.gen_bool(config.reorder) => {
+                held = Some(block);
+                nfaults += 1;
+            }
+            None => out.push(block),
+        }
+    }
+    out.extend(held);
+    (out, nfaults)
+}
+
+/// Corrupt a garbled circuit, returning the corrupted circuit and the number
+/// of faults injected.
+pub fn corrupt_garbled_circuit(
+    gc: &GarbledCircuit,
+    config: &FaultConfig,
+    rng: &mut AesRng,
+) -> (GarbledCircuit, usize) {
+    let (blocks, nfaults) = corrupt_blocks(gc.blocks(), config, rng);
+    (GarbledCircuit::new(blocks), nfaults)
+}
+
+/// Corrupt encoded inputs by flipping bits of their wire-labels, returning the
+/// corrupted wires and the number of faults injected. Only `config.flip` is
+/// used, since the number and order of inputs are fixed by the circuit.
+pub fn corrupt_wires(ws: &[Wire], config: &FaultConfig, rng: &mut AesRng) -> (Vec<Wire>, usize) {
+    let mut nfaults = 0;
+    let ws = ws
+        .iter()
+        .map(|w| {
+            if !rng.gen_bool(config.flip) {
+                return w.clone();
+            }
+            let mut bytes: [u8; 16] = w.as_block().into();
+            let i = rng.gen_range(0, 128);
+            bytes[i / 8] ^= 1 << (i % 8);
+            match Wire::try_from_block(Block::from(bytes), w.modulus()) {
+                Ok(w) => {
+                    nfaults += 1;
+                    w
+                }
+                // A mod-3 wire-label with a flipped bit may not be a
+                // wire-label at all, which is rejected when it is decoded,
+                // so it never reaches the evaluator.
+                Err(_) => w.clone(),
+            }
+        })
+        .collect();
+    (ws, nfaults)
+}
+
+/// Garble `c` with `rng`, corrupt the garbled circuit and the encoded inputs,
+/// and evaluate the result, returning a description of the failure if the
+/// evaluator accepts wrong outputs.
+///
+/// # Panics
+///
+/// A panic during evaluation propagates, and indicates a bug.
+pub fn check_fails_safely(
+    c: &Circuit,
+    garbler_inputs: &[u16],
+    evaluator_inputs: &[u16],
+    config: &FaultConfig,
+    mut rng: AesRng,
+) -> Result<(), String> {
+    let expected = c
+        .eval_plain(garbler_inputs, evaluator_inputs)
+        .map_err(|e| e.to_string())?;
+    let (en, gc) = garble_with_rng(c, rng.fork()).map_err(|e| e.to_string())?;
+    let (gc, n1) = corrupt_garbled_circuit(&gc, config, &mut rng);
+    let (gb, n2) = corrupt_wires(&en.encode_garbler_inputs(garbler_inputs), config, &mut rng);
+    let (ev, n3) = corrupt_wires(
+        &en.encode_evaluator_inputs(evaluator_inputs),
+        config,
+        &mut rng,
+    );
+    match gc.eval(c, &gb, &ev) {
+        Ok(outputs) if outputs != expected => Err(format!(
+            "accepted wrong outputs {:?} instead of {:?} after {} faults",
+            outputs,
+            expected,
+            n1 + n2 + n3
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{kat, util::RngExt};
+
+    #[test]
+    fn corrupt_blocks_faults() {
+        let mut rng = AesRng::new();
+        let blocks = (0..64).map(|_| rng.gen::<Block>()).collect::<Vec<_>>();
+        let (out, nfaults) = corrupt_blocks(&blocks, &FaultConfig::none(), &mut rng);
+        assert_eq!((out, nfaults), (blocks.clone(), 0));
+
+        let mut config = FaultConfig::none();
+        config.reorder = 1.0;
+        let (out, _) = corrupt_blocks(&blocks[..3], &config, &mut rng);
+        assert_eq!(out, vec![blocks[1], blocks[0], blocks[2]]);
+
+        let mut config = FaultConfig::none();
+        config.truncate = 1.0;
+        assert_eq!(corrupt_blocks(&blocks, &config, &mut rng), (vec![], 1));
+    }
+
+    #[test]
+    fn evaluator_fails_safely() {
+        let mut rng = AesRng::new();
+        for &p in [0.01, 0.1, 0.5].iter() {
+            let config = FaultConfig::uniform(p);
+            for name in kat::CIRCUITS.iter() {
+                let c = kat::circuit(name).unwrap();
+                for _ in 0..64 {
+                    let gb = (0..c.num_garbler_inputs())
+                        .map(|i| rng.gen_mod(c.garbler_input_mod(i)))
+                        .collect::<Vec<_>>();
+                    let ev = (0..c.num_evaluator_inputs())
+                        .map(|i| rng.gen_mod(c.evaluator_input_mod(i)))
+                        .collect::<Vec<_>>();
+                    check_fails_safely(&c, &gb, &ev, &config, rng.fork()).unwrap();
+                }
+            }
+        }
+    }
+}
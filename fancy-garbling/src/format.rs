@@ -0,0 +1,872 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! A stable, versioned binary format for circuits, garbled circuits, and encoded
+//! inputs.
+//!
+//! Every artifact is laid out as follows, with all integers little-endian:
+//!
+//! | field        | size          | description                               |
+//! |--------------|---------------|-------------------------------------------|
+//! | `magic`      | 4             | `FGCT`, `FGGC`, or `FGEW`, see below      |
+//! | `major`      | 1             | major format version                      |
+//! | `minor`      | 1             | minor format version                      |
+//! | `header_len` | 4             | length of the header in bytes             |
+//! | `header`     | `header_len`  | artifact-specific header                  |
+//! | `body`       | variable      | artifact-specific body                    |
+//! | `checksum`   | 4             | CRC-32 of all preceding bytes             |
+//!
+//! Circuits have magic `FGCT`, garbled circuits `FGGC`, and encoded wires
+//! `FGEW`. A garbled circuit's header holds the number of blocks as a `u64`,
+//! and its body holds the blocks themselves. Encoded wires have a header
+//! holding the number of wires as a `u64`, followed by a modulus table (the
+//! number of distinct moduli as a `u16` and then the moduli as `u16`s), and a
+//! body holding, for each wire, the index of its modulus in the table as a
+//! `u16` followed by the 16-byte wire-label.
+//!
+//! A circuit's header holds, as `u64`s, the number of gates, garbler inputs,
+//! evaluator inputs, constants, outputs, and non-free gates. Its body holds
+//! each gate as an opcode byte, the gate's modulus as a `u16`, and the gate's
+//! fields, followed by the lists of garbler input, evaluator input, constant,
+//! and output references. A reference is its index as a `u64` followed by its
+//! modulus as a `u16`, and an absent output index is written as `u64::MAX`.
+//!
+//! Compatibility rules: readers reject data with a different major version.
+//! Newer minor versions may only append fields to the end of the header, so
+//! readers accept any minor version and skip header bytes they do not
+//! understand.
+
+use crate::{
+    circuit::{Circuit, CircuitRef, Gate},
+    classic::GarbledCircuit,
+    errors::FormatError,
+    fancy::HasModulus,
+    garble::Evaluator,
+    wire::Wire,
+};
+use scuttlebutt::{Block, Channel};
+use std::io::{Read, Write};
+
+/// Magic bytes identifying a garbled circuit.
+pub const GARBLED_CIRCUIT_MAGIC: [u8; 4] = *b"FGGC";
+/// Magic bytes identifying a list of encoded wires.
+pub const ENCODED_WIRES_MAGIC: [u8; 4] = *b"FGEW";
+/// Magic bytes identifying a circuit.
+pub const CIRCUIT_MAGIC: [u8; 4] = *b"FGCT";
+/// The major version written by this library.
+pub const MAJOR_VERSION: u8 = 1;
+/// The minor version written by this library.
+pub const MINOR_VERSION: u8 = 0;
+
+/// CRC-32 (IEEE) state.
+struct Crc32 {
+    table: [u32; 256],
+    crc: u32,
+}
+
+impl Crc32 {
+    fn new() -> Self {
+        let mut table = [0u32; 256];
+        for (i, entry) in table.iter_mut().enumerate() {
+            let mut c = i as u32;
+            for _ in 0..8 {
+                c = if c & 1 == 1 {
+                    0xEDB8_8320 ^ (c >> 1)
+                } else {
+                    c >> 1
+                };
+            }
+            *entry = c;
+        }
+        Crc32 {
+            table,
+            crc: 0xFFFF_FFFF,
+        }
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.crc = self.table[((self.crc ^ b as u32) & 0xFF) as usize] ^ (self.crc >> 8);
+        }
+    }
+
+    fn finish(&self) -> u32 {
+        self.crc ^ 0xFFFF_FFFF
+    }
+}
+
+struct Writer<'a, W> {
+    inner: &'a mut W,
+    crc: Crc32,
+}
+
+impl<'a, W: Write> Writer<'a, W> {
+    fn new(inner: &'a mut W) -> Self {
+        Writer {
+            inner,
+            crc: Crc32::new(),
+        }
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), FormatError> {
+        self.crc.update(bytes);
+        self.inner.write_all(bytes)?;
+        Ok(())
+    }
+
+    fn write_preamble(&mut self, magic: [u8; 4], header: &[u8]) -> Result<(), FormatError> {
+        self.write(&magic)?;
+        self.write(&[MAJOR_VERSION, MINOR_VERSION])?;
+        self.write(&(header.len() as u32).to_le_bytes())?;
+        self.write(header)
+    }
+
+    fn finish(self) -> Result<(), FormatError> {
+        let checksum = self.crc.finish();
+        self.inner.write_all(&checksum.to_le_bytes())?;
+        self.inner.flush()?;
+        Ok(())
+    }
+}
+
+struct Reader<'a, R> {
+    inner: &'a mut R,
+    crc: Crc32,
+}
+
+impl<'a, R: Read> Reader<'a, R> {
+    fn new(inner: &'a mut R) -> Self {
+        Reader {
+            inner,
+            crc: Crc32::new(),
+        }
+    }
+
+    fn read(&mut self, bytes: &mut [u8]) -> Result<(), FormatError> {
+        self.inner.read_exact(bytes)?;
+        self.crc.update(bytes);
+        Ok(())
+    }
+
+    fn read_u8(&mut self) -> Result<u8, FormatError> {
+        let mut b = [0u8; 1];
+        self.read(&mut b)?;
+        Ok(b[0])
+    }
+
+    fn read_u16(&mut self) -> Result<u16, FormatError> {
+        let mut b = [0u8; 2];
+        self.read(&mut b)?;
+        Ok(u16::from_le_bytes(b))
+    }
+
+    fn read_u64(&mut self) -> Result<u64, FormatError> {
+        let mut b = [0u8; 8];
+        self.read(&mut b)?;
+        Ok(u64::from_le_bytes(b))
+    }
+
+    fn read_block(&mut self) -> Result<Block, FormatError> {
+        let mut b = [0u8; 16];
+        self.read(&mut b)?;
+        Ok(Block::from(b))
+    }
+
+    /// Read the magic bytes, version, and header, returning the header.
+    fn read_preamble(&mut self, magic: [u8; 4]) -> Result<Vec<u8>, FormatError> {
+        let mut m = [0u8; 4];
+        self.read(&mut m)?;
+        if m != magic {
+            return Err(FormatError::BadMagic(m));
+        }
+        let mut version = [0u8; 2];
+        self.read(&mut version)?;
+        if version[0] != MAJOR_VERSION {
+            return Err(FormatError::UnsupportedVersion {
+                major: version[0],
+                minor: version[1],
+            });
+        }
+        let mut len = [0u8; 4];
+        self.read(&mut len)?;
+        // Don't trust the length enough to allocate it up front.
+        let len = u32::from_le_bytes(len) as u64;
+        let mut header = Vec::new();
+        self.inner.by_ref().take(len).read_to_end(&mut header)?;
+        if header.len() as u64 != len {
+            return Err(FormatError::Malformed("truncated header".to_string()));
+        }
+        self.crc.update(&header);
+        Ok(header)
+    }
+
+    fn finish(self) -> Result<(), FormatError> {
+        let computed = self.crc.finish();
+        let mut b = [0u8; 4];
+        self.inner.read_exact(&mut b)?;
+        let stored = u32::from_le_bytes(b);
+        if stored != computed {
+            return Err(FormatError::ChecksumMismatch { stored, computed });
+        }
+        Ok(())
+    }
+}
+
+/// A cursor over a header, which fails on truncated headers and ignores any
+/// trailing fields.
+struct Header<'a>(&'a [u8]);
+
+impl<'a> Header<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], FormatError> {
+        if self.0.len() < n {
+            return Err(FormatError::Malformed("truncated header".to_string()));
+        }
+        let (x, rest) = self.0.split_at(n);
+        self.0 = rest;
+        Ok(x)
+    }
+
+    fn u16(&mut self) -> Result<u16, FormatError> {
+        let mut b = [0u8; 2];
+        b.copy_from_slice(self.take(2)?);
+        Ok(u16::from_le_bytes(b))
+    }
+
+    fn u64(&mut self) -> Result<u64, FormatError> {
+        let mut b = [0u8; 8];
+        b.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(b))
+    }
+}
+
+impl GarbledCircuit {
+    /// Write the garbled circuit to `writer` in the versioned binary format.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), FormatError> {
+        let mut w = Writer::new(writer);
+        w.write_preamble(GARBLED_CIRCUIT_MAGIC, &(self.size() as u64).to_le_bytes())?;
+        for block in self.blocks() {
+            w.write(block.as_ref())?;
+        }
+        w.finish()
+    }
+
+    /// Read a garbled circuit written by `write_to` from `reader`.
+    pub fn read_from<R: Read>(reader: &mut R) -> Result<Self, FormatError> {
+        let mut r = Reader::new(reader);
+        let header = r.read_preamble(GARBLED_CIRCUIT_MAGIC)?;
+        let nblocks = Header(&header).u64()? as usize;
+        let blocks = (0..nblocks)
+            .map(|_| r.read_block())
+            .collect::<Result<Vec<Block>, FormatError>>()?;
+        r.finish()?;
+        Ok(GarbledCircuit::new(blocks))
+    }
+
+    /// Evaluate a garbled circuit written by `write_to` directly from
+    /// `reader`, reading each block only when evaluation reaches it rather than
+    /// loading them all first. Memory use does not grow with the size of the
+    /// garbled circuit, so a `BufReader` over a file can evaluate one larger
+    /// than RAM.
+    ///
+    /// The checksum covers the whole file, so it can only be checked once
+    /// evaluation is done. The outputs are returned only if it matches, and if
+    /// `c` consumed exactly the blocks of the garbled circuit. If evaluation
+    /// fails, the rest of the data is read so that a checksum mismatch is
+    /// reported instead when it is the cause.
+    pub fn eval_from<R: Read>(
+        reader: &mut R,
+        c: &Circuit,
+        garbler_inputs: &[Wire],
+        evaluator_inputs: &[Wire],
+    ) -> Result<Vec<u16>, FormatError> {
+        let mut r = Reader::new(reader);
+        let header = r.read_preamble(GARBLED_CIRCUIT_MAGIC)?;
+        let remaining = Header(&header)
+            .u64()?
+            .checked_mul(16)
+            .ok_or_else(|| FormatError::Malformed("too many blocks".to_string()))?;
+        let mut blocks = BlockStream {
+            reader: &mut r,
+            remaining,
+        };
+        let channel = Channel::new(&mut blocks, std::io::sink());
+        let mut evaluator = Evaluator::new(channel);
+        let outputs = c.eval(&mut evaluator, garbler_inputs, evaluator_inputs);
+        drop(evaluator);
+        let outputs = match outputs {
+            Ok(outputs) => outputs,
+            Err(e) => {
+                // Report corrupted data ahead of the failure it caused.
+                std::io::copy(&mut blocks, &mut std::io::sink())?;
+                r.finish()?;
+                return Err(e.into());
+            }
+        };
+        if blocks.remaining > 0 {
+            return Err(FormatError::Malformed(
+                "garbled circuit has blocks the circuit does not use".to_string(),
+            ));
+        }
+        r.finish()?;
+        Ok(outputs.expect("evaluator outputs always are Some(u16)"))
+    }
+}
+
+/// The body of a garbled circuit, read through the checksum of `reader`, as
+/// a stream of `remaining` bytes.
+struct BlockStream<'a, 'b, R> {
+    reader: &'b mut Reader<'a, R>,
+    remaining: u64,
+}
+
+impl<'a, 'b, R: Read> Read for BlockStream<'a, 'b, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = std::cmp::min(buf.len() as u64, self.remaining) as usize;
+        let n = self.reader.inner.read(&mut buf[..n])?;
+        self.reader.crc.update(&buf[..n]);
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+// Circuit gate opcodes.
+const GARBLER_INPUT: u8 = 0;
+const EVALUATOR_INPUT: u8 = 1;
+const CONSTANT: u8 = 2;
+const ADD: u8 = 3;
+const SUB: u8 = 4;
+const CMUL: u8 = 5;
+const MUL: u8 = 6;
+const PROJ: u8 = 7;
+
+impl<'a, W: Write> Writer<'a, W> {
+    fn write_u64(&mut self, x: u64) -> Result<(), FormatError> {
+        self.write(&x.to_le_bytes())
+    }
+
+    fn write_ref(&mut self, r: &CircuitRef) -> Result<(), FormatError> {
+        self.write_u64(r.ix as u64)?;
+        self.write(&r.modulus.to_le_bytes())
+    }
+
+    fn write_out(&mut self, out: Option<usize>) -> Result<(), FormatError> {
+        self.write_u64(out.map_or(u64::max_value(), |ix| ix as u64))
+    }
+}
+
+impl<'a, R: Read> Reader<'a, R> {
+    /// Read an index, which must be below `n`.
+    fn read_index(&mut self, n: usize, what: &str) -> Result<usize, FormatError> {
+        let ix = self.read_u64()?;
+        if ix >= n as u64 {
+            return Err(FormatError::Malformed(format!(
+                "{} {} out of range",
+                what, ix
+            )));
+        }
+        Ok(ix as usize)
+    }
+
+    fn read_modulus(&mut self) -> Result<u16, FormatError> {
+        let q = self.read_u16()?;
+        if q < 2 {
+            return Err(FormatError::Malformed(format!("invalid modulus {}", q)));
+        }
+        Ok(q)
+    }
+
+    fn read_ref(&mut self, ngates: usize) -> Result<CircuitRef, FormatError> {
+        let ix = self.read_index(ngates, "reference")?;
+        let modulus = self.read_modulus()?;
+        Ok(CircuitRef { ix, modulus })
+    }
+
+    fn read_out(&mut self, ngates: usize) -> Result<Option<usize>, FormatError> {
+        match self.read_u64()? {
+            ix if ix == u64::max_value() => Ok(None),
+            ix if ix < ngates as u64 => Ok(Some(ix as usize)),
+            ix => Err(FormatError::Malformed(format!(
+                "output index {} out of range",
+                ix
+            ))),
+        }
+    }
+}
+
+impl Circuit {
+    /// Write the circuit to `writer` in the versioned binary format.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), FormatError> {
+        let mut header = Vec::with_capacity(48);
+        for n in [
+            self.gates.len(),
+            self.garbler_input_refs.len(),
+            self.evaluator_input_refs.len(),
+            self.const_refs.len(),
+            self.output_refs.len(),
+            self.num_nonfree_gates,
+        ]
+        .iter()
+        {
+            header.extend_from_slice(&(*n as u64).to_le_bytes());
+        }
+
+        let mut w = Writer::new(writer);
+        w.write_preamble(CIRCUIT_MAGIC, &header)?;
+        for (gate, q) in self.gates.iter().zip(self.gate_moduli.iter()) {
+            let opcode = match gate {
+                Gate::GarblerInput { .. } => GARBLER_INPUT,
+                Gate::EvaluatorInput { .. } => EVALUATOR_INPUT,
+                Gate::Constant { .. } => CONSTANT,
+                Gate::Add { .. } => ADD,
+                Gate::Sub { .. } => SUB,
+                Gate::Cmul { .. } => CMUL,
+                Gate::Mul { .. } => MUL,
+                Gate::Proj { .. } => PROJ,
+            };
+            w.write(&[opcode])?;
+            w.write(&q.to_le_bytes())?;
+            match gate {
+                Gate::GarblerInput { id } | Gate::EvaluatorInput { id } => {
+                    w.write_u64(*id as u64)?
+                }
+                Gate::Constant { val } => w.write(&val.to_le_bytes())?,
+                Gate::Add { xref, yref, out } | Gate::Sub { xref, yref, out } => {
+                    w.write_ref(xref)?;
+                    w.write_ref(yref)?;
+                    w.write_out(*out)?;
+                }
+                Gate::Cmul { xref, c, out } => {
+                    w.write_ref(xref)?;
+                    w.write(&c.to_le_bytes())?;
+                    w.write_out(*out)?;
+                }
+                Gate::Mul {
+                    xref,
+                    yref,
+                    id,
+                    out,
+                } => {
+                    w.write_ref(xref)?;
+                    w.write_ref(yref)?;
+                    w.write_u64(*id as u64)?;
+                    w.write_out(*out)?;
+                }
+                Gate::Proj { xref, tt, id, out } => {
+                    w.write_ref(xref)?;
+                    w.write_u64(tt.len() as u64)?;
+                    for x in tt.iter() {
+                        w.write(&x.to_le_bytes())?;
+                    }
+                    w.write_u64(*id as u64)?;
+                    w.write_out(*out)?;
+                }
+            }
+        }
+        for refs in [
+            &self.garbler_input_refs,
+            &self.evaluator_input_refs,
+            &self.const_refs,
+            &self.output_refs,
+        ]
+        .iter()
+        {
+            for r in refs.iter() {
+                w.write_ref(r)?;
+            }
+        }
+        w.finish()
+    }
+
+    /// Read a circuit written by `write_to` from `reader`, checking that every
+    /// index in it is in range, that every reference has the modulus of the
+    /// gate it refers to, and that every truth table fits its moduli.
+    pub fn read_from<R: Read>(reader: &mut R) -> Result<Self, FormatError> {
+        let mut r = Reader::new(reader);
+        let header = r.read_preamble(CIRCUIT_MAGIC)?;
+        let mut header = Header(&header);
+        let mut counts = [0usize; 6];
+        for n in counts.iter_mut() {
+            *n = header.u64()? as usize;
+        }
+        let [ngates, ngb, nev, nconsts, noutputs, nnonfree] = counts;
+
+        // Don't trust the counts enough to allocate them up front.
+        let mut c = Circuit::new(None);
+        for _ in 0..ngates {
+            let opcode = r.read_u8()?;
+            let q = r.read_modulus()?;
+            let gate = match opcode {
+                GARBLER_INPUT => Gate::GarblerInput {
+                    id: r.read_index(ngb, "garbler input")?,
+                },
+                EVALUATOR_INPUT => Gate::EvaluatorInput {
+                    id: r.read_index(nev, "evaluator input")?,
+                },
+                CONSTANT => Gate::Constant { val: r.read_u16()? },
+                ADD | SUB => {
+                    let xref = r.read_ref(ngates)?;
+                    let yref = r.read_ref(ngates)?;
+                    let out = r.read_out(ngates)?;
+                    if opcode == ADD {
+                        Gate::Add { xref, yref, out }
+                    } else {
+                        Gate::Sub { xref, yref, out }
+                    }
+                }
+                CMUL => Gate::Cmul {
+                    xref: r.read_ref(ngates)?,
+                    c: r.read_u16()?,
+                    out: r.read_out(ngates)?,
+                },
+                MUL => Gate::Mul {
+                    xref: r.read_ref(ngates)?,
+                    yref: r.read_ref(ngates)?,
+                    id: r.read_index(nnonfree, "ciphertext")?,
+                    out: r.read_out(ngates)?,
+                },
+                PROJ => {
+                    let xref = r.read_ref(ngates)?;
+                    let n = r.read_index(1 << 16, "truth table length")?;
+                    let tt = (0..n)
+                        .map(|_| r.read_u16())
+                        .collect::<Result<Vec<u16>, FormatError>>()?;
+                    if n != xref.modulus as usize {
+                        return Err(FormatError::Malformed(format!(
+                            "truth table of length {} for modulus {}",
+                            n, xref.modulus
+                        )));
+                    }
+                    if let Some(x) = tt.iter().find(|&&x| x >= q) {
+                        return Err(FormatError::Malformed(format!(
+                            "truth table entry {} out of range for modulus {}",
+                            x, q
+                        )));
+                    }
+                    Gate::Proj {
+                        xref,
+                        tt,
+                        id: r.read_index(nnonfree, "ciphertext")?,
+                        out: r.read_out(ngates)?,
+                    }
+                }
+                op => return Err(FormatError::Malformed(format!("invalid opcode {}", op))),
+            };
+            c.gates.push(gate);
+            c.gate_moduli.push(q);
+        }
+        for (refs, n) in [
+            (&mut c.garbler_input_refs, ngb),
+            (&mut c.evaluator_input_refs, nev),
+            (&mut c.const_refs, nconsts),
+            (&mut c.output_refs, noutputs),
+        ]
+        .iter_mut()
+        {
+            for _ in 0..*n {
+                refs.push(r.read_ref(ngates)?);
+            }
+        }
+        c.num_nonfree_gates = nnonfree;
+        r.finish()?;
+        c.check_ref_moduli()?;
+        Ok(c)
+    }
+
+    /// Check that every reference has the modulus of the gate it refers to.
+    fn check_ref_moduli(&self) -> Result<(), FormatError> {
+        let gate_refs = self.gates.iter().flat_map(|gate| match gate {
+            Gate::Add { xref, yref, .. }
+            | Gate::Sub { xref, yref, .. }
+            | Gate::Mul { xref, yref, .. } => vec![xref, yref],
+            Gate::Cmul { xref, .. } | Gate::Proj { xref, .. } => vec![xref],
+            _ => vec![],
+        });
+        let refs = gate_refs
+            .chain(self.garbler_input_refs.iter())
+            .chain(self.evaluator_input_refs.iter())
+            .chain(self.const_refs.iter())
+            .chain(self.output_refs.iter());
+        for r in refs {
+            if r.modulus != self.gate_moduli[r.ix] {
+                return Err(FormatError::Malformed(format!(
+                    "reference to gate {} with modulus {} instead of {}",
+                    r.ix, r.modulus, self.gate_moduli[r.ix]
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Write encoded wires, e.g. the output of `Encoder::encode_garbler_inputs`,
+/// to `writer` in the versioned binary format.
+pub fn write_wires<W: Write>(wires: &[Wire], writer: &mut W) -> Result<(), FormatError> {
+    let mut moduli: Vec<u16> = wires.iter().map(|w| w.modulus()).collect();
+    moduli.sort();
+    moduli.dedup();
+
+    let mut header = Vec::with_capacity(10 + 2 * moduli.len());
+    header.extend_from_slice(&(wires.len() as u64).to_le_bytes());
+    header.extend_from_slice(&(moduli.len() as u16).to_le_bytes());
+    for q in moduli.iter() {
+        header.extend_from_slice(&q.to_le_bytes());
+    }
+
+    let mut w = Writer::new(writer);
+    w.write_preamble(ENCODED_WIRES_MAGIC, &header)?;
+    for wire in wires {
+        let ix = moduli.binary_search(&wire.modulus()).unwrap() as u16;
+        w.write(&ix.to_le_bytes())?;
+        w.write(wire.as_block().as_ref())?;
+    }
+    w.finish()
+}
+
+/// Read encoded wires written by `write_wires` from `reader`.
+pub fn read_wires<R: Read>(reader: &mut R) -> Result<Vec<Wire>, FormatError> {
+    let mut r = Reader::new(reader);
+    let header = r.read_preamble(ENCODED_WIRES_MAGIC)?;
+    let mut header = Header(&header);
+    let nwires = header.u64()? as usize;
+    let nmoduli = header.u16()? as usize;
+    let moduli = (0..nmoduli)
+        .map(|_| header.u16())
+        .collect::<Result<Vec<u16>, FormatError>>()?;
+    if let Some(q) = moduli.iter().find(|&&q| q < 2) {
+        return Err(FormatError::Malformed(format!("invalid modulus {}", q)));
+    }
+    let mut wires = Vec::new();
+    for _ in 0..nwires {
+        let ix = r.read_u16()? as usize;
+        let q = *moduli.get(ix).ok_or_else(|| {
+            FormatError::Malformed(format!("modulus index {} out of range", ix))
+        })?;
+        let block = r.read_block()?;
+        let wire = Wire::try_from_block(block, q)
+            .map_err(|e| FormatError::Malformed(format!("invalid wire-label: {}", e)))?;
+        wires.push(wire);
+    }
+    r.finish()?;
+    Ok(wires)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{circuit::CircuitBuilder, classic::garble, fancy::Fancy, util::RngExt};
+    use rand::thread_rng;
+
+    #[test]
+    fn crc32_check_value() {
+        let mut crc = Crc32::new();
+        crc.update(b"123456789");
+        assert_eq!(crc.finish(), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn garbled_circuit_round_trip() {
+        let mut b = CircuitBuilder::new();
+        let x = b.garbler_input(7);
+        let y = b.evaluator_input(7);
+        let z = b.add(&x, &y).unwrap();
+        let z = b.mul(&z, &y).unwrap();
+        b.output(&z).unwrap();
+        let c = b.finish();
+
+        let (en, gc) = garble(&c).unwrap();
+        let mut bytes = Vec::new();
+        gc.write_to(&mut bytes).unwrap();
+        let gc = GarbledCircuit::read_from(&mut bytes.as_slice()).unwrap();
+
+        let mut gb_bytes = Vec::new();
+        write_wires(&en.encode_garbler_inputs(&[3]), &mut gb_bytes).unwrap();
+        let mut ev_bytes = Vec::new();
+        write_wires(&en.encode_evaluator_inputs(&[5]), &mut ev_bytes).unwrap();
+        let gb = read_wires(&mut gb_bytes.as_slice()).unwrap();
+        let ev = read_wires(&mut ev_bytes.as_slice()).unwrap();
+        assert_eq!(gc.eval(&c, &gb, &ev).unwrap(), vec![(3 + 5) * 5 % 7]);
+
+        // Corrupting a byte is detected by the checksum.
+        let n = bytes.len();
+        bytes[n / 2] ^= 1;
+        match GarbledCircuit::read_from(&mut bytes.as_slice()) {
+            Err(FormatError::ChecksumMismatch { .. }) => {}
+            r => panic!("expected checksum mismatch, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn eval_from_reader() {
+        let mut b = CircuitBuilder::new();
+        let x = b.garbler_input(5);
+        let y = b.evaluator_input(5);
+        let z = b.mul(&x, &y).unwrap();
+        let z = b.proj(&z, 2, Some(vec![0, 1, 0, 1, 0])).unwrap();
+        b.output(&z).unwrap();
+        let c = b.finish();
+
+        let (en, gc) = garble(&c).unwrap();
+        let mut bytes = Vec::new();
+        gc.write_to(&mut bytes).unwrap();
+        for x in 0..5 {
+            for y in 0..5 {
+                let gb = en.encode_garbler_inputs(&[x]);
+                let ev = en.encode_evaluator_inputs(&[y]);
+                let outputs = GarbledCircuit::eval_from(&mut bytes.as_slice(), &c, &gb, &ev);
+                assert_eq!(outputs.unwrap(), vec![x * y % 5 % 2]);
+            }
+        }
+
+        // Corruption is reported as such, even if it also breaks evaluation.
+        let gb = en.encode_garbler_inputs(&[1]);
+        let ev = en.encode_evaluator_inputs(&[1]);
+        let n = bytes.len();
+        for &i in [n / 2, n - 5].iter() {
+            let mut corrupt = bytes.clone();
+            corrupt[i] ^= 1;
+            match GarbledCircuit::eval_from(&mut corrupt.as_slice(), &c, &gb, &ev) {
+                Err(FormatError::ChecksumMismatch { .. }) => {}
+                r => panic!("expected checksum mismatch, got {:?}", r),
+            }
+        }
+        assert!(GarbledCircuit::eval_from(&mut &bytes[..n - 20], &c, &gb, &ev).is_err());
+    }
+
+    #[test]
+    fn circuit_round_trip() {
+        for name in crate::kat::CIRCUITS.iter() {
+            let c = crate::kat::circuit(name).unwrap();
+            let mut bytes = Vec::new();
+            c.write_to(&mut bytes).unwrap();
+            assert_eq!(Circuit::read_from(&mut bytes.as_slice()).unwrap(), c);
+        }
+        let c = Circuit::parse("circuits/adder_32bit.txt").unwrap();
+        let mut bytes = Vec::new();
+        c.write_to(&mut bytes).unwrap();
+        assert_eq!(Circuit::read_from(&mut bytes.as_slice()).unwrap(), c);
+
+        // Out-of-range references are rejected, even with a valid checksum.
+        let mut b = CircuitBuilder::new();
+        let x = b.garbler_input(3);
+        let z = b.cmul(&x, 2).unwrap();
+        b.output(&z).unwrap();
+        let mut c = b.finish();
+        c.output_refs[0].ix = 7;
+        let mut bytes = Vec::new();
+        c.write_to(&mut bytes).unwrap();
+        match Circuit::read_from(&mut bytes.as_slice()) {
+            Err(FormatError::Malformed(_)) => {}
+            r => panic!("expected malformed data, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn circuit_inconsistent_moduli() {
+        let malformed = |c: &Circuit| {
+            let mut bytes = Vec::new();
+            c.write_to(&mut bytes).unwrap();
+            match Circuit::read_from(&mut bytes.as_slice()) {
+                Err(FormatError::Malformed(_)) => {}
+                r => panic!("expected malformed data, got {:?}", r),
+            }
+        };
+        let mut b = CircuitBuilder::new();
+        let x = b.garbler_input(3);
+        let y = b.evaluator_input(3);
+        let z = b.add(&x, &y).unwrap();
+        let z = b.proj(&z, 2, Some(vec![0, 1, 1])).unwrap();
+        b.output(&z).unwrap();
+        let c = b.finish();
+        let mut bytes = Vec::new();
+        c.write_to(&mut bytes).unwrap();
+        assert_eq!(Circuit::read_from(&mut bytes.as_slice()).unwrap(), c);
+
+        // A truth table entry out of range for the output modulus.
+        let mut bad = c.clone();
+        for g in bad.gates.iter_mut() {
+            if let Gate::Proj { tt, .. } = g {
+                tt[2] = 2;
+            }
+        }
+        malformed(&bad);
+
+        // A truth table too short for the input modulus.
+        let mut bad = c.clone();
+        for g in bad.gates.iter_mut() {
+            if let Gate::Proj { tt, .. } = g {
+                tt.pop();
+            }
+        }
+        malformed(&bad);
+
+        // A gate referring to an input with the wrong modulus.
+        let mut bad = c.clone();
+        for g in bad.gates.iter_mut() {
+            if let Gate::Add { yref, .. } = g {
+                yref.modulus = 5;
+            }
+        }
+        malformed(&bad);
+
+        // An output reference with the wrong modulus.
+        let mut bad = c;
+        bad.output_refs[0].modulus = 3;
+        malformed(&bad);
+    }
+
+    #[test]
+    fn wires_invalid_label() {
+        let mut rng = thread_rng();
+        let mut bytes = Vec::new();
+        write_wires(&[Wire::rand(&mut rng, 3)], &mut bytes).unwrap();
+        // Set both bits of every mod-3 digit, with a valid checksum.
+        let n = bytes.len();
+        bytes.truncate(n - 4);
+        for b in bytes[n - 20..].iter_mut() {
+            *b = 0xFF;
+        }
+        let mut crc = Crc32::new();
+        crc.update(&bytes);
+        bytes.extend_from_slice(&crc.finish().to_le_bytes());
+        match read_wires(&mut bytes.as_slice()) {
+            Err(FormatError::Malformed(_)) => {}
+            r => panic!("expected malformed data, got {:?}", r),
+        }
+    }
+
+    #[test]
+    fn wires_forward_compatible() {
+        let mut rng = thread_rng();
+        let wires = (0..16)
+            .map(|_| {
+                let q = 2 + rng.gen_mod(100);
+                Wire::rand(&mut rng, q)
+            })
+            .collect::<Vec<_>>();
+        let mut bytes = Vec::new();
+        write_wires(&wires, &mut bytes).unwrap();
+
+        // Simulate a newer minor version that appends a field to the header.
+        let header_len = u32::from_le_bytes([bytes[6], bytes[7], bytes[8], bytes[9]]);
+        let end = 10 + header_len as usize;
+        let mut newer = bytes[..end].to_vec();
+        newer[5] = MINOR_VERSION + 1;
+        newer[6..10].copy_from_slice(&(header_len + 4).to_le_bytes());
+        newer.extend_from_slice(&[0xAA; 4]);
+        newer.extend_from_slice(&bytes[end..bytes.len() - 4]);
+        let mut crc = Crc32::new();
+        crc.update(&newer);
+        newer.extend_from_slice(&crc.finish().to_le_bytes());
+        assert_eq!(read_wires(&mut newer.as_slice()).unwrap(), wires);
+
+        // A newer major version is rejected.
+        bytes[4] = MAJOR_VERSION + 1;
+        match read_wires(&mut bytes.as_slice()) {
+            Err(FormatError::UnsupportedVersion { .. }) => {}
+            r => panic!("expected unsupported version, got {:?}", r),
+        }
+    }
+}
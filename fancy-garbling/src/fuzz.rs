@@ -0,0 +1,104 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Fuzz targets for the decoding paths that consume untrusted data, enabled by
+//! the `fuzz` feature.
+//!
+//! Each function takes arbitrary bytes and is meant to be called directly from
+//! a `cargo fuzz` harness, e.g.:
+//!
+//! ```ignore
+//! fuzz_target!(|data: &[u8]| fancy_garbling::fuzz::fuzz_read_wires(data));
+//! ```
+//!
+//! None of these functions panic on malformed input; a panic always indicates
+//! a bug.
+
+use crate::{circuit::Circuit, classic::GarbledCircuit, format, util};
+
+/// Fuzz the base-`q` conversions. The first two bytes give the modulus (values
+/// below 2 are skipped), and each following pair of bytes gives a digit, which
+/// is reduced `mod q`.
+///
+/// # Panics
+///
+/// Panics if converting the digits to a `u128` and back does not round-trip.
+pub fn fuzz_from_base_q(data: &[u8]) {
+    if data.len() < 2 {
+        return;
+    }
+    let q = u16::from_le_bytes([data[0], data[1]]);
+    if q < 2 {
+        return;
+    }
+    let ds = data[2..]
+        .chunks_exact(2)
+        .take(util::digits_per_u128(q))
+        .map(|d| u16::from_le_bytes([d[0], d[1]]) % q)
+        .collect::<Vec<u16>>();
+    let x = util::from_base_q(&ds, q);
+    let radii = vec![q; ds.len()];
+    assert_eq!(util::as_mixed_radix(x, &radii), ds, "q={} x={}", q, x);
+}
+
+/// Fuzz the parser for circuits in the Bristol format. Inputs whose header
+/// contains numbers of more than six digits are skipped, since the parser
+/// legitimately allocates memory proportional to the declared number of inputs.
+///
+/// # Panics
+///
+/// Never panics on malformed input, which is rejected with an error.
+pub fn fuzz_parse_bristol(data: &[u8]) {
+    for line in data.split(|&b| b == b'\n').take(2) {
+        let mut run = 0;
+        for &b in line {
+            run = if b.is_ascii_digit() { run + 1 } else { 0 };
+            if run > 6 {
+                return;
+            }
+        }
+    }
+    let _ = Circuit::parse_reader(data);
+}
+
+/// Fuzz the reader for garbled circuits in the format of `format`.
+///
+/// # Panics
+///
+/// Panics if a successfully read garbled circuit does not round-trip.
+pub fn fuzz_read_garbled_circuit(data: &[u8]) {
+    if let Ok(gc) = GarbledCircuit::read_from(&mut &data[..]) {
+        let mut bytes = Vec::new();
+        gc.write_to(&mut bytes).unwrap();
+        let gc_ = GarbledCircuit::read_from(&mut bytes.as_slice()).unwrap();
+        assert_eq!(gc.blocks(), gc_.blocks());
+    }
+}
+
+/// Fuzz the reader for encoded wires in the format of `format`.
+///
+/// # Panics
+///
+/// Never panics on malformed input, which is rejected with an error.
+pub fn fuzz_read_wires(data: &[u8]) {
+    let _ = format::read_wires(&mut &data[..]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn random_inputs() {
+        for len in 0..256 {
+            let data = (0..len).map(|_| rand::random::<u8>()).collect::<Vec<_>>();
+            fuzz_from_base_q(&data);
+            fuzz_parse_bristol(&data);
+            fuzz_read_garbled_circuit(&data);
+            fuzz_read_wires(&data);
+        }
+    }
+}
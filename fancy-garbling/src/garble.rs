@@ -9,7 +9,10 @@
 mod evaluator;
 mod garbler;
 
-pub use crate::garble::{evaluator::Evaluator, garbler::Garbler};
+pub use crate::garble::{
+    evaluator::{Evaluator, EvaluatorCheckpoint},
+    garbler::{Garbler, GarblerCheckpoint},
+};
 
 ////////////////////////////////////////////////////////////////////////////////
 // tests
@@ -40,7 +43,7 @@ mod nonstreaming {
                 let mut inps = Vec::new();
                 for i in 0..c.num_evaluator_inputs() {
                     let q = c.evaluator_input_mod(i);
-                    let x = rng.gen_u16() % q;
+                    let x = rng.gen_mod(q);
                     inps.push(x);
                 }
                 // Run the garbled circuit evaluator.
@@ -137,7 +140,7 @@ mod nonstreaming {
             let mut rng = thread_rng();
             let mut tab = Vec::new();
             for _ in 0..q {
-                tab.push(rng.gen_u16() % q);
+                tab.push(rng.gen_mod(q));
             }
             let mut b = CircuitBuilder::new();
             let x = b.evaluator_input(q);
@@ -239,7 +242,7 @@ mod nonstreaming {
         let mut rng = thread_rng();
 
         let q = rng.gen_modulus();
-        let c = rng.gen_u16() % q;
+        let c = rng.gen_mod(q);
 
         let y = b.constant(c, q).unwrap();
         b.output(&y).unwrap();
@@ -261,7 +264,7 @@ mod nonstreaming {
         let mut rng = thread_rng();
 
         let q = rng.gen_modulus();
-        let c = rng.gen_u16() % q;
+        let c = rng.gen_mod(q);
 
         let x = b.evaluator_input(q);
         let y = b.constant(c, q).unwrap();
@@ -272,7 +275,7 @@ mod nonstreaming {
         let (en, ev) = garble(&mut circ).unwrap();
 
         for _ in 0..64 {
-            let x = rng.gen_u16() % q;
+            let x = rng.gen_mod(q);
             let outputs = circ.eval_plain(&[], &[x]).unwrap();
             assert_eq!(outputs[0], (x + c) % q, "plaintext");
 
@@ -291,12 +294,14 @@ mod streaming {
         Evaluator,
         Fancy,
         FancyInput,
+        FancyReveal,
         Garbler,
+        RevealPolicy,
         Wire,
     };
     use itertools::Itertools;
     use rand::thread_rng;
-    use scuttlebutt::{unix_channel_pair, AesRng, UnixChannel};
+    use scuttlebutt::{unix_channel_pair, AbstractChannel, AesRng, Block, Channel, UnixChannel};
 
     // helper - checks that Streaming evaluation of a fancy function equals Dummy
     // evaluation of the same function
@@ -311,7 +316,7 @@ mod streaming {
         FDU: FnMut(&mut Dummy, &[DummyVal]) -> Option<u16>,
     {
         let mut rng = AesRng::new();
-        let inputs = input_mods.iter().map(|q| rng.gen_u16() % q).collect_vec();
+        let inputs = input_mods.iter().map(|q| rng.gen_mod(*q)).collect_vec();
 
         // evaluate f_gb as a dummy
         let mut dummy = Dummy::new();
@@ -437,6 +442,147 @@ mod streaming {
             );
         }
     }
+
+    #[test]
+    fn deterministic_from_seed() {
+        let mut rng = thread_rng();
+        let q = rng.gen_modulus();
+        let garble = |seed: Block| {
+            let channel = Channel::new(std::io::empty(), Vec::new());
+            let mut gb = Garbler::<_, AesRng>::from_seed(channel.clone(), seed);
+            let (gb_inp, ev_inp) = gb.encode_many_wires(&[1, 2], &[q, q]).unwrap();
+            for w in ev_inp.iter() {
+                gb.send_wire(w).unwrap();
+            }
+            let z = gb.mul(&gb_inp[0], &gb_inp[1]).unwrap();
+            let z = gb
+                .proj(&z, 2, Some((0..q).map(|x| x % 2).collect()))
+                .unwrap();
+            gb.output(&z).unwrap();
+            channel.writer().borrow().clone()
+        };
+        let seed = rng.gen_block();
+        assert_eq!(garble(seed), garble(seed));
+        assert_ne!(garble(seed), garble(rng.gen_block()));
+    }
+
+    #[test]
+    fn constant_time_evaluator() {
+        let mut rng = thread_rng();
+        for i in 0..16 {
+            // Cover equal moduli 2 and 3, whose multiplication by a color
+            // differs from that of larger moduli, and unequal moduli.
+            let qs = match i % 4 {
+                0 => [2, 2],
+                1 => [3, 3],
+                2 => [3, 2],
+                _ => [9 + rng.gen_mod(100), 2 + rng.gen_mod(7)],
+            };
+            let xs = [rng.gen_mod(qs[0]), rng.gen_mod(qs[1])];
+            let tt = (0..qs[0]).map(|_| rng.gen_mod(qs[1])).collect_vec();
+            let tt_ = tt.clone();
+            let (sender, receiver) = unix_channel_pair();
+            crossbeam::scope(|s| {
+                s.spawn(move |_| {
+                    let mut gb = Garbler::new(sender, AesRng::new());
+                    let (gb_inp, ev_inp) = gb.encode_many_wires(&xs, &qs).unwrap();
+                    for w in ev_inp.iter() {
+                        gb.send_wire(w).unwrap();
+                    }
+                    let z = gb.mul(&gb_inp[0], &gb_inp[1]).unwrap();
+                    let y = gb.proj(&gb_inp[0], qs[1], Some(tt_)).unwrap();
+                    let y = gb.mul(&y, &gb_inp[1]).unwrap();
+                    gb.outputs(&[z, y]).unwrap();
+                });
+                let mut ev = Evaluator::new(receiver).constant_time(true);
+                let ev_inp = [ev.read_wire(qs[0]).unwrap(), ev.read_wire(qs[1]).unwrap()];
+                let z = ev.mul(&ev_inp[0], &ev_inp[1]).unwrap();
+                let y = ev.proj(&ev_inp[0], qs[1], None).unwrap();
+                let y = ev.mul(&y, &ev_inp[1]).unwrap();
+                let outputs = ev.outputs(&[z, y]).unwrap().unwrap();
+                let (x, b) = (xs[0] as u32, xs[1] as u32);
+                let z = x * b % qs[0] as u32;
+                let y = tt[xs[0] as usize] as u32 * b % qs[1] as u32;
+                assert_eq!(outputs, vec![z as u16, y as u16]);
+            })
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn reveal_policies() {
+        let mut rng = thread_rng();
+        let policies = [
+            RevealPolicy::Garbler,
+            RevealPolicy::Evaluator,
+            RevealPolicy::Both,
+        ];
+        for &policy in policies.iter() {
+            let q = rng.gen_modulus();
+            let x = rng.gen_mod(q);
+            let (sender, receiver) = unix_channel_pair();
+            crossbeam::scope(|s| {
+                s.spawn(move |_| {
+                    let mut gb = Garbler::new(sender, AesRng::new());
+                    let (gb_inp, ev_inp) = gb.encode_many_wires(&[x], &[q]).unwrap();
+                    gb.send_wire(&ev_inp[0]).unwrap();
+                    let z = gb.reveal_to(&gb_inp[0], policy).unwrap();
+                    let learns = policy != RevealPolicy::Evaluator;
+                    assert_eq!(z, if learns { Some(x) } else { None });
+                });
+                let mut ev = Evaluator::new(receiver);
+                let w = ev.read_wire(q).unwrap();
+                let z = ev.reveal_to(&w, policy).unwrap();
+                let learns = policy != RevealPolicy::Garbler;
+                assert_eq!(z, if learns { Some(x) } else { None });
+            })
+            .unwrap();
+        }
+    }
+
+    #[test]
+    fn resume_from_checkpoint() {
+        let mut rng = thread_rng();
+        for i in 0..16 {
+            let q = rng.gen_modulus();
+            let x = rng.gen_mod(q);
+            let y = rng.gen_mod(q);
+
+            let (sender, receiver) = unix_channel_pair();
+            let (sender_, receiver_) = unix_channel_pair();
+
+            crossbeam::scope(|s| {
+                s.spawn(move |_| {
+                    let mut gb = Garbler::new(sender, AesRng::new());
+                    let (gb_inp, ev_inp) = gb.encode_many_wires(&[x, y], &[q, q]).unwrap();
+                    for w in ev_inp.iter() {
+                        gb.send_wire(w).unwrap();
+                    }
+                    let z = gb.mul(&gb_inp[0], &gb_inp[1]).unwrap();
+                    let checkpoint = gb.checkpoint();
+                    drop(gb);
+
+                    // Pick up where we left off over a fresh channel.
+                    let mut gb = Garbler::<_, AesRng>::from_checkpoint(sender_, checkpoint);
+                    let z = gb.mul(&z, &gb_inp[1]).unwrap();
+                    gb.output(&z).unwrap();
+                });
+
+                let mut ev = Evaluator::new(receiver).constant_time(i % 2 == 0);
+                let ev_inp = [ev.read_wire(q).unwrap(), ev.read_wire(q).unwrap()];
+                let z = ev.mul(&ev_inp[0], &ev_inp[1]).unwrap();
+                let checkpoint = ev.checkpoint();
+                drop(ev);
+
+                let mut ev = Evaluator::from_checkpoint(receiver_, checkpoint);
+                assert_eq!(ev.checkpoint(), checkpoint);
+                let z = ev.mul(&z, &ev_inp[1]).unwrap();
+                let result = ev.output(&z).unwrap().unwrap();
+                assert_eq!(result as u32, (x as u32 * y as u32 * y as u32) % q as u32);
+            })
+            .unwrap();
+        }
+    }
 }
 
 #[cfg(test)]
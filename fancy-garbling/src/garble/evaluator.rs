@@ -6,11 +6,11 @@
 
 use crate::{
     errors::{EvaluatorError, FancyError},
-    fancy::{Fancy, FancyReveal, HasModulus},
+    fancy::{Fancy, FancyReveal, HasModulus, RevealPolicy},
     util::{output_tweak, tweak, tweak2},
     wire::Wire,
 };
-use scuttlebutt::AbstractChannel;
+use scuttlebutt::{AbstractChannel, Block};
 
 /// Streaming evaluator using a callback to receive ciphertexts as needed.
 ///
@@ -20,6 +20,7 @@ pub struct Evaluator<C> {
     channel: C,
     current_gate: usize,
     current_output: usize,
+    constant_time: bool,
 }
 
 impl<C: AbstractChannel> Evaluator<C> {
@@ -29,9 +30,26 @@ impl<C: AbstractChannel> Evaluator<C> {
             channel,
             current_gate: 0,
             current_output: 0,
+            constant_time: false,
         }
     }
 
+    /// Enable or disable constant-time evaluation, which is off by default.
+    ///
+    /// In this mode, the evaluator reads every row of a garbled table and
+    /// selects the one it needs with masks, multiplies wire-labels by colors
+    /// with masks too, and decodes outputs by comparing against every
+    /// ciphertext, so neither branches nor memory accesses depend on the
+    /// colors of wire-labels. This is slower, and meant for evaluators
+    /// whose memory accesses may be observed through the cache, e.g., inside
+    /// an enclave. Converting a block into a wire-label of a modulus other
+    /// than a power of 2 still divides by the modulus, which may not take
+    /// constant time on every processor.
+    pub fn constant_time(mut self, enabled: bool) -> Self {
+        self.constant_time = enabled;
+        self
+    }
+
     /// The current non-free gate index of the garbling computation.
     fn current_gate(&mut self) -> usize {
         let current = self.current_gate;
@@ -49,8 +67,96 @@ impl<C: AbstractChannel> Evaluator<C> {
     /// Read a Wire from the reader.
     pub fn read_wire(&mut self, modulus: u16) -> Result<Wire, EvaluatorError> {
         let block = self.channel.read_block()?;
-        Ok(Wire::from_block(block, modulus))
+        Ok(Wire::try_from_block(block, modulus)?)
+    }
+
+    /// Take a checkpoint of the evaluator, recording the gate and output
+    /// indices it has reached, and whether it evaluates in constant time.
+    pub fn checkpoint(&self) -> EvaluatorCheckpoint {
+        EvaluatorCheckpoint {
+            current_gate: self.current_gate,
+            current_output: self.current_output,
+            constant_time: self.constant_time,
+        }
+    }
+
+    /// Resume evaluation from `checkpoint` over `channel`.
+    pub fn from_checkpoint(channel: C, checkpoint: EvaluatorCheckpoint) -> Self {
+        Evaluator {
+            channel,
+            current_gate: checkpoint.current_gate,
+            current_output: checkpoint.current_output,
+            constant_time: checkpoint.constant_time,
+        }
     }
+
+    /// Decrypt the row of `table` selected by the color of `x`, hashed with
+    /// tweak `t`, where the row for color 0 is implicit.
+    fn decrypt_row(
+        &self,
+        x: &Wire,
+        t: Block,
+        q: u16,
+        table: &[Block],
+    ) -> Result<Wire, EvaluatorError> {
+        let color = x.color() as usize;
+        if !self.constant_time {
+            return if color == 0 {
+                Ok(x.hashback(t, q))
+            } else {
+                Ok(Wire::try_from_block(table[color - 1] ^ x.hash(t), q)?)
+            };
+        }
+        let mut ct = Block::default();
+        for (i, row) in table.iter().enumerate() {
+            ct ^= *row & mask(i + 1 == color);
+        }
+        // Only the selected block is unpacked: for color 0, `other` is the
+        // hash masked with no row, which need not be a wire-label.
+        let zero = x.hashback(t, q).as_block();
+        let other = ct ^ x.hash(t);
+        Ok(Wire::try_from_block(
+            other ^ ((zero ^ other) & mask(color == 0)),
+            q,
+        )?)
+    }
+}
+
+/// All ones if `b` is set, and zero otherwise.
+#[inline]
+fn mask(b: bool) -> Block {
+    Block::from(0u128.wrapping_sub(b as u128))
+}
+
+/// Multiply `x` by `c`, which may be secret, selecting the result with masks
+/// rather than branching on `c` as `Wire::cmul` does for moduli 2 and 3.
+fn cmul_masked(x: &Wire, c: u16) -> Wire {
+    match *x {
+        Wire::Mod2 { val } => Wire::Mod2 {
+            val: val & mask(c & 1 == 1),
+        },
+        Wire::Mod3 { lsb, msb } => {
+            let c = c % 3;
+            let one = 0u64.wrapping_sub((c == 1) as u64);
+            let two = 0u64.wrapping_sub((c == 2) as u64);
+            // Multiplying by two swaps `lsb` and `msb`, as in `Wire::cmul`.
+            Wire::Mod3 {
+                lsb: (lsb & one) | (msb & two),
+                msb: (msb & one) | (lsb & two),
+            }
+        }
+        Wire::ModN { .. } => x.cmul(c),
+    }
+}
+
+/// A snapshot of the in-flight state of an `Evaluator`, matching a
+/// `GarblerCheckpoint` taken at the same point of the computation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+pub struct EvaluatorCheckpoint {
+    current_gate: usize,
+    current_output: usize,
+    constant_time: bool,
 }
 
 impl<C: AbstractChannel> FancyReveal for Evaluator<C> {
@@ -60,6 +166,19 @@ impl<C: AbstractChannel> FancyReveal for Evaluator<C> {
         self.channel.flush()?;
         Ok(val)
     }
+
+    fn reveal_to(&mut self, x: &Wire, policy: RevealPolicy) -> Result<Option<u16>, EvaluatorError> {
+        match policy {
+            RevealPolicy::Garbler => {
+                // Forward the label, which the garbler decodes using delta.
+                self.channel.write_block(&x.as_block())?;
+                self.channel.flush()?;
+                Ok(None)
+            }
+            RevealPolicy::Evaluator => self.output(x),
+            RevealPolicy::Both => self.reveal(x).map(Some),
+        }
+    }
 }
 
 impl<C: AbstractChannel> Fancy for Evaluator<C> {
@@ -88,6 +207,10 @@ impl<C: AbstractChannel> Fancy for Evaluator<C> {
         Ok(x.cmul(c))
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip_all, fields(q = A.modulus(), qb = B.modulus()))
+    )]
     fn mul(&mut self, A: &Wire, B: &Wire) -> Result<Wire, EvaluatorError> {
         if A.modulus() < B.modulus() {
             return self.mul(B, A);
@@ -107,35 +230,40 @@ impl<C: AbstractChannel> Fancy for Evaluator<C> {
         let g = tweak2(gate_num as u64, 0);
 
         // garbler's half gate
-        let L = if A.color() == 0 {
-            A.hashback(g, q)
-        } else {
-            let ct_left = gate[A.color() as usize - 1];
-            Wire::from_block(ct_left ^ A.hash(g), q)
-        };
+        let L = self.decrypt_row(A, g, q, &gate[..q as usize - 1])?;
 
         // evaluator's half gate
-        let R = if B.color() == 0 {
-            B.hashback(g, q)
-        } else {
-            let ct_right = gate[(q + B.color()) as usize - 2];
-            Wire::from_block(ct_right ^ B.hash(g), q)
-        };
+        let R = self.decrypt_row(B, g, q, &gate[q as usize - 1..(q + qb) as usize - 2])?;
 
         // hack for unequal mods
         let new_b_color = if unequal {
-            let minitable = *gate.last().unwrap();
-            let ct = u128::from(minitable) >> (B.color() * 16);
+            let minitable = u128::from(*gate.last().unwrap());
+            let ct = if self.constant_time {
+                (0..qb).fold(0, |ct, k| {
+                    ct | ((minitable >> (k * 16)) & 0u128.wrapping_sub((k == B.color()) as u128))
+                })
+            } else {
+                minitable >> (B.color() * 16)
+            };
             let pt = u128::from(B.hash(tweak2(gate_num as u64, 1))) ^ ct;
             pt as u16
         } else {
             B.color()
         };
 
-        let res = L.plus_mov(&R.plus_mov(&A.cmul(new_b_color)));
+        let A = if self.constant_time {
+            cmul_masked(A, new_b_color)
+        } else {
+            A.cmul(new_b_color)
+        };
+        let res = L.plus_mov(&R.plus_mov(&A));
         Ok(res)
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip_all, fields(q_in = x.modulus(), q = q))
+    )]
     fn proj(&mut self, x: &Wire, q: u16, _: Option<Vec<u16>>) -> Result<Wire, EvaluatorError> {
         let ngates = (x.modulus() - 1) as usize;
         let mut gate = Vec::with_capacity(ngates);
@@ -144,14 +272,13 @@ impl<C: AbstractChannel> Fancy for Evaluator<C> {
             gate.push(block);
         }
         let t = tweak(self.current_gate());
-        if x.color() == 0 {
-            Ok(x.hashback(t, q))
-        } else {
-            let ct = gate[x.color() as usize - 1];
-            Ok(Wire::from_block(ct ^ x.hash(t), q))
-        }
+        self.decrypt_row(x, t, q, &gate)
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip_all, fields(q = x.modulus()))
+    )]
     fn output(&mut self, x: &Wire) -> Result<Option<u16>, EvaluatorError> {
         let q = x.modulus();
         let i = self.current_output();
@@ -161,11 +288,21 @@ impl<C: AbstractChannel> Fancy for Evaluator<C> {
 
         // Attempt to brute force x using the output ciphertext
         let mut decoded = None;
-        for k in 0..q {
-            let hashed_wire = x.hash(output_tweak(i, k));
-            if hashed_wire == ct[k as usize] {
-                decoded = Some(k);
-                break;
+        if self.constant_time {
+            let (mut found, mut output) = (0u16, 0u16);
+            for k in 0..q {
+                let m = 0u16.wrapping_sub((x.hash(output_tweak(i, k)) == ct[k as usize]) as u16);
+                found |= m;
+                output |= k & m;
+            }
+            decoded = Some(output).filter(|_| found != 0);
+        } else {
+            for k in 0..q {
+                let hashed_wire = x.hash(output_tweak(i, k));
+                if hashed_wire == ct[k as usize] {
+                    decoded = Some(k);
+                    break;
+                }
             }
         }
 
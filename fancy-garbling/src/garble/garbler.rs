@@ -6,11 +6,11 @@
 
 use crate::{
     errors::{FancyError, GarblerError},
-    fancy::{BinaryBundle, CrtBundle, Fancy, FancyReveal, HasModulus},
+    fancy::{BinaryBundle, CrtBundle, Fancy, FancyReveal, HasModulus, RevealPolicy},
     util::{output_tweak, tweak, tweak2, RngExt},
     wire::Wire,
 };
-use rand::{CryptoRng, RngCore};
+use rand::{CryptoRng, RngCore, SeedableRng};
 use scuttlebutt::{AbstractChannel, Block};
 use std::collections::HashMap;
 
@@ -82,6 +82,10 @@ impl<C: AbstractChannel, RNG: CryptoRng + RngCore> Garbler<C, RNG> {
     }
 
     /// Encode many wires, producing zero wires as well as encoded values.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(self, vals), fields(n = vals.len()))
+    )]
     pub fn encode_many_wires(
         &mut self,
         vals: &[u16],
@@ -107,8 +111,8 @@ impl<C: AbstractChannel, RNG: CryptoRng + RngCore> Garbler<C, RNG> {
         val: u128,
         modulus: u128,
     ) -> Result<(CrtBundle<Wire>, CrtBundle<Wire>), GarblerError> {
-        let ms = crate::util::factor(modulus);
-        let xs = crate::util::crt(val, &ms);
+        let ms = crate::util::try_factor(modulus).map_err(FancyError::from)?;
+        let xs = crate::util::DigitArray::crt(val, &ms);
         let (gbs, evs) = self.encode_many_wires(&xs, &ms)?;
         Ok((CrtBundle::new(gbs), CrtBundle::new(evs)))
     }
@@ -126,6 +130,58 @@ impl<C: AbstractChannel, RNG: CryptoRng + RngCore> Garbler<C, RNG> {
     }
 }
 
+/// A snapshot of the in-flight state of a `Garbler`.
+///
+/// A checkpoint records the deltas, the gate and output indices, and a fresh
+/// seed for the garbler's RNG, which is enough to resume garbling at the next
+/// gate over a new channel. The wires held by the caller at the time of the
+/// checkpoint must be kept alongside it.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+pub struct GarblerCheckpoint {
+    deltas: HashMap<u16, Wire>,
+    current_gate: usize,
+    current_output: usize,
+    seed: Block,
+}
+
+impl<C: AbstractChannel, RNG: CryptoRng + RngCore + SeedableRng<Seed = Block>> Garbler<C, RNG> {
+    /// Create a new garbler whose labels, deltas, and permutation bits are all
+    /// drawn from an RNG seeded with `seed`. Garbling the same computation
+    /// from the same seed sends exactly the same messages, so failures can be
+    /// reproduced and test vectors pinned.
+    pub fn from_seed(channel: C, seed: Block) -> Self {
+        Garbler::new(channel, RNG::from_seed(seed))
+    }
+
+    /// Take a checkpoint of the garbler.
+    ///
+    /// The garbler's RNG is reseeded with the seed stored in the checkpoint,
+    /// so a garbler resumed from the checkpoint produces exactly the same
+    /// messages as this one from here on.
+    pub fn checkpoint(&mut self) -> GarblerCheckpoint {
+        let seed = self.rng.gen_block();
+        self.rng = RNG::from_seed(seed);
+        GarblerCheckpoint {
+            deltas: self.deltas.clone(),
+            current_gate: self.current_gate,
+            current_output: self.current_output,
+            seed,
+        }
+    }
+
+    /// Resume garbling from `checkpoint` over `channel`.
+    pub fn from_checkpoint(channel: C, checkpoint: GarblerCheckpoint) -> Self {
+        Garbler {
+            channel,
+            deltas: checkpoint.deltas,
+            current_gate: checkpoint.current_gate,
+            current_output: checkpoint.current_output,
+            rng: RNG::from_seed(checkpoint.seed),
+        }
+    }
+}
+
 impl<C: AbstractChannel, RNG: RngCore + CryptoRng> FancyReveal for Garbler<C, RNG> {
     fn reveal(&mut self, x: &Wire) -> Result<u16, GarblerError> {
         // The evaluator needs our cooperation in order to see the output.
@@ -135,6 +191,25 @@ impl<C: AbstractChannel, RNG: RngCore + CryptoRng> FancyReveal for Garbler<C, RN
         let val = self.channel.read_u16()?;
         Ok(val)
     }
+
+    fn reveal_to(&mut self, x: &Wire, policy: RevealPolicy) -> Result<Option<u16>, GarblerError> {
+        match policy {
+            RevealPolicy::Garbler => {
+                // The evaluator forwards its label without learning anything,
+                // and only we know which value it encodes.
+                self.channel.flush()?;
+                let label = self.channel.read_block()?;
+                let q = x.modulus();
+                let delta = self.delta(q);
+                (0..q)
+                    .find(|&k| x.plus(&delta.cmul(k)).as_block() == label)
+                    .map(Some)
+                    .ok_or(GarblerError::DecodingFailed)
+            }
+            RevealPolicy::Evaluator => self.output(x),
+            RevealPolicy::Both => self.reveal(x).map(Some),
+        }
+    }
 }
 
 impl<C: AbstractChannel, RNG: RngCore + CryptoRng> Fancy for Garbler<C, RNG> {
@@ -166,6 +241,10 @@ impl<C: AbstractChannel, RNG: RngCore + CryptoRng> Fancy for Garbler<C, RNG> {
         Ok(x.cmul(c))
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip_all, fields(q = A.modulus(), qb = B.modulus()))
+    )]
     fn mul(&mut self, A: &Wire, B: &Wire) -> Result<Wire, GarblerError> {
         if A.modulus() < B.modulus() {
             return self.mul(B, A);
@@ -188,7 +267,7 @@ impl<C: AbstractChannel, RNG: RngCore + CryptoRng> Fancy for Garbler<C, RNG> {
                 return Err(GarblerError::AsymmetricHalfGateModuliMax8(qb));
             }
 
-            r = self.rng.gen_u16() % q;
+            r = self.rng.gen_mod(q);
             let t = tweak2(gate_num as u64, 1);
 
             let mut minitable = vec![u128::default(); qb as usize];
@@ -281,6 +360,10 @@ impl<C: AbstractChannel, RNG: RngCore + CryptoRng> Fancy for Garbler<C, RNG> {
         Ok(X.plus_mov(&Y))
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip_all, fields(q = A.modulus(), q_out = q_out))
+    )]
     fn proj(&mut self, A: &Wire, q_out: u16, tt: Option<Vec<u16>>) -> Result<Wire, GarblerError> {
         let tt = tt.ok_or(GarblerError::TruthTableRequired)?;
 
@@ -334,6 +417,10 @@ impl<C: AbstractChannel, RNG: RngCore + CryptoRng> Fancy for Garbler<C, RNG> {
         Ok(C)
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "trace", skip_all, fields(q = X.modulus()))
+    )]
     fn output(&mut self, X: &Wire) -> Result<Option<u16>, GarblerError> {
         let q = X.modulus();
         let i = self.current_output();
@@ -0,0 +1,270 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Differential testing of gadgets between plaintext and garbled execution.
+//!
+//! `check_gadget` builds a circuit by running a closure over a
+//! `CircuitBuilder`, then evaluates the circuit on random inputs both with
+//! `Dummy` and with a `Garbler` and `Evaluator`. Every intermediate wire is
+//! compared, not just the outputs, so a failing check points at the first gate
+//! whose garbled value differs from its plaintext value.
+
+use crate::{
+    circuit::{Circuit, CircuitBuilder, Gate},
+    dummy::{Dummy, DummyVal},
+    errors::CircuitBuilderError,
+    fancy::{Fancy, HasModulus},
+    garble::{Evaluator, Garbler},
+    util::RngExt,
+    wire::Wire,
+};
+use itertools::Itertools;
+use scuttlebutt::{AbstractChannel, AesRng, Channel};
+use std::io::Cursor;
+
+/// The number of random inputs tried by `check_gadget`.
+const NTRIALS: usize = 16;
+
+/// Build a circuit with `gadget` and check that garbled evaluation agrees with
+/// plaintext evaluation on random inputs.
+///
+/// ```ignore
+/// check_gadget(|f| {
+///     let x = f.garbler_input(7);
+///     let y = f.evaluator_input(7);
+///     let z = f.mul(&x, &y)?;
+///     f.output(&z)?;
+///     Ok(())
+/// });
+/// ```
+///
+/// # Panics
+///
+/// Panics with a description of the first diverging gate if the two
+/// evaluations disagree, or if building, garbling, or evaluating fails.
+pub fn check_gadget<G>(gadget: G)
+where
+    G: FnOnce(&mut CircuitBuilder) -> Result<(), CircuitBuilderError>,
+{
+    let mut b = CircuitBuilder::new();
+    gadget(&mut b).unwrap_or_else(|e| panic!("building the gadget failed: {}", e));
+    let c = b.finish();
+
+    let mut rng = AesRng::new();
+    for _ in 0..NTRIALS {
+        let gb = (0..c.num_garbler_inputs())
+            .map(|i| rng.gen_mod(c.garbler_input_mod(i)))
+            .collect_vec();
+        let ev = (0..c.num_evaluator_inputs())
+            .map(|i| rng.gen_mod(c.evaluator_input_mod(i)))
+            .collect_vec();
+        if let Err(e) = check_circuit(&c, &gb, &ev, rng.fork()) {
+            panic!("garbler inputs {:?}, evaluator inputs {:?}: {}", gb, ev, e);
+        }
+    }
+}
+
+/// Evaluate `c` on the given inputs with `Dummy` and with a `Garbler` using
+/// `rng` and an `Evaluator`, returning a description of the first gate or
+/// output at which they disagree.
+pub fn check_circuit(
+    c: &Circuit,
+    garbler_inputs: &[u16],
+    evaluator_inputs: &[u16],
+    rng: AesRng,
+) -> Result<(), String> {
+    if garbler_inputs.len() != c.num_garbler_inputs()
+        || evaluator_inputs.len() != c.num_evaluator_inputs()
+    {
+        return Err("wrong number of inputs".to_string());
+    }
+
+    // Plaintext evaluation.
+    let gb = garbler_inputs
+        .iter()
+        .enumerate()
+        .map(|(i, &x)| DummyVal::new(x, c.garbler_input_mod(i)))
+        .collect_vec();
+    let ev = evaluator_inputs
+        .iter()
+        .enumerate()
+        .map(|(i, &x)| DummyVal::new(x, c.evaluator_input_mod(i)))
+        .collect_vec();
+    let mut dummy = Recorder::new(Dummy::new());
+    let expected = c
+        .eval(&mut dummy, &gb, &ev)
+        .map_err(|e| format!("dummy evaluation failed: {}", e))?
+        .expect("dummy will always return Some(u16) output");
+
+    // Garbling, producing the zero wire-label of every gate.
+    let channel = Channel::new(std::io::empty(), Vec::new());
+    let mut garbler = Recorder::new(Garbler::new(channel.clone(), rng));
+    let (gb_zeros, gb): (Vec<Wire>, Vec<Wire>) = garbler_inputs
+        .iter()
+        .enumerate()
+        .map(|(i, &x)| garbler.inner.encode_wire(x, c.garbler_input_mod(i)))
+        .unzip();
+    let (ev_zeros, ev): (Vec<Wire>, Vec<Wire>) = evaluator_inputs
+        .iter()
+        .enumerate()
+        .map(|(i, &x)| garbler.inner.encode_wire(x, c.evaluator_input_mod(i)))
+        .unzip();
+    c.eval(&mut garbler, &gb_zeros, &ev_zeros)
+        .map_err(|e| format!("garbling failed: {}", e))?;
+    let zeros = garbler.items;
+    let deltas = garbler.inner.get_deltas();
+    let bytes = channel.writer().borrow().clone();
+
+    // Evaluation, producing the actual wire-label of every gate.
+    let channel = Channel::new(Cursor::new(bytes), std::io::sink());
+    let mut evaluator = Recorder::new(Evaluator::new(channel));
+    let outputs = c
+        .eval(&mut evaluator, &gb, &ev)
+        .map_err(|e| format!("evaluation failed: {}", e))?
+        .expect("evaluator outputs always are Some(u16)");
+
+    // The wires recorded by each party correspond to the gates that are not
+    // inputs, in order.
+    let gates = c
+        .gates
+        .iter()
+        .enumerate()
+        .filter(|(_, g)| !matches!(g, Gate::GarblerInput { .. } | Gate::EvaluatorInput { .. }));
+    for (((i, gate), x), (zero, wire)) in gates
+        .zip(dummy.items.iter())
+        .zip(zeros.iter().zip(evaluator.items.iter()))
+    {
+        let q = x.modulus();
+        let actual = deltas
+            .get(&q)
+            .and_then(|delta| (0..q).find(|&k| &zero.plus(&delta.cmul(k)) == wire));
+        if actual != Some(x.val()) {
+            return Err(format!(
+                "gate {} ({}) mod {}: expected {}, got {}",
+                i,
+                gate,
+                q,
+                x.val(),
+                actual.map_or("an invalid wire-label".to_string(), |v| v.to_string())
+            ));
+        }
+    }
+    for (i, (x, y)) in expected.iter().zip(outputs.iter()).enumerate() {
+        if x != y {
+            return Err(format!("output {}: expected {}, got {}", i, x, y));
+        }
+    }
+    Ok(())
+}
+
+/// Wraps a `Fancy` object, recording every wire it creates.
+struct Recorder<F: Fancy> {
+    inner: F,
+    items: Vec<F::Item>,
+}
+
+impl<F: Fancy> Recorder<F> {
+    fn new(inner: F) -> Self {
+        Recorder {
+            inner,
+            items: Vec::new(),
+        }
+    }
+
+    fn record(&mut self, x: Result<F::Item, F::Error>) -> Result<F::Item, F::Error> {
+        let x = x?;
+        self.items.push(x.clone());
+        Ok(x)
+    }
+}
+
+impl<F: Fancy> Fancy for Recorder<F> {
+    type Item = F::Item;
+    type Error = F::Error;
+
+    fn constant(&mut self, x: u16, q: u16) -> Result<F::Item, F::Error> {
+        let z = self.inner.constant(x, q);
+        self.record(z)
+    }
+
+    fn add(&mut self, x: &F::Item, y: &F::Item) -> Result<F::Item, F::Error> {
+        let z = self.inner.add(x, y);
+        self.record(z)
+    }
+
+    fn sub(&mut self, x: &F::Item, y: &F::Item) -> Result<F::Item, F::Error> {
+        let z = self.inner.sub(x, y);
+        self.record(z)
+    }
+
+    fn cmul(&mut self, x: &F::Item, c: u16) -> Result<F::Item, F::Error> {
+        let z = self.inner.cmul(x, c);
+        self.record(z)
+    }
+
+    fn mul(&mut self, x: &F::Item, y: &F::Item) -> Result<F::Item, F::Error> {
+        let z = self.inner.mul(x, y);
+        self.record(z)
+    }
+
+    fn proj(&mut self, x: &F::Item, q: u16, tt: Option<Vec<u16>>) -> Result<F::Item, F::Error> {
+        let z = self.inner.proj(x, q, tt);
+        self.record(z)
+    }
+
+    fn output(&mut self, x: &F::Item) -> Result<Option<u16>, F::Error> {
+        self.inner.output(x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fancy::{BinaryGadgets, CrtGadgets};
+
+    #[test]
+    fn gadgets_agree() {
+        check_gadget(|f| {
+            let x = f.garbler_input(7);
+            let y = f.evaluator_input(7);
+            let z = f.mul(&x, &y)?;
+            let z = f.proj(&z, 5, Some(vec![0, 1, 2, 3, 4, 0, 1]))?;
+            f.output(&z)?;
+            Ok(())
+        });
+        check_gadget(|f| {
+            let x = f.bin_garbler_input(8);
+            let y = f.bin_evaluator_input(8);
+            let z = f.bin_addition_no_carry(&x, &y)?;
+            f.bin_output(&z)?;
+            Ok(())
+        });
+        check_gadget(|f| {
+            let q = crate::util::modulus_with_width(8);
+            let x = f.crt_garbler_input(q);
+            let y = f.crt_evaluator_input(q);
+            let z = f.crt_mul(&x, &y)?;
+            f.crt_output(&z)?;
+            Ok(())
+        });
+    }
+
+    #[test]
+    fn divergence_is_reported() {
+        // An out-of-range input is reduced by the garbler but not by `Dummy`,
+        // so a projection with an oversized truth table tells them apart.
+        let mut b = CircuitBuilder::new();
+        let x = b.garbler_input(3);
+        let y = b.add(&x, &x).unwrap();
+        let z = b.proj(&x, 3, Some(vec![0, 1, 2, 2, 0])).unwrap();
+        b.output(&y).unwrap();
+        b.output(&z).unwrap();
+        let c = b.finish();
+        assert!(check_circuit(&c, &[1], &[], AesRng::new()).is_ok());
+        let err = check_circuit(&c, &[4], &[], AesRng::new()).unwrap_err();
+        assert!(err.starts_with("gate 2 (Proj"), "{}", err);
+    }
+}
@@ -6,7 +6,7 @@
 
 //! `Informer` runs a fancy computation and learns information from it.
 
-use crate::fancy::{Fancy, FancyInput, FancyReveal, HasModulus};
+use crate::fancy::{Fancy, FancyInput, FancyReveal, HasModulus, RevealPolicy};
 use std::collections::{HashMap, HashSet};
 
 /// Implements `Fancy`. Used to learn information about a `Fancy` computation in
@@ -19,6 +19,7 @@ pub struct Informer<F: Fancy> {
 
 /// The statistics revealed by the informer.
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
 pub struct InformerStats {
     garbler_input_moduli: Vec<u16>,
     evaluator_input_moduli: Vec<u16>,
@@ -98,6 +99,72 @@ impl InformerStats {
     pub fn num_ciphertexts(&self) -> usize {
         self.nciphertexts
     }
+
+    /// Names of the fields exported by `to_json` and `to_csv`, in order. These
+    /// names are stable: fields are never renamed or removed, and new fields
+    /// are only ever appended.
+    pub const FIELDS: &'static [&'static str] = &[
+        "garbler_inputs",
+        "evaluator_inputs",
+        "constants",
+        "outputs",
+        "output_ciphertexts",
+        "additions",
+        "subtractions",
+        "cmuls",
+        "projections",
+        "multiplications",
+        "ciphertexts",
+    ];
+
+    fn values(&self) -> Vec<usize> {
+        vec![
+            self.num_garbler_inputs(),
+            self.num_evaluator_inputs(),
+            self.num_consts(),
+            self.num_outputs(),
+            self.num_output_ciphertexts(),
+            self.num_adds(),
+            self.num_subs(),
+            self.num_cmuls(),
+            self.num_projs(),
+            self.num_muls(),
+            self.num_ciphertexts(),
+        ]
+    }
+
+    /// Export the statistics as a JSON object with the fields in `FIELDS`,
+    /// plus a `moduli` object mapping each wire modulus to its number of
+    /// wires.
+    pub fn to_json(&self) -> String {
+        let mut fields = Self::FIELDS
+            .iter()
+            .zip(self.values())
+            .map(|(name, value)| format!("\"{}\":{}", name, value))
+            .collect::<Vec<_>>();
+        let mut moduli = self.moduli.iter().collect::<Vec<_>>();
+        moduli.sort();
+        let moduli = moduli
+            .iter()
+            .map(|(q, n)| format!("\"{}\":{}", q, n))
+            .collect::<Vec<_>>();
+        fields.push(format!("\"moduli\":{{{}}}", moduli.join(",")));
+        format!("{{{}}}", fields.join(","))
+    }
+
+    /// The CSV header matching `to_csv`.
+    pub fn csv_header() -> String {
+        Self::FIELDS.join(",")
+    }
+
+    /// Export the statistics as a single CSV row with the fields in `FIELDS`.
+    pub fn to_csv(&self) -> String {
+        self.values()
+            .iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
 }
 
 impl std::fmt::Display for InformerStats {
@@ -227,7 +294,7 @@ impl<F: Fancy + FancyInput<Item = <F as Fancy>::Item, Error = <F as Fancy>::Erro
 
     fn receive_many(&mut self, moduli: &[u16]) -> Result<Vec<Self::Item>, Self::Error> {
         self.stats
-            .garbler_input_moduli
+            .evaluator_input_moduli
             .extend(moduli.iter().cloned());
         let values = vec![0; moduli.len()];
         self.underlying.encode_many(&values, moduli)
@@ -319,4 +386,41 @@ impl<F: Fancy + FancyReveal> FancyReveal for Informer<F> {
     fn reveal(&mut self, x: &Self::Item) -> Result<u16, Self::Error> {
         self.underlying.reveal(x)
     }
+
+    fn reveal_to(
+        &mut self,
+        x: &Self::Item,
+        policy: RevealPolicy,
+    ) -> Result<Option<u16>, Self::Error> {
+        self.underlying.reveal_to(x, policy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dummy::Dummy;
+
+    #[test]
+    fn export() {
+        let mut f = Informer::new(Dummy::new());
+        let x = f.encode(1, 3).unwrap();
+        let y = f.encode(2, 3).unwrap();
+        let z = f.add(&x, &y).unwrap();
+        let z = f.mul(&z, &x).unwrap();
+        f.output(&z).unwrap();
+        let stats = f.stats();
+        assert_eq!(
+            InformerStats::csv_header(),
+            "garbler_inputs,evaluator_inputs,constants,outputs,output_ciphertexts,\
+             additions,subtractions,cmuls,projections,multiplications,ciphertexts"
+        );
+        assert_eq!(stats.to_csv(), "2,0,0,1,3,1,0,0,0,1,4");
+        assert!(stats.to_json().starts_with("{\"garbler_inputs\":2,\"evaluator_inputs\":0,"));
+
+        let mut f = Informer::new(Dummy::new());
+        f.receive(5).unwrap();
+        assert_eq!(f.stats().num_evaluator_inputs(), 1);
+        assert_eq!(f.stats().num_garbler_inputs(), 0);
+    }
 }
@@ -0,0 +1,255 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Known-answer tests for the garbling scheme.
+//!
+//! A known answer fixes a circuit (by name, from `circuit`), a seed for the
+//! garbler's randomness, and the parties' inputs, and records the expected
+//! outputs alongside a SHA-256 digest of the garbled material: the garbled
+//! circuit followed by the encoded garbler and evaluator inputs, all in the
+//! binary format of `format`. Any change to the garbling scheme changes the
+//! digest, so checking the known answers recorded in `VECTORS` with `verify`
+//! detects incompatibilities across versions and implementations.
+
+use crate::{
+    circuit::{Circuit, CircuitBuilder},
+    classic::garble_with_rng,
+    fancy::Fancy,
+    format,
+};
+use rand::SeedableRng;
+use scuttlebutt::{AesRng, Block};
+use sha2::{Digest, Sha256};
+
+/// The names of the circuits available through `circuit`.
+pub const CIRCUITS: &[&str] = &["arith_mod7", "binary", "projection"];
+
+/// Build the fixed circuit called `name`, if it exists.
+pub fn circuit(name: &str) -> Option<Circuit> {
+    let mut b = CircuitBuilder::new();
+    match name {
+        "arith_mod7" => {
+            let x = b.garbler_input(7);
+            let y = b.evaluator_input(7);
+            let z = b.add(&x, &y).unwrap();
+            let z = b.mul(&z, &y).unwrap();
+            let w = b.cmul(&x, 3).unwrap();
+            let z = b.sub(&z, &w).unwrap();
+            b.output(&z).unwrap();
+            let c = b.constant(5, 7).unwrap();
+            let z = b.mul(&x, &c).unwrap();
+            b.output(&z).unwrap();
+        }
+        "binary" => {
+            let xs = b.garbler_inputs(&[2; 4]);
+            let ys = b.evaluator_inputs(&[2; 4]);
+            let mut acc = b.and(&xs[0], &ys[0]).unwrap();
+            for (x, y) in xs.iter().zip(ys.iter()).skip(1) {
+                let z = b.xor(x, y).unwrap();
+                acc = b.and(&acc, &z).unwrap();
+                b.output(&z).unwrap();
+            }
+            b.output(&acc).unwrap();
+        }
+        "projection" => {
+            let x = b.garbler_input(5);
+            let y = b.evaluator_input(3);
+            let z = b.proj(&x, 3, Some(vec![2, 0, 1, 1, 0])).unwrap();
+            let z = b.add(&z, &y).unwrap();
+            b.output(&z).unwrap();
+        }
+        _ => return None,
+    }
+    Some(b.finish())
+}
+
+/// A single known-answer test.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KnownAnswer {
+    /// The name of the circuit, see `circuit`.
+    pub name: String,
+    /// The seed of the garbler's `AesRng`.
+    pub seed: u128,
+    /// The garbler's inputs.
+    pub garbler_inputs: Vec<u16>,
+    /// The evaluator's inputs.
+    pub evaluator_inputs: Vec<u16>,
+    /// The expected outputs.
+    pub outputs: Vec<u16>,
+    /// SHA-256 digest of the garbled circuit and the encoded inputs.
+    pub digest: [u8; 32],
+}
+
+impl KnownAnswer {
+    /// Compute the known answer for garbling and evaluating circuit `name` with
+    /// the given seed and inputs.
+    pub fn compute(
+        name: &str,
+        seed: u128,
+        garbler_inputs: &[u16],
+        evaluator_inputs: &[u16],
+    ) -> Result<Self, String> {
+        let c = circuit(name).ok_or_else(|| format!("unknown circuit {}", name))?;
+        let rng = AesRng::from_seed(Block::from(seed));
+        let (en, gc) = garble_with_rng(&c, rng).map_err(|e| e.to_string())?;
+        let gb = en.encode_garbler_inputs(garbler_inputs);
+        let ev = en.encode_evaluator_inputs(evaluator_inputs);
+
+        let mut bytes = Vec::new();
+        gc.write_to(&mut bytes).map_err(|e| e.to_string())?;
+        format::write_wires(&gb, &mut bytes).map_err(|e| e.to_string())?;
+        format::write_wires(&ev, &mut bytes).map_err(|e| e.to_string())?;
+        let mut digest = [0u8; 32];
+        digest.copy_from_slice(&Sha256::digest(&bytes));
+
+        let outputs = gc.eval(&c, &gb, &ev).map_err(|e| e.to_string())?;
+        Ok(KnownAnswer {
+            name: name.to_string(),
+            seed,
+            garbler_inputs: garbler_inputs.to_vec(),
+            evaluator_inputs: evaluator_inputs.to_vec(),
+            outputs,
+            digest,
+        })
+    }
+
+    /// Recompute this known answer, returning a description of the first
+    /// mismatch, if any.
+    pub fn verify(&self) -> Result<(), String> {
+        let kat = Self::compute(
+            &self.name,
+            self.seed,
+            &self.garbler_inputs,
+            &self.evaluator_inputs,
+        )?;
+        if kat.outputs != self.outputs {
+            return Err(format!(
+                "{}: expected outputs {:?}, got {:?}",
+                self.name, self.outputs, kat.outputs
+            ));
+        }
+        if kat.digest != self.digest {
+            return Err(format!(
+                "{}: expected digest {}, got {}",
+                self.name,
+                hex(&self.digest),
+                hex(&kat.digest)
+            ));
+        }
+        Ok(())
+    }
+
+    /// Serialize as a single line of whitespace-separated fields: the name,
+    /// the seed in hex, the comma-separated garbler inputs, evaluator inputs,
+    /// and outputs, and the digest in hex.
+    pub fn to_line(&self) -> String {
+        format!(
+            "{} {:032x} {} {} {} {}",
+            self.name,
+            self.seed,
+            join(&self.garbler_inputs),
+            join(&self.evaluator_inputs),
+            join(&self.outputs),
+            hex(&self.digest)
+        )
+    }
+
+    /// Parse a line produced by `to_line`.
+    pub fn from_line(line: &str) -> Option<Self> {
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+        if fields.len() != 6 || fields[5].len() != 64 {
+            return None;
+        }
+        let mut digest = [0u8; 32];
+        for (i, d) in digest.iter_mut().enumerate() {
+            *d = u8::from_str_radix(fields[5].get(2 * i..2 * i + 2)?, 16).ok()?;
+        }
+        Some(KnownAnswer {
+            name: fields[0].to_string(),
+            seed: u128::from_str_radix(fields[1], 16).ok()?,
+            garbler_inputs: split(fields[2])?,
+            evaluator_inputs: split(fields[3])?,
+            outputs: split(fields[4])?,
+            digest,
+        })
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn join(xs: &[u16]) -> String {
+    xs.iter()
+        .map(|x| x.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn split(s: &str) -> Option<Vec<u16>> {
+    s.split(',').map(|x| x.parse().ok()).collect()
+}
+
+/// Generate the known answers for every circuit in `CIRCUITS`, with fixed
+/// seeds and inputs.
+pub fn generate() -> Result<Vec<KnownAnswer>, String> {
+    Ok(vec![
+        KnownAnswer::compute(CIRCUITS[0], 0x0123_4567_89ab_cdef, &[3], &[5])?,
+        KnownAnswer::compute(CIRCUITS[1], 0xdead_beef, &[1, 0, 1, 1], &[1, 1, 0, 1])?,
+        KnownAnswer::compute(CIRCUITS[2], 42, &[3], &[2])?,
+    ])
+}
+
+/// The recorded output of `generate`, one `to_line` per known answer. These
+/// must only change along with an intentional change to the garbling scheme
+/// or to the binary format.
+pub const VECTORS: &[&str] = &[
+    "arith_mod7 00000000000000000123456789abcdef 3 5 3,1 \
+     eab00f1c5f0dea32f08f335b7d25d92c75b9737957e995e1420cf6b0f9e33381",
+    "binary 000000000000000000000000deadbeef 1,0,1,1 1,1,0,1 1,1,0,0 \
+     c23a97296c67b7f3bf2e69b61b605019e46790ca153ffa57208bb44c8892220f",
+    "projection 0000000000000000000000000000002a 3 2 0 \
+     a6dc5fbd11a73043c3a28b7e4470edbfa27a9d83049fe5625a04a42a3d2a87a5",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generate_and_verify() {
+        let kats = generate().unwrap();
+        assert_eq!(kats.len(), CIRCUITS.len());
+        assert_eq!(
+            kats[0].outputs,
+            vec![(((3 + 5) * 5 + 7 - 3 * 3) % 7), 3 * 5 % 7]
+        );
+        assert_eq!(kats[2].outputs, vec![(1 + 2) % 3]);
+        for kat in kats.iter() {
+            kat.verify().unwrap();
+            assert_eq!(KnownAnswer::from_line(&kat.to_line()).as_ref(), Some(kat));
+        }
+        // The digest depends on the seed.
+        let mut kat = kats[0].clone();
+        kat.seed += 1;
+        assert!(kat.verify().is_err());
+    }
+
+    #[test]
+    fn recorded_vectors() {
+        let kats = generate().unwrap();
+        assert_eq!(kats.len(), VECTORS.len());
+        for (kat, line) in kats.iter().zip(VECTORS.iter()) {
+            let recorded = KnownAnswer::from_line(line).unwrap();
+            assert_eq!(
+                kat, &recorded,
+                "{} no longer matches its recorded vector",
+                kat.name
+            );
+            recorded.verify().unwrap();
+        }
+    }
+}
@@ -21,16 +21,43 @@
 #![cfg_attr(feature = "nightly", doc(include = "../README.md"))]
 #![cfg_attr(feature = "nightly", deny(missing_docs))]
 
+#[cfg(feature = "capi")]
+pub mod capi;
 pub mod circuit;
 pub mod classic;
+pub mod config;
+pub mod crt_int;
+pub mod digit;
+pub mod dsl;
 pub mod dummy;
 pub mod errors;
 mod fancy;
+pub mod fault;
+pub mod format;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
 mod garble;
+pub mod harness;
 pub mod informer;
+pub mod kat;
+pub mod matrix;
+pub mod metrics;
+pub mod modulus;
 mod parser;
+pub mod pretty;
+#[cfg(feature = "protobuf")]
+pub mod proto;
+pub mod selftest;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod threepac;
 pub mod twopac;
+pub mod typed;
+pub mod u256;
 pub mod util;
+pub mod validate;
 mod wire;
 
 pub use crate::{errors::FancyError, fancy::*, garble::*, wire::*};
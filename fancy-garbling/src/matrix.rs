@@ -0,0 +1,285 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Plaintext matrices over `Z_q`, as a reference for verifying linear layers
+//! and permutation networks built from gadgets.
+
+use crate::{errors::NumberError, util};
+use std::{fmt, ops::Mul};
+
+/// A matrix with entries mod `q`, stored in row-major order.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct MatZq {
+    q: u16,
+    rows: usize,
+    cols: usize,
+    data: Vec<u16>,
+}
+
+impl MatZq {
+    /// The zero matrix with `rows` rows and `cols` columns.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `q` is below 2.
+    pub fn zero(q: u16, rows: usize, cols: usize) -> Self {
+        assert!(q >= 2, "invalid modulus {}", q);
+        MatZq {
+            q,
+            rows,
+            cols,
+            data: vec![0; rows * cols],
+        }
+    }
+
+    /// The `n` by `n` identity matrix.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `q` is below 2.
+    pub fn identity(q: u16, n: usize) -> Self {
+        let mut m = MatZq::zero(q, n, n);
+        for i in 0..n {
+            m.set(i, i, 1);
+        }
+        m
+    }
+
+    /// Create a matrix from its rows, returning an error if `q` is below 2 or
+    /// an entry is not below `q`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the rows have different lengths.
+    pub fn from_rows(q: u16, rows: &[Vec<u16>]) -> Result<Self, NumberError> {
+        if q < 2 {
+            return Err(NumberError::InvalidModulus(q as u128));
+        }
+        let cols = rows.first().map_or(0, Vec::len);
+        assert!(
+            rows.iter().all(|r| r.len() == cols),
+            "rows have different lengths"
+        );
+        let data = rows.concat();
+        if data.iter().any(|&x| x >= q) {
+            return Err(NumberError::Overflow);
+        }
+        Ok(MatZq {
+            q,
+            rows: rows.len(),
+            cols,
+            data,
+        })
+    }
+
+    /// The modulus of the entries.
+    pub fn modulus(&self) -> u16 {
+        self.q
+    }
+
+    /// The number of rows.
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// The number of columns.
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// The entry in row `i` and column `j`.
+    pub fn get(&self, i: usize, j: usize) -> u16 {
+        assert!(i < self.rows && j < self.cols, "index out of bounds");
+        self.data[i * self.cols + j]
+    }
+
+    /// Set the entry in row `i` and column `j` to `x mod q`.
+    pub fn set(&mut self, i: usize, j: usize, x: u16) {
+        assert!(i < self.rows && j < self.cols, "index out of bounds");
+        self.data[i * self.cols + j] = x % self.q;
+    }
+
+    /// Row `i`.
+    pub fn row(&self, i: usize) -> &[u16] {
+        &self.data[i * self.cols..(i + 1) * self.cols]
+    }
+
+    /// The transpose.
+    pub fn transpose(&self) -> MatZq {
+        let mut t = MatZq::zero(self.q, self.cols, self.rows);
+        for i in 0..self.rows {
+            for j in 0..self.cols {
+                t.data[j * self.rows + i] = self.data[i * self.cols + j];
+            }
+        }
+        t
+    }
+
+    /// Multiply by the column vector `v`, e.g., the plaintext inputs of a
+    /// linear layer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `v` does not have one entry per column.
+    pub fn mul_vec(&self, v: &[u16]) -> Vec<u16> {
+        assert_eq!(v.len(), self.cols, "dimensions differ");
+        (0..self.rows)
+            .map(|i| dot(self.row(i), v, self.q))
+            .collect()
+    }
+
+    /// The inverse, computed by Gauss-Jordan elimination, if the matrix is
+    /// invertible and every pivot is a unit mod `q`, as is always the case
+    /// for a prime `q`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the matrix is not square.
+    pub fn inverse(&self) -> Option<MatZq> {
+        assert_eq!(self.rows, self.cols, "matrix is not square");
+        let n = self.rows;
+        let q = self.q as u32;
+        let mut a = self.clone();
+        let mut b = MatZq::identity(self.q, n);
+        for c in 0..n {
+            // Find a row with an invertible entry in column `c`.
+            let (r, inv) = (c..n).find_map(|r| {
+                util::try_inv(a.get(r, c) as i128, q as i128)
+                    .ok()
+                    .map(|inv| (r, inv as u32))
+            })?;
+            a.swap_rows(r, c);
+            b.swap_rows(r, c);
+            a.scale_row(c, inv);
+            b.scale_row(c, inv);
+            for r in (0..n).filter(|&r| r != c) {
+                let f = a.get(r, c) as u32;
+                if f != 0 {
+                    a.sub_row(r, c, f);
+                    b.sub_row(r, c, f);
+                }
+            }
+        }
+        Some(b)
+    }
+
+    fn swap_rows(&mut self, i: usize, j: usize) {
+        for k in 0..self.cols {
+            self.data.swap(i * self.cols + k, j * self.cols + k);
+        }
+    }
+
+    fn scale_row(&mut self, i: usize, c: u32) {
+        let q = self.q as u32;
+        for x in self.data[i * self.cols..(i + 1) * self.cols].iter_mut() {
+            *x = (*x as u32 * c % q) as u16;
+        }
+    }
+
+    /// Subtract `c` times row `j` from row `i`.
+    fn sub_row(&mut self, i: usize, j: usize, c: u32) {
+        let q = self.q as u32;
+        for k in 0..self.cols {
+            let y = self.data[j * self.cols + k] as u32 * c % q;
+            let x = &mut self.data[i * self.cols + k];
+            *x = ((*x as u32 + q - y) % q) as u16;
+        }
+    }
+}
+
+impl Mul for &MatZq {
+    type Output = MatZq;
+
+    /// # Panics
+    ///
+    /// Panics if the moduli differ, or the number of columns of `self` is not
+    /// the number of rows of `other`.
+    fn mul(self, other: &MatZq) -> MatZq {
+        assert_eq!(self.q, other.q, "moduli differ");
+        assert_eq!(self.cols, other.rows, "dimensions differ");
+        let t = other.transpose();
+        let mut m = MatZq::zero(self.q, self.rows, other.cols);
+        for i in 0..self.rows {
+            let row = m.data[i * other.cols..(i + 1) * other.cols].iter_mut();
+            for (j, x) in row.enumerate() {
+                *x = dot(self.row(i), t.row(j), self.q);
+            }
+        }
+        m
+    }
+}
+
+fn dot(xs: &[u16], ys: &[u16], q: u16) -> u16 {
+    let q = q as u64;
+    xs.iter()
+        .zip(ys.iter())
+        .fold(0, |acc, (&x, &y)| (acc + x as u64 * y as u64) % q) as u16
+}
+
+impl fmt::Debug for MatZq {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MatZq(mod {}, [", self.q)?;
+        for i in 0..self.rows {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{:?}", self.row(i))?;
+        }
+        write!(f, "])")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::RngExt;
+    use rand::thread_rng;
+
+    fn random(q: u16, rows: usize, cols: usize) -> MatZq {
+        let mut rng = thread_rng();
+        let rows = (0..rows)
+            .map(|_| rng.gen_digits(q, cols))
+            .collect::<Vec<Vec<u16>>>();
+        MatZq::from_rows(q, &rows).unwrap()
+    }
+
+    #[test]
+    fn arithmetic() {
+        for &q in [2, 7, 109].iter() {
+            let a = random(q, 3, 5);
+            let b = random(q, 5, 4);
+            let v = random(q, 1, 4).row(0).to_vec();
+            assert_eq!((&a * &b).transpose(), &b.transpose() * &a.transpose());
+            assert_eq!((&a * &b).mul_vec(&v), a.mul_vec(&b.mul_vec(&v)));
+            assert_eq!(&a * &MatZq::identity(q, 5), a);
+        }
+        let m = MatZq::from_rows(5, &[vec![1, 2], vec![3, 4]]).unwrap();
+        assert_eq!(format!("{:?}", m), "MatZq(mod 5, [[1, 2], [3, 4]])");
+        assert_eq!(m.mul_vec(&[1, 1]), vec![3, 2]);
+        assert!(MatZq::from_rows(5, &[vec![5]]).is_err());
+    }
+
+    #[test]
+    fn inverse() {
+        for &q in [2, 7, 109].iter() {
+            let mut ninvertible = 0;
+            for _ in 0..32 {
+                let a = random(q, 6, 6);
+                if let Some(b) = a.inverse() {
+                    assert_eq!(&a * &b, MatZq::identity(q, 6));
+                    assert_eq!(&b * &a, MatZq::identity(q, 6));
+                    ninvertible += 1;
+                }
+            }
+            assert!(ninvertible > 0);
+        }
+        let singular = MatZq::from_rows(7, &[vec![1, 2], vec![2, 4]]).unwrap();
+        assert_eq!(singular.inverse(), None);
+        // Swapping rows needs a pivot search.
+        let swap = MatZq::from_rows(7, &[vec![0, 1], vec![1, 0]]).unwrap();
+        assert_eq!(swap.inverse(), Some(swap.clone()));
+    }
+}
@@ -0,0 +1,299 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Hooks for exporting live metrics from garbling and protocol runs.
+//!
+//! Implement `MetricsSink` on top of whatever metrics library a service uses,
+//! e.g., with Prometheus counters, then wrap a `Fancy` object in `Metered` to
+//! count the gates it processes, and its channel in `MeteredChannel` to count
+//! the bytes it moves in each phase of the protocol. The semi-honest 2PC
+//! parties additionally report their OTs to a sink set with `set_metrics`.
+//!
+//! The hooks only report counts. Rates, such as gates garbled per second, are
+//! left to the metrics backend, which derives them from the counters.
+
+use crate::fancy::{Fancy, FancyReveal, RevealPolicy};
+use scuttlebutt::AbstractChannel;
+use std::sync::{Arc, Mutex};
+
+/// The kinds of gates reported to a `MetricsSink`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GateKind {
+    /// A constant.
+    Constant,
+    /// An addition.
+    Add,
+    /// A subtraction.
+    Sub,
+    /// A multiplication by a constant.
+    Cmul,
+    /// A multiplication.
+    Mul,
+    /// A projection.
+    Proj,
+    /// An output or reveal.
+    Output,
+}
+
+/// Receives metrics from `Metered`, `MeteredChannel`, and the 2PC parties.
+///
+/// Every method does nothing by default, so a sink only implements the ones it
+/// exports. Methods take `&self`, as sinks are shared between threads and
+/// typically record into atomic counters.
+pub trait MetricsSink {
+    /// `n` gates of kind `kind` were garbled or evaluated.
+    fn gates(&self, kind: GateKind, n: u64) {
+        let _ = (kind, n);
+    }
+
+    /// `n` bytes were sent during `phase`.
+    fn bytes_sent(&self, phase: &str, n: u64) {
+        let _ = (phase, n);
+    }
+
+    /// `n` bytes were received during `phase`.
+    fn bytes_received(&self, phase: &str, n: u64) {
+        let _ = (phase, n);
+    }
+
+    /// `n` oblivious transfers were performed.
+    fn ots(&self, n: u64) {
+        let _ = n;
+    }
+}
+
+impl<S: MetricsSink + ?Sized> MetricsSink for Arc<S> {
+    fn gates(&self, kind: GateKind, n: u64) {
+        (**self).gates(kind, n)
+    }
+
+    fn bytes_sent(&self, phase: &str, n: u64) {
+        (**self).bytes_sent(phase, n)
+    }
+
+    fn bytes_received(&self, phase: &str, n: u64) {
+        (**self).bytes_received(phase, n)
+    }
+
+    fn ots(&self, n: u64) {
+        (**self).ots(n)
+    }
+}
+
+/// Implements `Fancy` by running an underlying `Fancy` object and reporting
+/// each gate to a sink.
+pub struct Metered<F, S> {
+    inner: F,
+    sink: S,
+}
+
+impl<F, S: MetricsSink> Metered<F, S> {
+    /// Wrap `inner`, reporting its gates to `sink`.
+    pub fn new(inner: F, sink: S) -> Self {
+        Metered { inner, sink }
+    }
+
+    /// The underlying fancy object.
+    pub fn inner(&mut self) -> &mut F {
+        &mut self.inner
+    }
+
+    /// Unwrap the underlying fancy object.
+    pub fn into_inner(self) -> F {
+        self.inner
+    }
+
+    fn report<T>(&self, kind: GateKind, result: T) -> T {
+        self.sink.gates(kind, 1);
+        result
+    }
+}
+
+impl<F: Fancy, S: MetricsSink> Fancy for Metered<F, S> {
+    type Item = F::Item;
+    type Error = F::Error;
+
+    fn constant(&mut self, x: u16, q: u16) -> Result<Self::Item, Self::Error> {
+        let z = self.inner.constant(x, q)?;
+        Ok(self.report(GateKind::Constant, z))
+    }
+
+    fn add(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error> {
+        let z = self.inner.add(x, y)?;
+        Ok(self.report(GateKind::Add, z))
+    }
+
+    fn sub(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error> {
+        let z = self.inner.sub(x, y)?;
+        Ok(self.report(GateKind::Sub, z))
+    }
+
+    fn cmul(&mut self, x: &Self::Item, c: u16) -> Result<Self::Item, Self::Error> {
+        let z = self.inner.cmul(x, c)?;
+        Ok(self.report(GateKind::Cmul, z))
+    }
+
+    fn mul(&mut self, x: &Self::Item, y: &Self::Item) -> Result<Self::Item, Self::Error> {
+        let z = self.inner.mul(x, y)?;
+        Ok(self.report(GateKind::Mul, z))
+    }
+
+    fn proj(
+        &mut self,
+        x: &Self::Item,
+        q: u16,
+        tt: Option<Vec<u16>>,
+    ) -> Result<Self::Item, Self::Error> {
+        let z = self.inner.proj(x, q, tt)?;
+        Ok(self.report(GateKind::Proj, z))
+    }
+
+    fn output(&mut self, x: &Self::Item) -> Result<Option<u16>, Self::Error> {
+        let z = self.inner.output(x)?;
+        Ok(self.report(GateKind::Output, z))
+    }
+}
+
+impl<F: FancyReveal, S: MetricsSink> FancyReveal for Metered<F, S> {
+    fn reveal(&mut self, x: &Self::Item) -> Result<u16, Self::Error> {
+        let z = self.inner.reveal(x)?;
+        Ok(self.report(GateKind::Output, z))
+    }
+
+    fn reveal_to(
+        &mut self,
+        x: &Self::Item,
+        policy: RevealPolicy,
+    ) -> Result<Option<u16>, Self::Error> {
+        let z = self.inner.reveal_to(x, policy)?;
+        Ok(self.report(GateKind::Output, z))
+    }
+}
+
+/// Implements `AbstractChannel` by running an underlying channel and reporting
+/// the bytes it sends and receives to a sink, labeled with the current phase.
+///
+/// Clones share their phase, so setting it on one clone sets it on all.
+pub struct MeteredChannel<C, S> {
+    channel: C,
+    sink: S,
+    phase: Arc<Mutex<&'static str>>,
+}
+
+impl<C: AbstractChannel, S: MetricsSink + Clone> MeteredChannel<C, S> {
+    /// Wrap `channel`, reporting its traffic to `sink` in phase `phase`.
+    pub fn new(channel: C, sink: S, phase: &'static str) -> Self {
+        MeteredChannel {
+            channel,
+            sink,
+            phase: Arc::new(Mutex::new(phase)),
+        }
+    }
+
+    /// Report subsequent traffic in phase `phase`.
+    pub fn set_phase(&self, phase: &'static str) {
+        *self.phase.lock().unwrap() = phase;
+    }
+
+    /// The current phase.
+    pub fn phase(&self) -> &'static str {
+        *self.phase.lock().unwrap()
+    }
+}
+
+impl<C: AbstractChannel, S: MetricsSink + Clone> AbstractChannel for MeteredChannel<C, S> {
+    fn read_bytes(&mut self, bytes: &mut [u8]) -> std::io::Result<()> {
+        self.channel.read_bytes(bytes)?;
+        self.sink.bytes_received(self.phase(), bytes.len() as u64);
+        Ok(())
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        self.channel.write_bytes(bytes)?;
+        self.sink.bytes_sent(self.phase(), bytes.len() as u64);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.channel.flush()
+    }
+
+    fn clone(&self) -> Self {
+        MeteredChannel {
+            channel: self.channel.clone(),
+            sink: self.sink.clone(),
+            phase: self.phase.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dummy::Dummy;
+    use scuttlebutt::Channel;
+    use std::{collections::HashMap, io::Cursor};
+
+    #[derive(Default)]
+    struct Counts {
+        gates: Mutex<HashMap<GateKind, u64>>,
+        sent: Mutex<HashMap<String, u64>>,
+        received: Mutex<HashMap<String, u64>>,
+    }
+
+    impl MetricsSink for Counts {
+        fn gates(&self, kind: GateKind, n: u64) {
+            *self.gates.lock().unwrap().entry(kind).or_insert(0) += n;
+        }
+
+        fn bytes_sent(&self, phase: &str, n: u64) {
+            *self
+                .sent
+                .lock()
+                .unwrap()
+                .entry(phase.to_string())
+                .or_insert(0) += n;
+        }
+
+        fn bytes_received(&self, phase: &str, n: u64) {
+            *self
+                .received
+                .lock()
+                .unwrap()
+                .entry(phase.to_string())
+                .or_insert(0) += n;
+        }
+    }
+
+    #[test]
+    fn counts() {
+        let sink = Arc::new(Counts::default());
+        let mut f = Metered::new(Dummy::new(), sink.clone());
+        let x = f.constant(3, 7).unwrap();
+        let y = f.mul(&x, &x).unwrap();
+        let z = f.add(&x, &y).unwrap();
+        let z = f.mul(&z, &y).unwrap();
+        assert_eq!(f.reveal(&z).unwrap(), (3 + 9) * 9 % 7);
+        let gates = sink.gates.lock().unwrap();
+        assert_eq!(gates[&GateKind::Mul], 2);
+        assert_eq!(gates[&GateKind::Add], 1);
+        assert_eq!(gates[&GateKind::Output], 1);
+        assert_eq!(gates.get(&GateKind::Proj), None);
+
+        let channel = Channel::new(Cursor::new(vec![0; 10]), Vec::new());
+        let mut channel = MeteredChannel::new(channel, sink.clone(), "setup");
+        channel.write_u64(1).unwrap();
+        let mut clone = channel.clone();
+        clone.set_phase("online");
+        assert_eq!(channel.phase(), "online");
+        channel.write_u16(1).unwrap();
+        channel.read_u64().unwrap();
+        clone.read_u16().unwrap();
+        assert_eq!(sink.sent.lock().unwrap()["setup"], 8);
+        assert_eq!(sink.sent.lock().unwrap()["online"], 2);
+        assert_eq!(sink.received.lock().unwrap()["online"], 10);
+    }
+}
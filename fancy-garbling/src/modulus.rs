@@ -0,0 +1,586 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! A validated modulus, carrying the constants derived from it.
+//!
+//! A bare `u16` says nothing about whether it is a usable modulus, and every
+//! function receiving one must recompute whatever it needs from it. A
+//! `Modulus` is checked once on construction, computes its Barrett constants
+//! eagerly, and looks up the `ModTables` of a prime modulus the first time
+//! they are needed.
+//!
+//! `ModTables` bundles a prime's primitive root with its exponent, discrete
+//! log, and inverse tables. They are built once per prime and kept in a
+//! global registry, so every user of a prime agrees on its generator.
+//!
+//! `verify_tables` checks a prime's tables against arithmetic that does not
+//! use them, which the registry also does for each new prime when the `strict`
+//! feature is enabled, and `regenerate_tables` builds fresh ones.
+
+use crate::{errors::NumberError, util};
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+};
+
+/// A modulus of at least 2.
+#[derive(Clone)]
+pub struct Modulus {
+    q: u16,
+    prime: bool,
+    reducer: BarrettReducer,
+    tables: OnceLock<Option<&'static ModTables>>,
+}
+
+impl Modulus {
+    /// Create a modulus `q`, returning an error if it is below 2.
+    pub fn new(q: u16) -> Result<Self, NumberError> {
+        if q < 2 {
+            return Err(NumberError::InvalidModulus(q as u128));
+        }
+        Ok(Modulus {
+            q,
+            prime: util::is_prime(q as u64),
+            reducer: BarrettReducer::new(q),
+            tables: OnceLock::new(),
+        })
+    }
+
+    /// Create a prime modulus `q`, returning an error if it is not prime.
+    pub fn prime(q: u16) -> Result<Self, NumberError> {
+        let m = Self::new(q)?;
+        if !m.prime {
+            return Err(NumberError::InvalidModulus(q as u128));
+        }
+        Ok(m)
+    }
+
+    /// The value of the modulus.
+    #[inline]
+    pub fn value(&self) -> u16 {
+        self.q
+    }
+
+    /// Whether the modulus is prime.
+    #[inline]
+    pub fn is_prime(&self) -> bool {
+        self.prime
+    }
+
+    /// The number of `mod q` digits that fit in a `u128`.
+    #[inline]
+    pub fn digits_per_u128(&self) -> usize {
+        util::digits_per_u128(self.q)
+    }
+
+    /// Convert `x` into base `q`, building a vector of length `n`.
+    pub fn as_base_q(&self, x: u128, n: usize) -> Vec<u16> {
+        util::as_base_q(x, self.q, n)
+    }
+
+    /// Convert base `q` digits into a `u128`.
+    pub fn from_base_q(&self, ds: &[u16]) -> u128 {
+        util::from_base_q(ds, self.q)
+    }
+
+    /// The Barrett reducer of the modulus.
+    #[inline]
+    pub fn reducer(&self) -> &BarrettReducer {
+        &self.reducer
+    }
+
+    /// Reduce `x` mod `q` using the Barrett constant, without a division.
+    #[inline]
+    pub fn reduce(&self, x: u32) -> u16 {
+        self.reducer.reduce(x)
+    }
+
+    /// Compute `x + y mod q`, for `x, y < q`.
+    #[inline]
+    pub fn add(&self, x: u16, y: u16) -> u16 {
+        self.reduce(x as u32 + y as u32)
+    }
+
+    /// Compute `x * y mod q`, for `x, y < q`.
+    #[inline]
+    pub fn mul(&self, x: u16, y: u16) -> u16 {
+        self.reduce(x as u32 * y as u32)
+    }
+
+    /// Compute `x^e mod q`.
+    pub fn pow(&self, x: u16, mut e: u16) -> u16 {
+        let mut b = self.reduce(x as u32);
+        let mut z = self.reduce(1);
+        while e > 0 {
+            if e & 1 == 1 {
+                z = self.mul(z, b);
+            }
+            b = self.mul(b, b);
+            e >>= 1;
+        }
+        z
+    }
+
+    /// The tables of the modulus, if it is prime.
+    pub fn tables(&self) -> Option<&'static ModTables> {
+        *self.tables.get_or_init(|| {
+            if self.prime {
+                ModTables::for_prime(self.q).ok()
+            } else {
+                None
+            }
+        })
+    }
+
+    /// The smallest primitive root, if the modulus is prime.
+    pub fn primitive_root(&self) -> Option<u16> {
+        self.tables().map(|t| t.primitive_root())
+    }
+
+    /// Compute `g^i mod q`, where `g` is the primitive root, if the modulus is
+    /// prime.
+    pub fn exp(&self, i: u16) -> Option<u16> {
+        self.tables().map(|t| t.exp(i))
+    }
+
+    /// Compute the discrete log of `x` to the base of the primitive root, if
+    /// the modulus is prime and `x` is nonzero mod `q`.
+    pub fn dlog(&self, x: u16) -> Option<u16> {
+        self.tables().and_then(|t| t.dlog(x))
+    }
+
+    /// Compute the inverse of `x` mod `q`, if the modulus is prime and `x` is
+    /// nonzero mod `q`.
+    pub fn inv(&self, x: u16) -> Option<u16> {
+        self.tables().and_then(|t| t.inv(x))
+    }
+
+    /// The table of `g^i mod q` for `i < q - 1`, if the modulus is prime.
+    pub fn exp_table(&self) -> Option<&[u16]> {
+        self.tables().map(|t| t.exp_table())
+    }
+
+    /// The table of discrete logs of `0 < x < q`, indexed by `x`, if the
+    /// modulus is prime. The entry for zero is meaningless.
+    pub fn dlog_table(&self) -> Option<&[u16]> {
+        self.tables().map(|t| t.dlog_table())
+    }
+}
+
+/// Reduces integers mod a fixed modulus `q` with Barrett's method, which
+/// replaces the division by multiplications by precomputed reciprocals.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BarrettReducer {
+    q: u16,
+    // `floor(2^32 / q)`.
+    m32: u64,
+    // `floor((2^64 - 1) / q)`.
+    m64: u64,
+    // `2^64 mod q`.
+    r64: u16,
+}
+
+impl BarrettReducer {
+    /// Precompute the constants of the modulus `q`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `q` is zero.
+    pub fn new(q: u16) -> Self {
+        let m64 = u64::max_value() / q as u64;
+        // `2^64 - 1 - m64 * q` is `(2^64 - 1) mod q`.
+        let r = u64::max_value() - m64 * q as u64 + 1;
+        BarrettReducer {
+            q,
+            m32: (1u64 << 32) / q as u64,
+            m64,
+            r64: if r == q as u64 { 0 } else { r as u16 },
+        }
+    }
+
+    /// The modulus.
+    #[inline]
+    pub fn modulus(&self) -> u16 {
+        self.q
+    }
+
+    /// Reduce `x` mod `q`.
+    #[inline]
+    pub fn reduce(&self, x: u32) -> u16 {
+        let t = ((x as u64 * self.m32) >> 32) as u32;
+        let r = x - t * self.q as u32;
+        // `t` underestimates `x / q` by at most one.
+        if r >= self.q as u32 {
+            (r - self.q as u32) as u16
+        } else {
+            r as u16
+        }
+    }
+
+    /// Reduce `x` mod `q`, without branching on `x`.
+    #[inline]
+    pub fn reduce_ct(&self, x: u32) -> u16 {
+        let t = ((x as u64 * self.m32) >> 32) as u32;
+        let r = x - t * self.q as u32;
+        // `r < 2q <= 2^17`, so the top bit of `s` is set exactly when `r < q`.
+        let s = r.wrapping_sub(self.q as u32);
+        let mask = 0u32.wrapping_sub(s >> 31);
+        s.wrapping_add(self.q as u32 & mask) as u16
+    }
+
+    /// Reduce `x` mod `q`.
+    #[inline]
+    pub fn reduce_u64(&self, x: u64) -> u16 {
+        let t = ((x as u128 * self.m64 as u128) >> 64) as u64;
+        let r = x - t * self.q as u64;
+        // `t` underestimates `x / q` by at most one.
+        if r >= self.q as u64 {
+            (r - self.q as u64) as u16
+        } else {
+            r as u16
+        }
+    }
+
+    /// Reduce `x` mod `q`, without the slow `u128` division.
+    #[inline]
+    pub fn reduce_u128(&self, x: u128) -> u16 {
+        let hi = self.reduce_u64((x >> 64) as u64) as u64;
+        let lo = self.reduce_u64(x as u64) as u64;
+        self.reduce_u64(hi * self.r64 as u64 + lo)
+    }
+}
+
+/// The primitive root of a prime `p` with its exponent, discrete log, and
+/// inverse tables.
+#[derive(Debug)]
+pub struct ModTables {
+    p: u16,
+    g: u16,
+    // `exp[i] = g^i` for `i < p - 1`.
+    exp: Vec<u16>,
+    // `dlog[x] = i` such that `g^i = x`, for `0 < x < p`. `dlog[0]` is unused.
+    dlog: Vec<u16>,
+    // `inv[x] * x = 1`, for `0 < x < p`. `inv[0]` is unused.
+    inv: Vec<u16>,
+}
+
+impl ModTables {
+    /// The tables of the prime `p`, built the first time they are requested.
+    /// Returns an error if `p` is not prime.
+    pub fn for_prime(p: u16) -> Result<&'static ModTables, NumberError> {
+        static REGISTRY: OnceLock<Mutex<HashMap<u16, &'static ModTables>>> = OnceLock::new();
+        let m = Modulus::prime(p)?;
+        let mut registry = REGISTRY
+            .get_or_init(|| Mutex::new(HashMap::new()))
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        if let Some(&t) = registry.get(&p) {
+            return Ok(t);
+        }
+        let t = ModTables::new(&m);
+        if cfg!(feature = "strict") {
+            t.verify()?;
+        }
+        // Tables are never freed, but there is at most one per prime.
+        let t = &*Box::leak(Box::new(t));
+        registry.insert(p, t);
+        Ok(t)
+    }
+
+    fn new(m: &Modulus) -> Self {
+        let p = m.q;
+        let g = primitive_root(m);
+        let mut exp = Vec::with_capacity(p as usize - 1);
+        let mut dlog = vec![0; p as usize];
+        let mut x = 1;
+        for i in 0..p - 1 {
+            exp.push(x);
+            dlog[x as usize] = i;
+            x = m.mul(x, g);
+        }
+        // `x^-1 = g^(p - 1 - dlog(x))`.
+        let n = p as usize - 1;
+        let inv = dlog
+            .iter()
+            .enumerate()
+            .map(|(x, &i)| if x == 0 { 0 } else { exp[(n - i as usize) % n] })
+            .collect();
+        ModTables {
+            p,
+            g,
+            exp,
+            dlog,
+            inv,
+        }
+    }
+
+    /// Check that the tables are consistent with each other and with
+    /// `Modulus::pow`, returning an error describing the first inconsistency.
+    pub fn verify(&self) -> Result<(), NumberError> {
+        let p = self.p;
+        let fail = |reason: String| Err(NumberError::InconsistentTables { p, reason });
+        let m = Modulus::prime(p)?;
+        if self.exp.len() != p as usize - 1
+            || self.dlog.len() != p as usize
+            || self.inv.len() != p as usize
+        {
+            return fail("wrong table sizes".to_string());
+        }
+        // `g` is a primitive root if its powers below `p - 1` are distinct.
+        let mut seen = vec![false; p as usize];
+        for (i, &x) in self.exp.iter().enumerate() {
+            let i = i as u16;
+            let y = m.pow(self.g, i);
+            if x != y {
+                return fail(format!("exp[{}] = {}, but {}^{} = {}", i, x, self.g, i, y));
+            }
+            if seen[x as usize] {
+                return fail(format!("{} is not a primitive root", self.g));
+            }
+            seen[x as usize] = true;
+            if self.dlog[x as usize] != i {
+                return fail(format!(
+                    "dlog[{}] = {}, not {}",
+                    x, self.dlog[x as usize], i
+                ));
+            }
+        }
+        for x in 1..p {
+            let y = self.inv[x as usize];
+            if m.mul(x, y) != 1 {
+                return fail(format!("inv[{}] = {} is not its inverse", x, y));
+            }
+        }
+        Ok(())
+    }
+
+    /// The prime.
+    pub fn prime(&self) -> u16 {
+        self.p
+    }
+
+    /// The smallest primitive root `g`.
+    pub fn primitive_root(&self) -> u16 {
+        self.g
+    }
+
+    /// Compute `g^i mod p`.
+    pub fn exp(&self, i: u16) -> u16 {
+        self.exp[(i as usize) % (self.p as usize - 1)]
+    }
+
+    /// Compute the discrete log of `x` to the base `g`, if `x` is nonzero mod
+    /// `p`.
+    pub fn dlog(&self, x: u16) -> Option<u16> {
+        match x % self.p {
+            0 => None,
+            x => Some(self.dlog[x as usize]),
+        }
+    }
+
+    /// Compute the inverse of `x` mod `p`, if `x` is nonzero mod `p`.
+    pub fn inv(&self, x: u16) -> Option<u16> {
+        match x % self.p {
+            0 => None,
+            x => Some(self.inv[x as usize]),
+        }
+    }
+
+    /// The table of `g^i mod p` for `i < p - 1`.
+    pub fn exp_table(&self) -> &[u16] {
+        &self.exp
+    }
+
+    /// The table of discrete logs of `0 < x < p`, indexed by `x`. The entry for
+    /// zero is meaningless.
+    pub fn dlog_table(&self) -> &[u16] {
+        &self.dlog
+    }
+
+    /// The table of inverses of `0 < x < p`, indexed by `x`. The entry for zero
+    /// is meaningless.
+    pub fn inv_table(&self) -> &[u16] {
+        &self.inv
+    }
+}
+
+/// Check the registry's tables for the prime `p`, as `ModTables::verify` does.
+/// Returns an error if `p` is not prime or the tables are inconsistent.
+pub fn verify_tables(p: u16) -> Result<(), NumberError> {
+    ModTables::for_prime(p)?.verify()
+}
+
+/// Build fresh tables for the prime `p`, bypassing the registry, e.g., to
+/// compare against those in use. Returns an error if `p` is not prime.
+pub fn regenerate_tables(p: u16) -> Result<ModTables, NumberError> {
+    Modulus::prime(p).map(|m| ModTables::new(&m))
+}
+
+/// The smallest primitive root of the prime modulus `m`.
+fn primitive_root(m: &Modulus) -> u16 {
+    let n = m.q - 1;
+    let mut fs = Vec::new();
+    let mut x = n;
+    let mut p = 2;
+    while p * p <= x {
+        if x % p == 0 {
+            fs.push(p);
+            while x % p == 0 {
+                x /= p;
+            }
+        }
+        p += 1;
+    }
+    if x > 1 {
+        fs.push(x);
+    }
+    (1..m.q)
+        .find(|&g| fs.iter().all(|&f| m.pow(g, n / f) != 1))
+        .expect("every prime has a primitive root")
+}
+
+impl PartialEq for Modulus {
+    fn eq(&self, other: &Modulus) -> bool {
+        self.q == other.q
+    }
+}
+
+impl Eq for Modulus {}
+
+impl std::hash::Hash for Modulus {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.q.hash(state)
+    }
+}
+
+impl std::fmt::Debug for Modulus {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Modulus({})", self.q)
+    }
+}
+
+impl std::fmt::Display for Modulus {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.q.fmt(f)
+    }
+}
+
+impl std::convert::TryFrom<u16> for Modulus {
+    type Error = NumberError;
+
+    fn try_from(q: u16) -> Result<Self, NumberError> {
+        Modulus::new(q)
+    }
+}
+
+impl From<&Modulus> for u16 {
+    fn from(m: &Modulus) -> u16 {
+        m.q
+    }
+}
+
+impl From<Modulus> for u16 {
+    fn from(m: Modulus) -> u16 {
+        m.q
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::{RngExt, PRIMES};
+
+    #[test]
+    fn validation() {
+        assert!(Modulus::new(0).is_err());
+        assert!(Modulus::new(1).is_err());
+        assert!(Modulus::new(12).is_ok());
+        assert!(Modulus::prime(12).is_err());
+        assert_eq!(Modulus::prime(65521).unwrap().value(), 65521);
+        assert_eq!(Modulus::new(12).unwrap().primitive_root(), None);
+    }
+
+    #[test]
+    fn reduce() {
+        for &q in [2, 3, 12, 255, 256, 65521, 65535].iter() {
+            let m = Modulus::new(q).unwrap();
+            for x in (0..u32::max_value()).step_by(9973) {
+                assert_eq!(m.reduce(x), (x % q as u32) as u16);
+            }
+            assert_eq!(
+                m.reduce(u32::max_value()),
+                (u32::max_value() % q as u32) as u16
+            );
+        }
+    }
+
+    #[test]
+    fn barrett_reducer() {
+        let mut rng = rand::thread_rng();
+        for &q in [1, 2, 3, 12, 255, 256, 65521, 65535].iter() {
+            let b = BarrettReducer::new(q);
+            for _ in 0..1000 {
+                let x = rng.gen_u128();
+                assert_eq!(b.reduce_u128(x), (x % q as u128) as u16);
+                assert_eq!(b.reduce_u64(x as u64), (x as u64 % q as u64) as u16);
+                assert_eq!(b.reduce(x as u32), (x as u32 % q as u32) as u16);
+            }
+            for &x in [0, u128::max_value(), u64::max_value() as u128].iter() {
+                assert_eq!(b.reduce_u128(x), (x % q as u128) as u16);
+                assert_eq!(b.reduce_u64(x as u64), (x as u64 % q as u64) as u16);
+            }
+        }
+    }
+
+    #[test]
+    fn tables() {
+        for &p in PRIMES.iter() {
+            let m = Modulus::prime(p).unwrap();
+            let g = m.primitive_root().unwrap();
+            for x in 1..p {
+                let i = m.dlog(x).unwrap();
+                assert_eq!(m.exp(i), Some(x));
+                assert_eq!(m.pow(g, i), x);
+                assert_eq!(m.mul(x, m.inv(x).unwrap()), 1);
+            }
+            assert_eq!(m.dlog(0), None);
+            assert_eq!(m.inv(p), None);
+            // Every modulus shares the registry's tables.
+            let exp = m.exp_table().unwrap().as_ptr();
+            assert_eq!(
+                Modulus::prime(p).unwrap().exp_table().unwrap().as_ptr(),
+                exp
+            );
+            assert_eq!(ModTables::for_prime(p).unwrap().exp_table().as_ptr(), exp);
+        }
+        assert!(ModTables::for_prime(12).is_err());
+        let t = ModTables::for_prime(65521).unwrap();
+        assert_eq!(t.prime(), 65521);
+        assert_eq!(t.inv_table()[2], 32761);
+    }
+
+    #[test]
+    fn verification() {
+        for &p in PRIMES.iter().chain([65521].iter()) {
+            assert_eq!(verify_tables(p), Ok(()));
+            let t = regenerate_tables(p).unwrap();
+            assert_eq!(t.exp_table(), ModTables::for_prime(p).unwrap().exp_table());
+        }
+        assert!(verify_tables(12).is_err());
+        assert!(regenerate_tables(12).is_err());
+        // A single transcription error is caught.
+        let corrupt = |f: &dyn Fn(&mut ModTables)| {
+            let mut t = regenerate_tables(113).unwrap();
+            f(&mut t);
+            t.verify()
+        };
+        assert!(corrupt(&|t| t.exp[57] += 1).is_err());
+        assert!(corrupt(&|t| t.dlog[9] += 1).is_err());
+        assert!(corrupt(&|t| t.inv[100] += 1).is_err());
+        assert!(corrupt(&|t| t.g = 4).is_err());
+        assert_eq!(corrupt(&|_| ()), Ok(()));
+    }
+}
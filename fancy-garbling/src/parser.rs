@@ -5,7 +5,8 @@
 // See LICENSE for licensing information.
 
 //! Functions for parsing and running a circuit file based on the format given
-//! here: <https://homes.esat.kuleuven.be/~nsmart/MPC/>.
+//! here: <https://homes.esat.kuleuven.be/~nsmart/MPC/>, and for writing
+//! binary circuits back out in it.
 
 use crate::{
     circuit::{Circuit, CircuitRef, Gate},
@@ -14,7 +15,7 @@ use crate::{
 use regex::{Captures, Regex};
 use std::{
     fs::File,
-    io::{BufRead, BufReader},
+    io::{BufRead, BufReader, Write},
     str::FromStr,
 };
 
@@ -38,6 +39,56 @@ fn cap2typ(cap: &Captures, idx: usize) -> Result<GateType, Error> {
     }
 }
 
+/// What a slot of the circuit holds while writing it as a Bristol circuit.
+#[derive(Clone, Copy, PartialEq)]
+enum Value {
+    Wire(usize),
+    Const(u16),
+}
+
+/// The gates of a Bristol circuit being written, numbering their output wires
+/// after the inputs.
+struct Bristol {
+    ninputs: usize,
+    lines: Vec<String>,
+}
+
+impl Bristol {
+    fn next_wire(&self) -> usize {
+        self.ninputs + self.lines.len()
+    }
+
+    fn inv(&mut self, x: usize) -> Value {
+        let z = self.next_wire();
+        self.lines.push(format!("1 1 {} {} INV", x, z));
+        Value::Wire(z)
+    }
+
+    fn binary(&mut self, x: usize, y: usize, op: &str) -> Value {
+        let z = self.next_wire();
+        self.lines.push(format!("2 1 {} {} {} {}", x, y, z, op));
+        Value::Wire(z)
+    }
+
+    fn xor(&mut self, x: Value, y: Value) -> Value {
+        match (x, y) {
+            (Value::Const(a), Value::Const(b)) => Value::Const(a ^ b),
+            (Value::Wire(w), Value::Const(0)) | (Value::Const(0), Value::Wire(w)) => Value::Wire(w),
+            (Value::Wire(w), Value::Const(_)) | (Value::Const(_), Value::Wire(w)) => self.inv(w),
+            (Value::Wire(a), Value::Wire(b)) => self.binary(a, b, "XOR"),
+        }
+    }
+
+    fn and(&mut self, x: Value, y: Value) -> Value {
+        match (x, y) {
+            (Value::Const(a), Value::Const(b)) => Value::Const(a & b),
+            (Value::Wire(w), Value::Const(1)) | (Value::Const(1), Value::Wire(w)) => Value::Wire(w),
+            (_, Value::Const(_)) | (Value::Const(_), _) => Value::Const(0),
+            (Value::Wire(a), Value::Wire(b)) => self.binary(a, b, "AND"),
+        }
+    }
+}
+
 fn regex2captures<'t>(re: &Regex, line: &'t str) -> Result<Captures<'t>, Error> {
     re.captures(&line)
         .ok_or_else(|| Error::ParseLineError(line.to_string()))
@@ -49,8 +100,14 @@ impl Circuit {
     /// otherwise a `CircuitParserError` is returned.
     pub fn parse(filename: &str) -> Result<Self, Error> {
         let f = File::open(filename)?;
-        let mut reader = BufReader::new(f);
+        Self::parse_reader(BufReader::new(f))
+    }
 
+    /// Generates a new `Circuit` from the circuit description in `reader`,
+    /// which must follow the same format as `parse`. This is useful on
+    /// platforms without a filesystem, e.g., when the circuit is fetched over
+    /// the network.
+    pub fn parse_reader<R: BufRead>(mut reader: R) -> Result<Self, Error> {
         // Parse first line: ngates nwires\n
         let mut line = String::new();
         reader.read_line(&mut line)?;
@@ -67,6 +124,9 @@ impl Circuit {
         let n1 = cap2int(&cap, 1)?; // Number of garbler inputs
         let n2 = cap2int(&cap, 2)?; // Number of evaluator inputs
         let n3 = cap2int(&cap, 3)?; // Number of outputs
+        if n1.checked_add(n2).and_then(|n| n.checked_add(ngates)) != Some(nwires) || n3 > nwires {
+            return Err(Error::ParseLineError(line.to_string()));
+        }
 
         // Parse third line: \n
         let mut line = String::new();
@@ -75,7 +135,8 @@ impl Circuit {
         let re = Regex::new(r"\n")?;
         let _ = regex2captures(&re, &line)?;
 
-        let mut circ = Self::new(Some(ngates));
+        // Don't trust the gate count enough to allocate it all up front.
+        let mut circ = Self::new(Some(std::cmp::min(ngates, 1 << 20)));
 
         let re1 = Regex::new(r"1 1 (\d+) (\d+) INV")?;
         let re2 = Regex::new(r"2 1 (\d+) (\d+) (\d+) ((AND|XOR))")?;
@@ -96,17 +157,21 @@ impl Circuit {
                 modulus: 2,
             });
         }
-        // Create a constant wire for negations.
+        // Create a constant wire for negations. It takes the slot after the
+        // inputs, which Bristol gives to the output of the first gate, so the
+        // remaining wires are shifted up by one to make room. Otherwise that
+        // gate would overwrite the constant and break every later INV gate.
         circ.gates.push(Gate::Constant { val: 1 });
         let oneref = CircuitRef {
             ix: n1 + n2,
             modulus: 2,
         };
         circ.const_refs.push(oneref);
+        let slot = |wire: usize| if wire < n1 + n2 { wire } else { wire + 1 };
         // Process outputs.
         for i in 0..n3 {
             circ.output_refs.push(CircuitRef {
-                ix: nwires - n3 + i,
+                ix: slot(nwires - n3 + i),
                 modulus: 2,
             });
         }
@@ -115,8 +180,8 @@ impl Circuit {
             match line.chars().next() {
                 Some('1') => {
                     let cap = regex2captures(&re1, &line)?;
-                    let yref = cap2int(&cap, 1)?;
-                    let out = cap2int(&cap, 2)?;
+                    let yref = slot(cap2int(&cap, 1)?);
+                    let out = slot(cap2int(&cap, 2)?);
                     let yref = CircuitRef {
                         ix: yref,
                         modulus: 2,
@@ -129,9 +194,9 @@ impl Circuit {
                 }
                 Some('2') => {
                     let cap = regex2captures(&re2, &line)?;
-                    let xref = cap2int(&cap, 1)?;
-                    let yref = cap2int(&cap, 2)?;
-                    let out = cap2int(&cap, 3)?;
+                    let xref = slot(cap2int(&cap, 1)?);
+                    let yref = slot(cap2int(&cap, 2)?);
+                    let out = slot(cap2int(&cap, 3)?);
                     let typ = cap2typ(&cap, 4)?;
                     let xref = CircuitRef {
                         ix: xref,
@@ -166,16 +231,117 @@ impl Circuit {
                 }
             }
         }
+        circ.num_nonfree_gates = id;
         for _ in 0..circ.gates.len() {
             circ.gate_moduli.push(2u16);
         }
         Ok(circ)
     }
+
+    /// Write the circuit to `writer` in the format read by `parse`, with the
+    /// garbler inputs first. Only circuits whose every wire is mod 2 can be
+    /// written. Constants are folded into the gates using them, negations
+    /// become `INV` gates, and outputs are copied to the last wires if they
+    /// are not there already.
+    pub fn write_bristol<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        let unsupported = |s: &str| Error::Unsupported(s.to_string());
+        if self.gate_moduli.iter().any(|&q| q != 2) {
+            return Err(unsupported("circuit has a wire not mod 2"));
+        }
+        let n1 = self.garbler_input_refs.len();
+        let n2 = self.evaluator_input_refs.len();
+        let mut bristol = Bristol {
+            ninputs: n1 + n2,
+            lines: Vec::new(),
+        };
+        let mut slots: Vec<Option<Value>> = vec![None; self.gates.len()];
+        let get = |slots: &[Option<Value>], r: &CircuitRef| match slots.get(r.ix) {
+            Some(Some(v)) if r.modulus == 2 => Ok(*v),
+            Some(Some(_)) => Err(unsupported("circuit has a wire not mod 2")),
+            _ => Err(unsupported("gate reads an unset wire")),
+        };
+        for (i, gate) in self.gates.iter().enumerate() {
+            let (v, out) = match gate {
+                Gate::GarblerInput { id } => (Value::Wire(*id), None),
+                Gate::EvaluatorInput { id } => (Value::Wire(n1 + id), None),
+                Gate::Constant { val } => (Value::Const(val % 2), None),
+                Gate::Add { xref, yref, out } | Gate::Sub { xref, yref, out } => {
+                    let x = get(&slots, xref)?;
+                    let y = get(&slots, yref)?;
+                    (bristol.xor(x, y), *out)
+                }
+                Gate::Cmul { xref, c, out } => {
+                    let x = get(&slots, xref)?;
+                    (bristol.and(x, Value::Const(c % 2)), *out)
+                }
+                Gate::Mul {
+                    xref, yref, out, ..
+                } => {
+                    let x = get(&slots, xref)?;
+                    let y = get(&slots, yref)?;
+                    (bristol.and(x, y), *out)
+                }
+                Gate::Proj { xref, tt, out, .. } => {
+                    let x = get(&slots, xref)?;
+                    let v = match tt.as_slice() {
+                        [0, 1] => x,
+                        [1, 0] => bristol.xor(x, Value::Const(1)),
+                        [a, b] if a == b => Value::Const(a % 2),
+                        _ => return Err(unsupported("projection is not a bit function")),
+                    };
+                    (v, *out)
+                }
+            };
+            match slots.get_mut(out.unwrap_or(i)) {
+                Some(slot) => *slot = Some(v),
+                None => return Err(unsupported("gate writes past the last wire")),
+            }
+        }
+
+        let mut outputs = Vec::with_capacity(self.output_refs.len());
+        for r in self.output_refs.iter() {
+            match get(&slots, r)? {
+                Value::Wire(w) => outputs.push(w),
+                Value::Const(_) => return Err(unsupported("circuit has a constant output")),
+            }
+        }
+        // Outputs are the last wires, in order, so copy them there with pairs
+        // of negations unless they already are.
+        let n = bristol.next_wire();
+        if outputs
+            .iter()
+            .enumerate()
+            .any(|(i, &w)| w + outputs.len() != n + i)
+        {
+            let negated = outputs
+                .iter()
+                .map(|&w| bristol.inv(w))
+                .collect::<Vec<Value>>();
+            for v in negated {
+                bristol.xor(v, Value::Const(1));
+            }
+        }
+
+        let ngates = bristol.lines.len();
+        writeln!(writer, "{} {}", ngates, bristol.next_wire())?;
+        writeln!(writer, "{} {} {}", n1, n2, outputs.len())?;
+        writeln!(writer)?;
+        for line in bristol.lines.iter() {
+            writeln!(writer, "{}", line)?;
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{circuit::Circuit, classic::garble};
+    use crate::{
+        circuit::{Circuit, CircuitBuilder},
+        classic::garble,
+        fancy::{BinaryGadgets, BundleGadgets, Fancy},
+        util::RngExt,
+    };
+    use rand::thread_rng;
 
     #[test]
     fn test_parser() {
@@ -206,6 +372,20 @@ mod tests {
                    "11011100000011101101100001011101111110010110000100011010101110110111001001001001110011011101000101101000110001010100011001111110");
     }
 
+    #[test]
+    fn test_parse_first_gate_wire() {
+        // The AND gate writes wire `n1 + n2`, where the parser keeps its
+        // constant one, and the INV gate after it needs that constant.
+        let circ =
+            Circuit::parse_reader("2 4\n1 1 1\n\n2 1 0 1 2 AND\n1 1 2 3 INV\n".as_bytes()).unwrap();
+        for x in 0..2 {
+            for y in 0..2 {
+                let output = circ.eval_plain(&[x], &[y]).unwrap();
+                assert_eq!(output, vec![1 - x * y]);
+            }
+        }
+    }
+
     #[test]
     fn test_gc_eval() {
         let mut circ = Circuit::parse("circuits/AES-non-expanded.txt").unwrap();
@@ -214,4 +394,55 @@ mod tests {
         let ev = en.encode_evaluator_inputs(&vec![0u16; 128]);
         gc.eval(&mut circ, &gb, &ev).unwrap();
     }
+
+    #[test]
+    fn test_write_bristol() {
+        let mut rng = thread_rng();
+        let circ = Circuit::parse("circuits/AES-non-expanded.txt").unwrap();
+        let mut bytes = Vec::new();
+        circ.write_bristol(&mut bytes).unwrap();
+        let circ_ = Circuit::parse_reader(bytes.as_slice()).unwrap();
+        for _ in 0..4 {
+            let pt = (0..128)
+                .map(|_| rng.gen_bool() as u16)
+                .collect::<Vec<u16>>();
+            let key = (0..128)
+                .map(|_| rng.gen_bool() as u16)
+                .collect::<Vec<u16>>();
+            assert_eq!(
+                circ.eval_plain(&pt, &key).unwrap(),
+                circ_.eval_plain(&pt, &key).unwrap()
+            );
+        }
+
+        // Builder circuits have constants, projections, and outputs that are
+        // not the last wires.
+        let mut b = CircuitBuilder::new();
+        let xs = b.bin_garbler_input(8);
+        let ys = b.bin_evaluator_input(8);
+        let zs = b.bin_addition_no_carry(&xs, &ys).unwrap();
+        let one = b.constant(1, 2).unwrap();
+        let w = b.add(&zs.wires()[0], &one).unwrap();
+        let w = b.proj(&w, 2, Some(vec![1, 0])).unwrap();
+        b.output(&w).unwrap();
+        b.output_bundle(&zs).unwrap();
+        b.output(&xs.wires()[3]).unwrap();
+        let circ = b.finish();
+        let mut bytes = Vec::new();
+        circ.write_bristol(&mut bytes).unwrap();
+        let circ_ = Circuit::parse_reader(bytes.as_slice()).unwrap();
+        for _ in 0..16 {
+            let x = (0..8).map(|_| rng.gen_bool() as u16).collect::<Vec<u16>>();
+            let y = (0..8).map(|_| rng.gen_bool() as u16).collect::<Vec<u16>>();
+            assert_eq!(
+                circ.eval_plain(&x, &y).unwrap(),
+                circ_.eval_plain(&x, &y).unwrap()
+            );
+        }
+
+        let mut b = CircuitBuilder::new();
+        let x = b.garbler_input(3);
+        b.output(&x).unwrap();
+        assert!(b.finish().write_bristol(&mut Vec::new()).is_err());
+    }
 }
@@ -0,0 +1,196 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Printers showing a value in several representations at once.
+//!
+//! A value is printed as its integer, followed by its CRT residues alongside
+//! their primes, and its base-`q` digits, most significant first:
+//!
+//! ```text
+//! 86 = {0 mod 2, 2 mod 3, 1 mod 5, 2 mod 7} = (1 5 2)_7
+//! ```
+//!
+//! Note that the digits are printed in the opposite order to the little-endian
+//! vectors used elsewhere in this library, so that they read like a number.
+
+use crate::util;
+use std::fmt::{self, Display, Formatter};
+
+/// Display the CRT residues `xs` with respect to the primes `ps`, preceded by
+/// the value they represent.
+pub fn crt<'a>(xs: &'a [u16], ps: &'a [u16]) -> Crt<'a> {
+    Crt { xs, ps }
+}
+
+/// Display the little-endian base-`q` digits `ds`, preceded by the value they
+/// represent.
+pub fn base_q(ds: &[u16], q: u16) -> BaseQ<'_> {
+    BaseQ { ds, q }
+}
+
+/// Display `x` as an integer, as CRT residues with respect to the primes
+/// `ps`, and as base-`q` digits.
+pub fn value(x: u128, ps: &[u16], q: u16) -> Value<'_> {
+    Value { x, ps, q }
+}
+
+/// The printer returned by `crt`.
+#[derive(Clone, Copy)]
+pub struct Crt<'a> {
+    xs: &'a [u16],
+    ps: &'a [u16],
+}
+
+/// The printer returned by `base_q`.
+#[derive(Clone, Copy)]
+pub struct BaseQ<'a> {
+    ds: &'a [u16],
+    q: u16,
+}
+
+/// The printer returned by `value`.
+#[derive(Clone, Copy)]
+pub struct Value<'a> {
+    x: u128,
+    ps: &'a [u16],
+    q: u16,
+}
+
+fn fmt_residues(f: &mut Formatter, xs: &[u16], ps: &[u16]) -> fmt::Result {
+    write!(f, "{{")?;
+    for (i, (x, p)) in xs.iter().zip(ps.iter()).enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{} mod {}", x, p)?;
+    }
+    write!(f, "}}")
+}
+
+fn fmt_digits(f: &mut Formatter, ds: &[u16], q: u16) -> fmt::Result {
+    write!(f, "(")?;
+    for (i, d) in ds.iter().rev().enumerate() {
+        if i > 0 {
+            write!(f, " ")?;
+        }
+        write!(f, "{}", d)?;
+    }
+    write!(f, ")_{}", q)
+}
+
+impl<'a> Display for Crt<'a> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        // Residues without a value, e.g., because they are out of range, are
+        // still worth seeing.
+        if let Some(x) = crt_value(self.xs, self.ps) {
+            write!(f, "{} = ", x)?;
+        }
+        fmt_residues(f, self.xs, self.ps)
+    }
+}
+
+impl<'a> Display for BaseQ<'a> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if let Some(x) = base_q_value(self.ds, self.q) {
+            write!(f, "{} = ", x)?;
+        }
+        fmt_digits(f, self.ds, self.q)
+    }
+}
+
+impl<'a> Display for Value<'a> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}", self.x)?;
+        if !self.ps.is_empty() {
+            write!(f, " = ")?;
+            fmt_residues(f, &util::crt(self.x, self.ps), self.ps)?;
+        }
+        if self.q >= 2 {
+            write!(f, " = ")?;
+            let n = digits_needed(self.x, self.q);
+            fmt_digits(f, &util::as_base_q(self.x, self.q, n), self.q)?;
+        }
+        Ok(())
+    }
+}
+
+macro_rules! debug_as_display {
+    ($($t:ident),*) => {
+        $(impl<'a> fmt::Debug for $t<'a> {
+            fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+                Display::fmt(self, f)
+            }
+        })*
+    };
+}
+
+debug_as_display!(Crt, BaseQ, Value);
+
+/// The value of the CRT residues `xs`, if they are in range, the primes `ps`
+/// are coprime, and their product fits in a `u128`.
+fn crt_value(xs: &[u16], ps: &[u16]) -> Option<u128> {
+    if xs.len() != ps.len() || xs.iter().zip(ps.iter()).any(|(x, p)| x >= p) {
+        return None;
+    }
+    // Garner's algorithm, which unlike `util::crt_inv` never overflows.
+    let (mut x, mut m) = (0u128, 1u128);
+    for (&a, &p) in xs.iter().zip(ps.iter()) {
+        let p_ = p as u128;
+        let minv = util::try_inv((m % p_) as i128, p as i128).ok()? as u128;
+        let t = (a as u128 + p_ - x % p_) % p_ * minv % p_;
+        let m_ = m.checked_mul(p_)?;
+        x += t * m;
+        m = m_;
+    }
+    Some(x)
+}
+
+/// The value of the base-`q` digits `ds`, if they are in range and it fits in
+/// a `u128`.
+fn base_q_value(ds: &[u16], q: u16) -> Option<u128> {
+    if q < 2 {
+        return None;
+    }
+    ds.iter().rev().try_fold(0u128, |x, &d| {
+        if d >= q {
+            return None;
+        }
+        x.checked_mul(q as u128)?.checked_add(d as u128)
+    })
+}
+
+/// The number of base-`q` digits needed to represent `x`, at least one.
+fn digits_needed(mut x: u128, q: u16) -> usize {
+    let mut n = 1;
+    while x >= q as u128 {
+        x /= q as u128;
+        n += 1;
+    }
+    n
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn printers() {
+        let ps = [2, 3, 5, 7];
+        assert_eq!(
+            value(86, &ps, 7).to_string(),
+            "86 = {0 mod 2, 2 mod 3, 1 mod 5, 2 mod 7} = (1 5 2)_7"
+        );
+        assert_eq!(value(0, &[], 3).to_string(), "0 = (0)_3");
+        assert_eq!(
+            crt(&[0, 2, 1, 2], &ps).to_string(),
+            "86 = {0 mod 2, 2 mod 3, 1 mod 5, 2 mod 7}"
+        );
+        assert_eq!(crt(&[0, 3], &[2, 3]).to_string(), "{0 mod 2, 3 mod 3}");
+        assert_eq!(crt(&[1, 1], &[2, 4]).to_string(), "{1 mod 2, 1 mod 4}");
+        assert_eq!(base_q(&[2, 5, 1], 7).to_string(), "86 = (1 5 2)_7");
+        assert_eq!(base_q(&[9], 7).to_string(), "(9)_7");
+    }
+}
@@ -0,0 +1,309 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Protocol buffer messages for protocol traffic, enabled by the `protobuf`
+//! feature.
+//!
+//! The messages are defined with `prost`'s derive macros, and the equivalent
+//! schema for use from other languages is `proto/fancy_garbling.proto`.
+//! Oblivious transfer messages are not covered, since their layout is specific
+//! to the OT protocol chosen from `ocelot`.
+
+use crate::{classic, errors::FormatError, fancy::HasModulus, format, wire::Wire};
+use scuttlebutt::Block;
+use std::convert::TryFrom;
+
+/// Sent by each party before running a protocol.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Handshake {
+    /// Major version of the binary format in `format`.
+    #[prost(uint32, tag = "1")]
+    pub major: u32,
+    /// Minor version of the binary format in `format`.
+    #[prost(uint32, tag = "2")]
+    pub minor: u32,
+}
+
+impl Handshake {
+    /// The handshake for the format version of this library.
+    pub fn current() -> Self {
+        Handshake {
+            major: format::MAJOR_VERSION as u32,
+            minor: format::MINOR_VERSION as u32,
+        }
+    }
+
+    /// Whether this library can talk to a peer sending this handshake.
+    pub fn is_compatible(&self) -> bool {
+        self.major == format::MAJOR_VERSION as u32
+    }
+}
+
+/// A single wire-label.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct WireLabel {
+    /// The modulus of the wire.
+    #[prost(uint32, tag = "1")]
+    pub modulus: u32,
+    /// The 16-byte wire-label.
+    #[prost(bytes, tag = "2")]
+    pub label: Vec<u8>,
+}
+
+/// Encoded garbler or evaluator inputs.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct EncodedInputs {
+    /// The wire-labels.
+    #[prost(message, repeated, tag = "1")]
+    pub wires: Vec<WireLabel>,
+}
+
+/// The garbled rows of a single gate.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GarbledGateFrame {
+    /// The id of the gate.
+    #[prost(uint64, tag = "1")]
+    pub gate_id: u64,
+    /// Concatenated 16-byte garbled rows.
+    #[prost(bytes, tag = "2")]
+    pub rows: Vec<u8>,
+}
+
+/// Decoding information for a single output wire.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct OutputDecoding {
+    /// The index of the output.
+    #[prost(uint64, tag = "1")]
+    pub output_id: u64,
+    /// The modulus of the output wire.
+    #[prost(uint32, tag = "2")]
+    pub modulus: u32,
+    /// Concatenated 16-byte decoding blocks, one per possible output value.
+    #[prost(bytes, tag = "3")]
+    pub blocks: Vec<u8>,
+}
+
+/// A full garbled circuit, as produced by `classic::garble`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GarbledCircuit {
+    /// Concatenated 16-byte garbled rows and constant wires.
+    #[prost(bytes, tag = "1")]
+    pub blocks: Vec<u8>,
+}
+
+/// Concatenate blocks into bytes.
+pub fn pack_blocks(blocks: &[Block]) -> Vec<u8> {
+    blocks
+        .iter()
+        .flat_map(|b| b.as_ref().iter().cloned())
+        .collect()
+}
+
+/// Split bytes produced by `pack_blocks` back into blocks.
+pub fn unpack_blocks(bytes: &[u8]) -> Result<Vec<Block>, FormatError> {
+    if bytes.len() % 16 != 0 {
+        return Err(FormatError::Malformed(format!(
+            "{} bytes is not a whole number of blocks",
+            bytes.len()
+        )));
+    }
+    Ok(bytes
+        .chunks(16)
+        .map(|chunk| {
+            let mut b = [0u8; 16];
+            b.copy_from_slice(chunk);
+            Block::from(b)
+        })
+        .collect())
+}
+
+impl From<&Wire> for WireLabel {
+    fn from(w: &Wire) -> Self {
+        WireLabel {
+            modulus: w.modulus() as u32,
+            label: w.as_block().as_ref().to_vec(),
+        }
+    }
+}
+
+impl TryFrom<&WireLabel> for Wire {
+    type Error = FormatError;
+
+    fn try_from(w: &WireLabel) -> Result<Self, FormatError> {
+        if w.modulus < 2 || w.modulus > u16::max_value() as u32 {
+            return Err(FormatError::Malformed(format!(
+                "invalid modulus {}",
+                w.modulus
+            )));
+        }
+        match unpack_blocks(&w.label)?.as_slice() {
+            [block] => Wire::try_from_block(*block, w.modulus as u16)
+                .map_err(|e| FormatError::Malformed(e.to_string())),
+            _ => Err(FormatError::Malformed(
+                "wire-label is not 16 bytes".to_string(),
+            )),
+        }
+    }
+}
+
+impl From<&[Wire]> for EncodedInputs {
+    fn from(ws: &[Wire]) -> Self {
+        EncodedInputs {
+            wires: ws.iter().map(WireLabel::from).collect(),
+        }
+    }
+}
+
+impl TryFrom<&EncodedInputs> for Vec<Wire> {
+    type Error = FormatError;
+
+    fn try_from(inputs: &EncodedInputs) -> Result<Self, FormatError> {
+        inputs.wires.iter().map(Wire::try_from).collect()
+    }
+}
+
+impl From<(usize, &[Block])> for GarbledGateFrame {
+    fn from((gate_id, rows): (usize, &[Block])) -> Self {
+        GarbledGateFrame {
+            gate_id: gate_id as u64,
+            rows: pack_blocks(rows),
+        }
+    }
+}
+
+impl TryFrom<&GarbledGateFrame> for (usize, Vec<Block>) {
+    type Error = FormatError;
+
+    fn try_from(frame: &GarbledGateFrame) -> Result<Self, FormatError> {
+        let gate_id = usize::try_from(frame.gate_id).map_err(|_| {
+            FormatError::Malformed(format!("gate id {} out of range", frame.gate_id))
+        })?;
+        Ok((gate_id, unpack_blocks(&frame.rows)?))
+    }
+}
+
+impl From<(usize, u16, &[Block])> for OutputDecoding {
+    fn from((output_id, modulus, blocks): (usize, u16, &[Block])) -> Self {
+        OutputDecoding {
+            output_id: output_id as u64,
+            modulus: modulus as u32,
+            blocks: pack_blocks(blocks),
+        }
+    }
+}
+
+impl TryFrom<&OutputDecoding> for (usize, u16, Vec<Block>) {
+    type Error = FormatError;
+
+    /// Fails unless there is one block for each value of the modulus.
+    fn try_from(d: &OutputDecoding) -> Result<Self, FormatError> {
+        let output_id = usize::try_from(d.output_id).map_err(|_| {
+            FormatError::Malformed(format!("output id {} out of range", d.output_id))
+        })?;
+        if d.modulus < 2 || d.modulus > u16::max_value() as u32 {
+            return Err(FormatError::Malformed(format!(
+                "invalid modulus {}",
+                d.modulus
+            )));
+        }
+        let blocks = unpack_blocks(&d.blocks)?;
+        if blocks.len() != d.modulus as usize {
+            return Err(FormatError::Malformed(format!(
+                "{} decoding blocks for modulus {}",
+                blocks.len(),
+                d.modulus
+            )));
+        }
+        Ok((output_id, d.modulus as u16, blocks))
+    }
+}
+
+impl From<&classic::GarbledCircuit> for GarbledCircuit {
+    fn from(gc: &classic::GarbledCircuit) -> Self {
+        GarbledCircuit {
+            blocks: pack_blocks(gc.blocks()),
+        }
+    }
+}
+
+impl TryFrom<&GarbledCircuit> for classic::GarbledCircuit {
+    type Error = FormatError;
+
+    fn try_from(gc: &GarbledCircuit) -> Result<Self, FormatError> {
+        Ok(classic::GarbledCircuit::new(unpack_blocks(&gc.blocks)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::RngExt;
+    use prost::Message;
+    use rand::thread_rng;
+
+    #[test]
+    fn encoded_inputs_round_trip() {
+        let mut rng = thread_rng();
+        let ws = (0..16)
+            .map(|_| {
+                let q = 2 + rng.gen_mod(100);
+                Wire::rand(&mut rng, q)
+            })
+            .collect::<Vec<_>>();
+        let msg = EncodedInputs::from(ws.as_slice());
+        let mut bytes = Vec::new();
+        msg.encode(&mut bytes).unwrap();
+        let msg = EncodedInputs::decode(bytes.as_slice()).unwrap();
+        assert_eq!(Vec::<Wire>::try_from(&msg).unwrap(), ws);
+    }
+
+    #[test]
+    fn gate_frame_round_trip() {
+        let mut rng = thread_rng();
+        let rows = (0..6).map(|_| rng.gen_block()).collect::<Vec<_>>();
+        let msg = GarbledGateFrame::from((17, rows.as_slice()));
+        let mut bytes = Vec::new();
+        msg.encode(&mut bytes).unwrap();
+        let msg = GarbledGateFrame::decode(bytes.as_slice()).unwrap();
+        assert_eq!(<(usize, Vec<Block>)>::try_from(&msg).unwrap(), (17, rows));
+    }
+
+    #[test]
+    fn output_decoding_round_trip() {
+        let mut rng = thread_rng();
+        let q = rng.gen_modulus();
+        let blocks = (0..q).map(|_| rng.gen_block()).collect::<Vec<_>>();
+        let msg = OutputDecoding::from((3, q, blocks.as_slice()));
+        let mut bytes = Vec::new();
+        msg.encode(&mut bytes).unwrap();
+        let mut msg = OutputDecoding::decode(bytes.as_slice()).unwrap();
+        assert_eq!(
+            <(usize, u16, Vec<Block>)>::try_from(&msg).unwrap(),
+            (3, q, blocks)
+        );
+        // One block short of the modulus.
+        msg.blocks.truncate(msg.blocks.len() - 16);
+        assert!(<(usize, u16, Vec<Block>)>::try_from(&msg).is_err());
+    }
+
+    #[test]
+    fn bad_label_length() {
+        let w = WireLabel {
+            modulus: 3,
+            label: vec![0; 15],
+        };
+        assert!(Wire::try_from(&w).is_err());
+    }
+
+    #[test]
+    fn bad_mod3_label() {
+        let w = WireLabel {
+            modulus: 3,
+            label: vec![1; 16],
+        };
+        assert!(Wire::try_from(&w).is_err());
+    }
+}
@@ -0,0 +1,161 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Statistical self-tests for randomness and wire-label encodings.
+//!
+//! `self_test` runs chi-square and serial-correlation checks on
+//! `RngExt::gen_mod` and on random wire-labels, including the balance of
+//! their color digits, which is useful for runtime assurance on platforms
+//! where the entropy source is suspect. The checks use a significance level of
+//! about `10^-6`, so a healthy generator essentially never fails them.
+
+use crate::{fancy::HasModulus, util::RngExt, wire::Wire};
+use rand::{CryptoRng, Rng};
+
+/// The standard normal quantile used for the critical values, corresponding
+/// to a one-sided significance level of about `10^-6`.
+const Z: f64 = 4.75;
+
+/// The chi-square statistic of `counts` against the uniform distribution.
+pub fn chi_square(counts: &[usize]) -> f64 {
+    let n: usize = counts.iter().sum();
+    let expected = n as f64 / counts.len() as f64;
+    counts
+        .iter()
+        .map(|&c| {
+            let d = c as f64 - expected;
+            d * d / expected
+        })
+        .sum()
+}
+
+/// An approximation of the critical value of the chi-square distribution with
+/// `df` degrees of freedom, using the Wilson–Hilferty transformation.
+pub fn chi_square_critical(df: usize) -> f64 {
+    let df = df as f64;
+    let a = 2.0 / (9.0 * df);
+    df * (1.0 - a + Z * a.sqrt()).powi(3)
+}
+
+/// The lag-one serial correlation coefficient of `xs`, which is close to zero
+/// for independent samples.
+pub fn serial_correlation(xs: &[f64]) -> f64 {
+    let n = xs.len() as f64;
+    let sum: f64 = xs.iter().sum();
+    let sum_sq: f64 = xs.iter().map(|x| x * x).sum();
+    let sum_lag: f64 = xs
+        .iter()
+        .zip(xs.iter().cycle().skip(1))
+        .map(|(x, y)| x * y)
+        .sum();
+    let denom = n * sum_sq - sum * sum;
+    if denom == 0.0 {
+        // A constant sequence is perfectly correlated.
+        return 1.0;
+    }
+    (n * sum_lag - sum * sum) / denom
+}
+
+/// Check that `counts` is consistent with the uniform distribution.
+fn check_uniform(what: &str, counts: &[usize]) -> Result<(), String> {
+    let chi2 = chi_square(counts);
+    let critical = chi_square_critical(counts.len() - 1);
+    if chi2 > critical {
+        return Err(format!(
+            "{}: chi-square statistic {:.2} exceeds critical value {:.2}",
+            what, chi2, critical
+        ));
+    }
+    Ok(())
+}
+
+/// Run the statistical self-tests using `rng`, returning a description of the
+/// first failing check.
+pub fn self_test<R: CryptoRng + Rng>(rng: &mut R) -> Result<(), String> {
+    const NSAMPLES: usize = 1 << 14;
+
+    for &q in [2u16, 3, 5, 7, 17, 64, 101].iter() {
+        // Uniformity and independence of `gen_mod`.
+        let xs = (0..NSAMPLES).map(|_| rng.gen_mod(q)).collect::<Vec<_>>();
+        let mut counts = vec![0; q as usize];
+        for &x in xs.iter() {
+            counts[x as usize] += 1;
+        }
+        check_uniform(&format!("gen_mod({})", q), &counts)?;
+        let xs = xs.iter().map(|&x| x as f64).collect::<Vec<_>>();
+        let r = serial_correlation(&xs);
+        let bound = Z / (NSAMPLES as f64).sqrt();
+        if r.abs() > bound {
+            return Err(format!(
+                "gen_mod({}): serial correlation {:.4} exceeds {:.4}",
+                q, r, bound
+            ));
+        }
+
+        // Uniformity of the digits of random wire-labels, and balance of
+        // their color digits.
+        let nwires = NSAMPLES / 16;
+        let mut colors = vec![0; q as usize];
+        let mut digits = vec![0; q as usize];
+        for _ in 0..nwires {
+            let w = Wire::rand(rng, q);
+            debug_assert_eq!(w.modulus(), q);
+            colors[w.color() as usize] += 1;
+            for d in w.digits() {
+                digits[d as usize] += 1;
+            }
+        }
+        check_uniform(&format!("wire-label colors mod {}", q), &colors)?;
+        check_uniform(&format!("wire-label digits mod {}", q), &digits)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{thread_rng, RngCore};
+
+    struct BrokenRng(u64);
+
+    impl RngCore for BrokenRng {
+        fn next_u32(&mut self) -> u32 {
+            self.next_u64() as u32
+        }
+        fn next_u64(&mut self) -> u64 {
+            self.0 = self.0.wrapping_add(1);
+            self.0 % 4
+        }
+        fn fill_bytes(&mut self, dest: &mut [u8]) {
+            for b in dest.iter_mut() {
+                *b = self.next_u64() as u8;
+            }
+        }
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+            self.fill_bytes(dest);
+            Ok(())
+        }
+    }
+
+    impl CryptoRng for BrokenRng {}
+
+    #[test]
+    fn statistics() {
+        assert_eq!(chi_square(&[10, 10, 10, 10]), 0.0);
+        assert!(chi_square(&[40, 0, 0, 0]) > chi_square_critical(3));
+        assert!((serial_correlation(&[0.0, 1.0, 0.0, 1.0]) + 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn healthy_rng_passes() {
+        self_test(&mut thread_rng()).unwrap();
+    }
+
+    #[test]
+    fn broken_rng_fails() {
+        assert!(self_test(&mut BrokenRng(0)).is_err());
+    }
+}
@@ -0,0 +1,314 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Helpers for writing tests against `fancy-garbling`, enabled by the
+//! `test-utils` feature.
+//!
+//! These are the random generators and round-trip assertions used by this
+//! crate's own tests, and `RandomCircuit` for generating random circuits of a
+//! given shape. See `testing` for `proptest` strategies.
+
+use crate::{
+    circuit::{Circuit, CircuitBuilder, CircuitRef},
+    classic::GarbledCircuit,
+    fancy::{Fancy, HasModulus},
+    format,
+    util::{self, RngExt, PRIMES},
+    wire::Wire,
+};
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    seq::SliceRandom,
+    Rng,
+};
+
+/// Randomly generate a `u128` with exactly `digits_per_u128(q)` digits in base
+/// `q`, i.e., the value of a random wire-label `mod q`.
+pub fn gen_usable_u128<R: Rng>(rng: &mut R, q: u16) -> u128 {
+    u128::from(rng.gen_usable_block(q))
+}
+
+/// Randomly generate `n` distinct supported primes, in increasing order.
+///
+/// # Panics
+///
+/// Panics if `n` exceeds the number of supported primes.
+pub fn gen_prime_set<R: Rng>(rng: &mut R, n: usize) -> Vec<u16> {
+    assert!(n <= PRIMES.len(), "only {} primes are supported", PRIMES.len());
+    let mut ps = rand::seq::index::sample(rng, PRIMES.len(), n)
+        .into_iter()
+        .map(|i| PRIMES[i])
+        .collect::<Vec<_>>();
+    ps.sort_unstable();
+    ps
+}
+
+/// Randomly generate a set of distinct supported primes whose product fits in
+/// a `u128`, together with that product.
+pub fn gen_crt_modulus<R: Rng>(rng: &mut R) -> (Vec<u16>, u128) {
+    let ps = rng.gen_usable_factors();
+    let q = util::product(&ps);
+    (ps, q)
+}
+
+/// Assert that `x` survives conversion to base-`q` digits and back.
+pub fn assert_base_q_round_trip(x: u128, q: u16) {
+    let ds = util::as_base_q_u128(x, q);
+    assert_eq!(util::from_base_q(&ds, q), x, "q={} ds={:?}", q, ds);
+}
+
+/// Assert that `digits` survive conversion from mixed radix `radii` to a
+/// `u128` and back.
+pub fn assert_mixed_radix_round_trip(digits: &[u16], radii: &[u16]) {
+    let x = util::from_mixed_radix(digits, radii);
+    assert_eq!(util::as_mixed_radix(x, radii), digits, "radii={:?}", radii);
+}
+
+/// Assert that `x`, which must be below the product of `ps`, survives
+/// conversion to CRT representation and back.
+pub fn assert_crt_round_trip(x: u128, ps: &[u16]) {
+    let xs = util::crt(x, ps);
+    assert_eq!(util::crt_inv(&xs, ps), x, "ps={:?} xs={:?}", ps, xs);
+}
+
+/// Assert that the low `nbits` bits of `x` survive conversion to bits and back.
+pub fn assert_bits_round_trip(x: u128, nbits: usize) {
+    let mask = if nbits >= 128 {
+        u128::max_value()
+    } else {
+        (1 << nbits) - 1
+    };
+    let bs = util::u128_to_bits(x, nbits);
+    assert_eq!(util::u128_from_bits(&bs), x & mask, "nbits={}", nbits);
+}
+
+/// Assert that `ws` survive serialization with `format::write_wires` and back.
+pub fn assert_wires_round_trip(ws: &[Wire]) {
+    let mut bytes = Vec::new();
+    format::write_wires(ws, &mut bytes).unwrap();
+    assert_eq!(format::read_wires(&mut bytes.as_slice()).unwrap(), ws);
+}
+
+/// Assert that `gc` survives serialization with `GarbledCircuit::write_to` and
+/// back.
+pub fn assert_garbled_circuit_round_trip(gc: &GarbledCircuit) {
+    let mut bytes = Vec::new();
+    gc.write_to(&mut bytes).unwrap();
+    let gc_ = GarbledCircuit::read_from(&mut bytes.as_slice()).unwrap();
+    assert_eq!(gc_.blocks(), gc.blocks());
+}
+
+/// The relative frequencies of each type of gate in a `RandomCircuit`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GateWeights {
+    /// Weight of addition gates.
+    pub add: u32,
+    /// Weight of subtraction gates.
+    pub sub: u32,
+    /// Weight of scalar multiplication gates.
+    pub cmul: u32,
+    /// Weight of multiplication gates.
+    pub mul: u32,
+    /// Weight of projection gates, which may change the modulus.
+    pub proj: u32,
+    /// Weight of constants.
+    pub constant: u32,
+}
+
+impl GateWeights {
+    /// Every type of gate is equally likely.
+    pub fn uniform() -> Self {
+        GateWeights {
+            add: 1,
+            sub: 1,
+            cmul: 1,
+            mul: 1,
+            proj: 1,
+            constant: 1,
+        }
+    }
+
+    /// Only free gates: addition, subtraction, scalar multiplication, and
+    /// constants.
+    pub fn free() -> Self {
+        GateWeights {
+            mul: 0,
+            proj: 0,
+            ..Self::uniform()
+        }
+    }
+}
+
+/// A generator of random well-formed circuits, for stress-testing the garbler
+/// and the serializers on topologies that hand-written circuits rarely have.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RandomCircuit {
+    /// The number of garbler inputs.
+    pub ngarbler_inputs: usize,
+    /// The number of evaluator inputs.
+    pub nevaluator_inputs: usize,
+    /// The number of gates, excluding inputs.
+    pub ngates: usize,
+    /// The maximum length of a path from an input or constant to a gate.
+    pub max_depth: usize,
+    /// The number of outputs, taken from the last gates.
+    pub noutputs: usize,
+    /// The moduli of inputs and gates, drawn uniformly.
+    pub moduli: Vec<u16>,
+    /// The distribution of gate types.
+    pub weights: GateWeights,
+}
+
+impl RandomCircuit {
+    /// A generator of circuits with four inputs per party, 64 gates of depth at
+    /// most 16, and four outputs, over the moduli `2`, `3`, `5`, and `7`.
+    pub fn new() -> Self {
+        RandomCircuit {
+            ngarbler_inputs: 4,
+            nevaluator_inputs: 4,
+            ngates: 64,
+            max_depth: 16,
+            noutputs: 4,
+            moduli: vec![2, 3, 5, 7],
+            weights: GateWeights::uniform(),
+        }
+    }
+
+    /// Generate a random circuit.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `moduli` is empty, contains a modulus below 2, or if every
+    /// gate weight is zero.
+    pub fn generate<R: Rng>(&self, rng: &mut R) -> Circuit {
+        let w = &self.weights;
+        let kinds = WeightedIndex::new([w.add, w.sub, w.cmul, w.mul, w.proj, w.constant])
+            .expect("gate weights must not all be zero");
+        assert!(self.moduli.iter().all(|&q| q >= 2), "invalid modulus");
+
+        let mut b = CircuitBuilder::new();
+        // Every gate alongside its depth.
+        let mut refs: Vec<(CircuitRef, usize)> = Vec::new();
+        for _ in 0..self.ngarbler_inputs {
+            refs.push((b.garbler_input(*self.moduli.choose(rng).unwrap()), 0));
+        }
+        for _ in 0..self.nevaluator_inputs {
+            refs.push((b.evaluator_input(*self.moduli.choose(rng).unwrap()), 0));
+        }
+
+        for _ in 0..self.ngates {
+            let q = *self.moduli.choose(rng).unwrap();
+            let candidates = refs
+                .iter()
+                .filter(|(r, d)| r.modulus() == q && *d < self.max_depth)
+                .cloned()
+                .collect::<Vec<_>>();
+            let kind = kinds.sample(rng);
+            let gate = if kind == 5 || candidates.is_empty() {
+                let c = rng.gen_mod(q);
+                b.constant(c, q).map(|r| (r, 0))
+            } else {
+                let (x, dx) = *candidates.choose(rng).unwrap();
+                let (y, dy) = *candidates.choose(rng).unwrap();
+                let d = 1 + dx.max(dy);
+                match kind {
+                    0 => b.add(&x, &y).map(|r| (r, d)),
+                    1 => b.sub(&x, &y).map(|r| (r, d)),
+                    2 => {
+                        let c = rng.gen_mod(q);
+                        b.cmul(&x, c).map(|r| (r, dx + 1))
+                    }
+                    3 => b.mul(&x, &y).map(|r| (r, d)),
+                    _ => {
+                        let q_out = *self.moduli.choose(rng).unwrap();
+                        let tt = rng.gen_digits(q_out, q as usize);
+                        b.proj(&x, q_out, Some(tt)).map(|r| (r, dx + 1))
+                    }
+                }
+            };
+            refs.push(gate.expect("random gates are well-formed"));
+        }
+
+        let ninputs = self.ngarbler_inputs + self.nevaluator_inputs;
+        let start = refs.len() - self.noutputs.min(refs.len() - ninputs);
+        for (r, _) in refs[start..].iter() {
+            b.output(r).unwrap();
+        }
+        b.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{classic::garble, harness::check_circuit};
+    use rand::thread_rng;
+    use scuttlebutt::AesRng;
+
+    #[test]
+    fn helpers() {
+        let mut rng = thread_rng();
+        for _ in 0..128 {
+            let q = rng.gen_modulus();
+            let x = gen_usable_u128(&mut rng, q);
+            assert_base_q_round_trip(x, q);
+
+            let n = 1 + rng.gen_usize() % 8;
+            let ps = gen_prime_set(&mut rng, n);
+            assert!(ps.windows(2).all(|w| w[0] < w[1]));
+            assert_crt_round_trip(rng.gen_u128() % util::product(&ps), &ps);
+            assert_bits_round_trip(rng.gen_u128(), rng.gen_usize() % 129);
+        }
+        let (ps, q) = gen_crt_modulus(&mut rng);
+        assert_eq!(util::product(&ps), q);
+        assert_mixed_radix_round_trip(&[1, 2, 3], &[2, 3, 4]);
+
+        let ws = (0..8)
+            .map(|_| {
+                let q = rng.gen_modulus();
+                rng.gen_wire_label(q)
+            })
+            .collect::<Vec<_>>();
+        assert_wires_round_trip(&ws);
+
+        let mut b = CircuitBuilder::new();
+        let x = b.garbler_input(5);
+        let y = b.evaluator_input(5);
+        let z = b.mul(&x, &y).unwrap();
+        b.output(&z).unwrap();
+        let (_, gc) = garble(&b.finish()).unwrap();
+        assert_garbled_circuit_round_trip(&gc);
+    }
+
+    #[test]
+    fn random_circuits() {
+        let mut rng = AesRng::new();
+        let mut gen = RandomCircuit::new();
+        for &(moduli, weights) in [
+            (&[2u16][..], GateWeights::uniform()),
+            (&[3, 5, 17][..], GateWeights::uniform()),
+            (&[2, 3, 5, 7][..], GateWeights::free()),
+        ]
+        .iter()
+        {
+            gen.moduli = moduli.to_vec();
+            gen.weights = weights;
+            for _ in 0..16 {
+                let c = gen.generate(&mut rng);
+                assert_eq!(c.noutputs(), gen.noutputs);
+                let gb = (0..c.num_garbler_inputs())
+                    .map(|i| rng.gen_mod(c.garbler_input_mod(i)))
+                    .collect::<Vec<_>>();
+                let ev = (0..c.num_evaluator_inputs())
+                    .map(|i| rng.gen_mod(c.evaluator_input_mod(i)))
+                    .collect::<Vec<_>>();
+                check_circuit(&c, &gb, &ev, rng.fork()).unwrap();
+                let (_, gc) = garble(&c).unwrap();
+                assert_garbled_circuit_round_trip(&gc);
+            }
+        }
+    }
+}
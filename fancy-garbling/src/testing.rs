@@ -0,0 +1,160 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! `proptest` strategies for the core types of `fancy-garbling`, enabled by the
+//! `testing` feature.
+//!
+//! These let downstream crates property-test their gadgets against the same
+//! invariants this crate relies on: moduli are in `2..=112`, digits are below
+//! their modulus, and circuits are well-formed.
+
+use crate::{
+    circuit::{Circuit, CircuitBuilder, CircuitRef},
+    fancy::{Bundle, Fancy},
+    util::PRIMES,
+    wire::Wire,
+};
+use proptest::{prelude::*, sample::Index};
+use rand::SeedableRng;
+use scuttlebutt::{AesRng, Block};
+
+/// A supported modulus.
+pub fn modulus() -> impl Strategy<Value = u16> {
+    2u16..113
+}
+
+/// One of the supported primes in `util::PRIMES`.
+pub fn prime() -> impl Strategy<Value = u16> {
+    prop::sample::select(PRIMES.to_vec())
+}
+
+/// Between one and `max` distinct supported primes, in increasing order, for
+/// use as CRT moduli.
+pub fn crt_moduli(max: usize) -> impl Strategy<Value = Vec<u16>> {
+    prop::sample::subsequence(PRIMES.to_vec(), 1..=max.min(PRIMES.len()))
+}
+
+/// `n` digits `mod q`.
+pub fn digits(q: u16, n: usize) -> impl Strategy<Value = Vec<u16>> {
+    prop::collection::vec(0..q, n)
+}
+
+/// A modulus alongside a vector of between one and `max_len` digits in that
+/// modulus.
+pub fn modulus_and_digits(max_len: usize) -> impl Strategy<Value = (u16, Vec<u16>)> {
+    modulus().prop_flat_map(move |q| (Just(q), prop::collection::vec(0..q, 1..=max_len)))
+}
+
+/// A mixed-radix value with between one and `max_len` digits, as a pair of the
+/// radii and the digits.
+pub fn mixed_radix(max_len: usize) -> impl Strategy<Value = (Vec<u16>, Vec<u16>)> {
+    prop::collection::vec(modulus(), 1..=max_len).prop_flat_map(|radii| {
+        let ds = radii.iter().map(|&q| 0..q).collect::<Vec<_>>();
+        (Just(radii), ds)
+    })
+}
+
+/// A random wire-label `mod q`.
+pub fn wire(q: u16) -> impl Strategy<Value = Wire> {
+    any::<[u8; 16]>().prop_map(move |seed| {
+        let mut rng = AesRng::from_seed(Block::from(seed));
+        Wire::rand(&mut rng, q)
+    })
+}
+
+/// A bundle of random wire-labels with the given moduli.
+pub fn bundle(moduli: Vec<u16>) -> impl Strategy<Value = Bundle<Wire>> {
+    moduli
+        .into_iter()
+        .map(wire)
+        .collect::<Vec<_>>()
+        .prop_map(Bundle::new)
+}
+
+impl Arbitrary for Wire {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Wire>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        modulus().prop_flat_map(wire).boxed()
+    }
+}
+
+/// A random well-formed circuit over `Z_q` with `ninputs` garbler inputs,
+/// `ninputs` evaluator inputs, `ngates` gates drawn from addition,
+/// subtraction, scalar multiplication, and multiplication, and a single output:
+/// the result of the last gate.
+pub fn circuit(q: u16, ninputs: usize, ngates: usize) -> impl Strategy<Value = Circuit> {
+    prop::collection::vec((0u8..4, any::<Index>(), any::<Index>(), 0..q), ngates).prop_map(
+        move |ops| {
+            let mut b = CircuitBuilder::new();
+            let mut refs: Vec<CircuitRef> = b.garbler_inputs(&vec![q; ninputs]);
+            refs.extend(b.evaluator_inputs(&vec![q; ninputs]));
+            for (op, x, y, c) in ops {
+                let x = refs[x.index(refs.len())];
+                let y = refs[y.index(refs.len())];
+                let z = match op {
+                    0 => b.add(&x, &y),
+                    1 => b.sub(&x, &y),
+                    2 => b.cmul(&x, c),
+                    _ => b.mul(&x, &y),
+                }
+                .unwrap();
+                refs.push(z);
+            }
+            b.output(refs.last().unwrap()).unwrap();
+            b.finish()
+        },
+    )
+}
+
+impl Arbitrary for Circuit {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Circuit>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        (modulus(), 1usize..4, 1usize..16)
+            .prop_flat_map(|(q, ninputs, ngates)| circuit(q, ninputs, ngates))
+            .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        classic::garble,
+        fancy::HasModulus,
+        util::{self, RngExt},
+    };
+
+    proptest! {
+        #[test]
+        fn mixed_radix_round_trip((radii, ds) in mixed_radix(8)) {
+            let x = util::from_mixed_radix(&ds, &radii);
+            prop_assert_eq!(util::as_mixed_radix(x, &radii), ds);
+        }
+
+        #[test]
+        fn wires_have_their_modulus(w in any::<Wire>()) {
+            prop_assert!(w.digits().iter().all(|&d| d < w.modulus()));
+        }
+
+        #[test]
+        fn garbled_circuit_matches_plaintext(c in any::<Circuit>(), seed in any::<u64>()) {
+            let mut rng = AesRng::seed_from_u64(seed);
+            let q = c.garbler_input_mod(0);
+            let gb = rng.gen_digits(q, c.num_garbler_inputs());
+            let ev = rng.gen_digits(q, c.num_evaluator_inputs());
+            let expected = c.eval_plain(&gb, &ev).unwrap();
+            let (en, gc) = garble(&c).unwrap();
+            let outputs = gc
+                .eval(&c, &en.encode_garbler_inputs(&gb), &en.encode_evaluator_inputs(&ev))
+                .unwrap();
+            prop_assert_eq!(outputs, expected);
+        }
+    }
+}
@@ -0,0 +1,9 @@
+// -*- mode: rust; -*-
+//
+// This file is part of threepac.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Implementations of three-party secure computation with an honest majority.
+
+pub mod semihonest;
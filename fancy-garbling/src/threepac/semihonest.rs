@@ -0,0 +1,372 @@
+// -*- mode: rust; -*-
+//
+// This file is part of threepac.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Implementation of semi-honest three-party computation using replicated
+//! secret sharing.
+//!
+//! A value `x` mod `q` is split into random `x_0 + x_1 + x_2 = x mod q`, and
+//! party `i` holds the pair `(x_i, x_{i+1})`, indices taken mod 3. Any two
+//! parties can reconstruct `x`, but no single party learns anything about it.
+//! Additions and multiplications by constants are local, a multiplication
+//! costs each party one element sent to the previous party, and a projection
+//! costs party 2 a message of one element per entry of the truth table, and
+//! every party a few elements besides.
+//!
+//! The parties form a ring, each holding a channel to the next party and one
+//! to the previous, and must run the same computation in the same order.
+//! Unlike with garbling, every party learns every output, and `mul` requires
+//! inputs of equal moduli.
+
+use crate::{
+    errors::{FancyError, ThreepacError},
+    fancy::{Fancy, HasModulus},
+};
+use rand::{CryptoRng, Rng, SeedableRng};
+use scuttlebutt::{AbstractChannel, AesRng, Block};
+
+/// A party's replicated share of a value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Share {
+    q: u16,
+    // The components `x_i` and `x_{i+1}` held by party `i`.
+    x: u16,
+    y: u16,
+}
+
+impl HasModulus for Share {
+    fn modulus(&self) -> u16 {
+        self.q
+    }
+}
+
+/// Semi-honest party.
+pub struct Party<C> {
+    id: usize,
+    next: C,
+    prev: C,
+    rng: AesRng,
+    // Randomness shared with the next party, and with the previous one.
+    rng_next: AesRng,
+    rng_prev: AesRng,
+}
+
+impl<C: AbstractChannel> Party<C> {
+    /// Make a new party `id`, given channels to parties `id + 1` and `id - 1`
+    /// mod 3. All three parties must be made concurrently.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `id` is not 0, 1, or 2.
+    pub fn new<RNG: CryptoRng + Rng>(
+        id: usize,
+        mut next: C,
+        mut prev: C,
+        mut rng: RNG,
+    ) -> Result<Self, ThreepacError> {
+        assert!(id < 3, "party id must be 0, 1, or 2");
+        let seed = rng.gen::<Block>();
+        next.write_block(&seed)?;
+        next.flush()?;
+        let prev_seed = prev.read_block()?;
+        Ok(Party {
+            id,
+            next,
+            prev,
+            rng: AesRng::from_seed(rng.gen()),
+            rng_next: AesRng::from_seed(seed),
+            rng_prev: AesRng::from_seed(prev_seed),
+        })
+    }
+
+    /// The id of this party.
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Share this party's input `x` mod `q`. The other parties must call
+    /// `receive` with this party's id at the same point.
+    pub fn encode(&mut self, x: u16, q: u16) -> Result<Share, ThreepacError> {
+        // `x_i` and `x_{i+1}` come from the randomness shared with the
+        // neighbor that also holds them, so only `x_{i-1}` is sent.
+        let xi = self.rng_prev.gen_range(0, q);
+        let xj = self.rng_next.gen_range(0, q);
+        let xk = sub(sub(x, xi, q), xj, q);
+        self.next.write_u16(xk)?;
+        self.next.flush()?;
+        self.prev.write_u16(xk)?;
+        self.prev.flush()?;
+        Ok(Share { q, x: xi, y: xj })
+    }
+
+    /// Share the inputs `xs` with moduli `qs`.
+    pub fn encode_many(&mut self, xs: &[u16], qs: &[u16]) -> Result<Vec<Share>, ThreepacError> {
+        if xs.len() != qs.len() {
+            return Err(ThreepacError::from(FancyError::InvalidArgNum {
+                got: xs.len(),
+                needed: qs.len(),
+            }));
+        }
+        xs.iter()
+            .zip(qs.iter())
+            .map(|(x, q)| self.encode(*x, *q))
+            .collect()
+    }
+
+    /// Receive a share of an input mod `q` of party `from`, in step with its
+    /// `encode`.
+    pub fn receive(&mut self, from: usize, q: u16) -> Result<Share, ThreepacError> {
+        if from == (self.id + 2) % 3 {
+            let x = self.rng_prev.gen_range(0, q);
+            let y = self.prev.read_u16()?;
+            Ok(Share { q, x, y })
+        } else if from == (self.id + 1) % 3 {
+            let x = self.next.read_u16()?;
+            let y = self.rng_next.gen_range(0, q);
+            Ok(Share { q, x, y })
+        } else {
+            Err(ThreepacError::from(FancyError::InvalidArg(format!(
+                "party {} cannot receive an input from party {}",
+                self.id, from
+            ))))
+        }
+    }
+
+    /// Receive shares of inputs with moduli `qs` of party `from`.
+    pub fn receive_many(&mut self, from: usize, qs: &[u16]) -> Result<Vec<Share>, ThreepacError> {
+        qs.iter().map(|q| self.receive(from, *q)).collect()
+    }
+}
+
+impl<C: AbstractChannel> Fancy for Party<C> {
+    type Item = Share;
+    type Error = ThreepacError;
+
+    fn constant(&mut self, x: u16, q: u16) -> Result<Share, ThreepacError> {
+        // `x_0 = x` and the other components are zero.
+        let (x, y) = match self.id {
+            0 => (x, 0),
+            1 => (0, 0),
+            _ => (0, x),
+        };
+        Ok(Share { q, x, y })
+    }
+
+    fn add(&mut self, x: &Share, y: &Share) -> Result<Share, ThreepacError> {
+        if x.q != y.q {
+            return Err(ThreepacError::from(FancyError::UnequalModuli));
+        }
+        Ok(Share {
+            q: x.q,
+            x: add(x.x, y.x, x.q),
+            y: add(x.y, y.y, x.q),
+        })
+    }
+
+    fn sub(&mut self, x: &Share, y: &Share) -> Result<Share, ThreepacError> {
+        if x.q != y.q {
+            return Err(ThreepacError::from(FancyError::UnequalModuli));
+        }
+        Ok(Share {
+            q: x.q,
+            x: sub(x.x, y.x, x.q),
+            y: sub(x.y, y.y, x.q),
+        })
+    }
+
+    fn cmul(&mut self, x: &Share, c: u16) -> Result<Share, ThreepacError> {
+        let q = u64::from(x.q);
+        let c = u64::from(c) % q;
+        Ok(Share {
+            q: x.q,
+            x: (u64::from(x.x) * c % q) as u16,
+            y: (u64::from(x.y) * c % q) as u16,
+        })
+    }
+
+    fn mul(&mut self, x: &Share, y: &Share) -> Result<Share, ThreepacError> {
+        if x.q != y.q {
+            return Err(ThreepacError::from(FancyError::UnequalModuli));
+        }
+        let q = x.q;
+        // Party `i` computes an additive share `z_i` of the product,
+        // rerandomized by a sharing of zero, and sends it to party `i - 1`.
+        let (xi, xj, yi, yj) = (
+            u64::from(x.x),
+            u64::from(x.y),
+            u64::from(y.x),
+            u64::from(y.y),
+        );
+        let z = ((xi * yi + xi * yj + xj * yi) % u64::from(q)) as u16;
+        let alpha = sub(
+            self.rng_next.gen_range(0, q),
+            self.rng_prev.gen_range(0, q),
+            q,
+        );
+        let z = add(z, alpha, q);
+        self.prev.write_u16(z)?;
+        self.prev.flush()?;
+        let y = self.next.read_u16()?;
+        Ok(Share { q, x: z, y })
+    }
+
+    fn proj(&mut self, x: &Share, q: u16, tt: Option<Vec<u16>>) -> Result<Share, ThreepacError> {
+        let tt = tt.ok_or(FancyError::NoTruthTable)?;
+        let p = x.q;
+        if tt.len() != p as usize {
+            return Err(ThreepacError::from(FancyError::InvalidTruthTable));
+        }
+        // Party 2 deals a random `s` mod `p`, along with its one-hot vector
+        // mod `q`, as additive shares to parties 0 and 1, whose shares of `s`
+        // and of the vector, `s_0` and `u`, come from the randomness party 2
+        // shares with party 0. Parties 0 and 1 open `c = x + s`, and compute
+        // additive shares of `tt[x] = tt[c - s]` as the inner products of the
+        // table rotated by `c` and their shares of the one-hot vector. They
+        // turn them into replicated shares with their shared randomness, so
+        // that party 2 receives two uniformly random components.
+        match self.id {
+            0 => {
+                let s0 = self.rng_prev.gen_range(0, p);
+                let u = (0..p)
+                    .map(|_| self.rng_prev.gen_range(0, q))
+                    .collect::<Vec<u16>>();
+                let a = add(add(x.x, x.y, p), s0, p);
+                self.next.write_u16(a)?;
+                self.next.flush()?;
+                let c = add(a, self.next.read_u16()?, p);
+                let z1 = self.rng_next.gen_range(0, q);
+                let w = self.rng_next.gen_range(0, q);
+                let z0 = sub(sub(lookup(&tt, c, &u, q), z1, q), w, q);
+                self.prev.write_u16(z0)?;
+                self.prev.flush()?;
+                Ok(Share { q, x: z0, y: z1 })
+            }
+            1 => {
+                let v = (0..p)
+                    .map(|_| self.next.read_u16())
+                    .collect::<Result<Vec<u16>, _>>()?;
+                let s1 = self.next.read_u16()?;
+                let b = add(x.y, s1, p);
+                self.prev.write_u16(b)?;
+                self.prev.flush()?;
+                let c = add(self.prev.read_u16()?, b, p);
+                let z1 = self.rng_prev.gen_range(0, q);
+                let w = self.rng_prev.gen_range(0, q);
+                let z2 = add(lookup(&tt, c, &v, q), w, q);
+                self.next.write_u16(z2)?;
+                self.next.flush()?;
+                Ok(Share { q, x: z1, y: z2 })
+            }
+            _ => {
+                let s0 = self.rng_next.gen_range(0, p);
+                let u = (0..p)
+                    .map(|_| self.rng_next.gen_range(0, q))
+                    .collect::<Vec<u16>>();
+                let s = self.rng.gen_range(0, p);
+                for (j, uj) in u.into_iter().enumerate() {
+                    self.prev.write_u16(sub((j == s as usize) as u16, uj, q))?;
+                }
+                self.prev.write_u16(sub(s, s0, p))?;
+                self.prev.flush()?;
+                let z2 = self.prev.read_u16()?;
+                let z0 = self.next.read_u16()?;
+                Ok(Share { q, x: z2, y: z0 })
+            }
+        }
+    }
+
+    fn output(&mut self, x: &Share) -> Result<Option<u16>, ThreepacError> {
+        // Party `i` lacks only `x_{i-1}`, which party `i - 1` holds.
+        self.next.write_u16(x.x)?;
+        self.next.flush()?;
+        let z = self.prev.read_u16()?;
+        Ok(Some(add(add(x.x, x.y, x.q), z, x.q)))
+    }
+}
+
+fn add(x: u16, y: u16, q: u16) -> u16 {
+    ((u32::from(x) + u32::from(y)) % u32::from(q)) as u16
+}
+
+fn sub(x: u16, y: u16, q: u16) -> u16 {
+    ((u32::from(x) + u32::from(q) - u32::from(y) % u32::from(q)) % u32::from(q)) as u16
+}
+
+/// The inner product mod `q` of `tt`, rotated by `c`, and `v`.
+fn lookup(tt: &[u16], c: u16, v: &[u16], q: u16) -> u16 {
+    let p = tt.len();
+    let z = v.iter().enumerate().fold(0, |acc, (j, vj)| {
+        let t = u64::from(tt[(c as usize + p - j) % p] % q);
+        (acc + t * u64::from(*vj)) % u64::from(q)
+    });
+    z as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{dummy::Dummy, FancyInput};
+    use scuttlebutt::{unix_channel_pair, UnixChannel};
+
+    fn computation<F: Fancy>(
+        f: &mut F,
+        x: &F::Item,
+        y: &F::Item,
+        z: &F::Item,
+    ) -> Result<Option<Vec<u16>>, F::Error> {
+        let a = f.mul(x, y)?;
+        let b = f.cmul(z, 3)?;
+        let c = f.add(&a, &b)?;
+        let one = f.constant(1, 7)?;
+        let d = f.sub(&c, &one)?;
+        let e = f.mod_change(&d, 5)?;
+        let g = f.mul(&e, &e)?;
+        let h = f.proj(&g, 2, Some(vec![0, 1, 1, 0, 1]))?;
+        f.outputs(&[d, e, g, h])
+    }
+
+    fn party(id: usize, next: UnixChannel, prev: UnixChannel, input: u16) -> Vec<u16> {
+        let mut p = Party::new(id, next, prev, AesRng::new()).unwrap();
+        let xs = (0..3)
+            .map(|i| {
+                if i == id {
+                    p.encode(input, 7)
+                } else {
+                    p.receive(i, 7)
+                }
+            })
+            .collect::<Result<Vec<Share>, _>>()
+            .unwrap();
+        computation(&mut p, &xs[0], &xs[1], &xs[2])
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_computation() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..16 {
+            let inputs = [
+                rng.gen_range(0, 7),
+                rng.gen_range(0, 7),
+                rng.gen_range(0, 7),
+            ];
+            let mut dummy = Dummy::new();
+            let xs = dummy.encode_many(&inputs, &[7, 7, 7]).unwrap();
+            let target = computation(&mut dummy, &xs[0], &xs[1], &xs[2])
+                .unwrap()
+                .unwrap();
+
+            let (c01, c10) = unix_channel_pair();
+            let (c12, c21) = unix_channel_pair();
+            let (c20, c02) = unix_channel_pair();
+            let h1 = std::thread::spawn(move || party(1, c12, c10, inputs[1]));
+            let h2 = std::thread::spawn(move || party(2, c20, c21, inputs[2]));
+            assert_eq!(party(0, c01, c02, inputs[0]), target);
+            assert_eq!(h1.join().unwrap(), target);
+            assert_eq!(h2.join().unwrap(), target);
+        }
+    }
+}
@@ -4,10 +4,28 @@
 // Copyright © 2019 Galois, Inc.
 // See LICENSE for licensing information.
 
-use crate::{errors::TwopacError, Evaluator as Ev, Fancy, FancyInput, FancyReveal, Wire};
+use super::LazyInput;
+use crate::{
+    errors::{FancyError, TwopacError},
+    metrics::MetricsSink,
+    util,
+    BinaryBundle,
+    BinaryGadgets,
+    BundleGadgets,
+    CrtBundle,
+    CrtGadgets,
+    Evaluator as Ev,
+    EvaluatorCheckpoint as EvCheckpoint,
+    Fancy,
+    FancyInput,
+    FancyReveal,
+    RevealPolicy,
+    Wire,
+};
 use ocelot::ot::Receiver as OtReceiver;
 use rand::{CryptoRng, Rng};
 use scuttlebutt::{AbstractChannel, Block, SemiHonest};
+use std::sync::Arc;
 
 /// Semi-honest evaluator.
 pub struct Evaluator<C, RNG, OT> {
@@ -15,6 +33,24 @@ pub struct Evaluator<C, RNG, OT> {
     channel: C,
     ot: OT,
     rng: RNG,
+    lazy: Vec<Lazy>,
+    metrics: Option<Arc<dyn MetricsSink + Send + Sync>>,
+}
+
+/// The state of a lazy input bundle: the callback supplying its values until
+/// it is forced, and its wires afterwards.
+enum Lazy {
+    Pending(Box<dyn FnOnce() -> Vec<u16> + Send>),
+    Forced(Vec<Wire>),
+}
+
+/// A snapshot of the in-flight state of a semi-honest `Evaluator`, matching a
+/// `GarblerCheckpoint` taken at the same point of the computation.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+pub struct EvaluatorCheckpoint {
+    evaluator: EvCheckpoint,
+    lazy: Vec<Vec<Wire>>,
 }
 
 impl<C, RNG, OT> Evaluator<C, RNG, OT> {}
@@ -31,6 +67,47 @@ impl<C: AbstractChannel, RNG: CryptoRng + Rng, OT: OtReceiver<Msg = Block> + Sem
             channel,
             ot,
             rng,
+            lazy: Vec::new(),
+            metrics: None,
+        })
+    }
+
+    /// Take a checkpoint of the evaluator, in step with `Garbler::checkpoint`.
+    /// Fails if a lazy input is still pending, as its callback cannot be
+    /// recorded.
+    pub fn checkpoint(&self) -> Result<EvaluatorCheckpoint, TwopacError> {
+        let lazy = self
+            .lazy
+            .iter()
+            .map(|lazy| match lazy {
+                Lazy::Forced(wires) => Ok(wires.clone()),
+                Lazy::Pending(_) => Err(FancyError::InvalidArg(
+                    "checkpoint with a pending lazy input".to_string(),
+                )),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(EvaluatorCheckpoint {
+            evaluator: self.evaluator.checkpoint(),
+            lazy,
+        })
+    }
+
+    /// Resume evaluation from `checkpoint` over `channel`, in step with
+    /// `Garbler::from_checkpoint`, running a fresh OT setup with `rng`.
+    pub fn from_checkpoint(
+        mut channel: C,
+        checkpoint: EvaluatorCheckpoint,
+        mut rng: RNG,
+    ) -> Result<Self, TwopacError> {
+        let ot = OT::init(&mut channel, &mut rng)?;
+        let evaluator = Ev::from_checkpoint(channel.clone(), checkpoint.evaluator);
+        Ok(Self {
+            evaluator,
+            channel,
+            ot,
+            rng,
+            lazy: checkpoint.lazy.into_iter().map(Lazy::Forced).collect(),
+            metrics: None,
         })
     }
 
@@ -39,10 +116,128 @@ impl<C: AbstractChannel, RNG: CryptoRng + Rng, OT: OtReceiver<Msg = Block> + Sem
         &mut self.channel
     }
 
+    /// Report the OTs run by this evaluator to `sink`.
+    pub fn set_metrics(&mut self, sink: Arc<dyn MetricsSink + Send + Sync>) {
+        self.metrics = Some(sink);
+    }
+
+    /// Register an input bundle with the given moduli whose values are
+    /// supplied by `values` only when the bundle is forced. The garbler must
+    /// call `Garbler::receive_lazy` at the same point.
+    pub fn encode_lazy<F>(&mut self, moduli: &[u16], values: F) -> LazyInput
+    where
+        F: FnOnce() -> Vec<u16> + Send + 'static,
+    {
+        self.lazy.push(Lazy::Pending(Box::new(values)));
+        LazyInput {
+            index: self.lazy.len() - 1,
+            moduli: moduli.to_vec(),
+        }
+    }
+
+    /// Obtain the wires of a lazy input bundle, calling its callback and
+    /// running OT the first time, in step with `Garbler::force`. If this
+    /// fails, the bundle stays pending, with the values the callback returned.
+    ///
+    /// The number of values is sent to the garbler before any OT, so if the
+    /// callback returns the wrong number, both parties return an error.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `input` was not created by this evaluator.
+    pub fn force(&mut self, input: &LazyInput) -> Result<Vec<Wire>, TwopacError> {
+        let lazy = std::mem::replace(&mut self.lazy[input.index], Lazy::Forced(Vec::new()));
+        let values = match lazy {
+            Lazy::Forced(wires) => {
+                self.lazy[input.index] = Lazy::Forced(wires.clone());
+                return Ok(wires);
+            }
+            Lazy::Pending(values) => values(),
+        };
+        let wires = self
+            .send_count(values.len(), input.moduli.len())
+            .and_then(|()| self.encode_many(&values, &input.moduli));
+        self.lazy[input.index] = match wires {
+            Ok(ref wires) => Lazy::Forced(wires.clone()),
+            Err(_) => Lazy::Pending(Box::new(move || values)),
+        };
+        wires
+    }
+
+    /// Tell the garbler how many values a lazy input was given, failing if
+    /// that is not the `needed` number.
+    fn send_count(&mut self, got: usize, needed: usize) -> Result<(), TwopacError> {
+        self.channel.write_u64(got as u64)?;
+        self.channel.flush()?;
+        if got != needed {
+            return Err(TwopacError::from(FancyError::InvalidArgNum { got, needed }));
+        }
+        Ok(())
+    }
+
+    /// Convert the binary bundle `x`, of at most 128 bits, into additive
+    /// shares mod `2^k`, where `k` is its number of bits, returning the
+    /// evaluator's share, in step with `Garbler::bin_to_shares`.
+    pub fn bin_to_shares(&mut self, x: &BinaryBundle<Wire>) -> Result<u128, TwopacError> {
+        if x.size() == 0 {
+            return Ok(0);
+        }
+        let rs = self.bin_receive(x.size())?;
+        let (z, _) = self.bin_subtraction(x, &rs)?;
+        let bits = self.reveal_shares(z.wires())?;
+        Ok(util::u128_from_bits(&bits))
+    }
+
+    /// Convert the CRT bundle `x` into additive shares mod its composite
+    /// modulus, returning the evaluator's share, in step with
+    /// `Garbler::crt_to_shares`.
+    pub fn crt_to_shares(&mut self, x: &CrtBundle<Wire>) -> Result<u128, TwopacError> {
+        let qs = x.moduli();
+        let r = self.receive_bundle(&qs)?;
+        let z = self.sub_bundles(x, &r)?;
+        let xs = self.reveal_shares(z.wires())?;
+        Ok(util::crt_inv(&xs, &qs))
+    }
+
+    /// Input an `nbits`-bit value held as additive shares mod `2^nbits`, where
+    /// `share` is the evaluator's, in step with `Garbler::bin_from_shares`.
+    pub fn bin_from_shares(
+        &mut self,
+        share: u128,
+        nbits: usize,
+    ) -> Result<BinaryBundle<Wire>, TwopacError> {
+        let xs = self.bin_receive(nbits)?;
+        let ys = self.bin_encode(share, nbits)?;
+        self.bin_addition_no_carry(&xs, &ys)
+    }
+
+    /// Input a value held as additive shares mod `modulus`, where `share` is
+    /// the evaluator's, in step with `Garbler::crt_from_shares`.
+    pub fn crt_from_shares(
+        &mut self,
+        share: u128,
+        modulus: u128,
+    ) -> Result<CrtBundle<Wire>, TwopacError> {
+        let xs = self.crt_receive(modulus)?;
+        let ys = self.crt_encode(share, modulus)?;
+        self.crt_add(&xs, &ys)
+    }
+
+    fn reveal_shares(&mut self, ws: &[Wire]) -> Result<Vec<u16>, TwopacError> {
+        ws.iter()
+            .map(|w| {
+                let x = self.reveal_to(w, RevealPolicy::Evaluator)?;
+                Ok(x.expect("Evaluator always outputs Some(u16)"))
+            })
+            .collect()
+    }
+
     fn run_ot(&mut self, inputs: &[bool]) -> Result<Vec<Block>, TwopacError> {
-        self.ot
-            .receive(&mut self.channel, &inputs, &mut self.rng)
-            .map_err(TwopacError::from)
+        let wires = self.ot.receive(&mut self.channel, inputs, &mut self.rng)?;
+        if let Some(sink) = &self.metrics {
+            sink.ots(inputs.len() as u64);
+        }
+        Ok(wires)
     }
 }
 
@@ -133,6 +328,16 @@ impl<C: AbstractChannel, RNG: CryptoRng + Rng, OT> FancyReveal for Evaluator<C,
     fn reveal(&mut self, x: &Self::Item) -> Result<u16, Self::Error> {
         self.evaluator.reveal(x).map_err(Self::Error::from)
     }
+
+    fn reveal_to(
+        &mut self,
+        x: &Self::Item,
+        policy: RevealPolicy,
+    ) -> Result<Option<u16>, Self::Error> {
+        self.evaluator
+            .reveal_to(x, policy)
+            .map_err(Self::Error::from)
+    }
 }
 
 impl<C: AbstractChannel, RNG, OT> SemiHonest for Evaluator<C, RNG, OT> {}
@@ -4,10 +4,28 @@
 // Copyright © 2019 Galois, Inc.
 // See LICENSE for licensing information.
 
-use crate::{errors::TwopacError, Fancy, FancyInput, FancyReveal, Garbler as Gb, Wire};
+use super::LazyInput;
+use crate::{
+    errors::{FancyError, TwopacError},
+    metrics::MetricsSink,
+    util,
+    BinaryBundle,
+    BinaryGadgets,
+    BundleGadgets,
+    CrtBundle,
+    CrtGadgets,
+    Fancy,
+    FancyInput,
+    FancyReveal,
+    Garbler as Gb,
+    GarblerCheckpoint as GbCheckpoint,
+    RevealPolicy,
+    Wire,
+};
 use ocelot::ot::Sender as OtSender;
 use rand::{CryptoRng, Rng, SeedableRng};
 use scuttlebutt::{AbstractChannel, Block, SemiHonest};
+use std::sync::Arc;
 
 /// Semi-honest garbler.
 pub struct Garbler<C, RNG, OT> {
@@ -15,6 +33,22 @@ pub struct Garbler<C, RNG, OT> {
     channel: C,
     ot: OT,
     rng: RNG,
+    // The wires of each lazy input bundle, once forced.
+    lazy: Vec<Option<Vec<Wire>>>,
+    metrics: Option<Arc<dyn MetricsSink + Send + Sync>>,
+}
+
+/// A snapshot of the in-flight state of a semi-honest `Garbler`, matching an
+/// `EvaluatorCheckpoint` taken at the same point of the computation.
+///
+/// It holds the checkpoint of the underlying garbler, a fresh seed for the RNG
+/// used for OT, and the wires of the lazy inputs forced so far.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+pub struct GarblerCheckpoint {
+    garbler: GbCheckpoint,
+    seed: Block,
+    lazy: Vec<Vec<Wire>>,
 }
 
 impl<C, OT, RNG> std::ops::Deref for Garbler<C, RNG, OT> {
@@ -45,6 +79,51 @@ impl<
             channel,
             ot,
             rng,
+            lazy: Vec::new(),
+            metrics: None,
+        })
+    }
+
+    /// Take a checkpoint of the garbler, in step with `Evaluator::checkpoint`.
+    /// Fails if a lazy input is still pending, as the evaluator cannot record
+    /// its callback.
+    ///
+    /// The garbler's RNG is reseeded with the seed stored in the checkpoint.
+    pub fn checkpoint(&mut self) -> Result<GarblerCheckpoint, TwopacError> {
+        let lazy = self
+            .lazy
+            .iter()
+            .cloned()
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| {
+                FancyError::InvalidArg("checkpoint with a pending lazy input".to_string())
+            })?;
+        let seed = self.rng.gen();
+        self.rng = RNG::from_seed(seed);
+        Ok(GarblerCheckpoint {
+            garbler: self.garbler.checkpoint(),
+            seed,
+            lazy,
+        })
+    }
+
+    /// Resume garbling from `checkpoint` over `channel`, in step with
+    /// `Evaluator::from_checkpoint`. OT state is not part of a checkpoint, so
+    /// this runs a fresh OT setup with the evaluator.
+    pub fn from_checkpoint(
+        mut channel: C,
+        checkpoint: GarblerCheckpoint,
+    ) -> Result<Self, TwopacError> {
+        let mut rng = RNG::from_seed(checkpoint.seed);
+        let ot = OT::init(&mut channel, &mut rng)?;
+        let garbler = Gb::from_checkpoint(channel.clone(), checkpoint.garbler);
+        Ok(Garbler {
+            garbler,
+            channel,
+            ot,
+            rng,
+            lazy: checkpoint.lazy.into_iter().map(Some).collect(),
+            metrics: None,
         })
     }
 
@@ -53,6 +132,111 @@ impl<
         &mut self.channel
     }
 
+    /// Report the OTs run by this garbler to `sink`.
+    pub fn set_metrics(&mut self, sink: Arc<dyn MetricsSink + Send + Sync>) {
+        self.metrics = Some(sink);
+    }
+
+    /// Register an evaluator input bundle with the given moduli, to be
+    /// obtained by OT only when forced. The evaluator must call
+    /// `Evaluator::encode_lazy` at the same point.
+    pub fn receive_lazy(&mut self, moduli: &[u16]) -> LazyInput {
+        self.lazy.push(None);
+        LazyInput {
+            index: self.lazy.len() - 1,
+            moduli: moduli.to_vec(),
+        }
+    }
+
+    /// Obtain the wires of a lazy input bundle, running OT the first time, in
+    /// step with `Evaluator::force`. Fails, leaving the bundle pending, if the
+    /// evaluator did not supply the number of values its moduli call for.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `input` was not created by this garbler.
+    pub fn force(&mut self, input: &LazyInput) -> Result<Vec<Wire>, TwopacError> {
+        if let Some(wires) = &self.lazy[input.index] {
+            return Ok(wires.clone());
+        }
+        // Flush any gates garbled so far, which the evaluator may still need
+        // before it reaches the matching `force`.
+        self.channel.flush()?;
+        let got = self.channel.read_u64()? as usize;
+        if got != input.moduli.len() {
+            return Err(TwopacError::from(FancyError::InvalidArgNum {
+                got,
+                needed: input.moduli.len(),
+            }));
+        }
+        let wires = self.receive_many(&input.moduli)?;
+        self.lazy[input.index] = Some(wires.clone());
+        Ok(wires)
+    }
+
+    /// Convert the binary bundle `x`, of at most 128 bits, into additive
+    /// shares mod `2^k`, where `k` is its number of bits, returning the
+    /// garbler's share. The evaluator must call `Evaluator::bin_to_shares` at
+    /// the same point to obtain its share.
+    ///
+    /// The garbler masks `x` with a random input `r` and reveals `x - r` to the
+    /// evaluator, so neither party learns anything about `x`. An empty bundle
+    /// has the share 0 and exchanges no messages.
+    pub fn bin_to_shares(&mut self, x: &BinaryBundle<Wire>) -> Result<u128, TwopacError> {
+        let nbits = x.size();
+        if nbits == 0 {
+            return Ok(0);
+        }
+        let r = self.rng.gen::<u128>() & (u128::max_value() >> (128 - nbits));
+        let rs = self.bin_encode(r, nbits)?;
+        let (z, _) = self.bin_subtraction(x, &rs)?;
+        for w in z.wires() {
+            self.reveal_to(w, RevealPolicy::Evaluator)?;
+        }
+        Ok(r)
+    }
+
+    /// Convert the CRT bundle `x` into additive shares mod its composite
+    /// modulus, returning the garbler's share. The evaluator must call
+    /// `Evaluator::crt_to_shares` at the same point to obtain its share.
+    pub fn crt_to_shares(&mut self, x: &CrtBundle<Wire>) -> Result<u128, TwopacError> {
+        let qs = x.moduli();
+        let rs = qs
+            .iter()
+            .map(|&q| self.rng.gen_range(0, q))
+            .collect::<Vec<u16>>();
+        let r = self.encode_bundle(&rs, &qs)?;
+        let z = self.sub_bundles(x, &r)?;
+        for w in z.wires() {
+            self.reveal_to(w, RevealPolicy::Evaluator)?;
+        }
+        Ok(util::crt_inv(&rs, &qs))
+    }
+
+    /// Input an `nbits`-bit value held as additive shares mod `2^nbits`, where
+    /// `share` is the garbler's, in step with `Evaluator::bin_from_shares`.
+    pub fn bin_from_shares(
+        &mut self,
+        share: u128,
+        nbits: usize,
+    ) -> Result<BinaryBundle<Wire>, TwopacError> {
+        let xs = self.bin_encode(share, nbits)?;
+        let ys = self.bin_receive(nbits)?;
+        self.bin_addition_no_carry(&xs, &ys)
+    }
+
+    /// Input a value held as additive shares mod `modulus`, where `share` is
+    /// the garbler's, in step with `Evaluator::crt_from_shares`.
+    pub fn crt_from_shares(
+        &mut self,
+        share: u128,
+        modulus: u128,
+    ) -> Result<CrtBundle<Wire>, TwopacError> {
+        let xs = self.crt_encode(share, modulus)?;
+        let ys = self.crt_receive(modulus)?;
+        self.crt_add(&xs, &ys)
+    }
+
     fn _evaluator_input(&mut self, delta: &Wire, q: u16) -> (Wire, Vec<(Block, Block)>) {
         let len = f32::from(q).log(2.0).ceil() as u16;
         let mut wire = Wire::zero(q);
@@ -113,6 +297,9 @@ impl<
             }
         }
         self.ot.send(&mut self.channel, &inputs, &mut self.rng)?;
+        if let Some(sink) = &self.metrics {
+            sink.ots(inputs.len() as u64);
+        }
         Ok(wires)
     }
 }
@@ -154,6 +341,14 @@ impl<C: AbstractChannel, RNG: CryptoRng + Rng, OT> FancyReveal for Garbler<C, RN
     fn reveal(&mut self, x: &Self::Item) -> Result<u16, Self::Error> {
         self.garbler.reveal(x).map_err(Self::Error::from)
     }
+
+    fn reveal_to(
+        &mut self,
+        x: &Self::Item,
+        policy: RevealPolicy,
+    ) -> Result<Option<u16>, Self::Error> {
+        self.garbler.reveal_to(x, policy).map_err(Self::Error::from)
+    }
 }
 
 impl<C, RNG, OT> SemiHonest for Garbler<C, RNG, OT> {}
@@ -9,8 +9,29 @@
 mod evaluator;
 mod garbler;
 
-pub use evaluator::Evaluator;
-pub use garbler::Garbler;
+pub use evaluator::{Evaluator, EvaluatorCheckpoint};
+pub use garbler::{Garbler, GarblerCheckpoint};
+
+/// A handle to an evaluator input bundle whose wires are obtained by OT only
+/// when first needed, created by `Garbler::receive_lazy` and
+/// `Evaluator::encode_lazy`.
+///
+/// Both parties must create their handles, and `force` them, in the same
+/// order, as each `force` runs an OT between them. Bundles that are never
+/// forced cost nothing, which saves most of the online time for circuits
+/// that exit early.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LazyInput {
+    index: usize,
+    moduli: Vec<u16>,
+}
+
+impl LazyInput {
+    /// The moduli of the wires of the bundle.
+    pub fn moduli(&self) -> &[u16] {
+        &self.moduli
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -18,7 +39,9 @@ mod tests {
     use crate::{
         circuit::Circuit,
         dummy::Dummy,
+        errors::{FancyError, TwopacError},
         util::RngExt,
+        BinaryBundle,
         CrtBundle,
         CrtGadgets,
         Fancy,
@@ -33,6 +56,150 @@ mod tests {
         f.output(&c)
     }
 
+    #[test]
+    fn test_lazy_inputs() {
+        use std::sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        };
+        let (sender, receiver) = unix_channel_pair();
+        let handle = std::thread::spawn(move || {
+            let rng = AesRng::new();
+            let mut gb =
+                Garbler::<UnixChannel, AesRng, ChouOrlandiSender>::new(sender, rng).unwrap();
+            let x = gb.encode(2, 5).unwrap();
+            let ys = gb.receive_lazy(&[5]);
+            let _unused = gb.receive_lazy(&[5, 5]);
+            let bad = gb.receive_lazy(&[5]);
+            for _ in 0..2 {
+                match gb.force(&bad) {
+                    Err(TwopacError::FancyError(FancyError::InvalidArgNum {
+                        got: 2,
+                        needed: 1,
+                    })) => {}
+                    r => panic!("expected an argument count error, got {:?}", r),
+                }
+            }
+            let y = gb.force(&ys).unwrap();
+            addition(&mut gb, &x, &y[0]).unwrap();
+        });
+        let rng = AesRng::new();
+        let mut ev =
+            Evaluator::<UnixChannel, AesRng, ChouOrlandiReceiver>::new(receiver, rng).unwrap();
+        let called = Arc::new(AtomicBool::new(false));
+        let called_ = called.clone();
+        let x = ev.receive(5).unwrap();
+        let ys = ev.encode_lazy(&[5], || vec![4]);
+        let unused = ev.encode_lazy(&[5, 5], move || {
+            called_.store(true, Ordering::SeqCst);
+            vec![0, 0]
+        });
+        assert_eq!(unused.moduli(), &[5, 5]);
+        // A callback returning the wrong number of values is an error for both
+        // parties, and leaves the bundle pending.
+        let bad = ev.encode_lazy(&[5], || vec![1, 2]);
+        for _ in 0..2 {
+            match ev.force(&bad) {
+                Err(TwopacError::FancyError(FancyError::InvalidArgNum { got: 2, needed: 1 })) => {}
+                r => panic!("expected an argument count error, got {:?}", r),
+            }
+        }
+        let y = ev.force(&ys).unwrap();
+        // Forcing again reuses the wires without another OT.
+        assert_eq!(ev.force(&ys).unwrap(), y);
+        let output = addition(&mut ev, &x, &y[0]).unwrap().unwrap();
+        assert_eq!(output, (2 + 4) % 5);
+        handle.join().unwrap();
+        assert!(!called.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        let (sender, receiver) = unix_channel_pair();
+        let handle = std::thread::spawn(move || {
+            let rng = AesRng::new();
+            let mut gb =
+                Garbler::<UnixChannel, AesRng, ChouOrlandiSender>::new(sender, rng).unwrap();
+            let x = gb.encode(3, 7).unwrap();
+            let ys = gb.receive_many(&[7]).unwrap();
+            let z = gb.mul(&x, &ys[0]).unwrap();
+            let pending = gb.receive_lazy(&[7]);
+            assert!(gb.checkpoint().is_err());
+            let w = gb.force(&pending).unwrap();
+            let checkpoint = gb.checkpoint().unwrap();
+            (checkpoint, z, w)
+        });
+        let rng = AesRng::new();
+        let mut ev =
+            Evaluator::<UnixChannel, AesRng, ChouOrlandiReceiver>::new(receiver, rng).unwrap();
+        let x = ev.receive(7).unwrap();
+        let ys = ev.encode_many(&[4], &[7]).unwrap();
+        let z = ev.mul(&x, &ys[0]).unwrap();
+        let pending = ev.encode_lazy(&[7], || vec![2]);
+        assert!(ev.checkpoint().is_err());
+        let w = ev.force(&pending).unwrap();
+        let checkpoint = ev.checkpoint().unwrap();
+        let (gb_checkpoint, gb_z, gb_w) = handle.join().unwrap();
+
+        // Resume both parties over a new channel.
+        let (sender, receiver) = unix_channel_pair();
+        let handle = std::thread::spawn(move || {
+            let mut gb = Garbler::<UnixChannel, AesRng, ChouOrlandiSender>::from_checkpoint(
+                sender,
+                gb_checkpoint,
+            )
+            .unwrap();
+            let v = gb.receive(7).unwrap();
+            let t = gb.mul(&gb_z, &gb_w[0]).unwrap();
+            let t = gb.add(&t, &v).unwrap();
+            gb.output(&t).unwrap();
+        });
+        let mut ev = Evaluator::<UnixChannel, AesRng, ChouOrlandiReceiver>::from_checkpoint(
+            receiver,
+            checkpoint,
+            AesRng::new(),
+        )
+        .unwrap();
+        let v = ev.encode(5, 7).unwrap();
+        let t = ev.mul(&z, &w[0]).unwrap();
+        let t = ev.add(&t, &v).unwrap();
+        let output = ev.output(&t).unwrap().unwrap();
+        assert_eq!(output, (3 * 4 * 2 + 5) % 7);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_shares() {
+        let mut rng = rand::thread_rng();
+        let nbits = 16;
+        let (a, b) = (rng.gen_u128() % (1 << nbits), rng.gen_u128() % (1 << nbits));
+        let q = 2 * 3 * 5 * 7 * 11;
+        let (c, d) = (rng.gen_u128() % q, rng.gen_u128() % q);
+        let (sender, receiver) = unix_channel_pair();
+        let handle = std::thread::spawn(move || {
+            let rng = AesRng::new();
+            let mut gb =
+                Garbler::<UnixChannel, AesRng, ChouOrlandiSender>::new(sender, rng).unwrap();
+            let x = gb.bin_from_shares(a, nbits).unwrap();
+            let y = gb.crt_from_shares(c, q).unwrap();
+            let y = gb.crt_cmul(&y, 3).unwrap();
+            assert_eq!(gb.bin_to_shares(&BinaryBundle::new(vec![])).unwrap(), 0);
+            (gb.bin_to_shares(&x).unwrap(), gb.crt_to_shares(&y).unwrap())
+        });
+        let rng = AesRng::new();
+        let mut ev =
+            Evaluator::<UnixChannel, AesRng, ChouOrlandiReceiver>::new(receiver, rng).unwrap();
+        let x = ev.bin_from_shares(b, nbits).unwrap();
+        let y = ev.crt_from_shares(d, q).unwrap();
+        let y = ev.crt_cmul(&y, 3).unwrap();
+        // An empty bundle has the share 0, and leaves the parties in step.
+        assert_eq!(ev.bin_to_shares(&BinaryBundle::new(vec![])).unwrap(), 0);
+        let (r, s) = (ev.bin_to_shares(&x).unwrap(), ev.crt_to_shares(&y).unwrap());
+        let (gr, gs) = handle.join().unwrap();
+        assert_eq!((r + gr) % (1 << nbits), (a + b) % (1 << nbits));
+        assert_eq!((s + gs) % q, (c + d) * 3 % q);
+    }
+
     #[test]
     fn test_addition_circuit() {
         for a in 0..2 {
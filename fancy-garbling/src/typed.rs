@@ -0,0 +1,402 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Values whose modulus is part of their type.
+//!
+//! Wires and digit vectors normally carry their modulus at runtime, so adding
+//! a wire mod 7 to a wire mod 11 is only caught when the gate is evaluated, if
+//! at all. The types here fix the modulus as a const parameter `Q`, turning
+//! such mistakes into compile errors:
+//!
+//! ```ignore
+//! let x = Typed::<_, 7>::new(b.garbler_input(7))?;
+//! let y = Typed::<_, 11>::new(b.evaluator_input(11))?;
+//! b.typed_add(&x, &y)?; // does not compile
+//! ```
+//!
+//! The modulus is checked once, when an untyped value is wrapped, and the
+//! operations of `TypedGadgets` then preserve it.
+
+use crate::{
+    errors::{FancyError, NumberError},
+    fancy::{Fancy, HasModulus},
+    pretty,
+    util,
+    wire::Wire,
+};
+use std::{
+    fmt,
+    ops::{Add, Mul, Neg, Sub},
+};
+
+/// A residue mod `Q`.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Zq<const Q: u16>(u16);
+
+impl<const Q: u16> Zq<Q> {
+    /// Create the residue of `x` mod `Q`.
+    pub fn new(x: u16) -> Self {
+        Zq(x % Q)
+    }
+
+    /// The value of this residue, below `Q`.
+    pub fn value(self) -> u16 {
+        self.0
+    }
+
+    /// The inverse of this residue, if it is a unit mod `Q`.
+    pub fn inv(self) -> Option<Self> {
+        util::try_inv(self.0 as i128, Q as i128)
+            .ok()
+            .map(|x| Zq(x as u16))
+    }
+
+    /// Raise this residue to the power `e`.
+    pub fn pow(self, mut e: u64) -> Self {
+        let (mut b, mut z) = (self, Zq::new(1));
+        while e > 0 {
+            if e & 1 == 1 {
+                z = z * b;
+            }
+            b = b * b;
+            e >>= 1;
+        }
+        z
+    }
+}
+
+impl<const Q: u16> Add for Zq<Q> {
+    type Output = Zq<Q>;
+
+    fn add(self, other: Zq<Q>) -> Zq<Q> {
+        Zq(((self.0 as u32 + other.0 as u32) % Q as u32) as u16)
+    }
+}
+
+impl<const Q: u16> Sub for Zq<Q> {
+    type Output = Zq<Q>;
+
+    fn sub(self, other: Zq<Q>) -> Zq<Q> {
+        Zq(((self.0 as u32 + Q as u32 - other.0 as u32) % Q as u32) as u16)
+    }
+}
+
+impl<const Q: u16> Mul for Zq<Q> {
+    type Output = Zq<Q>;
+
+    fn mul(self, other: Zq<Q>) -> Zq<Q> {
+        Zq((self.0 as u32 * other.0 as u32 % Q as u32) as u16)
+    }
+}
+
+impl<const Q: u16> Neg for Zq<Q> {
+    type Output = Zq<Q>;
+
+    fn neg(self) -> Zq<Q> {
+        Zq((Q - self.0) % Q)
+    }
+}
+
+impl<const Q: u16> HasModulus for Zq<Q> {
+    fn modulus(&self) -> u16 {
+        Q
+    }
+}
+
+impl<const Q: u16> fmt::Debug for Zq<Q> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} mod {}", self.0, Q)
+    }
+}
+
+impl<const Q: u16> fmt::Display for Zq<Q> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// A little-endian vector of base-`Q` digits.
+#[derive(Clone, Default, PartialEq, Eq, Hash)]
+pub struct Digits<const Q: u16>(Vec<u16>);
+
+impl<const Q: u16> Digits<Q> {
+    /// Wrap the digits `ds`, returning an error if any is not below `Q`.
+    pub fn new(ds: Vec<u16>) -> Result<Self, NumberError> {
+        if Q < 2 {
+            return Err(NumberError::InvalidModulus(Q as u128));
+        }
+        if ds.iter().any(|&d| d >= Q) {
+            return Err(NumberError::Overflow);
+        }
+        Ok(Digits(ds))
+    }
+
+    /// The `n` least significant base-`Q` digits of `x`.
+    pub fn from_u128(x: u128, n: usize) -> Self {
+        Digits(util::as_base_q(x, Q, n))
+    }
+
+    /// The value these digits represent.
+    pub fn to_u128(&self) -> u128 {
+        util::from_base_q(&self.0, Q)
+    }
+
+    /// The underlying digits.
+    pub fn digits(&self) -> &[u16] {
+        &self.0
+    }
+
+    /// The digit at position `i`.
+    pub fn digit(&self, i: usize) -> Zq<Q> {
+        Zq(self.0[i])
+    }
+
+    /// Extract the underlying digits.
+    pub fn into_inner(self) -> Vec<u16> {
+        self.0
+    }
+}
+
+impl<const Q: u16> Add for Digits<Q> {
+    type Output = Digits<Q>;
+
+    /// Add with carry, discarding the carry out of the most significant digit.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the operands have different numbers of digits.
+    fn add(mut self, other: Digits<Q>) -> Digits<Q> {
+        assert_eq!(self.0.len(), other.0.len(), "digit counts differ");
+        util::base_q_add_eq(&mut self.0, &other.0, Q);
+        self
+    }
+}
+
+impl<const Q: u16> fmt::Debug for Digits<Q> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Digits({})", pretty::base_q(&self.0, Q))
+    }
+}
+
+impl<const Q: u16> fmt::Display for Digits<Q> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(&pretty::base_q(&self.0, Q), f)
+    }
+}
+
+/// A wire or circuit reference `W` known to have modulus `Q`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Typed<W, const Q: u16>(W);
+
+impl<W: HasModulus, const Q: u16> Typed<W, Q> {
+    /// Wrap `w`, returning an error unless it has modulus `Q`.
+    pub fn new(w: W) -> Result<Self, FancyError> {
+        if w.modulus() != Q {
+            return Err(FancyError::InvalidArgMod {
+                got: w.modulus(),
+                needed: Q,
+            });
+        }
+        Ok(Typed(w))
+    }
+
+    /// The underlying value.
+    pub fn inner(&self) -> &W {
+        &self.0
+    }
+
+    /// Extract the underlying value.
+    pub fn into_inner(self) -> W {
+        self.0
+    }
+}
+
+impl<W, const Q: u16> HasModulus for Typed<W, Q> {
+    fn modulus(&self) -> u16 {
+        Q
+    }
+}
+
+impl<const Q: u16> Typed<Wire, Q> {
+    /// Multiply this wire-label by the constant `c`.
+    pub fn cmul(&self, c: Zq<Q>) -> Self {
+        Typed(self.0.cmul(c.value()))
+    }
+}
+
+impl<const Q: u16> Add for &Typed<Wire, Q> {
+    type Output = Typed<Wire, Q>;
+
+    fn add(self, other: &Typed<Wire, Q>) -> Typed<Wire, Q> {
+        Typed(self.0.plus(&other.0))
+    }
+}
+
+impl<const Q: u16> Sub for &Typed<Wire, Q> {
+    type Output = Typed<Wire, Q>;
+
+    fn sub(self, other: &Typed<Wire, Q>) -> Typed<Wire, Q> {
+        Typed(self.0.minus(&other.0))
+    }
+}
+
+impl<const Q: u16> Neg for &Typed<Wire, Q> {
+    type Output = Typed<Wire, Q>;
+
+    fn neg(self) -> Typed<Wire, Q> {
+        Typed(self.0.negate())
+    }
+}
+
+/// Fancy operations on `Typed` items, which preserve their moduli.
+pub trait TypedGadgets: Fancy {
+    /// Create a constant `x` with modulus `Q`.
+    fn typed_constant<const Q: u16>(
+        &mut self,
+        x: Zq<Q>,
+    ) -> Result<Typed<Self::Item, Q>, Self::Error> {
+        self.constant(x.value(), Q).map(Typed)
+    }
+
+    /// Add `x` and `y`.
+    fn typed_add<const Q: u16>(
+        &mut self,
+        x: &Typed<Self::Item, Q>,
+        y: &Typed<Self::Item, Q>,
+    ) -> Result<Typed<Self::Item, Q>, Self::Error> {
+        self.add(&x.0, &y.0).map(Typed)
+    }
+
+    /// Subtract `x` and `y`.
+    fn typed_sub<const Q: u16>(
+        &mut self,
+        x: &Typed<Self::Item, Q>,
+        y: &Typed<Self::Item, Q>,
+    ) -> Result<Typed<Self::Item, Q>, Self::Error> {
+        self.sub(&x.0, &y.0).map(Typed)
+    }
+
+    /// Multiply `x` times the constant `c`.
+    fn typed_cmul<const Q: u16>(
+        &mut self,
+        x: &Typed<Self::Item, Q>,
+        c: Zq<Q>,
+    ) -> Result<Typed<Self::Item, Q>, Self::Error> {
+        self.cmul(&x.0, c.value()).map(Typed)
+    }
+
+    /// Multiply `x` and `y`.
+    fn typed_mul<const Q: u16>(
+        &mut self,
+        x: &Typed<Self::Item, Q>,
+        y: &Typed<Self::Item, Q>,
+    ) -> Result<Typed<Self::Item, Q>, Self::Error> {
+        self.mul(&x.0, &y.0).map(Typed)
+    }
+
+    /// Project `x` mod `Q` to a wire mod `R` according to the truth table
+    /// `tt`, whose length must be `Q`.
+    fn typed_proj<const Q: u16, const R: u16>(
+        &mut self,
+        x: &Typed<Self::Item, Q>,
+        tt: &[Zq<R>],
+    ) -> Result<Typed<Self::Item, R>, Self::Error> {
+        if tt.len() != Q as usize {
+            return Err(Self::Error::from(FancyError::InvalidTruthTable));
+        }
+        let tt = tt.iter().map(|x| x.value()).collect();
+        self.proj(&x.0, R, Some(tt)).map(Typed)
+    }
+
+    /// Process `x` as output.
+    fn typed_output<const Q: u16>(
+        &mut self,
+        x: &Typed<Self::Item, Q>,
+    ) -> Result<Option<Zq<Q>>, Self::Error> {
+        self.output(&x.0).map(|o| o.map(Zq::new))
+    }
+}
+
+impl<F: Fancy> TypedGadgets for F {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{circuit::CircuitBuilder, util::RngExt};
+    use rand::thread_rng;
+
+    #[test]
+    fn residues() {
+        let mut rng = thread_rng();
+        for _ in 0..128 {
+            let (x, y) = (rng.gen_u16(), rng.gen_u16());
+            let (a, b) = (Zq::<65521>::new(x), Zq::<65521>::new(y));
+            let (x, y) = (x as u32 % 65521, y as u32 % 65521);
+            assert_eq!((a + b).value() as u32, (x + y) % 65521);
+            assert_eq!((a - b).value() as u32, (x + 65521 - y) % 65521);
+            assert_eq!((a * b).value() as u32, x * y % 65521);
+            assert_eq!(a + -a, Zq::new(0));
+            if x != 0 {
+                assert_eq!(a * a.inv().unwrap(), Zq::new(1));
+                assert_eq!(a.pow(65520), Zq::new(1));
+            }
+        }
+        let a = Zq::<12>::new(5);
+        assert_eq!(a.pow(0), Zq::new(1));
+        assert_eq!(a.pow(3), a * a * a);
+        assert_eq!(a.inv(), Some(a));
+        assert_eq!(Zq::<12>::new(4).inv(), None);
+    }
+
+    #[test]
+    fn digits() {
+        let mut rng = thread_rng();
+        for _ in 0..128 {
+            let x = rng.gen_u128() % (1 << 100);
+            let y = rng.gen_u128() % (1 << 100);
+            let xs = Digits::<7>::from_u128(x, 40);
+            let ys = Digits::<7>::from_u128(y, 40);
+            assert_eq!(xs.to_u128(), x);
+            assert_eq!((xs + ys).to_u128(), x + y);
+        }
+        assert!(Digits::<7>::new(vec![0, 6]).is_ok());
+        assert!(Digits::<7>::new(vec![0, 7]).is_err());
+        let xs = Digits::<7>::new(vec![2, 5, 1]).unwrap();
+        assert_eq!(format!("{:?}", xs), "Digits(86 = (1 5 2)_7)");
+        assert_eq!(format!("{:?}", xs.digit(1)), "5 mod 7");
+    }
+
+    #[test]
+    fn wires() {
+        let mut rng = thread_rng();
+        let w = Typed::<_, 5>::new(rng.gen_wire_label(5)).unwrap();
+        let v = Typed::<_, 5>::new(rng.gen_wire_label(5)).unwrap();
+        assert_eq!(&(&w + &v) - &v, w);
+        assert_eq!(&w + &-&w, Typed::new(Wire::zero(5)).unwrap());
+        assert_eq!(w.cmul(Zq::new(2)), &w + &w);
+        assert!(Typed::<Wire, 7>::new(rng.gen_wire_label(5)).is_err());
+    }
+
+    #[test]
+    fn gadgets() {
+        let mut b = CircuitBuilder::new();
+        let x = Typed::<_, 3>::new(b.garbler_input(3)).unwrap();
+        let y = Typed::<_, 3>::new(b.evaluator_input(3)).unwrap();
+        let z = b.typed_mul(&x, &y).unwrap();
+        let z = b.typed_add(&z, &x).unwrap();
+        let tt = [Zq::<5>::new(4), Zq::new(0), Zq::new(2)];
+        let z = b.typed_proj(&z, &tt).unwrap();
+        b.typed_output(&z).unwrap();
+        assert!(b.typed_proj(&x, &[Zq::<2>::new(0)]).is_err());
+        let c = b.finish();
+        for x in 0..3 {
+            for y in 0..3 {
+                let want = tt[((x * y + x) % 3) as usize].value();
+                assert_eq!(c.eval_plain(&[x], &[y]).unwrap(), vec![want]);
+            }
+        }
+    }
+}
@@ -0,0 +1,265 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! A 256-bit unsigned integer for intermediate values that overflow a `u128`.
+//!
+//! The product of all the supported primes exceeds `u128`, so reconstructing a
+//! value from its residues needs wider arithmetic even when the value itself
+//! fits. `U256` provides just enough of it without allocating.
+
+use std::ops::{Add, Mul, Sub};
+
+/// An unsigned integer of two `u128` limbs. Arithmetic operators panic on
+/// overflow, like those of the primitive integers in debug builds.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct U256 {
+    // Field order matters: the derived ordering compares `hi` first.
+    hi: u128,
+    lo: u128,
+}
+
+impl U256 {
+    /// Zero.
+    pub const ZERO: U256 = U256 { hi: 0, lo: 0 };
+
+    /// Create the integer `hi * 2^128 + lo`.
+    pub fn new(hi: u128, lo: u128) -> Self {
+        U256 { hi, lo }
+    }
+
+    /// The most significant limb.
+    pub fn hi(self) -> u128 {
+        self.hi
+    }
+
+    /// The least significant limb.
+    pub fn lo(self) -> u128 {
+        self.lo
+    }
+
+    /// The value as a `u128`, if it fits.
+    pub fn to_u128(self) -> Option<u128> {
+        if self.hi == 0 {
+            Some(self.lo)
+        } else {
+            None
+        }
+    }
+
+    /// Add `other`, returning `None` on overflow.
+    pub fn checked_add(self, other: U256) -> Option<U256> {
+        let (lo, carry) = self.lo.overflowing_add(other.lo);
+        let hi = self.hi.checked_add(other.hi)?.checked_add(carry as u128)?;
+        Some(U256 { hi, lo })
+    }
+
+    /// Subtract `other`, returning `None` on underflow.
+    pub fn checked_sub(self, other: U256) -> Option<U256> {
+        let (lo, borrow) = self.lo.overflowing_sub(other.lo);
+        let hi = self.hi.checked_sub(other.hi)?.checked_sub(borrow as u128)?;
+        Some(U256 { hi, lo })
+    }
+
+    /// Multiply by `other`, returning `None` on overflow.
+    pub fn checked_mul(self, other: U256) -> Option<U256> {
+        if self.hi != 0 && other.hi != 0 {
+            return None;
+        }
+        let U256 { hi, lo } = mul_u128(self.lo, other.lo);
+        // At most one of the cross terms is nonzero.
+        let cross = self
+            .hi
+            .checked_mul(other.lo)?
+            .checked_add(self.lo.checked_mul(other.hi)?)?;
+        Some(U256 {
+            hi: hi.checked_add(cross)?,
+            lo,
+        })
+    }
+
+    /// Divide by `d`, returning the quotient and remainder.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `d` is zero.
+    pub fn div_rem(self, d: U256) -> (U256, U256) {
+        assert!(d != U256::ZERO, "division by zero");
+        if self.hi == 0 && d.hi == 0 {
+            return (U256::from(self.lo / d.lo), U256::from(self.lo % d.lo));
+        }
+        // Shift-and-subtract long division, one bit at a time.
+        let (mut q, mut r) = (U256::ZERO, U256::ZERO);
+        for i in (0..256 - self.leading_zeros()).rev() {
+            let carry = r.hi >> 127 == 1;
+            r = r.shl1();
+            r.lo |= self.bit(i) as u128;
+            // With a carry out the true remainder exceeds `d`, and the
+            // subtraction below wraps back into range.
+            if carry || r >= d {
+                r = r.wrapping_sub(d);
+                q.set_bit(i);
+            }
+        }
+        (q, r)
+    }
+
+    /// The remainder mod the small modulus `p`, without a long division.
+    pub fn rem_u16(self, p: u16) -> u16 {
+        let p = p as u128;
+        // 2^128 mod p, computed as (2^128 - p) mod p.
+        let r = 0u128.wrapping_sub(p) % p;
+        ((self.hi % p * r + self.lo % p) % p) as u16
+    }
+
+    /// Divide by the small modulus `p`, returning the quotient and remainder, by short
+    /// division over 64-bit limbs rather than a long division.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `p` is zero.
+    pub fn div_rem_u16(self, p: u16) -> (U256, u16) {
+        const MASK: u128 = (1 << 64) - 1;
+        let p = p as u128;
+        let (mut q, mut r) = ([0u128; 4], 0u128);
+        let limbs = [self.hi >> 64, self.hi & MASK, self.lo >> 64, self.lo & MASK];
+        for (q, &limb) in q.iter_mut().zip(limbs.iter()) {
+            // `r < p < 2^16`, so this fits.
+            let x = r << 64 | limb;
+            *q = x / p;
+            r = x % p;
+        }
+        (U256::new(q[0] << 64 | q[1], q[2] << 64 | q[3]), r as u16)
+    }
+
+    fn leading_zeros(self) -> u32 {
+        if self.hi == 0 {
+            128 + self.lo.leading_zeros()
+        } else {
+            self.hi.leading_zeros()
+        }
+    }
+
+    fn bit(self, i: u32) -> bool {
+        if i < 128 {
+            (self.lo >> i) & 1 == 1
+        } else {
+            (self.hi >> (i - 128)) & 1 == 1
+        }
+    }
+
+    fn set_bit(&mut self, i: u32) {
+        if i < 128 {
+            self.lo |= 1 << i;
+        } else {
+            self.hi |= 1 << (i - 128);
+        }
+    }
+
+    fn shl1(self) -> U256 {
+        U256 {
+            hi: (self.hi << 1) | (self.lo >> 127),
+            lo: self.lo << 1,
+        }
+    }
+
+    fn wrapping_sub(self, other: U256) -> U256 {
+        let (lo, borrow) = self.lo.overflowing_sub(other.lo);
+        let hi = self.hi.wrapping_sub(other.hi).wrapping_sub(borrow as u128);
+        U256 { hi, lo }
+    }
+}
+
+/// Compute the full 256-bit product of `a` and `b`.
+fn mul_u128(a: u128, b: u128) -> U256 {
+    const MASK: u128 = (1 << 64) - 1;
+    let (a1, a0) = (a >> 64, a & MASK);
+    let (b1, b0) = (b >> 64, b & MASK);
+    let p00 = a0 * b0;
+    let p01 = a0 * b1;
+    let p10 = a1 * b0;
+    let p11 = a1 * b1;
+    let mid = (p00 >> 64) + (p01 & MASK) + (p10 & MASK);
+    U256 {
+        hi: p11 + (p01 >> 64) + (p10 >> 64) + (mid >> 64),
+        lo: (p00 & MASK) | (mid << 64),
+    }
+}
+
+impl From<u128> for U256 {
+    fn from(x: u128) -> Self {
+        U256 { hi: 0, lo: x }
+    }
+}
+
+impl Add for U256 {
+    type Output = U256;
+
+    fn add(self, other: U256) -> U256 {
+        self.checked_add(other).expect("U256 addition overflowed")
+    }
+}
+
+impl Sub for U256 {
+    type Output = U256;
+
+    fn sub(self, other: U256) -> U256 {
+        self.checked_sub(other)
+            .expect("U256 subtraction underflowed")
+    }
+}
+
+impl Mul for U256 {
+    type Output = U256;
+
+    fn mul(self, other: U256) -> U256 {
+        self.checked_mul(other)
+            .expect("U256 multiplication overflowed")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::RngExt;
+    use rand::thread_rng;
+
+    #[test]
+    fn arithmetic() {
+        let mut rng = thread_rng();
+        for _ in 0..1024 {
+            let a = rng.gen_u128();
+            let b = rng.gen_u128() >> (rng.gen_u16() % 128);
+            let ab = U256::from(a) * U256::from(b);
+            if let Some(c) = a.checked_mul(b) {
+                assert_eq!(ab, U256::from(c));
+            }
+            assert_eq!(ab.rem_u16(109), ((a % 109) * (b % 109) % 109) as u16);
+            let p = rng.gen_u16().max(1);
+            let (q, r) = ab.div_rem_u16(p);
+            assert_eq!(
+                (q, U256::from(r as u128)),
+                ab.div_rem(U256::from(p as u128))
+            );
+            if b != 0 {
+                assert_eq!(ab.div_rem(U256::from(b)), (U256::from(a), U256::ZERO));
+                let (q, r) = (ab + U256::from(b - 1)).div_rem(U256::from(b));
+                assert_eq!((q, r), (U256::from(a), U256::from(b - 1)));
+            }
+            assert_eq!(ab + U256::from(a) - U256::from(a), ab);
+        }
+        let max = U256::new(u128::max_value(), u128::max_value());
+        assert_eq!(max.checked_add(U256::from(1)), None);
+        assert_eq!(U256::ZERO.checked_sub(U256::from(1)), None);
+        assert_eq!(max.checked_mul(U256::new(1, 0)), None);
+        assert_eq!(max.div_rem(max), (U256::from(1), U256::ZERO));
+        assert_eq!(
+            max.div_rem(U256::new(1 << 127, 0)),
+            (U256::from(1), U256::new((1 << 127) - 1, u128::max_value()))
+        );
+        assert_eq!(U256::new(1, 0).to_u128(), None);
+        assert!(U256::new(1, 0) > U256::from(u128::max_value()));
+    }
+}
@@ -7,9 +7,20 @@
 //! Tools useful for interacting with `fancy-garbling`.
 //!
 //! Note: all number representations in this library are little-endian.
+//!
+//! The conversions here use `u16` digits and moduli; see `digit` for versions
+//! generic over wider moduli.
 
 #[cfg(feature = "nightly")]
 use core::arch::x86_64::*;
+use crate::{
+    config,
+    errors::NumberError,
+    modulus::{BarrettReducer, Modulus},
+    u256::U256,
+    validate,
+    wire::Wire,
+};
 use itertools::Itertools;
 use scuttlebutt::Block;
 
@@ -53,6 +64,9 @@ pub fn output_tweak(i: usize, k: u16) -> Block {
 // mixed radix stuff
 
 /// Add a base `q` slice `ys` into `xs`.
+///
+/// For `q` of at most `2^15`, the digits are added eight at a time with SSE4.1 or NEON
+/// when the CPU supports them.
 pub fn base_q_add_eq(xs: &mut [u16], ys: &[u16], q: u16) {
     debug_assert!(
         xs.len() >= ys.len(),
@@ -64,13 +78,13 @@ pub fn base_q_add_eq(xs: &mut [u16], ys: &[u16], q: u16) {
         ys
     );
 
-    let mut c = 0;
-    let mut i = 0;
+    let (mut i, mut c) = base_q_add_eq_simd(xs, ys, q);
 
     while i < ys.len() {
-        xs[i] += ys[i] + c;
-        c = (xs[i] >= q) as u16;
-        xs[i] -= c * q;
+        // The sum may overflow a `u16` for `q` above `2^15`.
+        let v = xs[i] as u32 + ys[i] as u32 + c as u32;
+        c = (v >= q as u32) as u16;
+        xs[i] = (v - c as u32 * q as u32) as u16;
         i += 1;
     }
 
@@ -88,309 +102,2440 @@ pub fn base_q_add_eq(xs: &mut [u16], ys: &[u16], q: u16) {
     }
 }
 
-/// Convert `x` into base `q`, building a vector of length `n`.
-fn as_base_q(x: u128, q: u16, n: usize) -> Vec<u16> {
-    let ms = std::iter::repeat(q).take(n).collect_vec();
-    as_mixed_radix(x, &ms)
+// Add as many whole vectors of digits of `ys` into `xs` as the CPU supports, returning the
+// number of digits added and the carry out of them.
+//
+// Each vector of eight digits is reduced lane by lane, giving masks of the lanes that
+// generate a carry, whose sum is at least `q`, and of those that propagate one, whose
+// reduced sum is `q - 1`. The carries into the lanes are then those of adding the masks
+// as binary numbers.
+#[cfg(target_arch = "x86_64")]
+fn base_q_add_eq_simd(xs: &mut [u16], ys: &[u16], q: u16) -> (usize, u16) {
+    if q <= 1 << 15 && is_x86_feature_detected!("sse4.1") {
+        // Safe because the CPU supports SSE4.1.
+        unsafe { base_q_add_eq_sse41(xs, ys, q) }
+    } else {
+        (0, 0)
+    }
 }
 
-/// Determine how many `mod q` digits fit into a `u128` (includes the color
-/// digit).
-pub fn digits_per_u128(modulus: u16) -> usize {
-    debug_assert_ne!(modulus, 0);
-    debug_assert_ne!(modulus, 1);
-    if modulus == 2 {
-        128
-    } else if modulus <= 4 {
-        64
-    } else if modulus <= 8 {
-        42
-    } else if modulus <= 16 {
-        32
-    } else if modulus <= 32 {
-        25
-    } else if modulus <= 64 {
-        21
-    } else if modulus <= 128 {
-        18
-    } else if modulus <= 256 {
-        16
-    } else if modulus <= 512 {
-        14
+#[cfg(target_arch = "aarch64")]
+fn base_q_add_eq_simd(xs: &mut [u16], ys: &[u16], q: u16) -> (usize, u16) {
+    if q <= 1 << 15 {
+        // Safe because NEON is part of every aarch64 CPU.
+        unsafe { base_q_add_eq_neon(xs, ys, q) }
     } else {
-        (128.0 / (modulus as f64).log2().ceil()).floor() as usize
+        (0, 0)
     }
 }
 
-/// Convert `x` into base `q`.
-pub fn as_base_q_u128(x: u128, q: u16) -> Vec<u16> {
-    as_base_q(x, q, digits_per_u128(q))
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn base_q_add_eq_simd(_xs: &mut [u16], _ys: &[u16], _q: u16) -> (usize, u16) {
+    (0, 0)
 }
 
-/// Convert `x` into mixed radix form using the provided `radii`.
-pub fn as_mixed_radix(x: u128, radii: &[u16]) -> Vec<u16> {
-    let mut x = x;
-    radii
-        .iter()
-        .map(|&m| {
-            if x >= m as u128 {
-                let d = x % m as u128;
-                x = (x - d) / m as u128;
-                d as u16
-            } else {
-                let d = x as u16;
-                x = 0;
-                d
-            }
-        })
-        .collect()
+// The carries into each of eight lanes, and out of the last, given the lanes generating
+// a carry, those propagating one, and the carry into the first.
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+#[inline]
+fn lane_carries(g: u32, p: u32, c: u32) -> (u32, u32) {
+    // Adding `g | p` and `g` generates exactly at `g` and propagates exactly at `p`, as
+    // the two are disjoint.
+    let sum = (g | p) + g + c;
+    ((sum ^ p) & 0xff, (sum >> 8) & 1)
 }
 
-/// Convert little-endian base `q` digits into `u128`.
-pub fn from_base_q(ds: &[u16], q: u16) -> u128 {
-    let mut x = 0u128;
-    for &d in ds.iter().rev() {
-        let (xp, overflow) = x.overflowing_mul(q.into());
-        debug_assert_eq!(overflow, false, "overflow!!!! x={}", x);
-        x = xp + d as u128;
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "sse4.1")]
+unsafe fn base_q_add_eq_sse41(xs: &mut [u16], ys: &[u16], q: u16) -> (usize, u16) {
+    use std::arch::x86_64::*;
+
+    let n = ys.len() / 8 * 8;
+    let qs = _mm_set1_epi16(q as i16);
+    let qs_1 = _mm_set1_epi16((q - 1) as i16);
+    let bits = _mm_setr_epi16(1, 2, 4, 8, 16, 32, 64, 128);
+    let mask = |v: __m128i| _mm_movemask_epi8(_mm_packs_epi16(v, _mm_setzero_si128())) as u32;
+    let mut c = 0;
+    for i in (0..n).step_by(8) {
+        let x = _mm_loadu_si128(xs.as_ptr().add(i) as *const __m128i);
+        let y = _mm_loadu_si128(ys.as_ptr().add(i) as *const __m128i);
+        let s = _mm_add_epi16(x, y);
+        // `s - q` wraps around to above `s` exactly when `s < q`.
+        let r = _mm_min_epu16(s, _mm_sub_epi16(s, qs));
+        let g = !mask(_mm_cmpeq_epi16(r, s)) & 0xff;
+        let p = mask(_mm_cmpeq_epi16(r, qs_1));
+        let (cs, c_) = lane_carries(g, p, c);
+        c = c_;
+        // All ones in the lanes with a carry in, so subtracting adds it.
+        let cs = _mm_cmpeq_epi16(_mm_and_si128(_mm_set1_epi16(cs as i16), bits), bits);
+        let r = _mm_sub_epi16(r, cs);
+        let r = _mm_andnot_si128(_mm_cmpeq_epi16(r, qs), r);
+        _mm_storeu_si128(xs.as_mut_ptr().add(i) as *mut __m128i, r);
     }
-    x
+    (n, c as u16)
 }
 
-/// Convert little-endian mixed radix digits into u128.
-pub fn from_mixed_radix(digits: &[u16], radii: &[u16]) -> u128 {
-    let mut x: u128 = 0;
-    for (&d, &q) in digits.iter().zip(radii.iter()).rev() {
-        let (xp, overflow) = x.overflowing_mul(q as u128);
-        debug_assert_eq!(overflow, false, "overflow!!!! x={}", x);
-        x = xp + d as u128;
+#[cfg(target_arch = "aarch64")]
+unsafe fn base_q_add_eq_neon(xs: &mut [u16], ys: &[u16], q: u16) -> (usize, u16) {
+    use std::arch::aarch64::*;
+
+    let n = ys.len() / 8 * 8;
+    let qs = vdupq_n_u16(q);
+    let qs_1 = vdupq_n_u16(q - 1);
+    let bits = vld1q_u16([1, 2, 4, 8, 16, 32, 64, 128].as_ptr());
+    let mask = |v: uint16x8_t| vaddvq_u16(vandq_u16(v, bits)) as u32;
+    let mut c = 0;
+    for i in (0..n).step_by(8) {
+        let x = vld1q_u16(xs.as_ptr().add(i));
+        let y = vld1q_u16(ys.as_ptr().add(i));
+        let s = vaddq_u16(x, y);
+        // `s - q` wraps around to above `s` exactly when `s < q`.
+        let r = vminq_u16(s, vsubq_u16(s, qs));
+        let g = mask(vmvnq_u16(vceqq_u16(r, s)));
+        let p = mask(vceqq_u16(r, qs_1));
+        let (cs, c_) = lane_carries(g, p, c);
+        c = c_;
+        // All ones in the lanes with a carry in, so subtracting adds it.
+        let cs = vtstq_u16(vdupq_n_u16(cs as u16), bits);
+        let r = vsubq_u16(r, cs);
+        let r = vbicq_u16(r, vceqq_u16(r, qs));
+        vst1q_u16(xs.as_mut_ptr().add(i), r);
     }
-    x
+    (n, c as u16)
 }
 
-////////////////////////////////////////////////////////////////////////////////
-// bits
+/// Subtract a base `q` slice `ys` from `xs`, returning whether it underflowed, i.e.,
+/// whether `xs < ys`. On underflow `xs` wraps around, i.e., it becomes `xs - ys` plus
+/// `q^n`, where `n` is the length of `xs`.
+pub fn base_q_sub_borrow_eq(xs: &mut [u16], ys: &[u16], q: u16) -> bool {
+    debug_assert!(
+        xs.len() >= ys.len(),
+        "q={} xs.len()={} ys.len()={} xs={:?} ys={:?}",
+        q,
+        xs.len(),
+        ys.len(),
+        xs,
+        ys
+    );
 
-/// Get the bits of a u128 encoded in 128 u16s, which is convenient for the rest of
-/// the library, which uses u16 as the base digit type in Wire.
-pub fn u128_to_bits(x: u128, n: usize) -> Vec<u16> {
-    let mut bits = Vec::with_capacity(n);
-    let mut y = x;
-    for _ in 0..n {
-        let b = y & 1;
-        bits.push(b as u16);
-        y -= b;
-        y /= 2;
+    let mut b = 0;
+    let mut i = 0;
+
+    while i < ys.len() {
+        let y = ys[i] as u32 + b;
+        b = ((xs[i] as u32) < y) as u32;
+        xs[i] = (xs[i] as u32 + b * q as u32 - y) as u16;
+        i += 1;
     }
-    bits
-}
 
-/// Convert into a u128 from the "bits" as u16. Assumes each "bit" is 0 or 1.
-pub fn u128_from_bits(bs: &[u16]) -> u128 {
-    let mut x = 0;
-    for &b in bs.iter().skip(1).rev() {
-        x += b as u128;
-        x *= 2;
+    // continue the borrowing if needed
+    while b == 1 && i < xs.len() {
+        if xs[i] == 0 {
+            xs[i] = q - 1;
+        } else {
+            xs[i] -= 1;
+            b = 0;
+        }
+        i += 1;
     }
-    x += bs[0] as u128;
-    x
+    b == 1
 }
 
-////////////////////////////////////////////////////////////////////////////////
-// primes & crt
+/// Subtract a base `q` slice `ys` from `xs`, wrapping around on underflow as
+/// `base_q_sub_borrow_eq` does.
+pub fn base_q_sub_eq(xs: &mut [u16], ys: &[u16], q: u16) {
+    base_q_sub_borrow_eq(xs, ys, q);
+}
+
+/// Subtract the base `q` digits `ys` from `xs`, returning the difference and whether it
+/// underflowed, as `base_q_sub_borrow_eq` does.
+pub fn base_q_sub_borrow(xs: &[u16], ys: &[u16], q: u16) -> (Vec<u16>, bool) {
+    let mut zs = xs.to_vec();
+    let borrow = base_q_sub_borrow_eq(&mut zs, ys, q);
+    (zs, borrow)
+}
+
+/// Subtract the base `q` digits `ys` from `xs`, wrapping around on underflow as
+/// `base_q_sub_borrow_eq` does.
+pub fn base_q_sub(xs: &[u16], ys: &[u16], q: u16) -> Vec<u16> {
+    base_q_sub_borrow(xs, ys, q).0
+}
 
-/// Factor using the primes in the global `PRIMES` array. Fancy garbling only supports
-/// composites with small prime factors.
+/// Compare the little-endian base `q` digits `xs` and `ys` from the most significant
+/// digit down, without converting them to integers. The vectors may have different
+/// lengths, with missing digits treated as zeros.
+pub fn base_q_cmp(xs: &[u16], ys: &[u16], q: u16) -> std::cmp::Ordering {
+    validate::digits(xs, q);
+    validate::digits(ys, q);
+    digits_cmp(xs, ys)
+}
+
+/// Compare the little-endian mixed radix digits `xs` and `ys`, with respect to the
+/// `radii`, as `base_q_cmp` does. Either may be shorter than `radii`, with missing digits
+/// treated as zeros.
 ///
-/// We are limited by the size of the digits in Wire, and besides, if need large moduli,
-/// you should use BundleGadgets and save.
-pub fn factor(inp: u128) -> Vec<u16> {
-    let mut x = inp;
-    let mut fs = Vec::new();
-    for &p in PRIMES.iter() {
-        let q = p as u128;
-        if x % q == 0 {
-            fs.push(p);
-            x /= q;
+/// # Panics
+///
+/// Panics if `xs` or `ys` is longer than `radii`.
+pub fn mixed_radix_cmp(xs: &[u16], ys: &[u16], radii: &[u16]) -> std::cmp::Ordering {
+    validate::mixed_radix(xs, &radii[..xs.len()]);
+    validate::mixed_radix(ys, &radii[..ys.len()]);
+    digits_cmp(xs, ys)
+}
+
+// Digits in the same positions have the same radix, so the first that differs from the
+// top decides the order.
+fn digits_cmp(xs: &[u16], ys: &[u16]) -> std::cmp::Ordering {
+    let digit = |ds: &[u16], i: usize| ds.get(i).cloned().unwrap_or(0);
+    (0..xs.len().max(ys.len()))
+        .rev()
+        .map(|i| digit(xs, i).cmp(&digit(ys, i)))
+        .find(|&o| o != std::cmp::Ordering::Equal)
+        .unwrap_or(std::cmp::Ordering::Equal)
+}
+
+/// Subtract the mixed radix digits `ys` from `xs`, with respect to the `radii`, returning
+/// the difference and whether it underflowed, i.e., whether `xs < ys`. On underflow the
+/// difference wraps around, i.e., it is `xs - ys` plus the product of the `radii`.
+///
+/// # Panics
+///
+/// Panics if `xs`, `ys`, and `radii` have different lengths.
+pub fn mixed_radix_sub(xs: &[u16], ys: &[u16], radii: &[u16]) -> (Vec<u16>, bool) {
+    assert!(
+        xs.len() == radii.len() && ys.len() == radii.len(),
+        "digit counts differ"
+    );
+    let mut borrow = false;
+    let ds = xs
+        .iter()
+        .zip(ys.iter())
+        .zip(radii.iter())
+        .map(|((&x, &y), &m)| {
+            let y = y as u32 + borrow as u32;
+            borrow = (x as u32) < y;
+            (x as u32 + borrow as u32 * m as u32 - y) as u16
+        })
+        .collect();
+    (ds, borrow)
+}
+
+/// Compute the dot product of the vectors `xs` and `ys`, whose elements are base `q`
+/// numbers of `n` digits each, as `n` base `q` digits, i.e., mod `q^n`. Unlike
+/// converting to `u128`, this never overflows, however long the vectors are.
+///
+/// # Panics
+///
+/// Panics if the vectors have different lengths, or an element does not have `n` digits.
+pub fn base_q_dot(xs: &[Vec<u16>], ys: &[Vec<u16>], q: u16, n: usize) -> Vec<u16> {
+    assert_eq!(xs.len(), ys.len(), "vector lengths differ");
+    let q = q as u64;
+    let mut acc = vec![0u64; n];
+    for (x, y) in xs.iter().zip(ys.iter()) {
+        assert!(x.len() == n && y.len() == n, "digit counts differ");
+        // Add the product truncated to `n` digits, then carry, so that each
+        // accumulator stays below `q + n * q^2`.
+        for (i, &a) in x.iter().enumerate() {
+            for (j, &b) in y[..n - i].iter().enumerate() {
+                acc[i + j] += a as u64 * b as u64;
+            }
+        }
+        let mut c = 0;
+        for d in acc.iter_mut() {
+            let v = *d + c;
+            *d = v % q;
+            c = v / q;
         }
     }
-    if x != 1 {
-        panic!("can only factor numbers with unique prime factors");
-    }
-    fs
+    acc.into_iter().map(|d| d as u16).collect()
 }
 
-/// Compute the CRT representation of x with respect to the primes ps.
-pub fn crt(x: u128, ps: &[u16]) -> Vec<u16> {
-    ps.iter().map(|&p| (x % p as u128) as u16).collect()
-}
+/// Operands with fewer digits than this are multiplied by the schoolbook method.
+const KARATSUBA_THRESHOLD: usize = 32;
 
-/// Compute the CRT representation of `x` with respect to the factorization of
-/// `q`.
-pub fn crt_factor(x: u128, q: u128) -> Vec<u16> {
-    crt(x, &factor(q))
+/// Multiply the base `q` numbers `xs` and `ys`, returning all `xs.len() + ys.len()` digits
+/// of the product. Long operands are multiplied with Karatsuba's method, in time
+/// `O(n^1.585)` rather than `O(n^2)`.
+pub fn base_q_mul(xs: &[u16], ys: &[u16], q: u16) -> Vec<u16> {
+    if xs.len().min(ys.len()) < KARATSUBA_THRESHOLD {
+        return base_q_mul_schoolbook(xs, ys, q);
+    }
+    // Split both at `m` digits, so that `x = x0 + x1 q^m` and likewise for `y`, and use
+    // `x0 y1 + x1 y0 = (x0 + x1)(y0 + y1) - x0 y0 - x1 y1`.
+    let m = xs.len().max(ys.len()) / 2;
+    let (x0, x1) = xs.split_at(m.min(xs.len()));
+    let (y0, y1) = ys.split_at(m.min(ys.len()));
+    let z0 = base_q_mul(x0, y0, q);
+    let z2 = base_q_mul(x1, y1, q);
+    let mut z1 = base_q_mul(&base_q_sum(x0, x1, q), &base_q_sum(y0, y1, q), q);
+    base_q_sub_eq(&mut z1, &z0, q);
+    base_q_sub_eq(&mut z1, &z2, q);
+    let mut zs = vec![0; xs.len() + ys.len()];
+    base_q_add_wide_eq(&mut zs, &z0, q);
+    base_q_add_wide_eq(&mut zs[m..], &z1, q);
+    base_q_add_wide_eq(&mut zs[2 * m..], &z2, q);
+    zs
 }
 
-/// Compute the value x given a list of CRT primes and residues.
-pub fn crt_inv(xs: &[u16], ps: &[u16]) -> u128 {
-    let mut ret = 0;
-    let M = ps.iter().fold(1, |acc, &x| x as i128 * acc);
-    for (&p, &a) in ps.iter().zip(xs.iter()) {
-        let p = p as i128;
-        let q = M / p;
-        ret += a as i128 * inv(q, p) * q;
-        ret %= &M;
+fn base_q_mul_schoolbook(xs: &[u16], ys: &[u16], q: u16) -> Vec<u16> {
+    // Each accumulator is the sum of at most `min(xs.len(), ys.len())` products.
+    let mut acc = vec![0u64; xs.len() + ys.len()];
+    for (i, &a) in xs.iter().enumerate() {
+        for (j, &b) in ys.iter().enumerate() {
+            acc[i + j] += a as u64 * b as u64;
+        }
+    }
+    let q = q as u64;
+    let mut c = 0;
+    for d in acc.iter_mut() {
+        let v = *d + c;
+        *d = v % q;
+        c = v / q;
     }
-    ret as u128
+    acc.into_iter().map(|d| d as u16).collect()
 }
 
-/// Compute the value `x` given a composite CRT modulus provided by `xs`.
-pub fn crt_inv_factor(xs: &[u16], q: u128) -> u128 {
-    crt_inv(xs, &factor(q))
+/// The sum of the base `q` numbers `xs` and `ys`, with one more digit than the longer.
+fn base_q_sum(xs: &[u16], ys: &[u16], q: u16) -> Vec<u16> {
+    let (xs, ys) = if xs.len() < ys.len() {
+        (ys, xs)
+    } else {
+        (xs, ys)
+    };
+    let mut zs = xs.to_vec();
+    zs.push(0);
+    base_q_add_wide_eq(&mut zs, ys, q);
+    zs
 }
 
-/// Generic algorithm to invert inp_a mod inp_b. As ref so as to support BigInts without
-/// copying.
-pub fn inv(inp_a: i128, inp_b: i128) -> i128 {
-    let mut a = inp_a;
-    let mut b = inp_b;
-    let mut q;
-    let mut tmp;
+/// Add the base `q` number `ys` into `xs`, as `base_q_add_eq` does, for any `q`. Leading
+/// zeros of `ys` may go beyond the end of `xs`, but the sum must fit.
+fn base_q_add_wide_eq(xs: &mut [u16], ys: &[u16], q: u16) {
+    let q = q as u32;
+    let mut c = 0;
+    for (i, x) in xs.iter_mut().enumerate() {
+        if i >= ys.len() && c == 0 {
+            return;
+        }
+        let v = *x as u32 + ys.get(i).cloned().unwrap_or(0) as u32 + c;
+        c = (v >= q) as u32;
+        *x = (v - c * q) as u16;
+    }
+    debug_assert!(
+        c == 0 && ys.iter().skip(xs.len()).all(|&y| y == 0),
+        "base q sum overflowed"
+    );
+}
 
-    let (mut x0, mut x1) = (0, 1);
+/// Multiply the base `q` number `xs` by the constant `c` in place, returning the carry out
+/// of the last digit, which does not fit.
+pub fn base_q_scale_eq(xs: &mut [u16], c: u16, q: u16) -> u32 {
+    let (c, q) = (c as u64, q as u64);
+    let mut carry = 0;
+    for x in xs.iter_mut() {
+        // The carry stays at most `c`, so this is below `q (c + 1)`.
+        let v = *x as u64 * c + carry;
+        *x = (v % q) as u16;
+        carry = v / q;
+    }
+    carry as u32
+}
 
-    if b == 1 {
-        return 1;
+/// Add `xs` times the constant `c` into the base `q` number `acc` in a single carry pass,
+/// returning the carry out of the last digit of `acc`, which does not fit.
+pub fn base_q_mac_eq(acc: &mut [u16], xs: &[u16], c: u16, q: u16) -> u32 {
+    debug_assert!(acc.len() >= xs.len(), "acc is shorter than xs");
+    let (c, q) = (c as u64, q as u64);
+    let mut carry = 0;
+    for (i, a) in acc.iter_mut().enumerate() {
+        if i >= xs.len() && carry == 0 {
+            break;
+        }
+        // The carry stays at most `c + 1`, so this is below `q (c + 2)`.
+        let v = *a as u64 + xs.get(i).map_or(0, |&x| x as u64) * c + carry;
+        *a = (v % q) as u16;
+        carry = v / q;
     }
+    carry as u32
+}
 
-    while a > 1 {
-        q = a / b;
+/// Multiply the digit vector `xs` by `q^k`, for any base `q`, by prepending `k` zero
+/// digits.
+pub fn shl_digits(xs: &[u16], k: usize) -> Vec<u16> {
+    let mut zs = vec![0; k + xs.len()];
+    zs[k..].copy_from_slice(xs);
+    zs
+}
 
-        // a, b = b, a%b
-        tmp = b;
-        b = a % b;
-        a = tmp;
+/// Divide the digit vector `xs` by `q^k`, for any base `q`, rounding down, by dropping its
+/// lowest `k` digits.
+pub fn shr_digits(xs: &[u16], k: usize) -> Vec<u16> {
+    xs[k.min(xs.len())..].to_vec()
+}
 
-        tmp = x0;
-        x0 = x1 - q * x0;
-        x1 = tmp;
+/// Divide the base `q` number `xs` by `ys` with schoolbook long division, returning the
+/// quotient, with as many digits as `xs`, and the remainder, with as many digits as `ys`.
+/// Each quotient digit is found by a binary search, so this takes `O(n m log q)` time
+/// for `n` and `m` digits.
+///
+/// # Panics
+///
+/// Panics if `ys` is zero.
+pub fn base_q_divmod(xs: &[u16], ys: &[u16], q: u16) -> (Vec<u16>, Vec<u16>) {
+    assert!(ys.iter().any(|&y| y != 0), "division by zero");
+    let m = ys.len();
+    let mut qs = vec![0; xs.len()];
+    // The remainder, which is below `ys` between steps, and below `ys q` after shifting in
+    // a digit, so it fits in `m + 1` digits.
+    let mut r = vec![0; m + 1];
+    // `ys` times a candidate quotient digit.
+    let mut t = vec![0; m + 1];
+    for (i, &x) in xs.iter().enumerate().rev() {
+        r.pop();
+        r.insert(0, x);
+        // The largest `d` with `ys d <= r`.
+        let (mut lo, mut hi) = (0, q - 1);
+        while lo < hi {
+            let mid = hi - (hi - lo) / 2;
+            t[..m].copy_from_slice(ys);
+            t[m] = 0;
+            base_q_scale_eq(&mut t, mid, q);
+            if digits_cmp(&t, &r) == std::cmp::Ordering::Greater {
+                hi = mid - 1;
+            } else {
+                lo = mid;
+            }
+        }
+        t[..m].copy_from_slice(ys);
+        t[m] = 0;
+        base_q_scale_eq(&mut t, lo, q);
+        base_q_sub_eq(&mut r, &t, q);
+        qs[i] = lo;
     }
+    r.truncate(m);
+    (qs, r)
+}
 
-    if x1 < 0 {
-        x1 += inp_b;
+/// Divide the base `q` number `xs` by the constant `c` with short division, returning
+/// the quotient, with as many digits as `xs`, and the remainder.
+///
+/// # Panics
+///
+/// Panics if `c` is zero.
+pub fn base_q_divmod_const(xs: &[u16], c: u16, q: u16) -> (Vec<u16>, u16) {
+    assert!(c != 0, "division by zero");
+    let (c, q) = (c as u64, q as u64);
+    let mut qs = vec![0; xs.len()];
+    let mut r = 0;
+    for (d, &x) in qs.iter_mut().zip(xs.iter()).rev() {
+        // `r < c`, so this is below `c q`.
+        let v = r * q + x as u64;
+        *d = (v / c) as u16;
+        r = v % c;
     }
-
-    x1
+    (qs, r as u16)
 }
 
-/// Number of primes supported by our library.
-pub const NPRIMES: usize = 29;
+/// Divide the base `q` number `xs` by the constant `c`, which must divide it exactly.
+///
+/// # Panics
+///
+/// Panics if `c` is zero or does not divide `xs`.
+pub fn base_q_div_exact(xs: &[u16], c: u16, q: u16) -> Vec<u16> {
+    let (qs, r) = base_q_divmod_const(xs, c, q);
+    assert_eq!(r, 0, "{} does not divide the dividend", c);
+    qs
+}
 
-/// Primes used in fancy garbling.
-pub const PRIMES: [u16; 29] = [
-    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
-    101, 103, 107, 109,
-];
+/// Convert `x` into base `q`, building a vector of length `n`. Digits beyond the `n`th are
+/// dropped; use `try_as_base_q` for untrusted input.
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip(x)))]
+pub fn as_base_q(x: u128, q: u16, n: usize) -> Vec<u16> {
+    let mut ds = vec![0; n];
+    as_base_q_into(x, q, &mut ds);
+    ds
+}
 
-/// Primes skipping the modulus 2, which allows certain gadgets.
-// pub const PRIMES_SKIP_2: [u16; 29] = [
-//     3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
-//     101, 103, 107, 109, 113,
-// ];
+/// Convert `x` into base `q`, writing as many digits as `ds` holds, as `as_base_q` does.
+pub fn as_base_q_into(x: u128, q: u16, ds: &mut [u16]) {
+    let q = q as u128;
+    let mut x = x;
+    for d in ds.iter_mut() {
+        if x >= q {
+            *d = (x % q) as u16;
+            x /= q;
+        } else {
+            *d = x as u16;
+            x = 0;
+        }
+    }
+}
 
-/// Generate a CRT modulus with n primes.
-pub fn modulus_with_nprimes(n: usize) -> u128 {
-    product(&PRIMES[0..n])
+/// Convert `x` into base `q`, building a vector of length `n`, returning an error if `x`
+/// does not fit in `n` digits.
+pub fn try_as_base_q(x: u128, q: u16, n: usize) -> Result<Vec<u16>, NumberError> {
+    let ms = core::iter::repeat(q).take(n).collect_vec();
+    try_as_mixed_radix(x, &ms)
 }
 
-/// Generate a CRT modulus that support at least n-bit integers, using the built-in
-/// PRIMES.
-pub fn modulus_with_width(n: u32) -> u128 {
-    base_modulus_with_width(n, &PRIMES)
+/// Determine how many `mod q` digits fit into a `u128` (includes the color
+/// digit).
+///
+/// This only uses integer arithmetic, so it does not depend on `std`'s
+/// floating point support.
+pub fn digits_per_u128(modulus: u16) -> usize {
+    debug_assert_ne!(modulus, 0);
+    debug_assert_ne!(modulus, 1);
+    // The number of bits needed to hold a digit, i.e., `ceil(log2(modulus))`.
+    let bits = 16 - (modulus - 1).leading_zeros() as usize;
+    128 / bits
 }
 
-/// Generate the factors of a CRT modulus that support at least n-bit integers, using the
-/// built-in PRIMES.
-pub fn primes_with_width(n: u32) -> Vec<u16> {
-    base_primes_with_width(n, &PRIMES)
+/// Determine how many `mod q` digits are needed to hold any integer of `bits` bits, for
+/// `bits` up to 256, i.e., the least `n` with `q^n >= 2^bits`.
+///
+/// Like `digits_per_u128`, this only uses integer arithmetic, so the result is exact even
+/// when `2^bits` is a power of `q`.
+///
+/// # Panics
+///
+/// Panics if `q` is below 2 or `bits` is above 256.
+pub fn digits_needed(bits: u32, q: u16) -> usize {
+    assert!(q >= 2, "modulus must be at least 2");
+    assert!(bits <= 256, "at most 256 bits are supported");
+    // `2^bits`, unless it does not fit.
+    let bound = match bits {
+        0..=127 => Some(U256::new(0, 1 << bits)),
+        128..=255 => Some(U256::new(1 << (bits - 128), 0)),
+        _ => None,
+    };
+    let q = U256::from(q as u128);
+    let mut power = U256::from(1);
+    let mut n = 0;
+    while !matches!(bound, Some(b) if power >= b) {
+        n += 1;
+        match power.checked_mul(q) {
+            Some(p) => power = p,
+            // `q^n` exceeds `2^256`, so it exceeds `2^bits`.
+            None => break,
+        }
+    }
+    n
 }
 
-/// Generate a CRT modulus that support at least n-bit integers, using provided primes.
-pub fn base_modulus_with_width(nbits: u32, primes: &[u16]) -> u128 {
-    product(&base_primes_with_width(nbits, primes))
+/// The number of carry digits a sum of base `q` digit vectors needs, beyond the digits
+/// of its terms, when the `i`th term is multiplied by `weights[i]`: the least `c` with
+/// `q^c >= sum(weights)`. With unit weights, this is the number needed to add
+/// `weights.len()` vectors.
+///
+/// # Panics
+///
+/// Panics if `q` is below 2.
+pub fn carry_digits_for_sum(q: u16, weights: &[u64]) -> usize {
+    assert!(q >= 2, "modulus must be at least 2");
+    let total = weights.iter().map(|&w| w as u128).sum::<u128>();
+    let mut power = 1u128;
+    let mut c = 0;
+    while power < total {
+        // `q^c` only overflows once it is above the total, which is below `2^128`.
+        power = power.saturating_mul(q as u128);
+        c += 1;
+    }
+    c
 }
 
-/// Generate the factors of a CRT modulus that support at least n-bit integers, using provided primes.
-pub fn base_primes_with_width(nbits: u32, primes: &[u16]) -> Vec<u16> {
-    let mut res = 1;
-    let mut ps = Vec::new();
-    for &p in primes.iter() {
-        res *= u128::from(p);
-        ps.push(p);
-        if (res >> nbits) > 0 {
-            break;
+/// The number of carry digits with radii taken in order from `radii` that a weighted sum
+/// of mixed radix digit vectors needs, as `carry_digits_for_sum` does for a fixed base.
+/// Returns `None` if `radii` run out first.
+///
+/// # Panics
+///
+/// Panics if a radix is below 2.
+pub fn carry_digits_for_mixed_radix_sum(radii: &[u16], weights: &[u64]) -> Option<usize> {
+    let total = weights.iter().map(|&w| w as u128).sum::<u128>();
+    let mut power = 1u128;
+    for (c, &q) in radii.iter().enumerate() {
+        if power >= total {
+            return Some(c);
         }
+        assert!(q >= 2, "modulus must be at least 2");
+        power = power.saturating_mul(q as u128);
+    }
+    if power >= total {
+        Some(radii.len())
+    } else {
+        None
     }
-    assert!((res >> nbits) > 0, "not enough primes!");
-    ps
 }
 
-/// Generate a CRT modulus that support at least n-bit integers, using the built-in
-/// PRIMES_SKIP_2 (does not include 2 as a factor).
-// pub fn modulus_with_width_skip2(nbits: u32) -> u128 {
-//     base_modulus_with_width(nbits, &PRIMES_SKIP_2)
-// }
+/// Convert `x` into base `q`.
+pub fn as_base_q_u128(x: u128, q: u16) -> Vec<u16> {
+    as_base_q(x, q, digits_per_u128(q))
+}
 
-/// Compute the product of some u16s as a u128.
-pub fn product(xs: &[u16]) -> u128 {
-    xs.iter().fold(1, |acc, &x| acc * x as u128)
+/// Convert `x` into mixed radix form using the provided `radii`.
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip(x)))]
+pub fn as_mixed_radix(x: u128, radii: &[u16]) -> Vec<u16> {
+    let mut ds = vec![0; radii.len()];
+    as_mixed_radix_into(x, radii, &mut ds);
+    ds
 }
 
-/// Raise a u16 to a power mod some value.
-// pub fn powm(inp: u16, pow: u16, modulus: u16) -> u16 {
-//     let mut x = inp as u16;
-//     let mut z = 1;
-//     let mut n = pow;
-//     while n > 0 {
-//         if n % 2 == 0 {
-//             x = x.pow(2) % modulus as u16;
-//             n /= 2;
-//         } else {
-//             z = x * z % modulus as u16;
-//             n -= 1;
-//         }
-//     }
-//     z as u16
-// }
+/// Convert `x` into mixed radix form using the provided `radii`, writing the digits into
+/// `ds`.
+///
+/// # Panics
+///
+/// Panics if `ds` and `radii` have different lengths.
+pub fn as_mixed_radix_into(x: u128, radii: &[u16], ds: &mut [u16]) {
+    assert_eq!(ds.len(), radii.len(), "digit and radix counts differ");
+    let mut x = x;
+    for (d, &m) in ds.iter_mut().zip(radii.iter()) {
+        if x >= m as u128 {
+            *d = (x % m as u128) as u16;
+            x /= m as u128;
+        } else {
+            *d = x as u16;
+            x = 0;
+        }
+    }
+}
 
-/// Returns `true` if `x` is a power of 2.
-pub fn is_power_of_2(x: u16) -> bool {
-    (x & (x - 1)) == 0
+/// An iterator over the `n` little-endian base `q` digits of a value, computed on demand,
+/// which yields the same digits as `as_base_q`.
+#[derive(Clone, Debug)]
+pub struct BaseQIter {
+    x: u128,
+    q: u128,
+    n: usize,
 }
 
-/// Extra Rng functionality, useful for `fancy-garbling`.
-pub trait RngExt: rand::Rng + Sized {
-    /// Randomly generate a `bool`.
-    fn gen_bool(&mut self) -> bool {
-        self.gen()
-    }
-    /// Randomly generate a `u16`.
-    fn gen_u16(&mut self) -> u16 {
-        self.gen()
+impl BaseQIter {
+    /// Iterate over the `n` base `q` digits of `x`.
+    pub fn new(x: u128, q: u16, n: usize) -> Self {
+        BaseQIter { x, q: q as u128, n }
     }
-    /// Randomly generate a `u32`.
-    fn gen_u32(&mut self) -> u32 {
-        self.gen()
+}
+
+impl Iterator for BaseQIter {
+    type Item = u16;
+
+    fn next(&mut self) -> Option<u16> {
+        if self.n == 0 {
+            return None;
+        }
+        self.n -= 1;
+        let d = self.x % self.q;
+        self.x /= self.q;
+        Some(d as u16)
     }
-    /// Randomly generate a `u64`.
-    fn gen_u64(&mut self) -> u64 {
-        self.gen()
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.n, Some(self.n))
     }
-    /// Randomly generate a `usize`.
-    fn gen_usize(&mut self) -> usize {
-        self.gen()
+}
+
+impl ExactSizeIterator for BaseQIter {}
+
+/// An iterator over the little-endian mixed radix digits of a value, computed on demand,
+/// which yields the same digits as `as_mixed_radix`.
+#[derive(Clone, Debug)]
+pub struct MixedRadixIter<'a> {
+    x: u128,
+    radii: std::slice::Iter<'a, u16>,
+}
+
+impl<'a> MixedRadixIter<'a> {
+    /// Iterate over the digits of `x` with respect to `radii`.
+    pub fn new(x: u128, radii: &'a [u16]) -> Self {
+        MixedRadixIter {
+            x,
+            radii: radii.iter(),
+        }
     }
-    /// Randomly generate a `u128`.
+}
+
+impl<'a> Iterator for MixedRadixIter<'a> {
+    type Item = u16;
+
+    fn next(&mut self) -> Option<u16> {
+        let m = *self.radii.next()? as u128;
+        let d = self.x % m;
+        self.x /= m;
+        Some(d as u16)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.radii.size_hint()
+    }
+}
+
+impl<'a> ExactSizeIterator for MixedRadixIter<'a> {}
+
+/// Digits held on the stack, for conversions bounded by the 128 digits a `u128` can have,
+/// which need not allocate. It dereferences to a slice of its digits.
+#[derive(Clone, Copy)]
+pub struct DigitArray {
+    len: usize,
+    ds: [u16; DigitArray::CAPACITY],
+}
+
+impl DigitArray {
+    /// The most digits an array holds.
+    pub const CAPACITY: usize = 128;
+
+    /// `n` zero digits.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` exceeds the capacity.
+    pub fn zeros(n: usize) -> Self {
+        assert!(n <= Self::CAPACITY, "{} digits exceed the capacity", n);
+        DigitArray {
+            len: n,
+            ds: [0; Self::CAPACITY],
+        }
+    }
+
+    /// Convert `x` into `n` base `q` digits, as `as_base_q` does.
+    pub fn base_q(x: u128, q: u16, n: usize) -> Self {
+        let mut ds = Self::zeros(n);
+        as_base_q_into(x, q, &mut ds);
+        ds
+    }
+
+    /// Convert `x` into mixed radix form, as `as_mixed_radix` does.
+    pub fn mixed_radix(x: u128, radii: &[u16]) -> Self {
+        let mut ds = Self::zeros(radii.len());
+        as_mixed_radix_into(x, radii, &mut ds);
+        ds
+    }
+
+    /// Compute the CRT representation of `x`, as `crt` does.
+    pub fn crt(x: u128, ps: &[u16]) -> Self {
+        let mut ds = Self::zeros(ps.len());
+        crt_into(x, ps, &mut ds);
+        ds
+    }
+}
+
+impl std::ops::Deref for DigitArray {
+    type Target = [u16];
+
+    fn deref(&self) -> &[u16] {
+        &self.ds[..self.len]
+    }
+}
+
+impl std::ops::DerefMut for DigitArray {
+    fn deref_mut(&mut self) -> &mut [u16] {
+        &mut self.ds[..self.len]
+    }
+}
+
+impl std::fmt::Debug for DigitArray {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl PartialEq for DigitArray {
+    fn eq(&self, other: &DigitArray) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for DigitArray {}
+
+/// Convert the 256-bit `x` into base `q`, building a vector of length `n`, as `as_base_q`
+/// does.
+pub fn as_base_q_u256(x: U256, q: u16, n: usize) -> Vec<u16> {
+    as_mixed_radix_u256(x, &vec![q; n])
+}
+
+/// Convert the 256-bit `x` into mixed radix form using the provided `radii`, as
+/// `as_mixed_radix` does.
+pub fn as_mixed_radix_u256(x: U256, radii: &[u16]) -> Vec<u16> {
+    let mut x = x;
+    radii
+        .iter()
+        .map(|&m| {
+            let (q, d) = x.div_rem_u16(m);
+            x = q;
+            d
+        })
+        .collect()
+}
+
+/// Convert `x` into mixed radix form using the provided `radii`, returning an error if `x`
+/// does not fit, or if a radix is below 2.
+pub fn try_as_mixed_radix(x: u128, radii: &[u16]) -> Result<Vec<u16>, NumberError> {
+    let mut x = x;
+    let mut ds = Vec::with_capacity(radii.len());
+    for &m in radii.iter() {
+        if m < 2 {
+            return Err(NumberError::InvalidModulus(m as u128));
+        }
+        ds.push((x % m as u128) as u16);
+        x /= m as u128;
+    }
+    if x != 0 {
+        return Err(NumberError::Overflow);
+    }
+    Ok(ds)
+}
+
+/// Convert little-endian base `q` digits into `u128`. Use `try_from_base_q` for untrusted
+/// input.
+pub fn from_base_q(ds: &[u16], q: u16) -> u128 {
+    validate::digits(ds, q);
+    let mut x = 0u128;
+    for &d in ds.iter().rev() {
+        let (xp, overflow) = x.overflowing_mul(q.into());
+        debug_assert_eq!(overflow, false, "overflow!!!! x={}", x);
+        x = xp + d as u128;
+    }
+    x
+}
+
+/// Convert little-endian base `q` digits into `u128`, returning an error if a digit is not
+/// below `q`, or if the value does not fit.
+pub fn try_from_base_q(ds: &[u16], q: u16) -> Result<u128, NumberError> {
+    let ms = core::iter::repeat(q).take(ds.len()).collect_vec();
+    try_from_mixed_radix(ds, &ms)
+}
+
+/// Convert little-endian base `q` digits into a `U256`, for values too large for a `u128`.
+/// Returns an error as `try_from_base_q` does, if the value does not fit in 256 bits.
+pub fn try_from_base_q_u256(ds: &[u16], q: u16) -> Result<U256, NumberError> {
+    let ms = core::iter::repeat(q).take(ds.len()).collect_vec();
+    try_from_mixed_radix_u256(ds, &ms)
+}
+
+/// Convert little-endian base `q` digits into a `U256`.
+///
+/// # Panics
+///
+/// Panics if a digit is not below `q`, or the value does not fit in 256 bits.
+pub fn from_base_q_u256(ds: &[u16], q: u16) -> U256 {
+    try_from_base_q_u256(ds, q).expect("invalid base q digits")
+}
+
+/// Convert little-endian mixed radix digits into u128. Use `try_from_mixed_radix` for
+/// untrusted input.
+pub fn from_mixed_radix(digits: &[u16], radii: &[u16]) -> u128 {
+    validate::mixed_radix(digits, radii);
+    let mut x: u128 = 0;
+    for (&d, &q) in digits.iter().zip(radii.iter()).rev() {
+        let (xp, overflow) = x.overflowing_mul(q as u128);
+        debug_assert_eq!(overflow, false, "overflow!!!! x={}", x);
+        x = xp + d as u128;
+    }
+    x
+}
+
+/// Convert little-endian mixed radix digits into u128, returning an error if there is not
+/// one digit per radix, a radix is below 2, a digit is not below its radix, or the value
+/// does not fit.
+pub fn try_from_mixed_radix(digits: &[u16], radii: &[u16]) -> Result<u128, NumberError> {
+    check_digits(digits, radii)?;
+    digits
+        .iter()
+        .zip(radii.iter())
+        .rev()
+        .try_fold(0u128, |x, (&d, &q)| {
+            x.checked_mul(q as u128)
+                .and_then(|x| x.checked_add(d as u128))
+        })
+        .ok_or(NumberError::Overflow)
+}
+
+/// Convert little-endian mixed radix digits into a `U256`, for values too large for a
+/// `u128`. Returns an error as `try_from_mixed_radix` does, if the value does not fit in
+/// 256 bits.
+pub fn try_from_mixed_radix_u256(digits: &[u16], radii: &[u16]) -> Result<U256, NumberError> {
+    check_digits(digits, radii)?;
+    digits
+        .iter()
+        .zip(radii.iter())
+        .rev()
+        .try_fold(U256::ZERO, |x, (&d, &q)| {
+            x.checked_mul(U256::from(q as u128))
+                .and_then(|x| x.checked_add(U256::from(d as u128)))
+        })
+        .ok_or(NumberError::Overflow)
+}
+
+/// Convert the little-endian base `q_from` digits `ds` into base `q_to`, whatever their
+/// length, without converting to an integer. The result has no leading zeros, so zero
+/// becomes the empty vector.
+///
+/// # Panics
+///
+/// Panics if `q_to` is less than 2.
+pub fn rebase(ds: &[u16], q_from: u16, q_to: u16) -> Vec<u16> {
+    validate::digits(ds, q_from);
+    let radii = vec![q_from; ds.len()];
+    mixed_radix_rebase(ds, &radii, q_to)
+}
+
+/// Convert the little-endian mixed radix digits `ds`, with respect to the `radii`, into
+/// base `q`, as `rebase` does.
+///
+/// # Panics
+///
+/// Panics if `q` is less than 2: base 1 would never terminate and base 0 divides by zero.
+pub fn mixed_radix_rebase(ds: &[u16], radii: &[u16], q: u16) -> Vec<u16> {
+    assert!(q >= 2, "invalid modulus {}", q);
+    validate::mixed_radix(ds, radii);
+    let q = q as u32;
+    let mut xs = ds.to_vec();
+    // The number of digits of `xs` below its leading zeros.
+    let mut n = xs.iter().rposition(|&d| d != 0).map_or(0, |i| i + 1);
+    let mut out = Vec::new();
+    while n > 0 {
+        // Divide `xs` by `q` in place, keeping its radii, from the most significant
+        // digit down. Each remainder is below `q`, so each quotient digit is below its
+        // radix.
+        let mut r = 0;
+        for (x, &m) in xs[..n].iter_mut().zip(radii.iter()).rev() {
+            let v = r * m as u32 + *x as u32;
+            *x = (v / q) as u16;
+            r = v % q;
+        }
+        out.push(r as u16);
+        while n > 0 && xs[n - 1] == 0 {
+            n -= 1;
+        }
+    }
+    out
+}
+
+/// Convert `x` into base `q` of length `n` as a signed number, i.e., into `x mod q^n`,
+/// which `from_base_q_signed` reads back for any `x` in the symmetric range
+/// `[-q^n/2, q^n/2)`. Digits beyond the `n`th are dropped, as in `as_base_q`.
+pub fn as_base_q_signed(x: i128, q: u16, n: usize) -> Vec<u16> {
+    let ds = as_base_q(x.unsigned_abs(), q, n);
+    if x < 0 {
+        base_q_sub(&vec![0; n], &ds, q)
+    } else {
+        ds
+    }
+}
+
+/// Convert little-endian base `q` digits into an `i128`, reading them the way two's
+/// complement reads bits: with `M = q^n`, values of at least `M - M/2` represent
+/// `x - M`.
+pub fn from_base_q_signed(ds: &[u16], q: u16) -> i128 {
+    let n = ds.len();
+    if n == 0 {
+        return 0;
+    }
+    // The digits of `M - M/2`.
+    let mut half = if q % 2 == 0 {
+        vec![0; n]
+    } else {
+        vec![(q - 1) / 2; n]
+    };
+    if q % 2 == 0 {
+        half[n - 1] = q / 2;
+    } else {
+        base_q_add_eq(&mut half, &[1], q);
+    }
+    if base_q_sub_borrow(ds, &half, q).1 {
+        from_base_q(ds, q) as i128
+    } else {
+        // `M - x`, which is at most `2^127` if the value fits.
+        let neg = from_base_q(&base_q_sub(&vec![0; n], ds, q), q);
+        0i128.wrapping_sub(neg as i128)
+    }
+}
+
+/// Convert the signed `x` into balanced base `q`, whose digits are in the range
+/// `[-(q-1)/2, (q-1)/2]`, building a vector of length `n`. Digits beyond the `n`th are
+/// dropped, as in `as_base_q`.
+///
+/// # Panics
+///
+/// Panics if `q` is even, as then the digits are not symmetric about zero.
+pub fn as_balanced_base_q(x: i128, q: u16, n: usize) -> Vec<i16> {
+    assert!(q % 2 == 1, "balanced base {} is not odd", q);
+    let h = (q as i128 - 1) / 2;
+    let q = q as i128;
+    let mut x = x;
+    (0..n)
+        .map(|_| {
+            let (mut d, mut y) = (x.rem_euclid(q), x.div_euclid(q));
+            if d > h {
+                d -= q;
+                y += 1;
+            }
+            x = y;
+            d as i16
+        })
+        .collect()
+}
+
+/// Convert little-endian balanced base `q` digits into an `i128`.
+pub fn from_balanced_base_q(ds: &[i16], q: u16) -> i128 {
+    debug_assert!(
+        ds.iter().all(|&d| d.unsigned_abs() <= q / 2),
+        "q={} ds={:?}",
+        q,
+        ds
+    );
+    ds.iter().rev().fold(0, |x, &d| x * q as i128 + d as i128)
+}
+
+/// Add the balanced base `q` slice `ys` into `xs`, wrapping around mod `q^n`, where `n` is
+/// the length of `xs`, as `base_q_add_eq` does.
+pub fn balanced_base_q_add_eq(xs: &mut [i16], ys: &[i16], q: u16) {
+    debug_assert!(
+        xs.len() >= ys.len(),
+        "xs.len()={} ys.len()={}",
+        xs.len(),
+        ys.len()
+    );
+    let h = (q as i32 - 1) / 2;
+    let mut c = 0;
+    for (i, x) in xs.iter_mut().enumerate() {
+        if i >= ys.len() && c == 0 {
+            break;
+        }
+        let mut v = *x as i32 + ys.get(i).cloned().unwrap_or(0) as i32 + c;
+        c = 0;
+        if v > h {
+            v -= q as i32;
+            c = 1;
+        } else if v < -h {
+            v += q as i32;
+            c = -1;
+        }
+        *x = v as i16;
+    }
+}
+
+/// Negate the balanced base `q` digits `xs`, which only negates each digit.
+pub fn balanced_base_q_neg(xs: &[i16]) -> Vec<i16> {
+    xs.iter().map(|&d| -d).collect()
+}
+
+/// Check that there is one digit per modulus, and that each digit is below its modulus.
+fn check_digits(digits: &[u16], moduli: &[u16]) -> Result<(), NumberError> {
+    if digits.len() != moduli.len() {
+        return Err(NumberError::DigitCount {
+            got: digits.len(),
+            needed: moduli.len(),
+        });
+    }
+    for (&digit, &modulus) in digits.iter().zip(moduli.iter()) {
+        if modulus < 2 {
+            return Err(NumberError::InvalidModulus(modulus as u128));
+        }
+        if digit >= modulus {
+            return Err(NumberError::InvalidDigit { digit, modulus });
+        }
+    }
+    Ok(())
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// bits
+
+/// Get the low `n` bits of a u128 as u16s, least significant first, which is convenient
+/// for the rest of the library, which uses u16 as the base digit type in Wire. Bits
+/// beyond the 128th are zero. `u128_from_bits` is the inverse.
+pub fn u128_to_bits(x: u128, n: usize) -> Vec<u16> {
+    (0..n)
+        .map(|i| if i < 128 { (x >> i) as u16 & 1 } else { 0 })
+        .collect()
+}
+
+/// Convert into a u128 from the "bits" as u16, least significant first, as produced by
+/// `u128_to_bits`. Assumes each "bit" is 0 or 1, and that any beyond the 128th are zero;
+/// use `u128_from_bits_checked` for untrusted input.
+pub fn u128_from_bits(bs: &[u16]) -> u128 {
+    validate::digits(bs, 2);
+    bs.iter()
+        .take(128)
+        .rev()
+        .fold(0, |x, &b| x << 1 | b as u128)
+}
+
+/// Convert into a u128 from the "bits" as u16, as `u128_from_bits` does, returning an
+/// error if a "bit" is not 0 or 1, or if a bit beyond the 128th is set.
+pub fn u128_from_bits_checked(bs: &[u16]) -> Result<u128, NumberError> {
+    if let Some(&digit) = bs.iter().find(|&&b| b > 1) {
+        return Err(NumberError::InvalidDigit { digit, modulus: 2 });
+    }
+    if bs.iter().skip(128).any(|&b| b != 0) {
+        return Err(NumberError::Overflow);
+    }
+    Ok(u128_from_bits(bs))
+}
+
+/// Get the bits of a byte slice, least significant bit of the first byte first,
+/// for use as the inputs of a `BinaryBundle`.
+pub fn bytes_to_bits(bytes: &[u8]) -> Vec<u16> {
+    bytes
+        .iter()
+        .flat_map(|&b| (0..8).map(move |i| ((b >> i) & 1) as u16))
+        .collect()
+}
+
+/// Convert "bits" as u16, e.g. the outputs of a `BinaryBundle`, into bytes. The
+/// inverse of `bytes_to_bits`; if the number of bits is not a multiple of 8, the
+/// last byte is padded with zeros. Assumes each "bit" is 0 or 1.
+pub fn bytes_from_bits(bs: &[u16]) -> Vec<u8> {
+    bs.chunks(8)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .enumerate()
+                .fold(0u8, |acc, (i, &b)| acc | ((b as u8) << i))
+        })
+        .collect()
+}
+
+/// Get the bits of a slice of u64s, 64 bits per value, least significant bit of
+/// the first value first.
+pub fn u64s_to_bits(xs: &[u64]) -> Vec<u16> {
+    xs.iter()
+        .flat_map(|&x| u128_to_bits(x as u128, 64))
+        .collect()
+}
+
+/// Convert "bits" as u16 into u64s, 64 bits per value. The inverse of
+/// `u64s_to_bits`; if the number of bits is not a multiple of 64, the last value
+/// is padded with zeros. Assumes each "bit" is 0 or 1.
+pub fn u64s_from_bits(bs: &[u16]) -> Vec<u64> {
+    bs.chunks(64).map(|chunk| u128_from_bits(chunk) as u64).collect()
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// primes & crt
+
+/// Factor using the primes in the global `PRIMES` array, followed by any others in the
+/// basis of the `config`. Fancy garbling only supports composites with small prime
+/// factors.
+///
+/// We are limited by the size of the digits in Wire, and besides, if need large moduli,
+/// you should use BundleGadgets and save.
+///
+/// # Panics
+///
+/// Panics if `inp` is not a product of distinct primes in `PRIMES` or the basis of the
+/// `config`. Use `try_factor` for untrusted input.
+pub fn factor(inp: u128) -> Vec<u16> {
+    try_factor(inp).expect("can only factor numbers with unique prime factors")
+}
+
+/// Factor as `factor` does, returning an error if `inp` is not a product of distinct
+/// primes in `PRIMES` or the basis of the `config`.
+pub fn try_factor(inp: u128) -> Result<Vec<u16>, NumberError> {
+    // Every prime below the largest of `PRIMES` is one of them, so the others
+    // are larger and the factors stay in increasing order.
+    let others = config::get().primes().primes();
+    let others = others.iter().filter(|&&p| p > PRIMES[NPRIMES - 1]);
+    factor_with(inp, PRIMES.iter().chain(others))
+}
+
+/// Factor `inp` into powers of the primes in `PRIMES` or the basis of the `config`,
+/// returning each prime with its exponent, in increasing order of the primes. Returns an
+/// error if `inp` has any other prime factor.
+pub fn try_factor_powers(inp: u128) -> Result<Vec<(u16, u32)>, NumberError> {
+    let others = config::get().primes().primes();
+    let others = others.iter().filter(|&&p| p > PRIMES[NPRIMES - 1]);
+    let mut x = inp;
+    let mut fs = Vec::new();
+    for &p in PRIMES.iter().chain(others) {
+        let q = p as u128;
+        let mut e = 0;
+        while x != 0 && x % q == 0 {
+            x /= q;
+            e += 1;
+        }
+        if e > 0 {
+            fs.push((p, e));
+        }
+    }
+    if x != 1 {
+        return Err(NumberError::NotFactorable(inp));
+    }
+    Ok(fs)
+}
+
+/// Factor `inp` into pairwise coprime prime powers, as `try_factor_powers` does, for use
+/// as CRT moduli, which `crt` and `crt_inv` accept just as they do primes. Returns an
+/// error if `inp` cannot be factored, or a prime power does not fit in a `u16`.
+///
+/// ```
+/// use fancy_garbling::util;
+///
+/// let ms = util::try_prime_power_factors(36 * 5).unwrap();
+/// assert_eq!(ms, vec![4, 9, 5]);
+/// assert_eq!(util::crt_inv(&util::crt(100, &ms), &ms), 100);
+/// ```
+pub fn try_prime_power_factors(inp: u128) -> Result<Vec<u16>, NumberError> {
+    try_factor_powers(inp)?
+        .into_iter()
+        .map(|(p, e)| p.checked_pow(e).ok_or(NumberError::Overflow))
+        .collect()
+}
+
+fn factor_with<'a>(
+    inp: u128,
+    primes: impl IntoIterator<Item = &'a u16>,
+) -> Result<Vec<u16>, NumberError> {
+    let mut x = inp;
+    let mut fs = Vec::new();
+    for &p in primes {
+        let q = p as u128;
+        if x % q == 0 {
+            fs.push(p);
+            x /= q;
+        }
+    }
+    if x != 1 {
+        return Err(NumberError::NotFactorable(inp));
+    }
+    Ok(fs)
+}
+
+/// Compute the CRT representation of x with respect to the primes ps.
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip(x)))]
+pub fn crt(x: u128, ps: &[u16]) -> Vec<u16> {
+    ps.iter()
+        .map(|&p| BarrettReducer::new(p).reduce_u128(x))
+        .collect()
+}
+
+/// Compute the CRT representation of x with respect to the primes ps, writing the
+/// residues into `xs`.
+///
+/// # Panics
+///
+/// Panics if `xs` and `ps` have different lengths.
+pub fn crt_into(x: u128, ps: &[u16], xs: &mut [u16]) {
+    assert_eq!(xs.len(), ps.len(), "residue and prime counts differ");
+    for (r, &p) in xs.iter_mut().zip(ps.iter()) {
+        *r = BarrettReducer::new(p).reduce_u128(x);
+    }
+}
+
+/// Compute the CRT representations of each of `xs` with respect to the primes `ps`, as
+/// `crt` does, precomputing the Barrett constants of each prime once for all of them.
+pub fn crt_batch(ps: &[u16], xs: &[u128]) -> Vec<Vec<u16>> {
+    let rs = ps.iter().map(|&p| BarrettReducer::new(p)).collect_vec();
+    xs.iter()
+        .map(|&x| rs.iter().map(|r| r.reduce_u128(x)).collect())
+        .collect()
+}
+
+/// Compute the CRT representations of `xs` as `crt_batch` does, transposed: the `i`th
+/// vector holds the residues of all of `xs` mod `ps[i]`. The loop over `xs` for a single
+/// prime has no branches, which suits vectorization.
+pub fn crt_batch_transposed(ps: &[u16], xs: &[u128]) -> Vec<Vec<u16>> {
+    ps.iter()
+        .map(|&p| {
+            let r = BarrettReducer::new(p);
+            xs.iter().map(|&x| r.reduce_u128(x)).collect()
+        })
+        .collect()
+}
+
+/// Compute the CRT representation of the big-endian integer `bytes`, of any length, with
+/// respect to the primes `ps`. This reduces mod each prime in a single pass over `bytes`,
+/// so no intermediate is ever wider than a `u32`.
+pub fn crt_from_bytes(ps: &[u16], bytes: &[u8]) -> Vec<u16> {
+    // `256 mod p` for each prime, for Horner's rule.
+    let shifts = ps.iter().map(|&p| 256 % p as u32).collect_vec();
+    let mut xs = vec![0u32; ps.len()];
+    for &b in bytes.iter() {
+        for ((x, &s), &p) in xs.iter_mut().zip(shifts.iter()).zip(ps.iter()) {
+            *x = (*x * s + b as u32) % p as u32;
+        }
+    }
+    xs.into_iter().map(|x| x as u16).collect()
+}
+
+/// Compute the CRT representation of `x` with respect to the factorization of
+/// `q`.
+pub fn crt_factor(x: u128, q: u128) -> Vec<u16> {
+    crt(x, &factor(q))
+}
+
+/// Compute the value x given a list of CRT primes and residues.
+///
+/// The value is reconstructed with Garner's algorithm using only `u128` arithmetic, so
+/// any number of primes works, e.g., all of `PRIMES`, whose product overflows a `u128`,
+/// as long as the value itself fits. The moduli need only be pairwise coprime, so prime
+/// powers, such as those of `try_prime_power_factors`, work too.
+///
+/// # Panics
+///
+/// Panics if the value does not fit in a `u128`. Use `try_crt_inv` for untrusted input.
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "trace", skip(xs)))]
+pub fn crt_inv(xs: &[u16], ps: &[u16]) -> u128 {
+    crt_inv_garner(xs, ps).expect("CRT value does not fit in a u128")
+}
+
+/// Compute the value x given a list of CRT primes and residues, as `crt_inv` does,
+/// returning an error instead of panicking, or of computing garbage, on bad input: if
+/// there is not one residue per modulus, a residue is not below its modulus, the moduli
+/// are not pairwise coprime, or the value does not fit in a `u128`.
+pub fn try_crt_inv(xs: &[u16], ps: &[u16]) -> Result<u128, NumberError> {
+    check_digits(xs, ps)?;
+    check_coprime(ps)?;
+    crt_inv_garner(xs, ps).ok_or(NumberError::Overflow)
+}
+
+/// Compute the CRT representation of the 256-bit `x` with respect to the primes `ps`.
+pub fn crt_u256(x: U256, ps: &[u16]) -> Vec<u16> {
+    ps.iter().map(|&p| x.rem_u16(p)).collect()
+}
+
+/// Compute the value x given a list of CRT primes and residues, for values too large for
+/// a `u128`, returning an error as `try_crt_inv` does, if the value does not fit in 256
+/// bits.
+pub fn try_crt_inv_u256(xs: &[u16], ps: &[u16]) -> Result<U256, NumberError> {
+    check_digits(xs, ps)?;
+    check_coprime(ps)?;
+    try_from_mixed_radix_u256(&garner_digits(xs, ps), ps)
+}
+
+/// A value of CRT residues, held in a `u128` when it fits and in a `U256` otherwise, so
+/// that callers need not know how many primes the representation has.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CrtValue {
+    /// A value that fits in a `u128`.
+    Small(u128),
+    /// A value that does not fit in a `u128`.
+    Wide(U256),
+}
+
+impl CrtValue {
+    /// Compute the value with CRT residues `xs` with respect to the primes `ps`,
+    /// returning an error as `try_crt_inv_u256` does.
+    pub fn from_crt(xs: &[u16], ps: &[u16]) -> Result<Self, NumberError> {
+        try_crt_inv_u256(xs, ps).map(CrtValue::from)
+    }
+
+    /// Compute the CRT representation of the value with respect to the primes `ps`.
+    pub fn crt(&self, ps: &[u16]) -> Vec<u16> {
+        match *self {
+            CrtValue::Small(x) => crt(x, ps),
+            CrtValue::Wide(x) => crt_u256(x, ps),
+        }
+    }
+
+    /// The value, if it fits in a `u128`.
+    pub fn to_u128(&self) -> Option<u128> {
+        match *self {
+            CrtValue::Small(x) => Some(x),
+            CrtValue::Wide(_) => None,
+        }
+    }
+
+    /// The value as a `U256`.
+    pub fn to_u256(&self) -> U256 {
+        match *self {
+            CrtValue::Small(x) => U256::from(x),
+            CrtValue::Wide(x) => x,
+        }
+    }
+}
+
+impl From<u128> for CrtValue {
+    fn from(x: u128) -> Self {
+        CrtValue::Small(x)
+    }
+}
+
+impl From<U256> for CrtValue {
+    fn from(x: U256) -> Self {
+        match x.to_u128() {
+            Some(x) => CrtValue::Small(x),
+            None => CrtValue::Wide(x),
+        }
+    }
+}
+
+// Check that the moduli are pairwise coprime.
+fn check_coprime(ps: &[u16]) -> Result<(), NumberError> {
+    for (i, &p) in ps.iter().enumerate() {
+        for &q in ps[i + 1..].iter() {
+            try_inv(q as i128, p as i128)?;
+        }
+    }
+    Ok(())
+}
+
+// Garner's algorithm: compute the value from the mixed radix digits returned by
+// `garner_digits`, returning `None` if it overflows. Every partial value of Horner's
+// rule is at most the value, so this overflows exactly when the value does not fit.
+fn crt_inv_garner(xs: &[u16], ps: &[u16]) -> Option<u128> {
+    garner_digits(xs, ps)
+        .iter()
+        .zip(ps.iter())
+        .rev()
+        .try_fold(0u128, |acc, (&d, &q)| {
+            acc.checked_mul(q as u128)?.checked_add(d as u128)
+        })
+}
+
+// Compute the mixed radix digits, with the primes as radii, of the value with CRT
+// residues `xs`.
+fn garner_digits(xs: &[u16], ps: &[u16]) -> Vec<u16> {
+    let mut ds = Vec::with_capacity(ps.len());
+    for (&x, &p) in xs.iter().zip(ps.iter()) {
+        let p_ = p as u64;
+        // The value of the digits so far, and the product of their radii, mod `p`.
+        let (t, m) = ds.iter().zip(ps.iter()).fold((0, 1), |(t, m), (&d, &q)| {
+            ((t + d as u64 * m) % p_, m * q as u64 % p_)
+        });
+        let c = inv(m as i128, p as i128) as u64;
+        ds.push(((x as u64 % p_ + p_ - t) * c % p_) as u16);
+    }
+    ds
+}
+
+/// Compute the value `x` given a composite CRT modulus provided by `xs`, returning an
+/// error if `q` cannot be factored, or the residues are invalid.
+pub fn try_crt_inv_factor(xs: &[u16], q: u128) -> Result<u128, NumberError> {
+    try_crt_inv(xs, &try_factor(q)?)
+}
+
+/// Compute the value x given a list of CRT primes and residues, using `U256` arithmetic.
+fn crt_inv_u256(xs: &[u16], ps: &[u16]) -> U256 {
+    try_from_mixed_radix_u256(&garner_digits(xs, ps), ps).expect("CRT value does not fit in a U256")
+}
+
+/// Compute the value `x` given a composite CRT modulus provided by `xs`.
+pub fn crt_inv_factor(xs: &[u16], q: u128) -> u128 {
+    crt_inv(xs, &factor(q))
+}
+
+/// Combine the congruences `x = xs[i] mod ms[i]`, whose moduli need not be coprime.
+/// Returns the least nonnegative solution together with the lcm of the moduli, or `None`
+/// if the congruences are inconsistent, a modulus is zero, or the lcm does not fit in a
+/// `u128`. The residues need not be reduced.
+///
+/// # Panics
+///
+/// Panics if `ms` and `xs` have different lengths.
+pub fn crt_combine_general(ms: &[u128], xs: &[u128]) -> Option<(u128, u128)> {
+    assert_eq!(ms.len(), xs.len(), "modulus and residue counts differ");
+    ms.iter()
+        .zip(xs.iter())
+        .try_fold((0, 1), |(x, m), (&n, &y)| {
+            if n == 0 {
+                return None;
+            }
+            // Find `t` with `x + m*t = y mod n`, which exists exactly when the gcd `g`
+            // divides `y - x`, and is unique mod `n/g`.
+            let g = gcd_u128(m, n);
+            let diff = sub_mod(y % n, x % n, n);
+            if diff % g != 0 {
+                return None;
+            }
+            let n_ = n / g;
+            let t = mul_mod(diff / g % n_, inv_u128((m / g) % n_, n_), n_);
+            let l = (m / g).checked_mul(n)?;
+            // `x < m` and `t < n/g`, so this is below `l`.
+            Some((x + m * t, l))
+        })
+}
+
+fn gcd_u128(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        let t = a % b;
+        a = b;
+        b = t;
+    }
+    a
+}
+
+// Compute `x - y mod n`, for `x, y < n`.
+fn sub_mod(x: u128, y: u128, n: u128) -> u128 {
+    if x >= y {
+        x - y
+    } else {
+        x + (n - y)
+    }
+}
+
+// Compute `x * y mod n` through a `U256` product.
+fn mul_mod(x: u128, y: u128, n: u128) -> u128 {
+    (U256::from(x) * U256::from(y))
+        .div_rem(U256::from(n))
+        .1
+        .lo()
+}
+
+// Invert `a` mod `n`, for coprime `a < n`, with the extended Euclidean algorithm keeping
+// its coefficients reduced mod `n`, so that any `u128` modulus works.
+fn inv_u128(a: u128, n: u128) -> u128 {
+    // `r0 = t0 * a` and `r1 = t1 * a` mod `n`.
+    let (mut r0, mut r1) = (n, a);
+    let (mut t0, mut t1) = (0, 1 % n);
+    while r1 != 0 {
+        let q = r0 / r1;
+        let r = r0 - q * r1;
+        r0 = r1;
+        r1 = r;
+        let t = sub_mod(t0, mul_mod(q % n, t1, n), n);
+        t0 = t1;
+        t1 = t;
+    }
+    t0
+}
+
+/// Compute the dot product of the vectors `xs` and `ys`, whose elements are CRT residues
+/// with respect to the primes `ps`, as CRT residues. Unlike converting to `u128`, this
+/// never overflows, however long the vectors are.
+///
+/// # Panics
+///
+/// Panics if the vectors have different lengths, or an element does not have one
+/// residue per prime.
+pub fn crt_dot(xs: &[Vec<u16>], ys: &[Vec<u16>], ps: &[u16]) -> Vec<u16> {
+    assert_eq!(xs.len(), ys.len(), "vector lengths differ");
+    let mut acc = vec![0u64; ps.len()];
+    for (x, y) in xs.iter().zip(ys.iter()) {
+        assert!(
+            x.len() == ps.len() && y.len() == ps.len(),
+            "residue counts differ"
+        );
+        for (k, &p) in ps.iter().enumerate() {
+            acc[k] = (acc[k] + x[k] as u64 * y[k] as u64) % p as u64;
+        }
+    }
+    acc.into_iter().map(|x| x as u16).collect()
+}
+
+/// Compute the CRT representation of the signed `x` with respect to the primes `ps`,
+/// such that `crt_inv_signed` recovers any `x` in the symmetric range `[-M/2, M/2)`,
+/// where `M` is the product of `ps`.
+pub fn crt_signed(x: i128, ps: &[u16]) -> Vec<u16> {
+    ps.iter().map(|&p| x.rem_euclid(p as i128) as u16).collect()
+}
+
+/// Compute the value in the symmetric range `[-M/2, M/2)` given a list of CRT primes and
+/// residues, where `M` is the product of the primes.
+///
+/// # Panics
+///
+/// Panics if the value does not fit in an `i128`, which can only happen if `M` does not
+/// fit in a `u128`.
+pub fn crt_inv_signed(xs: &[u16], ps: &[u16]) -> i128 {
+    // Values of at least `M - M/2` represent negative numbers.
+    if let Some(M) = ps
+        .iter()
+        .try_fold(1u128, |acc, &p| acc.checked_mul(p as u128))
+    {
+        let x = crt_inv(xs, ps);
+        return if x >= M - M / 2 {
+            -((M - x) as i128)
+        } else {
+            x as i128
+        };
+    }
+    let M = ps
+        .iter()
+        .fold(U256::from(1), |acc, &p| acc * U256::from(p as u128));
+    let x = crt_inv_u256(xs, ps);
+    let fits = |x: U256| x.to_u128().filter(|&x| x <= 1 << 127);
+    if x >= M - M.div_rem(U256::from(2)).0 {
+        // `M - x` may be exactly `2^127`, whose negation is `i128::MIN`.
+        let x = fits(M - x).expect("CRT value does not fit in an i128");
+        0i128.wrapping_sub(x as i128)
+    } else {
+        fits(x)
+            .filter(|&x| x < 1 << 127)
+            .expect("CRT value does not fit in an i128") as i128
+    }
+}
+
+/// Estimate the sign of the CRT value `xs` with respect to the primes `ps`, as the gadget
+/// `CrtGadgets::crt_sign` does using the fractional mixed radix trick, with the mixed
+/// radix moduli `precision` in place of those it chooses for an accuracy.
+///
+/// With `X` the value and `Q` the product of `ps`, this sums an approximation of each
+/// residue's share of the fraction `X/Q` as a multiple of `1/M`, where `M` is the product
+/// of `precision`, and reports a negative value if the most significant digit `d` of the
+/// sum, whose radix is the last of `precision`, `p`, satisfies `d >= p/2`. Returns the
+/// sign, `true` if negative, and a bound on the error of the estimated fraction. The sign
+/// is correct whenever `X/Q` is farther than that bound from both `0` and the threshold
+/// `(p/2)/p`.
+///
+/// # Panics
+///
+/// Panics if `xs` and `ps` have different lengths, or `precision` is empty.
+pub fn crt_fractional_sign(ps: &[u16], xs: &[u16], precision: &[u16]) -> (bool, f64) {
+    assert_eq!(xs.len(), ps.len(), "residue and prime counts differ");
+    let M = product(precision);
+    let mut sum = 0;
+    for (i, (&p, &x)) in ps.iter().zip(xs.iter()).enumerate() {
+        // The CRT coefficient of `x` is the inverse of the other primes mod `p`.
+        let others = ps
+            .iter()
+            .enumerate()
+            .filter(|&(j, _)| j != i)
+            .fold(1, |acc, (_, &q)| acc * q as u128 % p as u128);
+        let c = inv(others as i128, p as i128);
+        // This rounding, of at most `1/2M`, is the only error.
+        let y = (M as f64 * x as f64 * c as f64 / p as f64).round() as u128 % M;
+        sum = (sum + y) % M;
+    }
+    let p = *precision.last().expect("precision is empty");
+    let d = *as_mixed_radix(sum, precision).last().unwrap();
+    (d >= p / 2, ps.len() as f64 / (2.0 * M as f64))
+}
+
+/// Precomputed constants for converting to and from the CRT representation with respect
+/// to fixed primes, for callers converting many values, such as evaluators decoding every
+/// output bundle.
+///
+/// The coefficients of Garner's algorithm are precomputed too, for converting residues
+/// to mixed radix form, which unlike the value itself can be compared digit by digit.
+///
+/// ```ignore
+/// let ctx = CrtContext::new(&PRIMES[..10])?;
+/// assert_eq!(ctx.decode(&ctx.encode(1234)), 1234);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CrtContext {
+    ps: Vec<u16>,
+    // The product of `ps`.
+    M: u128,
+    // `cofactors[i]` is `M / ps[i]`.
+    cofactors: Vec<u128>,
+    // `inverses[i]` is the inverse of `cofactors[i]` mod `ps[i]`.
+    inverses: Vec<u16>,
+    // `garner[i]` is the inverse of the product of `ps[..i]` mod `ps[i]`.
+    garner: Vec<u16>,
+    reducers: Vec<BarrettReducer>,
+}
+
+impl CrtContext {
+    /// Precompute the constants for the primes `ps`, returning an error if their product
+    /// overflows a `u128` or they are not coprime.
+    pub fn new(ps: &[u16]) -> Result<Self, NumberError> {
+        if let Some(&p) = ps.iter().find(|&&p| p < 2) {
+            return Err(NumberError::InvalidModulus(p as u128));
+        }
+        let M = ps
+            .iter()
+            .try_fold(1u128, |acc, &p| acc.checked_mul(p as u128))
+            .ok_or(NumberError::Overflow)?;
+        let cofactors = ps.iter().map(|&p| M / p as u128).collect::<Vec<u128>>();
+        let inverses = ps
+            .iter()
+            .zip(cofactors.iter())
+            .map(|(&p, &q)| try_inv((q % p as u128) as i128, p as i128).map(|x| x as u16))
+            .collect::<Result<Vec<u16>, NumberError>>()?;
+        // The primes are coprime, so each prefix product is invertible.
+        let garner = ps
+            .iter()
+            .enumerate()
+            .map(|(i, &p)| {
+                let prefix = ps[..i]
+                    .iter()
+                    .fold(1, |acc, &q| acc * q as u128 % p as u128);
+                inv(prefix as i128, p as i128) as u16
+            })
+            .collect();
+        Ok(CrtContext {
+            ps: ps.to_vec(),
+            M,
+            cofactors,
+            inverses,
+            garner,
+            reducers: ps.iter().map(|&p| BarrettReducer::new(p)).collect(),
+        })
+    }
+
+    /// The primes.
+    pub fn primes(&self) -> &[u16] {
+        &self.ps
+    }
+
+    /// The product of the primes.
+    pub fn modulus(&self) -> u128 {
+        self.M
+    }
+
+    /// Compute the CRT representation of `x`, reduced mod the product of the primes.
+    pub fn encode(&self, x: u128) -> Vec<u16> {
+        self.reducers.iter().map(|r| r.reduce_u128(x)).collect()
+    }
+
+    /// Compute the value below the product of the primes with CRT residues `xs`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of residues differs from the number of primes.
+    pub fn decode(&self, xs: &[u16]) -> u128 {
+        assert_eq!(xs.len(), self.ps.len(), "residue counts differ");
+        let mut ret = 0u128;
+        let constants = self.inverses.iter().zip(self.cofactors.iter());
+        for ((&x, &p), (&inv, &q)) in xs.iter().zip(self.ps.iter()).zip(constants) {
+            let p = p as u32;
+            let c = (x as u32 % p * inv as u32 % p) as u128;
+            // Both terms are below `M`, but their sum may overflow.
+            let (sum, overflow) = ret.overflowing_add(c * q);
+            ret = if overflow || sum >= self.M {
+                sum.wrapping_sub(self.M)
+            } else {
+                sum
+            };
+        }
+        ret
+    }
+
+    /// Compute the mixed radix digits, with the primes as radii, of the value with CRT
+    /// residues `xs`, using Garner's algorithm.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of residues differs from the number of primes.
+    pub fn decode_mixed_radix(&self, xs: &[u16]) -> Vec<u16> {
+        assert_eq!(xs.len(), self.ps.len(), "residue counts differ");
+        let mut ds: Vec<u16> = Vec::with_capacity(xs.len());
+        for (i, (&x, r)) in xs.iter().zip(self.reducers.iter()).enumerate() {
+            let p = self.ps[i] as u32;
+            // The value of the digits so far, mod `p`, by Horner's rule.
+            let t = (0..i).rev().fold(0, |t, j| {
+                r.reduce(t as u32 * self.ps[j] as u32 + ds[j] as u32)
+            });
+            let x = r.reduce(x as u32);
+            let d = r.reduce(x as u32 + p - t as u32);
+            ds.push(r.reduce(d as u32 * self.garner[i] as u32));
+        }
+        ds
+    }
+}
+
+/// Extended Euclidean algorithm. Returns `(g, x, y)` such that `g` is the nonnegative
+/// greatest common divisor of `a` and `b`, and `a*x + b*y = g`.
+pub fn egcd(a: i128, b: i128) -> (i128, i128, i128) {
+    let (mut r0, mut r1) = (a, b);
+    let (mut x0, mut x1) = (1, 0);
+    let (mut y0, mut y1) = (0, 1);
+    while r1 != 0 {
+        let q = r0 / r1;
+        let r = r0 - q * r1;
+        r0 = r1;
+        r1 = r;
+        let x = x0 - q * x1;
+        x0 = x1;
+        x1 = x;
+        let y = y0 - q * y1;
+        y0 = y1;
+        y1 = y;
+    }
+    if r0 < 0 {
+        (-r0, -x0, -y0)
+    } else {
+        (r0, x0, y0)
+    }
+}
+
+/// Invert inp_a mod inp_b, using `egcd`. The result is only meaningful if they are
+/// coprime; use `try_inv` otherwise. This branches on its inputs, so use `inv_ct` for
+/// secret values.
+pub fn inv(inp_a: i128, inp_b: i128) -> i128 {
+    if inp_b == 1 {
+        return 1;
+    }
+    let (_, x, _) = egcd(inp_a, inp_b);
+    x.rem_euclid(inp_b)
+}
+
+/// Invert `a` mod `b`, returning an error if `b` is below 2 or `a` and `b` are not coprime,
+/// in which case `inv` silently returns a wrong result.
+pub fn try_inv(a: i128, b: i128) -> Result<i128, NumberError> {
+    let err = NumberError::NotInvertible { x: a, modulus: b };
+    if b < 2 {
+        return Err(err);
+    }
+    let (g, x, _) = egcd(a.rem_euclid(b), b);
+    if g != 1 {
+        return Err(err);
+    }
+    Ok(x.rem_euclid(b))
+}
+
+/// Invert each of `xs` mod `p` using Montgomery's trick, which takes a single
+/// inversion and `3(n - 1)` multiplications rather than `n` inversions. Returns an error
+/// naming the first of `xs` that is not invertible, or if `p` is below 2.
+pub fn batch_inv(xs: &[u16], p: u16) -> Result<Vec<u16>, NumberError> {
+    if p < 2 {
+        return Err(NumberError::InvalidModulus(p as u128));
+    }
+    let p_ = p as u32;
+    let mul = |x: u16, y: u16| (x as u32 * y as u32 % p_) as u16;
+    // `prefix[i]` is the product of `xs[..=i]`.
+    let mut prefix = Vec::with_capacity(xs.len());
+    let mut acc = 1 % p;
+    for &x in xs.iter() {
+        acc = mul(acc, x % p);
+        prefix.push(acc);
+    }
+    let mut inv = match prefix.last() {
+        None => return Ok(Vec::new()),
+        Some(&y) => match try_inv(y as i128, p as i128) {
+            Ok(inv) => inv as u16,
+            Err(_) => {
+                let x = xs
+                    .iter()
+                    .find(|&&x| try_inv(x as i128, p as i128).is_err())
+                    .expect("some element is not invertible");
+                return Err(NumberError::NotInvertible {
+                    x: *x as i128,
+                    modulus: p as i128,
+                });
+            }
+        },
+    };
+    // Walk back, with `inv` the inverse of `prefix[i]`.
+    let mut out = vec![0; xs.len()];
+    for i in (1..xs.len()).rev() {
+        out[i] = mul(inv, prefix[i - 1]);
+        inv = mul(inv, xs[i] % p);
+    }
+    out[0] = inv;
+    Ok(out)
+}
+
+/// Number of primes supported by our library.
+pub const NPRIMES: usize = 29;
+
+/// Primes used in fancy garbling.
+pub const PRIMES: [u16; 29] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109,
+];
+
+/// Test whether `n` is prime, using Miller-Rabin with the first twelve primes as bases,
+/// which is deterministic for every `u64`.
+pub fn is_prime(n: u64) -> bool {
+    const BASES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+    if n < 2 {
+        return false;
+    }
+    for &p in BASES.iter() {
+        if n % p == 0 {
+            return n == p;
+        }
+    }
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+    'bases: for &a in BASES.iter() {
+        let mut x = powm_u64(a, d, n);
+        if x == 1 || x == n - 1 {
+            continue;
+        }
+        for _ in 1..s {
+            x = (x as u128 * x as u128 % n as u128) as u64;
+            if x == n - 1 {
+                continue 'bases;
+            }
+        }
+        return false;
+    }
+    true
+}
+
+/// The least prime above `p`, or `None` if it does not fit in a `u16`.
+pub fn next_prime(p: u16) -> Option<u16> {
+    (p.checked_add(1)?..=u16::max_value()).find(|&q| is_prime(q as u64))
+}
+
+/// The primes up to and including `n`, in increasing order.
+pub fn primes_up_to(n: u16) -> Vec<u16> {
+    (2..=n).filter(|&q| is_prime(q as u64)).collect()
+}
+
+/// Primes skipping the modulus 2, which allows certain gadgets.
+// pub const PRIMES_SKIP_2: [u16; 29] = [
+//     3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+//     101, 103, 107, 109, 113,
+// ];
+
+/// A set of distinct primes to use as the CRT basis in place of `PRIMES`, which is the
+/// default. Its methods mirror the functions here that use `PRIMES`.
+///
+/// ```
+/// use fancy_garbling::util::PrimeSet;
+///
+/// let ps = PrimeSet::new(&[7, 3, 5]).unwrap();
+/// assert_eq!(ps.factor(35), vec![5, 7]);
+/// assert_eq!(ps.crt_inv(&ps.crt(12, 105), 105), 12);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PrimeSet {
+    ps: Vec<u16>,
+}
+
+impl PrimeSet {
+    /// Create the set of the primes `ps`, in increasing order. Returns an error if one of
+    /// them is not prime or is repeated.
+    pub fn new(ps: &[u16]) -> Result<Self, NumberError> {
+        let mut ps = ps.to_vec();
+        ps.sort_unstable();
+        for (i, &p) in ps.iter().enumerate() {
+            if !Modulus::new(p).is_ok_and(|m| m.is_prime()) || (i > 0 && ps[i - 1] == p) {
+                return Err(NumberError::InvalidModulus(p as u128));
+            }
+        }
+        Ok(PrimeSet { ps })
+    }
+
+    /// The set of `PRIMES` without 2, for gadgets that need odd moduli.
+    pub fn skip_2() -> Self {
+        PrimeSet {
+            ps: PRIMES[1..].to_vec(),
+        }
+    }
+
+    /// The primes, in increasing order.
+    pub fn primes(&self) -> &[u16] {
+        &self.ps
+    }
+
+    /// The number of primes.
+    pub fn len(&self) -> usize {
+        self.ps.len()
+    }
+
+    /// Whether the set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.ps.is_empty()
+    }
+
+    /// Factor `q` as in `factor`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `q` is not a product of distinct primes in the set.
+    pub fn factor(&self, q: u128) -> Vec<u16> {
+        self.try_factor(q)
+            .expect("can only factor numbers with unique prime factors")
+    }
+
+    /// Factor `q` as in `try_factor`, returning an error if it is not a product of
+    /// distinct primes in the set.
+    pub fn try_factor(&self, q: u128) -> Result<Vec<u16>, NumberError> {
+        factor_with(q, &self.ps)
+    }
+
+    /// Compute the CRT representation of `x` with respect to the factorization of `q`,
+    /// as in `crt_factor`.
+    pub fn crt(&self, x: u128, q: u128) -> Vec<u16> {
+        crt(x, &self.factor(q))
+    }
+
+    /// Compute the value `x` given its CRT representation `xs` with respect to the
+    /// factorization of `q`, as in `crt_inv_factor`.
+    pub fn crt_inv(&self, xs: &[u16], q: u128) -> u128 {
+        crt_inv(xs, &self.factor(q))
+    }
+
+    /// Generate a CRT modulus with the `n` smallest primes, as in `modulus_with_nprimes`.
+    pub fn modulus_with_nprimes(&self, n: usize) -> u128 {
+        product(&self.ps[..n])
+    }
+
+    /// Generate a CRT modulus that supports at least `n`-bit integers, as in
+    /// `modulus_with_width`.
+    pub fn modulus_with_width(&self, n: u32) -> u128 {
+        base_modulus_with_width(n, &self.ps)
+    }
+
+    /// Generate the factors of a CRT modulus that supports at least `n`-bit integers, as
+    /// in `primes_with_width`.
+    pub fn primes_with_width(&self, n: u32) -> Vec<u16> {
+        base_primes_with_width(n, &self.ps)
+    }
+}
+
+impl Default for PrimeSet {
+    fn default() -> Self {
+        PrimeSet {
+            ps: PRIMES.to_vec(),
+        }
+    }
+}
+
+/// Generate a CRT modulus with n primes.
+pub fn modulus_with_nprimes(n: usize) -> u128 {
+    product(&PRIMES[0..n])
+}
+
+/// Generate a CRT modulus that support at least n-bit integers, using the primes and
+/// width policy of the `config`, by default the smallest of the built-in PRIMES.
+pub fn modulus_with_width(n: u32) -> u128 {
+    product(&primes_with_width(n))
+}
+
+/// Generate the factors of a CRT modulus that support at least n-bit integers, using the
+/// primes and width policy of the `config`, by default the smallest of the built-in
+/// PRIMES. If the primes of the `config` run out, this continues with the primes above
+/// the largest of them, as in `try_extended_primes_with_width`.
+pub fn primes_with_width(n: u32) -> Vec<u16> {
+    let config = config::get();
+    config
+        .primes_with_width(n)
+        .or_else(|e| match e {
+            NumberError::NotEnoughPrimes(_) => {
+                try_extended_primes_with_width(n, config.primes().primes())
+            }
+            e => Err(e),
+        })
+        .expect("not enough primes!")
+}
+
+/// Generate a CRT modulus that support at least n-bit integers, using provided primes.
+pub fn base_modulus_with_width(nbits: u32, primes: &[u16]) -> u128 {
+    product(&base_primes_with_width(nbits, primes))
+}
+
+/// Generate the factors of a CRT modulus that support at least n-bit integers, using provided
+/// primes.
+///
+/// # Panics
+///
+/// Panics if there are not enough primes. Use `try_base_primes_with_width` for untrusted
+/// input.
+pub fn base_primes_with_width(nbits: u32, primes: &[u16]) -> Vec<u16> {
+    try_base_primes_with_width(nbits, primes).expect("not enough primes!")
+}
+
+/// Generate the factors of a CRT modulus that support at least n-bit integers, using provided
+/// primes, returning an error if there are not enough primes or their product overflows.
+pub fn try_base_primes_with_width(nbits: u32, primes: &[u16]) -> Result<Vec<u16>, NumberError> {
+    primes_with_width_from(nbits, primes.iter().cloned())
+}
+
+/// Generate the factors of a CRT modulus that support at least n-bit integers, using
+/// provided primes followed, once they are exhausted, by the primes above the largest of
+/// them. Returns an error if the primes of a `u16` run out or their product overflows.
+pub fn try_extended_primes_with_width(nbits: u32, primes: &[u16]) -> Result<Vec<u16>, NumberError> {
+    let largest = primes.iter().cloned().max().unwrap_or(1);
+    let more = std::iter::successors(next_prime(largest), |&p| next_prime(p));
+    primes_with_width_from(nbits, primes.iter().cloned().chain(more))
+}
+
+fn primes_with_width_from<I>(nbits: u32, primes: I) -> Result<Vec<u16>, NumberError>
+where
+    I: IntoIterator<Item = u16>,
+{
+    if nbits >= 128 {
+        return Err(NumberError::Overflow);
+    }
+    let mut res: u128 = 1;
+    let mut ps = Vec::new();
+    for p in primes {
+        res = res
+            .checked_mul(u128::from(p))
+            .ok_or(NumberError::Overflow)?;
+        ps.push(p);
+        if (res >> nbits) > 0 {
+            return Ok(ps);
+        }
+    }
+    Err(NumberError::NotEnoughPrimes(nbits))
+}
+
+/// A builder for the factors of a CRT modulus supporting at least `nbits`-bit
+/// integers, under constraints on which primes may be used.
+///
+/// ```ignore
+/// let ps = ModulusBuilder::new(64).exclude(&[2, 3]).max_factors(10).primes()?;
+/// ```
+///
+/// Among the runs of at most `max_factors` consecutive allowed primes, starting
+/// from the smallest, the first that is wide enough and satisfies every
+/// constraint is chosen, so without constraints this agrees with
+/// `base_primes_with_width`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ModulusBuilder {
+    nbits: u32,
+    primes: Vec<u16>,
+    exclude: Vec<u16>,
+    min_prime: u16,
+    max_factors: Option<usize>,
+    digit_budget: Option<usize>,
+}
+
+impl ModulusBuilder {
+    /// A builder for moduli supporting at least `nbits`-bit integers, using the
+    /// built-in PRIMES.
+    pub fn new(nbits: u32) -> Self {
+        ModulusBuilder {
+            nbits,
+            primes: PRIMES.to_vec(),
+            exclude: Vec::new(),
+            min_prime: 2,
+            max_factors: None,
+            digit_budget: None,
+        }
+    }
+
+    /// Choose from `primes` instead of the built-in PRIMES.
+    pub fn from_primes(mut self, primes: &[u16]) -> Self {
+        self.primes = primes.to_vec();
+        self
+    }
+
+    /// Never use any of `primes`.
+    pub fn exclude(mut self, primes: &[u16]) -> Self {
+        self.exclude.extend_from_slice(primes);
+        self
+    }
+
+    /// Only use primes of at least `p`.
+    pub fn min_prime(mut self, p: u16) -> Self {
+        self.min_prime = p;
+        self
+    }
+
+    /// Use at most `n` primes.
+    pub fn max_factors(mut self, n: usize) -> Self {
+        self.max_factors = Some(n);
+        self
+    }
+
+    /// Use primes summing to at most `n`, i.e., at most `n` residues in total.
+    /// This bounds the size of the projections on the resulting CRT bundles,
+    /// whose truth tables have one row per residue.
+    pub fn digit_budget(mut self, n: usize) -> Self {
+        self.digit_budget = Some(n);
+        self
+    }
+
+    /// Choose the factors of the modulus, returning an error explaining why
+    /// no choice satisfies the constraints.
+    pub fn primes(&self) -> Result<Vec<u16>, NumberError> {
+        if self.nbits >= 128 {
+            return Err(NumberError::Overflow);
+        }
+        let mut allowed = self
+            .primes
+            .iter()
+            .cloned()
+            .filter(|p| *p >= self.min_prime && !self.exclude.contains(p))
+            .collect_vec();
+        allowed.sort_unstable();
+        allowed.dedup();
+        if allowed.is_empty() {
+            return Err(NumberError::Unsatisfiable(
+                "every prime is excluded".to_string(),
+            ));
+        }
+
+        let k = self.max_factors.unwrap_or(allowed.len()).min(allowed.len());
+        if k == 0 {
+            return Err(NumberError::Unsatisfiable(
+                "at most zero factors are allowed".to_string(),
+            ));
+        }
+        // The most bits any run supports, and the fewest digits of any run
+        // that is wide enough but over budget.
+        let mut widest = 0;
+        let mut fewest_digits: Option<usize> = None;
+        let mut overflowed = false;
+        for start in 0..allowed.len() {
+            let run = &allowed[start..allowed.len().min(start + k)];
+            let mut res: u128 = 1;
+            for (i, &p) in run.iter().enumerate() {
+                res = match res.checked_mul(u128::from(p)) {
+                    Some(res) => res,
+                    None => {
+                        overflowed = true;
+                        break;
+                    }
+                };
+                if (res >> self.nbits) == 0 {
+                    continue;
+                }
+                let ndigits = run[..=i].iter().map(|&p| p as usize).sum::<usize>();
+                if !matches!(self.digit_budget, Some(budget) if ndigits > budget) {
+                    return Ok(run[..=i].to_vec());
+                }
+                fewest_digits = Some(fewest_digits.map_or(ndigits, |n| n.min(ndigits)));
+                break;
+            }
+            widest = widest.max(127 - res.leading_zeros());
+        }
+
+        let reason = match (fewest_digits, self.digit_budget) {
+            (Some(n), Some(budget)) => format!(
+                "{} bits need at least {} digits, over the budget of {}",
+                self.nbits, n, budget
+            ),
+            _ if overflowed => return Err(NumberError::Overflow),
+            _ => format!(
+                "{} of the allowed primes {:?} support only {} bits, not {}",
+                k, allowed, widest, self.nbits
+            ),
+        };
+        Err(NumberError::Unsatisfiable(reason))
+    }
+
+    /// Choose the modulus, the product of the factors chosen by `primes`.
+    pub fn modulus(&self) -> Result<u128, NumberError> {
+        self.primes().map(|ps| product(&ps))
+    }
+}
+
+/// Generate a CRT modulus that support at least n-bit integers, using the built-in
+/// PRIMES_SKIP_2 (does not include 2 as a factor).
+// pub fn modulus_with_width_skip2(nbits: u32) -> u128 {
+//     base_modulus_with_width(nbits, &PRIMES_SKIP_2)
+// }
+
+/// Compute the product of some u16s as a u128.
+pub fn product(xs: &[u16]) -> u128 {
+    xs.iter().fold(1, |acc, &x| acc * x as u128)
+}
+
+/// Raise a u16 to a power mod some value. The number of steps depends on the bit length
+/// of `pow`, so use `powm_ct` for secret exponents.
+///
+/// # Panics
+///
+/// Panics if `modulus` is zero.
+pub fn powm(inp: u16, pow: u16, modulus: u16) -> u16 {
+    let b = BarrettReducer::new(modulus);
+    let x = b.reduce(inp as u32) as u64;
+    ladder(x, pow as u64, b.reduce(1) as u64, |x, y| {
+        b.reduce((x * y) as u32) as u64
+    }) as u16
+}
+
+/// Raise a u64 to a power mod some value, with products computed in a u128 so that
+/// any modulus works.
+///
+/// # Panics
+///
+/// Panics if `modulus` is zero.
+pub fn powm_u64(inp: u64, pow: u64, modulus: u64) -> u64 {
+    let m = modulus as u128;
+    ladder(inp % modulus, pow, 1 % modulus, |x, y| {
+        (x as u128 * y as u128 % m) as u64
+    })
+}
+
+// Compute `x^e` with the Montgomery ladder, given the reduced `x`, the reduced one, and a
+// modular multiplication. The ladder does a multiplication and a squaring for every bit of
+// `e`, whatever its value.
+fn ladder<F: Fn(u64, u64) -> u64>(x: u64, e: u64, one: u64, mul: F) -> u64 {
+    let (mut r0, mut r1) = (one, x);
+    for i in (0..64 - e.leading_zeros()).rev() {
+        if (e >> i) & 1 == 0 {
+            r1 = mul(r0, r1);
+            r0 = mul(r0, r0);
+        } else {
+            r0 = mul(r0, r1);
+            r1 = mul(r1, r1);
+        }
+    }
+    r0
+}
+
+/// Raise a u16 to a power mod some value, in constant time: neither branches nor memory
+/// accesses depend on `inp` or `pow`, only on `modulus`.
+///
+/// # Panics
+///
+/// Panics if `modulus` is zero.
+pub fn powm_ct(inp: u16, pow: u16, modulus: u16) -> u16 {
+    let b = BarrettReducer::new(modulus);
+    let (mut r0, mut r1) = (b.reduce_ct(1), b.reduce_ct(inp as u32));
+    for i in (0..16).rev() {
+        let bit = (pow >> i) & 1;
+        cswap(&mut r0, &mut r1, bit);
+        r1 = b.reduce_ct(r0 as u32 * r1 as u32);
+        r0 = b.reduce_ct(r0 as u32 * r0 as u32);
+        cswap(&mut r0, &mut r1, bit);
+    }
+    r0
+}
+
+/// Invert `a` mod an odd `modulus` in constant time, with the binary extended GCD run for a
+/// fixed number of steps. Neither branches nor memory accesses depend on `a`, other than
+/// whether to return `None`, which it does if `a` is not invertible.
+///
+/// # Panics
+///
+/// Panics if `modulus` is even.
+pub fn inv_ct(a: u16, modulus: u16) -> Option<u16> {
+    assert_eq!(modulus % 2, 1, "modulus {} is not odd", modulus);
+    let m = modulus as u32;
+    // `x = u * a` and `y = v * a` mod `m` throughout, and `y` stays odd. Each step shortens
+    // `x` or `y` by a bit until `x` is zero and `y` is the gcd, so 32 steps suffice.
+    let (mut x, mut y) = (BarrettReducer::new(modulus).reduce_ct(a as u32) as u32, m);
+    let (mut u, mut v) = (1 % m, 0);
+    for _ in 0..32 {
+        let odd = 0u32.wrapping_sub(x & 1);
+        // All ones if `x` is odd and below `y`.
+        let swap = odd & 0u32.wrapping_sub(x.wrapping_sub(y) >> 31);
+        let t = (x ^ y) & swap;
+        x ^= t;
+        y ^= t;
+        let t = (u ^ v) & swap;
+        u ^= t;
+        v ^= t;
+        // If `x` is odd then `x >= y`, and `x - y` is even.
+        x -= y & odd;
+        let w = u + (m - v);
+        let w = w.wrapping_sub(m);
+        let w = w.wrapping_add(m & 0u32.wrapping_sub(w >> 31));
+        u ^= (u ^ w) & odd;
+        x >>= 1;
+        // Halve `u` mod `m`, adding `m` first if `u` is odd.
+        u = (u + (m & 0u32.wrapping_sub(u & 1))) >> 1;
+    }
+    if y == 1 {
+        Some(v as u16)
+    } else {
+        None
+    }
+}
+
+// Swap `x` and `y` if `bit` is one, without branching.
+#[inline]
+fn cswap(x: &mut u16, y: &mut u16, bit: u16) {
+    let t = (*x ^ *y) & 0u16.wrapping_sub(bit);
+    *x ^= t;
+    *y ^= t;
+}
+
+/// Compute the discrete log of `y` to the base `g` mod `modulus`: the least `x` with
+/// `g^x = y mod modulus`, or `None` if there is none, or if `g` is not coprime to
+/// `modulus`.
+///
+/// This uses Pohlig-Hellman over the prime factors of the order of `g`, with
+/// baby-step giant-step in each subgroup of prime order, so its cost is dominated by the
+/// square root of the largest of them, and by factoring `modulus` and the order by trial
+/// division.
+///
+/// # Panics
+///
+/// Panics if `modulus` is zero.
+pub fn dlog(g: u32, y: u32, modulus: u32) -> Option<u32> {
+    let m = modulus as u64;
+    let (g, y) = (g as u64 % m, y as u64 % m);
+    let pow = |x: u64, e: u64| powm_u64(x, e, m);
+    if egcd(g as i128, m as i128).0 != 1 {
+        return None;
+    }
+    // The order of the group, and then of `g`.
+    let phi = trial_factor(m)
+        .iter()
+        .fold(1, |phi, &(p, e)| phi * (p - 1) * p.pow(e - 1));
+    let mut n = phi;
+    for &(p, _) in trial_factor(phi).iter() {
+        while n % p == 0 && pow(g, n / p) == 1 {
+            n /= p;
+        }
+    }
+    // Find `x mod p^e` for each prime power dividing the order, a digit base `p` at a time.
+    let g_inv = pow(g, n - 1);
+    let mut ms = Vec::new();
+    let mut xs = Vec::new();
+    for &(p, e) in trial_factor(n).iter() {
+        let gamma = pow(g, n / p);
+        let (mut x, mut pk) = (0, 1);
+        for _ in 0..e {
+            let h = pow(pow(g_inv, x) * y % m, n / (pk * p));
+            x += bsgs(gamma, h, p, m)? * pk;
+            pk *= p;
+        }
+        ms.push(pk as u128);
+        xs.push(x as u128);
+    }
+    let (x, _) = crt_combine_general(&ms, &xs)?;
+    if pow(g, x as u64) == y {
+        Some(x as u32)
+    } else {
+        None
+    }
+}
+
+// Find `x < n` with `g^x = h mod m` by baby-step giant-step, where `g` has order `n`.
+fn bsgs(g: u64, h: u64, n: u64, m: u64) -> Option<u64> {
+    let k = (1..).find(|&k| k * k >= n).unwrap();
+    let mut table = std::collections::HashMap::with_capacity(k as usize);
+    let mut z = 1 % m;
+    for j in 0..k {
+        table.entry(z).or_insert(j);
+        z = z * g % m;
+    }
+    // `g^-k`.
+    let step = powm_u64(g, (n - k % n) % n, m);
+    let mut z = h;
+    for i in 0..k {
+        if let Some(&j) = table.get(&z) {
+            return Some(i * k + j);
+        }
+        z = z * step % m;
+    }
+    None
+}
+
+// Factor `n` by trial division into primes and their exponents, in increasing order.
+fn trial_factor(mut n: u64) -> Vec<(u64, u32)> {
+    let mut fs = Vec::new();
+    let mut p = 2;
+    while p * p <= n {
+        if n % p == 0 {
+            let mut e = 0;
+            while n % p == 0 {
+                n /= p;
+                e += 1;
+            }
+            fs.push((p, e));
+        }
+        p += 1;
+    }
+    if n > 1 {
+        fs.push((n, 1));
+    }
+    fs
+}
+
+/// Returns `true` if `x` is a power of 2.
+pub fn is_power_of_2(x: u16) -> bool {
+    (x & (x - 1)) == 0
+}
+
+/// Extra Rng functionality, useful for `fancy-garbling`.
+pub trait RngExt: rand::Rng + Sized {
+    /// Randomly generate a `bool`.
+    fn gen_bool(&mut self) -> bool {
+        self.gen()
+    }
+    /// Randomly generate a `u16`.
+    fn gen_u16(&mut self) -> u16 {
+        self.gen()
+    }
+    /// Randomly generate a `u32`.
+    fn gen_u32(&mut self) -> u32 {
+        self.gen()
+    }
+    /// Randomly generate a `u64`.
+    fn gen_u64(&mut self) -> u64 {
+        self.gen()
+    }
+    /// Randomly generate a `usize`.
+    fn gen_usize(&mut self) -> usize {
+        self.gen()
+    }
+    /// Randomly generate a `u128`.
     fn gen_u128(&mut self) -> u128 {
         self.gen()
     }
@@ -410,13 +2555,33 @@ pub trait RngExt: rand::Rng + Sized {
         let max = (modulus as u128).pow(n as u32);
         Block::from(self.gen_u128() % max)
     }
+    /// Randomly generate a value `mod q`, without modulo bias.
+    fn gen_mod(&mut self, q: u16) -> u16 {
+        self.gen_range(0, q)
+    }
+    /// Randomly generate `n` digits `mod q`.
+    fn gen_digits(&mut self, q: u16, n: usize) -> Vec<u16> {
+        (0..n).map(|_| self.gen_mod(q)).collect()
+    }
+    /// Randomly generate an element of `Z_{product(ps)}` in CRT representation
+    /// with respect to the moduli `ps`.
+    fn gen_crt(&mut self, ps: &[u16]) -> Vec<u16> {
+        ps.iter().map(|&p| self.gen_mod(p)).collect()
+    }
+    /// Randomly generate a wire-label `mod q`.
+    fn gen_wire_label(&mut self, q: u16) -> Wire
+    where
+        Self: rand::CryptoRng,
+    {
+        Wire::rand(self, q)
+    }
     /// Randomly generate a prime (among the set of supported primes).
     fn gen_prime(&mut self) -> u16 {
-        PRIMES[self.gen::<usize>() % NPRIMES]
+        PRIMES[self.gen_range(0, NPRIMES)]
     }
     /// Randomly generate a (supported) modulus.
     fn gen_modulus(&mut self) -> u16 {
-        2 + (self.gen::<u16>() % 111)
+        self.gen_range(2, 113)
     }
     /// Randomly generate a valid composite modulus.
     fn gen_usable_composite_modulus(&mut self) -> u128 {
@@ -451,7 +2616,7 @@ impl<R: rand::Rng + Sized> RngExt for R {}
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::util::RngExt;
+    use crate::{fancy::HasModulus, util::RngExt};
     use rand::thread_rng;
 
     #[test]
@@ -466,6 +2631,737 @@ mod tests {
         }
     }
 
+    #[test]
+    fn crt_conversion_wide() {
+        // The product of all the primes overflows a `u128`.
+        assert!(PRIMES
+            .iter()
+            .try_fold(1u128, |acc, &p| acc.checked_mul(p as u128))
+            .is_none());
+        let mut rng = thread_rng();
+        for _ in 0..128 {
+            let x = rng.gen_u128();
+            assert_eq!(crt_inv(&crt(x, &PRIMES), &PRIMES), x);
+        }
+        assert_eq!(crt_inv(&crt(0, &PRIMES), &PRIMES), 0);
+        assert_eq!(
+            crt_inv(&crt(u128::max_value(), &PRIMES), &PRIMES),
+            u128::max_value()
+        );
+    }
+
+    #[test]
+    fn crt_conversion_u256() {
+        let mut rng = thread_rng();
+        for _ in 0..128 {
+            let x = U256::new(rng.gen_u128() % (1 << 19), rng.gen_u128());
+            let xs = crt_u256(x, &PRIMES);
+            assert_eq!(try_crt_inv_u256(&xs, &PRIMES), Ok(x));
+            let v = CrtValue::from_crt(&xs, &PRIMES).unwrap();
+            assert_eq!(v, CrtValue::from(x));
+            assert_eq!(v.crt(&PRIMES), xs);
+            assert_eq!(v.to_u256(), x);
+        }
+        let v = CrtValue::from_crt(&crt(12345, &PRIMES), &PRIMES).unwrap();
+        assert_eq!(v, CrtValue::Small(12345));
+        assert_eq!(v.to_u128(), Some(12345));
+        assert_eq!(
+            CrtValue::from(U256::new(1, 0)),
+            CrtValue::Wide(U256::new(1, 0))
+        );
+        assert!(try_crt_inv_u256(&[1, 1], &[3, 6]).is_err());
+    }
+
+    #[test]
+    fn base_q_conversion_u256() {
+        let mut rng = thread_rng();
+        for _ in 0..64 {
+            let x = U256::new(rng.gen_u128(), rng.gen_u128());
+            let q = 2 + rng.gen_mod(1000);
+            let n = digits_needed(256, q);
+            let ds = as_base_q_u256(x, q, n);
+            assert_eq!(from_base_q_u256(&ds, q), x);
+            assert_eq!(try_from_base_q_u256(&ds, q), Ok(x));
+            let y = rng.gen_u128();
+            assert_eq!(as_base_q_u256(U256::from(y), q, n), as_base_q(y, q, n));
+            let radii = (0..10).map(|_| 2 + rng.gen_mod(100)).collect_vec();
+            assert_eq!(
+                as_mixed_radix_u256(U256::from(y), &radii),
+                as_mixed_radix(y, &radii)
+            );
+        }
+    }
+
+    #[test]
+    fn rebasing() {
+        let mut rng = thread_rng();
+        for _ in 0..64 {
+            let (q1, q2) = (2 + rng.gen_mod(1000), 2 + rng.gen_mod(1000));
+            let x = rng.gen_u64() as u128;
+            let n = digits_needed(64, q1);
+            let ys = rebase(&as_base_q(x, q1, n), q1, q2);
+            assert_ne!(ys.last(), Some(&0));
+            assert_eq!(from_base_q(&ys, q2), x);
+        }
+        // Values far too wide for a `u128` survive a round trip.
+        for &(q1, q2) in [(2, 3), (65535, 7), (10, 256)].iter() {
+            let ds = rng.gen_digits(q1, 300);
+            let mut back = rebase(&rebase(&ds, q1, q2), q2, q1);
+            back.resize(ds.len(), 0);
+            assert_eq!(back, ds);
+        }
+        let radii = [3, 5, 7, 11];
+        let ds = as_mixed_radix(1000, &radii);
+        assert_eq!(mixed_radix_rebase(&ds, &radii, 10), vec![0, 0, 0, 1]);
+        assert_eq!(rebase(&[0, 0], 3, 5), Vec::<u16>::new());
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid modulus 1")]
+    fn rebasing_to_base_one() {
+        rebase(&[1], 2, 1);
+    }
+
+    #[test]
+    fn signed_digits() {
+        let mut rng = thread_rng();
+        for &q in [3u16, 5, 7, 11, 255, 65535].iter() {
+            let n = 64 / (16 - (q - 1).leading_zeros() as usize);
+            let M = (q as i128).pow(n as u32);
+            for _ in 0..64 {
+                let x = (rng.gen_u128() % M as u128) as i128 - M / 2;
+                let ds = as_balanced_base_q(x, q, n);
+                assert!(ds.iter().all(|&d| d.abs() as u16 <= (q - 1) / 2));
+                assert_eq!(from_balanced_base_q(&ds, q), x);
+                assert_eq!(from_balanced_base_q(&balanced_base_q_neg(&ds), q), -x);
+                let y = (rng.gen_u128() % M as u128) as i128 - M / 2;
+                let mut zs = ds.clone();
+                balanced_base_q_add_eq(&mut zs, &as_balanced_base_q(y, q, n), q);
+                let z = (x + y + M / 2).rem_euclid(M) - M / 2;
+                assert_eq!(from_balanced_base_q(&zs, q), z);
+            }
+        }
+        for &q in [2, 3, 10, 256].iter() {
+            let n = 5;
+            let M = (q as i128).pow(n as u32);
+            for &x in [0, 1, -1, M / 2 - 1, -M / 2, 12, -12].iter() {
+                let ds = as_base_q_signed(x, q, n);
+                assert_eq!(from_base_q(&ds, q) as i128, x.rem_euclid(M));
+                assert_eq!(from_base_q_signed(&ds, q), x, "q={} x={}", q, x);
+            }
+        }
+        let ds = as_base_q_signed(i128::min_value(), 2, 128);
+        assert_eq!(from_base_q_signed(&ds, 2), i128::min_value());
+        assert_eq!(from_base_q_signed(&[], 7), 0);
+    }
+
+    #[test]
+    fn dot_products() {
+        let mut rng = thread_rng();
+        let ps = &PRIMES[..8];
+        let q = product(ps);
+        let xs = (0..1000).map(|_| rng.gen_u128() % q).collect::<Vec<u128>>();
+        let ys = (0..1000).map(|_| rng.gen_u128() % q).collect::<Vec<u128>>();
+        let want = xs
+            .iter()
+            .zip(ys.iter())
+            .fold(0, |acc, (&x, &y)| (acc + x * y % q) % q);
+        let crts = |vs: &[u128]| vs.iter().map(|&v| crt(v, ps)).collect::<Vec<_>>();
+        assert_eq!(crt_inv(&crt_dot(&crts(&xs), &crts(&ys), ps), ps), want);
+
+        // Base 7 with 21 digits, whose products overflow a `u64`.
+        let m = 7u128.pow(21);
+        let xs = (0..1000).map(|_| rng.gen_u128() % m).collect::<Vec<u128>>();
+        let ys = (0..1000).map(|_| rng.gen_u128() % m).collect::<Vec<u128>>();
+        let want = xs
+            .iter()
+            .zip(ys.iter())
+            .fold(0, |acc, (&x, &y)| (acc + x * y % m) % m);
+        let digits = |vs: &[u128]| vs.iter().map(|&v| as_base_q(v, 7, 21)).collect::<Vec<_>>();
+        let zs = base_q_dot(&digits(&xs), &digits(&ys), 7, 21);
+        assert_eq!(from_base_q(&zs, 7), want);
+        assert_eq!(base_q_dot(&[], &[], 7, 3), vec![0, 0, 0]);
+    }
+
+    #[test]
+    fn base_q_addition() {
+        let mut rng = thread_rng();
+        for &q in [2, 3, 10, 255, 256, 1 << 15, 65535].iter() {
+            for _ in 0..64 {
+                let n = rng.gen_u16() as usize % 40;
+                let m = rng.gen_u16() as usize % (n + 1);
+                // Many digits of `q - 1` make long carry chains.
+                let digit = |rng: &mut rand::rngs::ThreadRng| {
+                    if rng.gen_bool() {
+                        q - 1
+                    } else {
+                        rng.gen_mod(q)
+                    }
+                };
+                let xs = (0..n).map(|_| digit(&mut rng)).collect_vec();
+                let ys = (0..m).map(|_| digit(&mut rng)).collect_vec();
+                let mut expected = xs.clone();
+                let mut c = 0;
+                for (i, x) in expected.iter_mut().enumerate() {
+                    let v = *x as u32 + ys.get(i).cloned().unwrap_or(0) as u32 + c;
+                    c = (v >= q as u32) as u32;
+                    *x = (v - c * q as u32) as u16;
+                }
+                let mut zs = xs.clone();
+                base_q_add_eq(&mut zs, &ys, q);
+                assert_eq!(zs, expected, "q={} xs={:?} ys={:?}", q, xs, ys);
+            }
+        }
+    }
+
+    #[test]
+    fn mixed_radix_subtraction() {
+        let mut rng = thread_rng();
+        for _ in 0..128 {
+            let radii = (0..8).map(|_| 2 + rng.gen_mod(100)).collect_vec();
+            let M = radii.iter().map(|&m| m as u128).product::<u128>();
+            let x = rng.gen_u128() % M;
+            let y = rng.gen_u128() % M;
+            let (ds, underflow) = mixed_radix_sub(
+                &as_mixed_radix(x, &radii),
+                &as_mixed_radix(y, &radii),
+                &radii,
+            );
+            assert_eq!(underflow, x < y);
+            assert_eq!(from_mixed_radix(&ds, &radii), (x + M - y) % M);
+        }
+        assert_eq!(
+            mixed_radix_sub(&[1, 2], &[1, 2], &[3, 5]),
+            (vec![0, 0], false)
+        );
+        assert_eq!(
+            mixed_radix_sub(&[0, 0], &[1, 0], &[3, 5]),
+            (vec![2, 4], true)
+        );
+    }
+
+    #[test]
+    fn scaling_and_shifts() {
+        let mut rng = thread_rng();
+        for _ in 0..256 {
+            let q = 2 + rng.gen_mod(1000);
+            let c = rng.gen_u16();
+            let x = rng.gen_u64() as u128;
+            let n = digits_needed(64, q);
+            let mut xs = as_base_q(x, q, n);
+            let carry = base_q_scale_eq(&mut xs, c, q);
+            let qn = (q as u128).pow(n as u32);
+            assert_eq!(from_base_q(&xs, q), x * c as u128 % qn);
+            assert_eq!(carry as u128, x * c as u128 / qn);
+            let xs = as_base_q(x, q, n);
+            let k = rng.gen_u16() as usize % 4;
+            let qk = (q as u128).pow(k as u32);
+            assert_eq!(from_base_q(&shl_digits(&xs, k), q), x * qk);
+            assert_eq!(from_base_q(&shr_digits(&xs, k), q), x / qk);
+        }
+        for _ in 0..256 {
+            let q = 2 + rng.gen_mod(1000);
+            let n = digits_needed(64, q);
+            let (a, x, c) = (rng.gen_u64() as u128, rng.gen_u64() as u128, rng.gen_u16());
+            let mut acc = as_base_q(a, q, digits_needed(81, q));
+            let xs = as_base_q(x, q, n);
+            assert_eq!(base_q_mac_eq(&mut acc, &xs, c, q), 0);
+            assert_eq!(from_base_q(&acc, q), a + x * c as u128);
+            // Dropping the carry leaves the sum mod `q^n`.
+            let mut acc = as_base_q(a, q, n);
+            let carry = base_q_mac_eq(&mut acc, &xs, c, q);
+            let qn = (q as u128).pow(n as u32);
+            assert_eq!(from_base_q(&acc, q), (a + x * c as u128) % qn);
+            assert_eq!(carry as u128, (a + x * c as u128) / qn);
+        }
+        assert_eq!(shr_digits(&[1, 2], 3), Vec::<u16>::new());
+        assert_eq!(shl_digits(&[1, 2], 1), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn division() {
+        let mut rng = thread_rng();
+        for _ in 0..256 {
+            let q = 2 + rng.gen_mod(1000);
+            let x = rng.gen_u128();
+            let y = (rng.gen_u128() >> (rng.gen_u16() % 128)).max(1);
+            let n = digits_needed(128, q);
+            let m = digits_needed(128 - y.leading_zeros(), q);
+            let (qs, rs) = base_q_divmod(&as_base_q(x, q, n), &as_base_q(y, q, m), q);
+            assert_eq!(qs.len(), n);
+            assert_eq!(rs.len(), m);
+            assert_eq!(try_from_base_q(&qs, q), Ok(x / y));
+            assert_eq!(try_from_base_q(&rs, q), Ok(x % y));
+            let c = rng.gen_u16().max(1);
+            let (qs, r) = base_q_divmod_const(&as_base_q(x, q, n), c, q);
+            assert_eq!(try_from_base_q(&qs, q), Ok(x / c as u128));
+            assert_eq!(r as u128, x % c as u128);
+            let z = x / c as u128 * c as u128;
+            let qs = base_q_div_exact(&as_base_q(z, q, n), c, q);
+            assert_eq!(try_from_base_q(&qs, q), Ok(x / c as u128));
+        }
+        assert_eq!(base_q_divmod(&[1, 2], &[0, 1], 3), (vec![2, 0], vec![1, 0]));
+        assert_eq!(base_q_divmod(&[], &[1], 3), (vec![], vec![0]));
+    }
+
+    #[test]
+    fn base_q_multiplication() {
+        let mut rng = thread_rng();
+        for _ in 0..128 {
+            let q = 2 + rng.gen_mod(100);
+            let n = digits_per_u128(q) / 2;
+            let M = (q as u128).pow(n as u32);
+            let (x, y) = (rng.gen_u128() % M, rng.gen_u128() % M);
+            let zs = base_q_mul(&as_base_q(x, q, n), &as_base_q(y, q, n), q);
+            assert_eq!(zs.len(), 2 * n);
+            assert_eq!(from_base_q(&zs, q), x * y);
+        }
+        // Long and unbalanced operands go through Karatsuba.
+        for &(n, m, q) in [(200, 200, 7), (301, 77, 2), (64, 500, 65521), (40, 33, 9)].iter() {
+            let xs = rng.gen_digits(q, n);
+            let ys = rng.gen_digits(q, m);
+            assert_eq!(base_q_mul(&xs, &ys, q), base_q_mul_schoolbook(&xs, &ys, q));
+        }
+        let max = vec![6; 100];
+        assert_eq!(
+            base_q_mul(&max, &max, 7),
+            base_q_mul_schoolbook(&max, &max, 7)
+        );
+        assert_eq!(base_q_mul(&[], &[1, 2], 3), vec![0, 0]);
+    }
+
+    #[test]
+    fn digit_comparison() {
+        use std::cmp::Ordering;
+        let mut rng = thread_rng();
+        for _ in 0..256 {
+            let q = 2 + rng.gen_mod(1000);
+            let x = rng.gen_u64() as u128;
+            let y = rng.gen_u64() as u128 >> (rng.gen_u16() % 64);
+            let n = digits_needed(64, q);
+            let (xs, ys) = (as_base_q(x, q, n), as_base_q(y, q, n));
+            assert_eq!(base_q_cmp(&xs, &ys, q), x.cmp(&y));
+            assert_eq!(base_q_cmp(&xs, &xs, q), Ordering::Equal);
+            let m = digits_needed(128 - y.leading_zeros(), q);
+            assert_eq!(base_q_cmp(&xs, &ys[..m], q), x.cmp(&y));
+            let radii = (0..64).map(|_| 2 + rng.gen_mod(100)).collect_vec();
+            let (xs, ys) = (as_mixed_radix(x, &radii), as_mixed_radix(y, &radii));
+            assert_eq!(mixed_radix_cmp(&xs, &ys, &radii), x.cmp(&y));
+        }
+        assert_eq!(base_q_cmp(&[1, 2, 0, 0], &[1, 2], 3), Ordering::Equal);
+        assert_eq!(base_q_cmp(&[], &[0, 1], 3), Ordering::Less);
+        assert_eq!(
+            mixed_radix_cmp(&[1, 2], &[0, 0, 1], &[2, 3, 4]),
+            Ordering::Less
+        );
+    }
+
+    #[test]
+    fn base_q_subtraction() {
+        let mut rng = thread_rng();
+        for _ in 0..128 {
+            let q = 2 + rng.gen_mod(100);
+            let n = digits_per_u128(q) / 2;
+            let M = (q as u128).pow(n as u32);
+            let x = rng.gen_u128() % M;
+            let y = rng.gen_u128() % M;
+            // `ys` may be shorter than `xs`.
+            let ys = as_base_q(y, q, n);
+            let ys = &ys[..rng.gen_usize() % (n + 1)];
+            let y = from_base_q(ys, q);
+            let (ds, underflow) = base_q_sub_borrow(&as_base_q(x, q, n), ys, q);
+            assert_eq!(underflow, x < y);
+            assert_eq!(from_base_q(&ds, q), (x + M - y) % M);
+            let mut xs = as_base_q(x, q, n);
+            base_q_sub_eq(&mut xs, ys, q);
+            assert_eq!(xs, ds);
+            assert_eq!(base_q_sub(&as_base_q(x, q, n), ys, q), ds);
+        }
+        assert_eq!(
+            base_q_sub_borrow(&[0, 0, 1], &[1], 3),
+            (vec![2, 2, 0], false)
+        );
+        assert_eq!(base_q_sub_borrow(&[0, 0], &[0, 1], 3), (vec![0, 2], true));
+    }
+
+    #[test]
+    fn fractional_sign() {
+        let cases: [(&[u16], &[u16]); 2] = [(&PRIMES[..3], &[2; 5]), (&PRIMES[..4], &[3, 26])];
+        for (ps, precision) in cases.iter() {
+            let q = product(ps);
+            let p = *precision.last().unwrap();
+            let threshold = (p / 2) as f64 / p as f64;
+            for x in 0..q {
+                let (negative, error) = crt_fractional_sign(ps, &crt(x, ps), precision);
+                assert_eq!(error, ps.len() as f64 / (2.0 * product(precision) as f64));
+                let f = x as f64 / q as f64;
+                if (f - threshold).abs() > error && f > error && 1.0 - f > error {
+                    assert_eq!(negative, f >= threshold, "x={} q={}", x, q);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn prime_sets() {
+        let d = PrimeSet::default();
+        assert_eq!(d.primes(), &PRIMES[..]);
+        for n in 1..100 {
+            assert_eq!(d.modulus_with_width(n), modulus_with_width(n));
+            assert_eq!(d.primes_with_width(n), primes_with_width(n));
+        }
+        let q = modulus_with_nprimes(6);
+        assert_eq!(d.modulus_with_nprimes(6), q);
+        assert_eq!(d.factor(q), factor(q));
+        assert_eq!(d.crt(1234, q), crt_factor(1234, q));
+        assert_eq!(d.crt_inv(&d.crt(1234, q), q), 1234);
+
+        let odd = PrimeSet::skip_2();
+        assert_eq!(odd.len(), NPRIMES - 1);
+        assert!(odd.try_factor(2 * 3).is_err());
+        assert!(odd.primes_with_width(10).iter().all(|&p| p != 2));
+        let custom = PrimeSet::new(&[113, 127, 131]).unwrap();
+        assert_eq!(custom.factor(113 * 131), vec![113, 131]);
+        assert_eq!(custom.modulus_with_width(20), 113 * 127 * 131);
+        assert!(PrimeSet::new(&[3, 9]).is_err());
+        assert!(PrimeSet::new(&[3, 5, 3]).is_err());
+        assert!(PrimeSet::new(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn digit_iterators() {
+        let mut rng = thread_rng();
+        for _ in 0..64 {
+            let x = rng.gen_u128();
+            let q = 2 + rng.gen_mod(1000);
+            let n = digits_per_u128(q) + 2;
+            let it = BaseQIter::new(x, q, n);
+            assert_eq!(it.len(), n);
+            assert_eq!(it.collect_vec(), as_base_q(x, q, n));
+            assert_eq!(
+                BaseQIter::new(x, q, n).take(3).collect_vec(),
+                as_base_q(x, q, 3)
+            );
+            let radii = (0..10).map(|_| 2 + rng.gen_mod(100)).collect_vec();
+            let it = MixedRadixIter::new(x, &radii);
+            assert_eq!(it.len(), radii.len());
+            assert_eq!(it.collect_vec(), as_mixed_radix(x, &radii));
+        }
+        assert_eq!(BaseQIter::new(5, 2, 0).next(), None);
+    }
+
+    #[test]
+    fn conversions_into() {
+        let mut rng = thread_rng();
+        for _ in 0..64 {
+            let x = rng.gen_u128();
+            let q = 2 + rng.gen_mod(1000);
+            let n = digits_per_u128(q);
+            let ds = DigitArray::base_q(x, q, n);
+            assert_eq!(&ds[..], &as_base_q(x, q, n)[..]);
+            let radii = (0..10).map(|_| 2 + rng.gen_mod(100)).collect_vec();
+            let ds = DigitArray::mixed_radix(x, &radii);
+            assert_eq!(&ds[..], &as_mixed_radix(x, &radii)[..]);
+            let ds = DigitArray::crt(x, &PRIMES);
+            assert_eq!(&ds[..], &crt(x, &PRIMES)[..]);
+            let mut xs = vec![0; 3];
+            crt_into(x, &PRIMES[..3], &mut xs);
+            assert_eq!(xs, crt(x, &PRIMES[..3]));
+        }
+        assert_eq!(DigitArray::base_q(5, 2, 128).len(), 128);
+        assert_eq!(DigitArray::zeros(2), DigitArray::base_q(9, 3, 2));
+    }
+
+    #[test]
+    fn crt_batches() {
+        let mut rng = thread_rng();
+        let ps = &PRIMES[..20];
+        let xs = (0..100).map(|_| rng.gen_u128()).collect_vec();
+        let ys = crt_batch(ps, &xs);
+        let zs = crt_batch_transposed(ps, &xs);
+        assert_eq!(ys.len(), xs.len());
+        assert_eq!(zs.len(), ps.len());
+        for (i, &x) in xs.iter().enumerate() {
+            assert_eq!(ys[i], crt(x, ps));
+            for (j, z) in zs.iter().enumerate() {
+                assert_eq!(z[i], ys[i][j]);
+            }
+        }
+        assert!(crt_batch(ps, &[]).is_empty());
+    }
+
+    #[test]
+    fn crt_bytes() {
+        let mut rng = thread_rng();
+        for _ in 0..128 {
+            let x = rng.gen_u128();
+            assert_eq!(crt_from_bytes(&PRIMES, &x.to_be_bytes()), crt(x, &PRIMES));
+            assert_eq!(
+                crt_from_bytes(&PRIMES, &x.to_be_bytes()[8..]),
+                crt(x % (1 << 64), &PRIMES)
+            );
+        }
+        // A 2048-bit value of `hi` followed by 240 bytes holding `lo`, i.e., `hi * 2^1920 + lo`.
+        let (hi, lo) = (rng.gen_u128(), rng.gen_u128());
+        let mut bytes = hi.to_be_bytes().to_vec();
+        bytes.extend(core::iter::repeat(0).take(224));
+        bytes.extend(lo.to_be_bytes().iter());
+        let xs = crt_from_bytes(&PRIMES, &bytes);
+        for (i, &p) in PRIMES.iter().enumerate() {
+            let p_ = p as u128;
+            let shift = (0..1920).fold(1, |acc, _| acc * 2 % p_);
+            assert_eq!(xs[i] as u128, (hi % p_ * shift + lo % p_) % p_);
+        }
+        assert_eq!(crt_from_bytes(&[3, 5], &[]), vec![0, 0]);
+    }
+
+    #[test]
+    fn crt_signed_conversion() {
+        // Both an even and an odd modulus.
+        for ps in [&PRIMES[..5], &PRIMES[1..4]].iter() {
+            let M = product(ps) as i128;
+            let (lo, hi) = (-(M / 2), M - M / 2);
+            for x in lo..hi {
+                assert_eq!(crt_inv_signed(&crt_signed(x, ps), ps), x);
+            }
+            assert_eq!(crt_inv_signed(&crt_signed(hi, ps), ps), lo);
+            assert_eq!(crt_signed(-1, ps), crt((M - 1) as u128, ps));
+        }
+        let mut rng = thread_rng();
+        for _ in 0..128 {
+            let x = rng.gen_u128() as i128;
+            assert_eq!(crt_inv_signed(&crt_signed(x, &PRIMES), &PRIMES), x);
+        }
+        for &x in [i128::min_value(), i128::max_value(), -1, 0].iter() {
+            assert_eq!(crt_inv_signed(&crt_signed(x, &PRIMES), &PRIMES), x);
+        }
+    }
+
+    #[test]
+    fn crt_context() {
+        let mut rng = thread_rng();
+        for n in [1, 10, 25].iter() {
+            let ps = &PRIMES[..*n];
+            let ctx = CrtContext::new(ps).unwrap();
+            assert_eq!(ctx.modulus(), product(ps));
+            for _ in 0..128 {
+                let x = rng.gen_u128() % ctx.modulus();
+                let xs = ctx.encode(x);
+                assert_eq!(xs, crt(x, ps));
+                assert_eq!(ctx.decode(&xs), x);
+                assert_eq!(ctx.decode_mixed_radix(&xs), as_mixed_radix(x, ps));
+            }
+        }
+        // The sum of the terms overflows for a modulus close to 2^128.
+        let ps = [65521, 65519, 65497, 65479, 65449, 65447, 65437, 65423];
+        let ctx = CrtContext::new(&ps).unwrap();
+        assert!(ctx.modulus() > 1 << 127);
+        for &x in [0, 1, ctx.modulus() - 1, ctx.modulus() / 3].iter() {
+            assert_eq!(ctx.decode(&ctx.encode(x)), x);
+            let ds = ctx.decode_mixed_radix(&ctx.encode(x));
+            assert_eq!(from_mixed_radix(&ds, &ps), x);
+        }
+        assert_eq!(CrtContext::new(&PRIMES), Err(NumberError::Overflow));
+        assert!(CrtContext::new(&[3, 6]).is_err());
+        assert!(CrtContext::new(&[1, 3]).is_err());
+    }
+
+    #[test]
+    fn extended_gcd() {
+        let mut rng = thread_rng();
+        for _ in 0..1000 {
+            let a = (rng.gen_u64() >> 1) as i128 - (1 << 62);
+            let b = (rng.gen_u64() >> 1) as i128 - (1 << 62);
+            let (g, x, y) = egcd(a, b);
+            assert!(g >= 0);
+            assert_eq!(a * x + b * y, g);
+            if g != 0 {
+                assert_eq!(a % g, 0);
+                assert_eq!(b % g, 0);
+            }
+        }
+        assert_eq!(egcd(240, 46), (2, -9, 47));
+        assert_eq!(egcd(0, 0), (0, 1, 0));
+        assert_eq!(egcd(0, -5), (5, 0, -1));
+        for &p in PRIMES.iter() {
+            for x in 1..p as i128 {
+                assert_eq!(x * inv(x, p as i128) % p as i128, 1);
+            }
+        }
+    }
+
+    #[test]
+    fn batch_inversion() {
+        let mut rng = thread_rng();
+        for &p in PRIMES.iter() {
+            let xs = (0..100)
+                .map(|_| 1 + rng.gen_mod(p - 1))
+                .collect::<Vec<u16>>();
+            let invs = batch_inv(&xs, p).unwrap();
+            for (&x, &y) in xs.iter().zip(invs.iter()) {
+                assert_eq!(x as u32 * y as u32 % p as u32, 1);
+            }
+        }
+        assert_eq!(batch_inv(&[], 7), Ok(vec![]));
+        assert_eq!(batch_inv(&[1], 0), Err(NumberError::InvalidModulus(0)));
+        assert_eq!(batch_inv(&[], 1), Err(NumberError::InvalidModulus(1)));
+        assert_eq!(batch_inv(&[3], 7), Ok(vec![5]));
+        assert_eq!(
+            batch_inv(&[3, 14, 2], 7),
+            Err(NumberError::NotInvertible { x: 14, modulus: 7 })
+        );
+        assert_eq!(
+            batch_inv(&[3, 4, 2], 8),
+            Err(NumberError::NotInvertible { x: 4, modulus: 8 })
+        );
+    }
+
+    #[test]
+    fn modular_exponentiation() {
+        let mut rng = thread_rng();
+        fn naive(x: u64, e: u64, q: u64) -> u64 {
+            (0..e).fold(1 % q, |z, _| (z as u128 * x as u128 % q as u128) as u64)
+        }
+        for &q in [1, 2, 3, 255, 256, 257, 65521, 65535].iter() {
+            for _ in 0..16 {
+                let x = rng.gen_u16();
+                let e = rng.gen_mod(1000);
+                let z = naive(x as u64, e as u64, q as u64);
+                assert_eq!(powm(x, e, q) as u64, z);
+                assert_eq!(powm_u64(x as u64, e as u64, q as u64), z);
+            }
+        }
+        // Fermat's little theorem for a large prime.
+        let p = 18_446_744_073_709_551_557;
+        for _ in 0..16 {
+            let x = rng.gen_u64() % (p - 1) + 1;
+            assert_eq!(powm_u64(x, p - 1, p), 1);
+        }
+        assert_eq!(powm(7, 0, 13), 1);
+        assert_eq!(powm_u64(0, 0, 1), 0);
+        for &q in [1, 2, 3, 255, 256, 257, 65521, 65535].iter() {
+            for _ in 0..16 {
+                let (x, e) = (rng.gen_u16(), rng.gen_u16());
+                assert_eq!(powm_ct(x, e, q), powm(x, e, q));
+            }
+        }
+    }
+
+    #[test]
+    fn discrete_logarithms() {
+        fn naive(g: u32, y: u32, m: u32) -> Option<u32> {
+            let (g, y, m) = (g as u64, y as u64 % m as u64, m as u64);
+            let mut z = 1 % m;
+            for x in 0..m {
+                if z == y {
+                    return Some(x as u32);
+                }
+                z = z * g % m;
+            }
+            None
+        }
+        let mut rng = thread_rng();
+        for &m in [1, 2, 9, 15, 101, 256, 1000, 7919, 65536].iter() {
+            for _ in 0..16 {
+                let (g, y) = (rng.gen_u16() as u32, rng.gen_u16() as u32);
+                let expected = if try_inv(g as i128, m as i128).is_ok() || m == 1 {
+                    naive(g, y, m)
+                } else {
+                    None
+                };
+                assert_eq!(dlog(g, y, m), expected);
+            }
+        }
+        // A prime with `p - 1 = 2 * 3^3 * 5^2 * 7^2 * 11 * 13 * 17 * 19`, and a generator.
+        let p = 3_055_402_351;
+        let g = 11;
+        assert!(is_prime(p));
+        for _ in 0..16 {
+            let x = rng.gen_u64() % (p - 1);
+            let y = powm_u64(g, x, p);
+            let z = dlog(g as u32, y as u32, p as u32).unwrap() as u64;
+            assert_eq!(powm_u64(g, z, p), y);
+        }
+    }
+
+    #[test]
+    fn constant_time_inversion() {
+        let mut rng = thread_rng();
+        for &q in [3, 9, 255, 257, 65521, 65535].iter() {
+            for _ in 0..64 {
+                let x = rng.gen_u16();
+                match try_inv(x as i128, q as i128) {
+                    Ok(y) => assert_eq!(inv_ct(x, q), Some(y as u16)),
+                    Err(_) => assert_eq!(inv_ct(x, q), None),
+                }
+            }
+        }
+        for x in 1..257 {
+            assert_eq!(inv_ct(x, 257), Some(inv(x as i128, 257) as u16));
+        }
+        assert_eq!(inv_ct(0, 5), None);
+        assert_eq!(inv_ct(6, 9), None);
+        assert_eq!(inv_ct(0, 1), Some(0));
+    }
+
+    #[test]
+    fn primality() {
+        assert_eq!(primes_up_to(109), PRIMES.to_vec());
+        assert_eq!(primes_up_to(1), Vec::<u16>::new());
+        for q in 0..=u16::max_value() as u64 {
+            let trial = q >= 2 && (2..q).take_while(|p| p * p <= q).all(|p| q % p != 0);
+            assert_eq!(is_prime(q), trial, "q={}", q);
+        }
+        // Carmichael numbers and strong pseudoprimes to small bases.
+        for &n in [561, 41041, 3_215_031_751, 3_825_123_056_546_413_051].iter() {
+            assert!(!is_prime(n), "n={}", n);
+        }
+        for &p in [(1 << 61) - 1, 18_446_744_073_709_551_557].iter() {
+            assert!(is_prime(p), "p={}", p);
+        }
+        assert_eq!(next_prime(0), Some(2));
+        assert_eq!(next_prime(109), Some(113));
+        assert_eq!(next_prime(65521), None);
+        assert_eq!(
+            try_extended_primes_with_width(20, &[3, 5, 7]),
+            Ok(vec![3, 5, 7, 11, 13, 17, 19])
+        );
+        assert_eq!(
+            try_base_primes_with_width(20, &[3, 5, 7]),
+            Err(NumberError::NotEnoughPrimes(20))
+        );
+    }
+
+    #[test]
+    fn general_crt_combination() {
+        let mut rng = thread_rng();
+        for _ in 0..256 {
+            let ms = (0..3).map(|_| 1 + rng.gen_u128() % 60).collect_vec();
+            let xs = (0..3).map(|_| rng.gen_u128() % 100).collect_vec();
+            let l = ms.iter().fold(1, |l, &m| l / gcd_u128(l, m) * m);
+            let expected =
+                (0..l).find(|&x| ms.iter().zip(xs.iter()).all(|(&m, &y)| x % m == y % m));
+            assert_eq!(crt_combine_general(&ms, &xs), expected.map(|x| (x, l)));
+        }
+        assert_eq!(crt_combine_general(&[4, 6], &[3, 5]), Some((11, 12)));
+        assert_eq!(crt_combine_general(&[4, 6], &[1, 2]), None);
+        assert_eq!(crt_combine_general(&[], &[]), Some((0, 1)));
+        assert_eq!(crt_combine_general(&[0], &[0]), None);
+        assert_eq!(crt_combine_general(&[u128::max_value(), 2], &[0, 0]), None);
+        let (x, l) = crt_combine_general(&[1 << 125, 1 << 126, 3], &[7, 7, 2]).unwrap();
+        assert_eq!(l, 3 << 126);
+        assert_eq!((x % (1 << 126), x % 3), (7, 2));
+        // The largest prime below 2^128, and the largest below 2^64.
+        let (p, q) = (u128::max_value() - 158, 18_446_744_073_709_551_557);
+        let x = 0x1234_5678_9abc_def0_1234_5678_9abc_def0;
+        assert_eq!(crt_combine_general(&[p, p], &[x, x]), Some((x, p)));
+        assert_eq!(crt_combine_general(&[p, p], &[x, x + 1]), None);
+        assert_eq!(crt_combine_general(&[p, 2], &[x, 1]), None);
+        let (y, l) = crt_combine_general(&[q, q + 2], &[x, x]).unwrap();
+        assert_eq!(l, q * (q + 2));
+        assert_eq!((y % q, y % (q + 2)), (x % q, x % (q + 2)));
+    }
+
     #[test]
     fn factoring() {
         let mut rng = thread_rng();
@@ -483,6 +3379,33 @@ mod tests {
             }
             assert_eq!(factor(q), ps);
         }
+        assert_eq!(try_factor(4), Err(NumberError::NotFactorable(4)));
+        assert_eq!(
+            try_factor_powers(4 * 27 * 7),
+            Ok(vec![(2, 2), (3, 3), (7, 1)])
+        );
+        assert_eq!(try_factor_powers(1), Ok(vec![]));
+        assert_eq!(try_factor_powers(0), Err(NumberError::NotFactorable(0)));
+        assert_eq!(try_prime_power_factors(1 << 17), Err(NumberError::Overflow));
+        let ms = try_prime_power_factors(8 * 9 * 25 * 11).unwrap();
+        assert_eq!(ms, vec![8, 9, 25, 11]);
+        for x in 0..product(&ms) {
+            assert_eq!(crt_inv(&crt(x, &ms), &ms), x);
+        }
+        assert_eq!(try_crt_inv(&crt(1234, &ms), &ms), Ok(1234));
+        assert_eq!(try_factor(113), Err(NumberError::NotFactorable(113)));
+        assert_eq!(
+            try_base_primes_with_width(10, &PRIMES),
+            Ok(primes_with_width(10))
+        );
+        assert_eq!(
+            try_base_primes_with_width(64, &PRIMES[..5]),
+            Err(NumberError::NotEnoughPrimes(64))
+        );
+        assert_eq!(
+            try_base_primes_with_width(128, &PRIMES),
+            Err(NumberError::Overflow)
+        );
     }
 
     #[test]
@@ -492,6 +3415,171 @@ mod tests {
             let x = rng.gen_u128();
             assert_eq!(u128_from_bits(&u128_to_bits(x, 128)), x);
         }
+        for n in 1..=128 {
+            for _ in 0..16 {
+                let x = rng.gen_u128();
+                crate::test_utils::assert_bits_round_trip(x, n);
+                let bs = u128_to_bits(x, n);
+                assert_eq!(bs.len(), n);
+                assert_eq!(u128_to_bits(u128_from_bits(&bs), n), bs);
+                assert_eq!(u128_from_bits_checked(&bs), Ok(u128_from_bits(&bs)));
+            }
+        }
+        assert_eq!(u128_from_bits(&[]), 0);
+        assert_eq!(u128_from_bits(&[0, 1]), 2);
+        assert_eq!(u128_to_bits(5, 130)[..4], [1, 0, 1, 0]);
+        assert_eq!(u128_from_bits(&u128_to_bits(5, 130)), 5);
+        assert_eq!(
+            u128_from_bits_checked(&[1, 2]),
+            Err(NumberError::InvalidDigit {
+                digit: 2,
+                modulus: 2
+            })
+        );
+        let mut bs = vec![0; 129];
+        assert_eq!(u128_from_bits_checked(&bs), Ok(0));
+        bs[128] = 1;
+        assert_eq!(u128_from_bits_checked(&bs), Err(NumberError::Overflow));
+    }
+
+    #[test]
+    fn bytes_and_u64s_to_bits() {
+        let bytes = (0..33).map(|_| rand::random::<u8>()).collect::<Vec<_>>();
+        let bits = bytes_to_bits(&bytes);
+        assert_eq!(bits.len(), 8 * bytes.len());
+        assert_eq!(bytes_from_bits(&bits), bytes);
+        assert_eq!(bytes_from_bits(&[1, 0, 1]), vec![5]);
+
+        let xs = (0..5).map(|_| rand::random::<u64>()).collect::<Vec<_>>();
+        let bits = u64s_to_bits(&xs);
+        assert_eq!(bits.len(), 64 * xs.len());
+        assert_eq!(u64s_from_bits(&bits), xs);
+        assert_eq!(bits[..64], u128_to_bits(xs[0] as u128, 64)[..]);
+    }
+
+    #[test]
+    fn checked_conversions() {
+        assert_eq!(try_as_base_q(26, 3, 3), Ok(vec![2, 2, 2]));
+        assert_eq!(try_as_base_q(27, 3, 3), Err(NumberError::Overflow));
+        assert_eq!(as_base_q(27, 3, 3), vec![0, 0, 0]);
+        assert_eq!(
+            try_as_mixed_radix(23, &[2, 3, 4]),
+            Ok(as_mixed_radix(23, &[2, 3, 4]))
+        );
+        assert_eq!(
+            try_as_mixed_radix(24, &[2, 3, 4]),
+            Err(NumberError::Overflow)
+        );
+        assert_eq!(
+            try_as_mixed_radix(0, &[2, 1]),
+            Err(NumberError::InvalidModulus(1))
+        );
+
+        assert_eq!(try_from_base_q(&[2, 2, 2], 3), Ok(26));
+        assert_eq!(
+            try_from_base_q(&[3], 3),
+            Err(NumberError::InvalidDigit {
+                digit: 3,
+                modulus: 3
+            })
+        );
+        assert_eq!(try_from_base_q(&[1; 128], 2), Ok(u128::max_value()));
+        assert_eq!(try_from_base_q(&[1; 129], 2), Err(NumberError::Overflow));
+        assert_eq!(
+            try_from_base_q_u256(&[1; 129], 2),
+            Ok(U256::new(1, u128::max_value()))
+        );
+        assert_eq!(
+            try_from_base_q_u256(&[1; 257], 2),
+            Err(NumberError::Overflow)
+        );
+        assert_eq!(try_from_mixed_radix(&[1, 2, 3], &[2, 3, 4]), Ok(23));
+        assert_eq!(
+            try_from_mixed_radix(&[1, 2], &[2, 3, 4]),
+            Err(NumberError::DigitCount { got: 2, needed: 3 })
+        );
+
+        // The largest 60-digit decimal number, which needs 200 bits.
+        let big = (0..60).fold(U256::from(1), |acc, _| acc * U256::from(10));
+        assert_eq!(
+            try_from_mixed_radix_u256(&[9; 60], &[10; 60]),
+            Ok(big - U256::from(1))
+        );
+        assert_eq!(
+            try_from_mixed_radix_u256(&[1, 2, 3], &[2, 3, 4]),
+            Ok(U256::from(23))
+        );
+        assert_eq!(
+            try_from_mixed_radix_u256(&[1; 257], &[2; 257]),
+            Err(NumberError::Overflow)
+        );
+
+        let ps = [3, 5, 7];
+        assert_eq!(try_crt_inv(&crt(52, &ps), &ps), Ok(52));
+        assert_eq!(try_crt_inv_factor(&crt(52, &ps), 105), Ok(52));
+        assert_eq!(
+            try_crt_inv(&[1, 5, 1], &ps),
+            Err(NumberError::InvalidDigit {
+                digit: 5,
+                modulus: 5
+            })
+        );
+        assert!(try_crt_inv(&[1, 1], &[3, 6]).is_err());
+        assert!(try_crt_inv_factor(&[1, 1], 4).is_err());
+        assert_eq!(
+            try_crt_inv(&crt(u128::max_value(), &PRIMES), &PRIMES),
+            Ok(u128::max_value())
+        );
+        // The value one below the product of the primes does not fit.
+        let xs = PRIMES.iter().map(|&p| p - 1).collect_vec();
+        assert_eq!(try_crt_inv(&xs, &PRIMES), Err(NumberError::Overflow));
+
+        assert_eq!(try_inv(3, 7), Ok(5));
+        assert_eq!(try_inv(-4, 7), Ok(5));
+        assert_eq!(
+            try_inv(4, 6),
+            Err(NumberError::NotInvertible { x: 4, modulus: 6 })
+        );
+        assert!(try_inv(1, 1).is_err());
+    }
+
+    #[test]
+    fn modulus_builder() {
+        for nbits in 1..100 {
+            assert_eq!(
+                ModulusBuilder::new(nbits).primes().unwrap(),
+                primes_with_width(nbits)
+            );
+        }
+        let ps = ModulusBuilder::new(64).exclude(&[2, 3]).primes().unwrap();
+        assert_eq!(ps[0], 5);
+        assert!(product(&ps) >> 64 > 0);
+        let ps = ModulusBuilder::new(64)
+            .min_prime(11)
+            .max_factors(10)
+            .primes()
+            .unwrap();
+        assert!(ps.len() <= 10 && ps[0] >= 11);
+        assert!(product(&ps) >> 64 > 0);
+        let ps = ModulusBuilder::new(32).digit_budget(130).primes().unwrap();
+        assert!(ps.iter().map(|&p| p as usize).sum::<usize>() <= 130);
+
+        let err = ModulusBuilder::new(64).max_factors(2).primes().unwrap_err();
+        assert!(err.to_string().contains("support only 13 bits"), "{}", err);
+        let err = ModulusBuilder::new(64)
+            .digit_budget(10)
+            .primes()
+            .unwrap_err();
+        assert!(err.to_string().contains("over the budget of 10"), "{}", err);
+        let err = ModulusBuilder::new(8)
+            .from_primes(&[2, 3])
+            .exclude(&[2, 3])
+            .primes();
+        assert!(err.is_err());
+        assert_eq!(
+            ModulusBuilder::new(128).primes(),
+            Err(NumberError::Overflow)
+        );
     }
 
     #[test]
@@ -505,6 +3593,92 @@ mod tests {
             assert_eq!(x, z);
         }
     }
+
+    #[test]
+    fn sampling() {
+        let mut rng = thread_rng();
+        for _ in 0..128 {
+            let q = rng.gen_modulus();
+            assert!(q >= 2 && q <= 112);
+            assert!(rng.gen_mod(q) < q);
+            assert!(rng.gen_digits(q, 10).iter().all(|&d| d < q));
+            assert_eq!(rng.gen_wire_label(q).modulus(), q);
+        }
+        let ps = &PRIMES[..5];
+        let xs = rng.gen_crt(ps);
+        assert!(xs.iter().zip(ps).all(|(&x, &p)| x < p));
+    }
+
+    #[test]
+    fn carry_digits() {
+        assert_eq!(carry_digits_for_sum(2, &[]), 0);
+        assert_eq!(carry_digits_for_sum(2, &[1]), 0);
+        assert_eq!(carry_digits_for_sum(2, &[1, 1]), 1);
+        assert_eq!(carry_digits_for_sum(2, &[1, 1, 1]), 2);
+        assert_eq!(carry_digits_for_sum(10, &[1; 10]), 1);
+        assert_eq!(carry_digits_for_sum(10, &[1; 11]), 2);
+        assert_eq!(carry_digits_for_sum(3, &[5, 4]), 2);
+        assert_eq!(carry_digits_for_sum(2, &[u64::max_value(); 4]), 66);
+        let mut rng = thread_rng();
+        for _ in 0..64 {
+            let q = 2 + rng.gen_mod(100);
+            let n = 12;
+            let ws = (0..4).map(|_| rng.gen_u64() % 1000).collect_vec();
+            let c = carry_digits_for_sum(q, &ws);
+            // The largest weighted sum fits in `n + c` digits, but not always in fewer.
+            let max = (q as u128).pow(n) - 1;
+            let sum = ws.iter().map(|&w| w as u128 * max).sum::<u128>();
+            assert!(sum < (q as u128).pow(n + c as u32));
+            if c > 0 {
+                assert!(sum >= (q as u128).pow(n + c as u32 - 1));
+            }
+            assert_eq!(carry_digits_for_mixed_radix_sum(&[q; 16], &ws), Some(c));
+        }
+        assert_eq!(
+            carry_digits_for_mixed_radix_sum(&[2, 3, 5], &[1; 6]),
+            Some(2)
+        );
+        assert_eq!(
+            carry_digits_for_mixed_radix_sum(&[2, 3, 5], &[1; 7]),
+            Some(3)
+        );
+        assert_eq!(carry_digits_for_mixed_radix_sum(&[2, 3, 5], &[1; 31]), None);
+        assert_eq!(carry_digits_for_mixed_radix_sum(&[], &[1]), Some(0));
+    }
+
+    #[test]
+    fn digits_needed_is_exact() {
+        for q in 2..=300 {
+            for bits in 0..=128 {
+                // Count the digits of `2^bits - 1`.
+                let mut x = if bits == 0 {
+                    0
+                } else {
+                    u128::max_value() >> (128 - bits)
+                };
+                let mut n = 0;
+                while x > 0 {
+                    x /= q as u128;
+                    n += 1;
+                }
+                assert_eq!(digits_needed(bits, q), n, "bits={}, q={}", bits, q);
+            }
+        }
+        assert_eq!(digits_needed(64, 2), 64);
+        assert_eq!(digits_needed(64, 65535), 5);
+        assert_eq!(digits_needed(256, 2), 256);
+        assert_eq!(digits_needed(256, 256), 32);
+        assert_eq!(digits_needed(256, 257), 32);
+        assert_eq!(digits_needed(256, 65535), 17);
+    }
+
+    #[test]
+    fn digits_per_u128_matches_float() {
+        for q in 2..=u16::max_value() {
+            let expected = (128.0 / (q as f64).log2().ceil()).floor() as usize;
+            assert_eq!(digits_per_u128(q), expected, "q={}", q);
+        }
+    }
 }
 
 #[cfg(all(feature = "nightly", test))]
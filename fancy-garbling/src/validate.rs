@@ -0,0 +1,208 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `fancy-garbling`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! Invariant checks for the representations used by `fancy-garbling`.
+//!
+//! Each invariant has a `check_*` function returning an error, and a function
+//! of the same name without the prefix that panics if the invariant does not
+//! hold. The latter are called at API boundaries, such as input encoding and
+//! digit conversion, so that a corrupted representation is caught where it
+//! enters rather than surfacing as wrong outputs much later. They only check
+//! anything in debug builds, unless the `strict` feature is enabled, in which
+//! case they always check.
+
+use crate::{
+    errors::FancyError,
+    fancy::{CrtBundle, HasModulus},
+    util::egcd,
+    wire::Wire,
+};
+
+/// Whether the panicking checks are enabled.
+pub const ENABLED: bool = cfg!(any(debug_assertions, feature = "strict"));
+
+#[inline]
+fn enforce(r: Result<(), FancyError>) {
+    if let Err(e) = r {
+        panic!("invariant violated: {}", e);
+    }
+}
+
+/// Check that every digit of `ds` is below the modulus `q`.
+pub fn check_digits(ds: &[u16], q: u16) -> Result<(), FancyError> {
+    check_modulus(q)?;
+    match ds.iter().find(|&&d| d >= q) {
+        Some(d) => Err(FancyError::InvalidArg(format!(
+            "digit {} is not below modulus {}",
+            d, q
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Check that `q` is a valid modulus, i.e., at least 2.
+pub fn check_modulus(q: u16) -> Result<(), FancyError> {
+    if q < 2 {
+        return Err(FancyError::InvalidArg(format!("invalid modulus {}", q)));
+    }
+    Ok(())
+}
+
+/// Check that every digit of `ds` is below its radix in `radii`.
+pub fn check_mixed_radix(ds: &[u16], radii: &[u16]) -> Result<(), FancyError> {
+    if ds.len() > radii.len() {
+        return Err(FancyError::InvalidArgNum {
+            got: ds.len(),
+            needed: radii.len(),
+        });
+    }
+    for (&d, &q) in ds.iter().zip(radii.iter()) {
+        check_digits(&[d], q)?;
+    }
+    Ok(())
+}
+
+/// Check that the moduli of `ws` are exactly `moduli`.
+pub fn check_moduli<W: HasModulus>(ws: &[W], moduli: &[u16]) -> Result<(), FancyError> {
+    if ws.len() != moduli.len() {
+        return Err(FancyError::InvalidArgNum {
+            got: ws.len(),
+            needed: moduli.len(),
+        });
+    }
+    for (w, &q) in ws.iter().zip(moduli.iter()) {
+        if w.modulus() != q {
+            return Err(FancyError::InvalidArgMod {
+                got: w.modulus(),
+                needed: q,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Check that the moduli of a CRT bundle are pairwise coprime, so that its
+/// value is determined mod their product.
+pub fn check_crt_bundle<W: Clone + HasModulus>(x: &CrtBundle<W>) -> Result<(), FancyError> {
+    let ps = x.moduli();
+    for (i, &p) in ps.iter().enumerate() {
+        check_modulus(p)?;
+        if let Some(&q) = ps[..i].iter().find(|&&q| egcd(p.into(), q.into()).0 != 1) {
+            return Err(FancyError::InvalidArg(format!(
+                "CRT moduli {} and {} are not coprime",
+                q, p
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Check that `w` has modulus `q` and a well-formed representation.
+pub fn check_wire(w: &Wire, q: u16) -> Result<(), FancyError> {
+    if w.modulus() != q {
+        return Err(FancyError::InvalidArgMod {
+            got: w.modulus(),
+            needed: q,
+        });
+    }
+    match w {
+        Wire::Mod2 { .. } => Ok(()),
+        Wire::Mod3 { lsb, msb } => {
+            if lsb & msb != 0 {
+                return Err(FancyError::InvalidArg(
+                    "mod-3 wire-label has a digit equal to 3".to_string(),
+                ));
+            }
+            Ok(())
+        }
+        Wire::ModN { ds, .. } => check_digits(ds, q),
+    }
+}
+
+/// Panic if `check_digits` fails and checks are enabled.
+#[inline]
+pub fn digits(ds: &[u16], q: u16) {
+    if ENABLED {
+        enforce(check_digits(ds, q))
+    }
+}
+
+/// Panic if `check_mixed_radix` fails and checks are enabled.
+#[inline]
+pub fn mixed_radix(ds: &[u16], radii: &[u16]) {
+    if ENABLED {
+        enforce(check_mixed_radix(ds, radii))
+    }
+}
+
+/// Panic if `check_moduli` fails and checks are enabled.
+#[inline]
+pub fn moduli<W: HasModulus>(ws: &[W], moduli: &[u16]) {
+    if ENABLED {
+        enforce(check_moduli(ws, moduli))
+    }
+}
+
+/// Panic if `check_crt_bundle` fails and checks are enabled.
+#[inline]
+pub fn crt_bundle<W: Clone + HasModulus>(x: &CrtBundle<W>) {
+    if ENABLED {
+        enforce(check_crt_bundle(x))
+    }
+}
+
+/// Panic if `check_wire` fails and checks are enabled.
+#[inline]
+pub fn wire(w: &Wire, q: u16) {
+    if ENABLED {
+        enforce(check_wire(w, q))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::RngExt;
+    use rand::thread_rng;
+
+    #[test]
+    fn checks() {
+        assert!(check_digits(&[0, 1, 2], 3).is_ok());
+        assert!(check_digits(&[0, 3], 3).is_err());
+        assert!(check_digits(&[], 1).is_err());
+        assert!(check_mixed_radix(&[1, 2], &[2, 3, 4]).is_ok());
+        assert!(check_mixed_radix(&[1, 3], &[2, 3]).is_err());
+        assert!(check_mixed_radix(&[1, 1, 1], &[2, 3]).is_err());
+
+        let mut rng = thread_rng();
+        let ws = [rng.gen_wire_label(2), rng.gen_wire_label(3), rng.gen_wire_label(7)];
+        assert!(check_moduli(&ws, &[2, 3, 7]).is_ok());
+        assert!(check_moduli(&ws, &[2, 3, 5]).is_err());
+        assert!(check_moduli(&ws, &[2, 3]).is_err());
+        for w in ws.iter() {
+            assert!(check_wire(w, w.modulus()).is_ok());
+        }
+        assert!(check_wire(&ws[0], 3).is_err());
+        let bad = Wire::Mod3 { lsb: 1, msb: 1 };
+        assert!(check_wire(&bad, 3).is_err());
+
+        assert!(check_crt_bundle(&CrtBundle::new(ws.to_vec())).is_ok());
+        let ws = vec![rng.gen_wire_label(3), rng.gen_wire_label(3)];
+        assert!(check_crt_bundle(&CrtBundle::new(ws)).is_err());
+        let ws = vec![rng.gen_wire_label(4), rng.gen_wire_label(6)];
+        assert!(check_crt_bundle(&CrtBundle::new(ws)).is_err());
+        // Moduli need not be from `PRIMES`, as with a configured basis.
+        let ws = vec![rng.gen_wire_label(4), rng.gen_wire_label(113)];
+        assert!(check_crt_bundle(&CrtBundle::new(ws)).is_ok());
+    }
+
+    #[test]
+    #[cfg_attr(not(any(debug_assertions, feature = "strict")), ignore)]
+    #[should_panic(expected = "invariant violated")]
+    fn enforced() {
+        digits(&[5], 5);
+    }
+}
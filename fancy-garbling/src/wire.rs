@@ -6,7 +6,7 @@
 
 //! Low-level operations on wire-labels, the basic building block of garbled circuits.
 
-use crate::{fancy::HasModulus, util};
+use crate::{errors::FancyError, fancy::HasModulus, util, validate};
 use rand::{CryptoRng, Rng, RngCore};
 use scuttlebutt::{Block, AES_HASH};
 
@@ -75,7 +75,7 @@ impl Wire {
                 .map(|i| ((u128::from(*val) >> i) as u16) & 1)
                 .collect(),
             Wire::Mod3 { lsb, msb } => (0..64)
-                .map(|i| (((lsb >> i) as u16) & 1) & ((((msb >> i) as u16) & 1) << 1))
+                .map(|i| (((lsb >> i) as u16) & 1) | ((((msb >> i) as u16) & 1) << 1))
                 .collect(),
             Wire::ModN { ds, .. } => ds.clone(),
         }
@@ -159,8 +159,9 @@ impl Wire {
             let inp = u128::from(inp);
             let lsb = inp as u64;
             let msb = (inp >> 64) as u64;
-            debug_assert_eq!(lsb & msb, 0);
-            Wire::Mod3 { lsb, msb }
+            let w = Wire::Mod3 { lsb, msb };
+            validate::wire(&w, q);
+            w
         } else {
             let ds = if util::is_power_of_2(q) {
                 // It's a power of 2, just split the digits.
@@ -183,6 +184,24 @@ impl Wire {
         }
     }
 
+    /// Unpack the wire represented by a `Block` with modulus `q`, returning an
+    /// error if the block is not a valid wire-label. Unlike `from_block`, this
+    /// is suitable for blocks that may be corrupted, such as those received
+    /// from the garbler.
+    pub fn try_from_block(inp: Block, q: u16) -> Result<Self, FancyError> {
+        validate::check_modulus(q)?;
+        if q == 3 {
+            let inp = u128::from(inp);
+            let w = Wire::Mod3 {
+                lsb: inp as u64,
+                msb: (inp >> 64) as u64,
+            };
+            validate::check_wire(&w, q)?;
+            return Ok(w);
+        }
+        Ok(Self::from_block(inp, q))
+    }
+
     /// Pack the wire into a `Block`.
     pub fn as_block(&self) -> Block {
         match self {
@@ -405,7 +424,7 @@ impl Wire {
             // `msb`.
             let mut lsb = 0u64;
             let mut msb = 0u64;
-            for (i, v) in (0..64).map(|_| rng.gen::<u8>() % 3).enumerate() {
+            for (i, v) in (0..64).map(|_| rng.gen_range(0u8, 3)).enumerate() {
                 lsb |= ((v & 1) as u64) << i;
                 msb |= (((v >> 1) & 1) as u64) << i;
             }
@@ -413,7 +432,7 @@ impl Wire {
             Wire::Mod3 { lsb, msb }
         } else {
             let ds = (0..util::digits_per_u128(q))
-                .map(|_| rng.gen::<u16>() % q)
+                .map(|_| rng.gen_range(0, q))
                 .collect();
             Wire::ModN { q, ds }
         }
@@ -468,8 +487,13 @@ mod tests {
             for _ in 0..1000 {
                 let w = Wire::rand(rng, q);
                 assert_eq!(w, Wire::from_block(w.as_block(), q));
+                assert_eq!(w, Wire::try_from_block(w.as_block(), q).unwrap());
             }
         }
+        // A mod-3 block with both bits of a digit set is not a wire-label.
+        let bad = Block::from((1u128 << 64) | 1);
+        assert!(Wire::try_from_block(bad, 3).is_err());
+        assert!(Wire::try_from_block(bad, 1).is_err());
     }
 
     #[test]
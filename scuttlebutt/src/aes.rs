@@ -5,10 +5,12 @@
 // See LICENSE for licensing information.
 
 //! Implementations of AES-128 and AES-256, encryption only, using Intel's
-//! AES-NI instructions.
+//! AES-NI instructions. AES-128 falls back to a portable software
+//! implementation on x86-64 processors without them.
 //!
 //! Most of this implementation is borrowed and simplified from the `aesni`
 //! crate.
 
 pub mod aes128;
 pub mod aes256;
+mod soft;
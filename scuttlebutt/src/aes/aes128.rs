@@ -27,13 +27,21 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
+use super::soft;
 use crate::Block;
 use std::arch::x86_64::*;
 
 /// AES-128, encryption only.
+///
+/// On processors without AES-NI this falls back to the constant-time software
+/// implementation in `soft`, which produces the same ciphertexts.
 #[derive(Clone)]
 pub struct Aes128 {
     rkeys: [__m128i; 11],
+    // Whether to use AES-NI, detected once by `new`. Constants cannot detect
+    // it, so unless it is enabled at compile time they leave this `None` and
+    // detect it on every call.
+    aesni: Option<bool>,
 }
 
 macro_rules! xor4 {
@@ -106,12 +114,28 @@ impl Aes128 {
     /// Create a new `Aes128` object, using `key` as the AES key.
     #[inline]
     pub fn new(key: Block) -> Self {
-        let rkeys = expand(key.0);
-        Aes128 { rkeys }
+        let aesni = soft::aesni();
+        let rkeys = if aesni {
+            expand(key.0)
+        } else {
+            soft::expand(key)
+        };
+        Aes128 {
+            rkeys,
+            aesni: Some(aesni),
+        }
+    }
+    /// Whether to encrypt with AES-NI.
+    #[inline(always)]
+    fn aesni(&self) -> bool {
+        self.aesni.unwrap_or_else(soft::aesni)
     }
     /// Encrypt a block, outputting the ciphertext.
     #[inline(always)]
     pub fn encrypt(&self, m: Block) -> Block {
+        if !self.aesni() {
+            return soft::encrypt(&self.rkeys, m);
+        }
         let rkeys = self.rkeys;
         unsafe {
             let mut c: __m128i = m.into();
@@ -134,6 +158,12 @@ impl Aes128 {
     /// that doesn't appear to be the case...
     #[inline(always)]
     pub fn encrypt4(&self, mut blocks: [Block; 4]) -> [Block; 4] {
+        if !self.aesni() {
+            for b in blocks.iter_mut() {
+                *b = soft::encrypt(&self.rkeys, *b);
+            }
+            return blocks;
+        }
         let rkeys = self.rkeys;
         unsafe {
             xor4!(blocks, rkeys[0]);
@@ -153,6 +183,12 @@ impl Aes128 {
     /// Encrypt eight blocks at a time, outputting the ciphertexts.
     #[inline(always)]
     pub fn encrypt8(&self, mut blocks: [Block; 8]) -> [Block; 8] {
+        if !self.aesni() {
+            for b in blocks.iter_mut() {
+                *b = soft::encrypt(&self.rkeys, *b);
+            }
+            return blocks;
+        }
         let rkeys = self.rkeys;
         unsafe {
             xor8!(blocks, rkeys[0]);
@@ -265,6 +301,11 @@ pub const FIXED_KEY_AES128: Aes128 = Aes128 {
             .vector,
         ]
     },
+    aesni: if cfg!(target_feature = "aes") {
+        Some(true)
+    } else {
+        None
+    },
 };
 
 mod tests {
@@ -279,4 +320,25 @@ mod tests {
         let ct = cipher.encrypt(pt);
         assert_eq!(ct, Block::from(0x97EF6624F3CA9EA860367A0DB47BD73A));
     }
+
+    #[test]
+    fn test_soft_fallback() {
+        if !soft::aesni() {
+            return;
+        }
+        for _ in 0..16 {
+            let key = rand::random::<Block>();
+            let cipher = Aes128::new(key);
+            let rkeys = soft::expand(key);
+            for (a, b) in rkeys.iter().zip(cipher.rkeys.iter()) {
+                assert_eq!(Block(*a), Block(*b));
+            }
+            let pt = rand::random::<Block>();
+            assert_eq!(soft::encrypt(&rkeys, pt), cipher.encrypt(pt));
+            assert_eq!(
+                soft::encrypt(&FIXED_KEY_AES128.rkeys, pt),
+                FIXED_KEY_AES128.encrypt(pt)
+            );
+        }
+    }
 }
@@ -0,0 +1,160 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `scuttlebutt`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+//! A software implementation of AES-128 encryption, used in place of AES-NI
+//! on x86-64 processors without it. Other architectures are not supported, as
+//! round keys and blocks are still held in SSE registers.
+//!
+//! Both implementations compute the same function, so parties garbling and
+//! evaluating on different hardware produce identical ciphertexts. This one is
+//! constant time: rather than looking up the S-box in a table indexed by
+//! secret data, it computes it as an inversion in `GF(2^8)` followed by the
+//! affine map, using only branch-free arithmetic. The price is speed, as it is
+//! far slower than AES-NI.
+
+use crate::Block;
+use std::arch::x86_64::__m128i;
+
+/// Whether AES-NI is available. If the crate is compiled with the `aes` target
+/// feature enabled, this is known at compile time and the check disappears.
+#[inline(always)]
+pub(crate) fn aesni() -> bool {
+    cfg!(target_feature = "aes") || is_x86_feature_detected!("aes")
+}
+
+/// Multiply by `x` in `GF(2^8)`.
+#[inline(always)]
+fn xtime(a: u8) -> u8 {
+    (a << 1) ^ (0x1B & 0u8.wrapping_sub(a >> 7))
+}
+
+/// Multiply in `GF(2^8)`, without branching on either operand.
+#[inline(always)]
+fn gmul(mut a: u8, mut b: u8) -> u8 {
+    let mut p = 0;
+    for _ in 0..8 {
+        p ^= a & 0u8.wrapping_sub(b & 1);
+        a = xtime(a);
+        b >>= 1;
+    }
+    p
+}
+
+/// The AES S-box.
+#[inline(always)]
+fn sbox(x: u8) -> u8 {
+    // `x^254` is the inverse of `x`, or zero for zero.
+    let x2 = gmul(x, x);
+    let x3 = gmul(x2, x);
+    let x12 = gmul(gmul(x3, x3), gmul(x3, x3));
+    let x15 = gmul(x12, x3);
+    let x30 = gmul(x15, x15);
+    let x60 = gmul(x30, x30);
+    let x120 = gmul(x60, x60);
+    let x240 = gmul(x120, x120);
+    let b = gmul(gmul(x240, x12), x2);
+    b ^ b.rotate_left(1) ^ b.rotate_left(2) ^ b.rotate_left(3) ^ b.rotate_left(4) ^ 0x63
+}
+
+#[inline(always)]
+fn to_bytes(x: __m128i) -> [u8; 16] {
+    u128::from(Block(x)).to_le_bytes()
+}
+
+#[inline(always)]
+fn from_bytes(bytes: [u8; 16]) -> __m128i {
+    Block::from(u128::from_le_bytes(bytes)).0
+}
+
+/// One AES round, as computed by `_mm_aesenc_si128`, or by
+/// `_mm_aesenclast_si128` if `last` is set. Byte `i` of the state is in row
+/// `i % 4` and column `i / 4`.
+#[inline(always)]
+fn round(s: &mut [u8; 16], rkey: &[u8; 16], last: bool) {
+    // SubBytes and ShiftRows, which moves row `r` left by `r` columns.
+    let mut t = [0u8; 16];
+    for (i, x) in t.iter_mut().enumerate() {
+        let (r, c) = (i % 4, i / 4);
+        *x = sbox(s[r + 4 * ((c + r) % 4)]);
+    }
+    if !last {
+        // MixColumns.
+        for c in t.chunks_mut(4) {
+            let (a0, a1, a2, a3) = (c[0], c[1], c[2], c[3]);
+            let all = a0 ^ a1 ^ a2 ^ a3;
+            c[0] ^= all ^ xtime(a0 ^ a1);
+            c[1] ^= all ^ xtime(a1 ^ a2);
+            c[2] ^= all ^ xtime(a2 ^ a3);
+            c[3] ^= all ^ xtime(a3 ^ a0);
+        }
+    }
+    for ((x, y), k) in s.iter_mut().zip(t.iter()).zip(rkey.iter()) {
+        *x = y ^ k;
+    }
+}
+
+/// Encrypt `m` with the expanded round keys `rkeys`.
+pub(crate) fn encrypt(rkeys: &[__m128i; 11], m: Block) -> Block {
+    let mut s = u128::from(m).to_le_bytes();
+    for (x, k) in s.iter_mut().zip(to_bytes(rkeys[0]).iter()) {
+        *x ^= k;
+    }
+    for (i, &rkey) in rkeys.iter().enumerate().skip(1) {
+        round(&mut s, &to_bytes(rkey), i == 10);
+    }
+    Block::from(u128::from_le_bytes(s))
+}
+
+/// Expand `key` into round keys, as `_mm_aeskeygenassist_si128` does.
+pub(crate) fn expand(key: Block) -> [__m128i; 11] {
+    const RCON: [u8; 10] = [0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x1B, 0x36];
+    let mut keys = [key.0; 11];
+    let mut prev = u128::from(key).to_le_bytes();
+    for (k, &rcon) in keys.iter_mut().skip(1).zip(RCON.iter()) {
+        let mut next = [0u8; 16];
+        // RotWord, SubWord, and the round constant, applied to the last word.
+        let mut t = [prev[13], prev[14], prev[15], prev[12]];
+        for x in t.iter_mut() {
+            *x = sbox(*x);
+        }
+        t[0] ^= rcon;
+        for i in 0..16 {
+            next[i] = prev[i] ^ if i < 4 { t[i] } else { next[i - 4] };
+        }
+        *k = from_bytes(next);
+        prev = next;
+    }
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sbox() {
+        assert_eq!(sbox(0x00), 0x63);
+        assert_eq!(sbox(0x01), 0x7C);
+        assert_eq!(sbox(0x53), 0xED);
+        assert_eq!(sbox(0xFF), 0x16);
+    }
+
+    #[test]
+    fn test_fips_197() {
+        // The example of FIPS-197, Appendix B.
+        let key = Block::from(u128::from_le_bytes(
+            0x2B7E_1516_28AE_D2A6_ABF7_1588_09CF_4F3C_u128.to_be_bytes(),
+        ));
+        let pt = Block::from(u128::from_le_bytes(
+            0x3243_F6A8_885A_308D_3131_98A2_E037_0734_u128.to_be_bytes(),
+        ));
+        let ct = encrypt(&expand(key), pt);
+        assert_eq!(
+            u128::from(ct).to_le_bytes(),
+            0x3925_841D_02DC_09FB_DC11_8597_196A_0B32_u128.to_be_bytes()
+        );
+    }
+}
@@ -4,15 +4,53 @@
 // Copyright © 2019 Galois, Inc.
 // See LICENSE for licensing information.
 
+#[cfg(not(target_arch = "wasm32"))]
+mod bounded_channel;
+mod fault_channel;
 mod hash_channel;
+mod memory_channel;
+mod simulated_channel;
 mod sync_channel;
+#[cfg(feature = "tls")]
+mod tls_channel;
 mod track_channel;
+mod transcript_channel;
 #[cfg(unix)]
 mod unix_channel;
 
+#[cfg(not(target_arch = "wasm32"))]
+pub use bounded_channel::BoundedChannel;
+pub use fault_channel::{FaultChannel, FaultConfig};
 pub use hash_channel::HashChannel;
+pub use memory_channel::{
+    memory_channel_pair,
+    pipe,
+    track_memory_channel_pair,
+    MemoryChannel,
+    PipeReader,
+    PipeWriter,
+    TrackMemoryChannel,
+};
+pub use simulated_channel::{NetworkProfile, SimulatedChannel};
 pub use sync_channel::SyncChannel;
-pub use track_channel::TrackChannel;
+pub use track_channel::{ChannelStats, TrackChannel};
+pub use transcript_channel::{
+    Direction,
+    RecordingChannel,
+    ReplayChannel,
+    Transcript,
+    TranscriptEntry,
+};
+
+#[cfg(feature = "tls")]
+pub use tls_channel::{
+    pinned_client_config,
+    tls_client_channel,
+    tls_server_channel,
+    PinnedCertVerifier,
+    TlsChannel,
+    TlsStream,
+};
 
 #[cfg(unix)]
 pub use unix_channel::{track_unix_channel_pair, unix_channel_pair, TrackUnixChannel, UnixChannel};
@@ -0,0 +1,192 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `scuttlebutt`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+use crate::AbstractChannel;
+use std::{
+    io::{Error, ErrorKind, Read, Result, Write},
+    sync::{
+        mpsc::{channel, sync_channel, Sender, SyncSender},
+        Arc,
+        Mutex,
+    },
+};
+
+enum Message {
+    Data(Vec<u8>),
+    Flush(Sender<std::result::Result<(), String>>),
+}
+
+struct Outgoing {
+    buffer: Vec<u8>,
+    sender: SyncSender<Message>,
+}
+
+fn disconnected() -> Error {
+    Error::new(ErrorKind::BrokenPipe, "bounded channel writer thread exited")
+}
+
+/// A channel which hands written data to a dedicated writer thread through a
+/// bounded queue.
+///
+/// Writes are collected into chunks of `chunk_size` bytes, splitting any write
+/// larger than that, and at most `capacity` chunks may be pending at any one
+/// time. Once the queue is full, writers block until the network catches up,
+/// which bounds the memory used by pending messages to roughly
+/// `capacity * chunk_size` bytes no matter how much faster the producer (e.g.,
+/// a garbler) is than the network, or how large its messages are.
+pub struct BoundedChannel<R> {
+    reader: Arc<Mutex<R>>,
+    outgoing: Arc<Mutex<Outgoing>>,
+    chunk_size: usize,
+}
+
+impl<R: Read> BoundedChannel<R> {
+    /// Make a new `BoundedChannel` from a `reader` and a `writer`, spawning a
+    /// thread that owns `writer`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_size` is zero.
+    pub fn new<W: Write + Send + 'static>(
+        reader: R,
+        mut writer: W,
+        capacity: usize,
+        chunk_size: usize,
+    ) -> Self {
+        assert!(chunk_size > 0, "chunk size is zero");
+        let (sender, receiver) = sync_channel::<Message>(capacity);
+        std::thread::spawn(move || {
+            for message in receiver.iter() {
+                match message {
+                    Message::Data(bytes) => {
+                        if writer.write_all(&bytes).is_err() {
+                            break;
+                        }
+                    }
+                    Message::Flush(ack) => {
+                        let result = writer.flush().map_err(|e| e.to_string());
+                        let failed = result.is_err();
+                        let _ = ack.send(result);
+                        if failed {
+                            break;
+                        }
+                    }
+                }
+            }
+            let _ = writer.flush();
+        });
+        let outgoing = Outgoing {
+            buffer: Vec::with_capacity(chunk_size),
+            sender,
+        };
+        Self {
+            reader: Arc::new(Mutex::new(reader)),
+            outgoing: Arc::new(Mutex::new(outgoing)),
+            chunk_size,
+        }
+    }
+}
+
+impl<R: Read> AbstractChannel for BoundedChannel<R> {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        let mut out = self.outgoing.lock().unwrap();
+        let mut bytes = bytes;
+        while !bytes.is_empty() {
+            let n = std::cmp::min(self.chunk_size - out.buffer.len(), bytes.len());
+            out.buffer.extend_from_slice(&bytes[..n]);
+            bytes = &bytes[n..];
+            if out.buffer.len() == self.chunk_size {
+                let chunk = std::mem::replace(&mut out.buffer, Vec::with_capacity(self.chunk_size));
+                out.sender
+                    .send(Message::Data(chunk))
+                    .map_err(|_| disconnected())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn read_bytes(&mut self, bytes: &mut [u8]) -> Result<()> {
+        self.reader.lock().unwrap().read_exact(bytes)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let (ack, done) = channel();
+        {
+            let mut out = self.outgoing.lock().unwrap();
+            if !out.buffer.is_empty() {
+                let chunk = std::mem::replace(&mut out.buffer, Vec::with_capacity(self.chunk_size));
+                out.sender
+                    .send(Message::Data(chunk))
+                    .map_err(|_| disconnected())?;
+            }
+            out.sender
+                .send(Message::Flush(ack))
+                .map_err(|_| disconnected())?;
+        }
+        done.recv()
+            .map_err(|_| disconnected())?
+            .map_err(Error::other)
+    }
+
+    fn clone(&self) -> Self {
+        Self {
+            reader: self.reader.clone(),
+            outgoing: self.outgoing.clone(),
+            chunk_size: self.chunk_size,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{channel::pipe, AbstractChannel, Block};
+
+    #[test]
+    fn test() {
+        let (r0, w0) = pipe();
+        let (r1, w1) = pipe();
+        let mut sender = BoundedChannel::new(r0, w1, 4, 64);
+        let mut receiver = BoundedChannel::new(r1, w0, 4, 64);
+        let blocks = (0..1024).map(|_| rand::random::<Block>()).collect::<Vec<_>>();
+        let blocks_ = blocks.clone();
+        let handle = std::thread::spawn(move || {
+            for b in blocks_.iter() {
+                sender.write_block(b).unwrap();
+            }
+            sender.flush().unwrap();
+            assert!(sender.read_bool().unwrap());
+        });
+        assert_eq!(receiver.read_blocks(blocks.len()).unwrap(), blocks);
+        receiver.write_bool(true).unwrap();
+        receiver.flush().unwrap();
+        handle.join().unwrap();
+    }
+
+    /// A writer recording the largest single write it was given.
+    struct Largest(Arc<Mutex<usize>>);
+
+    impl Write for Largest {
+        fn write(&mut self, buf: &[u8]) -> Result<usize> {
+            let mut largest = self.0.lock().unwrap();
+            *largest = std::cmp::max(*largest, buf.len());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_large_write() {
+        let largest = Arc::new(Mutex::new(0));
+        let mut channel = BoundedChannel::new(std::io::empty(), Largest(largest.clone()), 2, 64);
+        channel.write_bytes(&vec![0u8; 1000]).unwrap();
+        channel.flush().unwrap();
+        assert_eq!(*largest.lock().unwrap(), 64);
+    }
+}
@@ -0,0 +1,213 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `scuttlebutt`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+use crate::{AbstractChannel, AesRng};
+use rand::Rng;
+use std::{
+    cell::{Cell, RefCell},
+    io::Result,
+    rc::Rc,
+};
+
+/// The probabilities with which faults are injected into each message.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FaultConfig {
+    /// Probability of flipping a random bit of a message.
+    pub flip: f64,
+    /// Probability of dropping a random non-empty suffix of a message.
+    pub truncate: f64,
+    /// Probability of sending a message twice.
+    pub duplicate: f64,
+    /// Probability of holding back a message until after the next one.
+    pub reorder: f64,
+}
+
+impl FaultConfig {
+    /// A configuration which never injects faults.
+    pub fn none() -> Self {
+        Self {
+            flip: 0.0,
+            truncate: 0.0,
+            duplicate: 0.0,
+            reorder: 0.0,
+        }
+    }
+
+    /// A configuration which injects every kind of fault with probability `p`.
+    pub fn uniform(p: f64) -> Self {
+        Self {
+            flip: p,
+            truncate: p,
+            duplicate: p,
+            reorder: p,
+        }
+    }
+
+    /// Apply the bit-flip, truncation, and duplication faults to `message`,
+    /// returning the number of faults injected. Reordering is left to the
+    /// caller, since it depends on the surrounding messages.
+    pub fn corrupt<R: Rng>(&self, rng: &mut R, message: &mut Vec<u8>) -> usize {
+        let mut nfaults = 0;
+        if message.is_empty() {
+            return nfaults;
+        }
+        if rng.gen_bool(self.flip) {
+            let i = rng.gen_range(0, message.len() * 8);
+            message[i / 8] ^= 1 << (i % 8);
+            nfaults += 1;
+        }
+        if rng.gen_bool(self.truncate) {
+            let n = rng.gen_range(0, message.len());
+            message.truncate(n);
+            nfaults += 1;
+        }
+        if rng.gen_bool(self.duplicate) {
+            message.extend_from_slice(&message.clone());
+            nfaults += 1;
+        }
+        nfaults
+    }
+}
+
+/// A channel decorator which injects faults into written data: each message,
+/// i.e., each call to `write_bytes`, may have a bit flipped, be truncated, be
+/// sent twice, or be swapped with the message after it, with the
+/// probabilities given by a `FaultConfig`.
+///
+/// This is useful for checking that a protocol fails safely under corruption,
+/// by wrapping the channel of the sending party. Clones share the random
+/// number generator and any held-back message, and the faults are
+/// reproducible given a seeded `AesRng`.
+pub struct FaultChannel<C> {
+    channel: C,
+    config: FaultConfig,
+    rng: Rc<RefCell<AesRng>>,
+    held: Rc<RefCell<Option<Vec<u8>>>>,
+    nfaults: Rc<Cell<usize>>,
+}
+
+impl<C: AbstractChannel> FaultChannel<C> {
+    /// Make a new `FaultChannel` wrapping `channel`.
+    pub fn new(channel: C, config: FaultConfig, rng: AesRng) -> Self {
+        Self {
+            channel,
+            config,
+            rng: Rc::new(RefCell::new(rng)),
+            held: Rc::new(RefCell::new(None)),
+            nfaults: Rc::new(Cell::new(0)),
+        }
+    }
+
+    /// The number of faults injected so far.
+    pub fn nfaults(&self) -> usize {
+        self.nfaults.get()
+    }
+
+    /// Extract the underlying channel. A held-back message is dropped.
+    pub fn into_inner(self) -> C {
+        self.channel
+    }
+}
+
+impl<C: AbstractChannel> AbstractChannel for FaultChannel<C> {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        let mut message = bytes.to_vec();
+        let mut rng = self.rng.borrow_mut();
+        let mut nfaults = self.config.corrupt(&mut *rng, &mut message);
+        let held = self.held.borrow_mut().take();
+        if held.is_none() && rng.gen_bool(self.config.reorder) {
+            *self.held.borrow_mut() = Some(message);
+            nfaults += 1;
+        } else {
+            self.channel.write_bytes(&message)?;
+            if let Some(held) = held {
+                self.channel.write_bytes(&held)?;
+            }
+        }
+        self.nfaults.set(self.nfaults.get() + nfaults);
+        Ok(())
+    }
+
+    fn read_bytes(&mut self, bytes: &mut [u8]) -> Result<()> {
+        self.channel.read_bytes(bytes)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        // A message cannot be held back across a round of communication, or
+        // the peer would block forever.
+        if let Some(held) = self.held.borrow_mut().take() {
+            self.channel.write_bytes(&held)?;
+        }
+        self.channel.flush()
+    }
+
+    fn clone(&self) -> Self {
+        Self {
+            channel: self.channel.clone(),
+            config: self.config,
+            rng: self.rng.clone(),
+            held: self.held.clone(),
+            nfaults: self.nfaults.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Channel;
+    use std::io::{empty, Cursor};
+
+    fn send(config: FaultConfig, messages: &[&[u8]]) -> (Vec<u8>, usize) {
+        let rng = AesRng::new();
+        let mut channel = FaultChannel::new(Channel::new(empty(), Vec::new()), config, rng);
+        for m in messages {
+            channel.write_bytes(m).unwrap();
+        }
+        channel.flush().unwrap();
+        let nfaults = channel.nfaults();
+        let written = channel.into_inner().writer().borrow().clone();
+        (written, nfaults)
+    }
+
+    #[test]
+    fn no_faults() {
+        let (written, nfaults) = send(FaultConfig::none(), &[b"hello", b"world"]);
+        assert_eq!(written, b"helloworld");
+        assert_eq!(nfaults, 0);
+    }
+
+    #[test]
+    fn every_fault() {
+        let mut config = FaultConfig::none();
+        config.duplicate = 1.0;
+        assert_eq!(send(config, &[b"ab", b"c"]), (b"ababcc".to_vec(), 2));
+
+        let mut config = FaultConfig::none();
+        config.reorder = 1.0;
+        // Only one message is held back at a time.
+        assert_eq!(send(config, &[b"a", b"b", b"c"]), (b"bac".to_vec(), 2));
+
+        let mut config = FaultConfig::none();
+        config.flip = 1.0;
+        let (written, nfaults) = send(config, &[&[0u8; 8]]);
+        assert_eq!(nfaults, 1);
+        assert_eq!(written.iter().map(|b| b.count_ones()).sum::<u32>(), 1);
+
+        let mut config = FaultConfig::none();
+        config.truncate = 1.0;
+        let (written, _) = send(config, &[&[0u8; 8]]);
+        assert!(written.len() < 8);
+
+        // The reader side is untouched.
+        let mut channel = FaultChannel::new(
+            Channel::new(Cursor::new(vec![1, 2, 3]), Vec::new()),
+            FaultConfig::uniform(1.0),
+            AesRng::new(),
+        );
+        assert_eq!(channel.read_vec(3).unwrap(), vec![1, 2, 3]);
+    }
+}
@@ -0,0 +1,127 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `scuttlebutt`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+use crate::{SyncChannel, TrackChannel};
+use std::{
+    collections::VecDeque,
+    io::{Read, Result, Write},
+    sync::{Arc, Condvar, Mutex},
+};
+
+struct Pipe {
+    state: Mutex<PipeState>,
+    ready: Condvar,
+}
+
+struct PipeState {
+    data: VecDeque<u8>,
+    closed: bool,
+}
+
+/// The reading end of an in-memory pipe. Reads block until the writing end
+/// has produced data, and return end-of-file once the writing end is dropped.
+pub struct PipeReader(Arc<Pipe>);
+
+/// The writing end of an in-memory pipe.
+pub struct PipeWriter(Arc<Pipe>);
+
+/// Create a unidirectional in-memory pipe.
+pub fn pipe() -> (PipeReader, PipeWriter) {
+    let pipe = Arc::new(Pipe {
+        state: Mutex::new(PipeState {
+            data: VecDeque::new(),
+            closed: false,
+        }),
+        ready: Condvar::new(),
+    });
+    (PipeReader(pipe.clone()), PipeWriter(pipe))
+}
+
+impl Read for PipeReader {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let mut state = self.0.state.lock().unwrap();
+        while state.data.is_empty() && !state.closed {
+            state = self.0.ready.wait(state).unwrap();
+        }
+        let n = std::cmp::min(buf.len(), state.data.len());
+        for (b, d) in buf.iter_mut().zip(state.data.drain(..n)) {
+            *b = d;
+        }
+        Ok(n)
+    }
+}
+
+impl Write for PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let mut state = self.0.state.lock().unwrap();
+        state.data.extend(buf.iter());
+        self.0.ready.notify_all();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for PipeWriter {
+    fn drop(&mut self) {
+        let mut state = self.0.state.lock().unwrap();
+        state.closed = true;
+        self.0.ready.notify_all();
+    }
+}
+
+/// A SyncChannel which uses in-memory pipes.
+pub type MemoryChannel = SyncChannel<PipeReader, PipeWriter>;
+
+/// A TrackChannel which uses in-memory pipes.
+pub type TrackMemoryChannel = TrackChannel<PipeReader, PipeWriter>;
+
+/// Convenience function to create a pair of MemoryChannels for local tests in
+/// `swanky`. Unlike `unix_channel_pair`, this does not touch the operating
+/// system's networking stack and works on every platform.
+pub fn memory_channel_pair() -> (MemoryChannel, MemoryChannel) {
+    let (r0, w0) = pipe();
+    let (r1, w1) = pipe();
+    (SyncChannel::new(r0, w1), SyncChannel::new(r1, w0))
+}
+
+/// Convenience function to create a pair of TrackMemoryChannels for local tests
+/// in `swanky`.
+pub fn track_memory_channel_pair() -> (TrackMemoryChannel, TrackMemoryChannel) {
+    let (r0, w0) = pipe();
+    let (r1, w1) = pipe();
+    (TrackChannel::new(r0, w1), TrackChannel::new(r1, w0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AbstractChannel, Block};
+
+    #[test]
+    fn test() {
+        let (mut sender, mut receiver) = memory_channel_pair();
+        let blocks = (0..128).map(|_| rand::random::<Block>()).collect::<Vec<_>>();
+        let blocks_ = blocks.clone();
+        let handle = std::thread::spawn(move || {
+            for b in blocks_.iter() {
+                sender.write_block(b).unwrap();
+            }
+            sender.flush().unwrap();
+            assert_eq!(sender.read_u64().unwrap(), 0xdead_beef);
+        });
+        let received = receiver.read_blocks(blocks.len()).unwrap();
+        assert_eq!(received, blocks);
+        receiver.write_u64(0xdead_beef).unwrap();
+        receiver.flush().unwrap();
+        handle.join().unwrap();
+    }
+}
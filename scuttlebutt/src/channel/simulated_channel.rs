@@ -0,0 +1,211 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `scuttlebutt`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+use crate::AbstractChannel;
+use rand::Rng;
+use std::{io::Result, time::Duration};
+
+/// The characteristics of a simulated network link.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NetworkProfile {
+    /// One-way latency added to every flush.
+    pub latency: Duration,
+    /// Maximum additional random delay added to every flush.
+    pub jitter: Duration,
+    /// Bandwidth cap in bytes per second, or `None` for unlimited bandwidth.
+    pub bandwidth: Option<u64>,
+}
+
+impl NetworkProfile {
+    /// A link without any latency, jitter, or bandwidth cap.
+    pub fn unlimited() -> Self {
+        Self {
+            latency: Duration::from_secs(0),
+            jitter: Duration::from_secs(0),
+            bandwidth: None,
+        }
+    }
+
+    /// A typical local area network: 0.1ms latency and 1 Gbit/s.
+    pub fn lan() -> Self {
+        Self {
+            latency: Duration::from_micros(100),
+            jitter: Duration::from_micros(10),
+            bandwidth: Some(125_000_000),
+        }
+    }
+
+    /// A typical wide area network: 50ms latency and 100 Mbit/s.
+    pub fn wan() -> Self {
+        Self {
+            latency: Duration::from_millis(50),
+            jitter: Duration::from_millis(5),
+            bandwidth: Some(12_500_000),
+        }
+    }
+
+    /// The time needed to push `nbytes` through the link.
+    fn transmission_time(&self, nbytes: usize) -> Duration {
+        match self.bandwidth {
+            Some(bw) if bw > 0 => {
+                Duration::from_nanos((nbytes as u128 * 1_000_000_000 / bw as u128) as u64)
+            }
+            _ => Duration::from_secs(0),
+        }
+    }
+}
+
+/// A channel decorator which simulates the latency, jitter, and bandwidth of a
+/// network link on top of an underlying channel, which is useful for
+/// benchmarking LAN and WAN performance in a single process.
+///
+/// Writes are delayed by the time needed to transmit them given the bandwidth
+/// cap, and each flush, which marks the end of a round of communication, is
+/// delayed by the latency plus a uniformly random jitter.
+pub struct SimulatedChannel<C> {
+    channel: C,
+    profile: NetworkProfile,
+}
+
+impl<C: AbstractChannel> SimulatedChannel<C> {
+    /// Make a new `SimulatedChannel` wrapping `channel`.
+    pub fn new(channel: C, profile: NetworkProfile) -> Self {
+        Self { channel, profile }
+    }
+
+    /// The network profile being simulated.
+    pub fn profile(&self) -> NetworkProfile {
+        self.profile
+    }
+
+    /// Extract the underlying channel.
+    pub fn into_inner(self) -> C {
+        self.channel
+    }
+}
+
+fn sleep(d: Duration) {
+    if d > Duration::from_secs(0) {
+        std::thread::sleep(d);
+    }
+}
+
+impl<C: AbstractChannel> AbstractChannel for SimulatedChannel<C> {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        sleep(self.profile.transmission_time(bytes.len()));
+        self.channel.write_bytes(bytes)
+    }
+
+    fn read_bytes(&mut self, bytes: &mut [u8]) -> Result<()> {
+        self.channel.read_bytes(bytes)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let jitter = self.profile.jitter.as_nanos() as u64;
+        let jitter = if jitter > 0 {
+            Duration::from_nanos(rand::thread_rng().gen_range(0, jitter))
+        } else {
+            Duration::from_secs(0)
+        };
+        sleep(self.profile.latency + jitter);
+        self.channel.flush()
+    }
+
+    fn clone(&self) -> Self {
+        Self {
+            channel: self.channel.clone(),
+            profile: self.profile,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{memory_channel_pair, track_memory_channel_pair};
+    use std::time::Instant;
+
+    // Generous slack for a loaded test machine.
+    const SLACK: Duration = Duration::from_secs(1);
+
+    fn profile(latency_ms: u64, jitter_ms: u64, bandwidth: Option<u64>) -> NetworkProfile {
+        NetworkProfile {
+            latency: Duration::from_millis(latency_ms),
+            jitter: Duration::from_millis(jitter_ms),
+            bandwidth,
+        }
+    }
+
+    #[test]
+    fn latency() {
+        let (sender, mut receiver) = memory_channel_pair();
+        let mut sender = SimulatedChannel::new(sender, profile(50, 0, None));
+        sender.write_u64(7).unwrap();
+        let start = Instant::now();
+        sender.flush().unwrap();
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(50), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_millis(50) + SLACK, "{:?}", elapsed);
+        assert_eq!(receiver.read_u64().unwrap(), 7);
+    }
+
+    #[test]
+    fn bandwidth() {
+        // 10,000 bytes at 100,000 bytes per second take 100ms.
+        let (sender, mut receiver) = memory_channel_pair();
+        let mut sender = SimulatedChannel::new(sender, profile(0, 0, Some(100_000)));
+        let bytes = vec![0xAB; 10_000];
+        let start = Instant::now();
+        sender.write_bytes(&bytes).unwrap();
+        sender.flush().unwrap();
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(100), "{:?}", elapsed);
+        assert!(
+            elapsed < Duration::from_millis(100) + SLACK,
+            "{:?}",
+            elapsed
+        );
+        assert_eq!(receiver.read_vec(bytes.len()).unwrap(), bytes);
+    }
+
+    #[test]
+    fn jitter_bounds() {
+        let (sender, _receiver) = memory_channel_pair();
+        let mut sender = SimulatedChannel::new(sender, profile(5, 10, None));
+        let n = 20;
+        let start = Instant::now();
+        for _ in 0..n {
+            let start = Instant::now();
+            sender.flush().unwrap();
+            let elapsed = start.elapsed();
+            assert!(elapsed >= Duration::from_millis(5), "{:?}", elapsed);
+            assert!(elapsed < Duration::from_millis(15) + SLACK, "{:?}", elapsed);
+        }
+        // The sum of 20 uniform jitters below 10ms is almost surely above 20ms.
+        let elapsed = start.elapsed();
+        assert!(
+            elapsed >= Duration::from_millis(5 * n + 20),
+            "{:?}",
+            elapsed
+        );
+    }
+
+    #[test]
+    fn unlimited_passes_through() {
+        let (sender, mut receiver) = track_memory_channel_pair();
+        let mut sender = SimulatedChannel::new(sender, NetworkProfile::unlimited());
+        assert_eq!(sender.profile(), NetworkProfile::unlimited());
+        let bytes = (0..=255).collect::<Vec<u8>>();
+        let start = Instant::now();
+        sender.write_bytes(&bytes).unwrap();
+        sender.flush().unwrap();
+        assert!(start.elapsed() < SLACK);
+        assert_eq!(receiver.read_vec(bytes.len()).unwrap(), bytes);
+        // Nothing but the bytes themselves reaches the underlying channel.
+        let written = sender.into_inner().kilobits_written();
+        assert_eq!(written, (8 * bytes.len()) as f64 / 1000.0);
+    }
+}
@@ -0,0 +1,273 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `scuttlebutt`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+use crate::SyncChannel;
+use rustls::{
+    Certificate,
+    ClientConfig,
+    ClientSession,
+    RootCertStore,
+    ServerCertVerified,
+    ServerCertVerifier,
+    ServerConfig,
+    ServerSession,
+    Session,
+    TLSError,
+};
+use sha2::{Digest, Sha256};
+use std::{
+    io::{BufWriter, Read, Result, Write},
+    net::TcpStream,
+    sync::{Arc, Mutex},
+};
+use webpki::DNSNameRef;
+
+/// A TLS stream shared between the reading and writing halves of a
+/// `TlsChannel`.
+///
+/// The halves share the TLS session and the socket, but a read only holds the
+/// session's lock while processing data, not while it blocks on the socket.
+/// So a write on one half proceeds while a read on the other waits for data,
+/// e.g., for the reply to that write. Each half must only be used from one
+/// thread at a time, which `SyncChannel` ensures.
+pub struct TlsStream<S: Session> {
+    session: Arc<Mutex<S>>,
+    socket: Arc<TcpStream>,
+}
+
+impl<S: Session> Clone for TlsStream<S> {
+    fn clone(&self) -> Self {
+        Self {
+            session: self.session.clone(),
+            socket: self.socket.clone(),
+        }
+    }
+}
+
+impl<S: Session> TlsStream<S> {
+    /// Send the TLS records the session has queued, e.g., handshake messages
+    /// or encrypted data.
+    fn write_tls(&self, session: &mut S) -> Result<()> {
+        while session.wants_write() {
+            session.write_tls(&mut &*self.socket)?;
+        }
+        Ok(())
+    }
+}
+
+impl<S: Session> Read for TlsStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let mut incoming = [0u8; 4096];
+        loop {
+            {
+                let mut session = self.session.lock().unwrap();
+                self.write_tls(&mut session)?;
+                let n = session.read(buf)?;
+                if n > 0 {
+                    return Ok(n);
+                }
+            }
+            // Block on the socket without holding the lock.
+            let n = (&*self.socket).read(&mut incoming)?;
+            if n == 0 {
+                return Ok(0);
+            }
+            let mut session = self.session.lock().unwrap();
+            let mut rd = &incoming[..n];
+            while !rd.is_empty() {
+                session.read_tls(&mut rd)?;
+                if let Err(e) = session.process_new_packets() {
+                    // Let the peer know, through the alert the session queued.
+                    let _ = self.write_tls(&mut session);
+                    return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, e));
+                }
+            }
+        }
+    }
+}
+
+impl<S: Session> Write for TlsStream<S> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let mut session = self.session.lock().unwrap();
+        let n = session.write(buf)?;
+        self.write_tls(&mut session)?;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        let mut session = self.session.lock().unwrap();
+        session.flush()?;
+        self.write_tls(&mut session)
+    }
+}
+
+/// A SyncChannel which runs over a TLS-secured TcpStream.
+pub type TlsChannel<S> = SyncChannel<TlsStream<S>, BufWriter<TlsStream<S>>>;
+
+fn tls_channel<S: Session>(session: S, stream: TcpStream) -> TlsChannel<S> {
+    let stream = TlsStream {
+        session: Arc::new(Mutex::new(session)),
+        socket: Arc::new(stream),
+    };
+    SyncChannel::new(stream.clone(), BufWriter::new(stream))
+}
+
+/// Create a client-side `TlsChannel` connected over `stream` to the server
+/// named `hostname`.
+pub fn tls_client_channel(
+    stream: TcpStream,
+    config: Arc<ClientConfig>,
+    hostname: &str,
+) -> Result<TlsChannel<ClientSession>> {
+    let name = DNSNameRef::try_from_ascii_str(hostname).map_err(|_| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("invalid DNS name: {}", hostname),
+        )
+    })?;
+    let session = ClientSession::new(&config, name);
+    Ok(tls_channel(session, stream))
+}
+
+/// Create a server-side `TlsChannel` over an accepted `stream`.
+pub fn tls_server_channel(
+    stream: TcpStream,
+    config: Arc<ServerConfig>,
+) -> TlsChannel<ServerSession> {
+    let session = ServerSession::new(&config);
+    tls_channel(session, stream)
+}
+
+/// A certificate verifier which accepts a server if and only if the SHA-256
+/// hash of its end-entity certificate is among a set of pinned hashes.
+///
+/// This is useful for deployments where both parties know each other's
+/// certificates ahead of time and there is no certificate authority to defer
+/// to.
+pub struct PinnedCertVerifier {
+    pins: Vec<[u8; 32]>,
+}
+
+impl PinnedCertVerifier {
+    /// Make a new `PinnedCertVerifier` accepting the certificates whose SHA-256
+    /// hashes are given in `pins`.
+    pub fn new(pins: &[[u8; 32]]) -> Self {
+        Self {
+            pins: pins.to_vec(),
+        }
+    }
+
+    /// Compute the SHA-256 hash of a DER-encoded certificate, as used for
+    /// pinning.
+    pub fn fingerprint(cert: &Certificate) -> [u8; 32] {
+        let mut h = [0u8; 32];
+        h.copy_from_slice(&Sha256::digest(&cert.0));
+        h
+    }
+}
+
+impl ServerCertVerifier for PinnedCertVerifier {
+    fn verify_server_cert(
+        &self,
+        _roots: &RootCertStore,
+        presented_certs: &[Certificate],
+        _dns_name: DNSNameRef,
+        _ocsp_response: &[u8],
+    ) -> std::result::Result<ServerCertVerified, TLSError> {
+        let cert = presented_certs
+            .first()
+            .ok_or(TLSError::NoCertificatesPresented)?;
+        let fingerprint = Self::fingerprint(cert);
+        if self.pins.contains(&fingerprint) {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            Err(TLSError::General(
+                "server certificate does not match any pinned certificate".to_string(),
+            ))
+        }
+    }
+}
+
+/// Make a `ClientConfig` which only accepts servers presenting one of the
+/// pinned certificates.
+pub fn pinned_client_config(pins: &[[u8; 32]]) -> ClientConfig {
+    let mut config = ClientConfig::new();
+    config
+        .dangerous()
+        .set_certificate_verifier(Arc::new(PinnedCertVerifier::new(pins)));
+    config
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AbstractChannel;
+    use rustls::{NoClientAuth, PrivateKey};
+    use std::{net::TcpListener, thread, time::Duration};
+
+    const CERT: &[u8] = include_bytes!("../../testdata/tls_cert.der");
+    const KEY: &[u8] = include_bytes!("../../testdata/tls_key.der");
+
+    #[test]
+    fn loopback() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let mut config = ServerConfig::new(NoClientAuth::new());
+            config
+                .set_single_cert(vec![Certificate(CERT.to_vec())], PrivateKey(KEY.to_vec()))
+                .unwrap();
+            let (stream, _) = listener.accept().unwrap();
+            let mut channel = tls_server_channel(stream, Arc::new(config));
+            for _ in 0..2 {
+                let x = channel.read_u64().unwrap();
+                channel.write_u64(x + 1).unwrap();
+                channel.flush().unwrap();
+            }
+        });
+
+        let pin = PinnedCertVerifier::fingerprint(&Certificate(CERT.to_vec()));
+        let config = Arc::new(pinned_client_config(&[pin]));
+        let stream = TcpStream::connect(addr).unwrap();
+        let mut channel = tls_client_channel(stream, config, "localhost").unwrap();
+        channel.write_u64(1).unwrap();
+        channel.flush().unwrap();
+        assert_eq!(channel.read_u64().unwrap(), 2);
+
+        // A read on one thread waiting for the reply to a write on a clone of
+        // the channel, on another thread.
+        let mut reader = channel.clone();
+        let reply = thread::spawn(move || reader.read_u64().unwrap());
+        thread::sleep(Duration::from_millis(50));
+        channel.write_u64(41).unwrap();
+        channel.flush().unwrap();
+        assert_eq!(reply.join().unwrap(), 42);
+        server.join().unwrap();
+
+        // A client pinning another certificate rejects the server.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let mut config = ServerConfig::new(NoClientAuth::new());
+            config
+                .set_single_cert(vec![Certificate(CERT.to_vec())], PrivateKey(KEY.to_vec()))
+                .unwrap();
+            let (stream, _) = listener.accept().unwrap();
+            let mut channel = tls_server_channel(stream, Arc::new(config));
+            assert!(channel.read_u64().is_err());
+        });
+        let config = Arc::new(pinned_client_config(&[[0; 32]]));
+        let stream = TcpStream::connect(addr).unwrap();
+        let mut channel = tls_client_channel(stream, config, "localhost").unwrap();
+        channel.write_u64(1).unwrap();
+        channel.flush().unwrap();
+        assert!(channel.read_u64().is_err());
+        server.join().unwrap();
+    }
+}
@@ -10,6 +10,54 @@ use std::{
     sync::{Arc, Mutex},
 };
 
+/// Communication statistics collected by a `TrackChannel`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChannelStats {
+    /// Number of bytes read from the channel.
+    pub bytes_read: usize,
+    /// Number of bytes written to the channel.
+    pub bytes_written: usize,
+    /// Number of communication rounds, counted as the number of times the
+    /// channel switched from writing to reading.
+    pub rounds: usize,
+}
+
+impl ChannelStats {
+    /// Names of the fields exported by `to_json` and `to_csv`, in order. These
+    /// names are stable: fields are never renamed or removed, and new fields
+    /// are only ever appended.
+    pub const FIELDS: &'static [&'static str] = &["bytes_read", "bytes_written", "rounds"];
+
+    fn values(&self) -> [usize; 3] {
+        [self.bytes_read, self.bytes_written, self.rounds]
+    }
+
+    /// Export the statistics as a JSON object with the fields in `FIELDS`.
+    pub fn to_json(&self) -> String {
+        let fields = Self::FIELDS
+            .iter()
+            .zip(self.values().iter())
+            .map(|(name, value)| format!("\"{}\":{}", name, value))
+            .collect::<Vec<_>>();
+        format!("{{{}}}", fields.join(","))
+    }
+
+    /// The CSV header matching `to_csv`.
+    pub fn csv_header() -> String {
+        Self::FIELDS.join(",")
+    }
+
+    /// Export the statistics as a single CSV row with the fields in `FIELDS`.
+    pub fn to_csv(&self) -> String {
+        self.values()
+            .iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
 /// A channel for tracking the number of bits read/written.
 pub struct TrackChannel<R, W>(Arc<Mutex<InternalTrackChannel<R, W>>>);
 
@@ -17,6 +65,8 @@ struct InternalTrackChannel<R, W> {
     channel: Channel<R, W>,
     nbits_read: usize,
     nbits_written: usize,
+    nrounds: usize,
+    last_was_write: bool,
 }
 
 impl<R: Read, W: Write> TrackChannel<R, W> {
@@ -27,6 +77,8 @@ impl<R: Read, W: Write> TrackChannel<R, W> {
             channel,
             nbits_read: 0,
             nbits_written: 0,
+            nrounds: 0,
+            last_was_write: false,
         };
         Self(Arc::new(Mutex::new(internal)))
     }
@@ -36,6 +88,18 @@ impl<R: Read, W: Write> TrackChannel<R, W> {
         let mut int = self.0.lock().unwrap();
         int.nbits_read = 0;
         int.nbits_written = 0;
+        int.nrounds = 0;
+        int.last_was_write = false;
+    }
+
+    /// Return the communication statistics collected so far.
+    pub fn stats(&self) -> ChannelStats {
+        let int = self.0.lock().unwrap();
+        ChannelStats {
+            bytes_read: int.nbits_read / 8,
+            bytes_written: int.nbits_written / 8,
+            rounds: int.nrounds + int.last_was_write as usize,
+        }
     }
 
     /// Return the number of kilobits written to the channel.
@@ -74,12 +138,17 @@ impl<R: Read, W: Write> AbstractChannel for TrackChannel<R, W> {
     fn write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
         let mut int = self.0.lock().unwrap();
         int.nbits_written += bytes.len() * 8;
+        int.last_was_write = true;
         int.channel.write_bytes(bytes)
     }
 
     fn read_bytes(&mut self, mut bytes: &mut [u8]) -> Result<()> {
         let mut int = self.0.lock().unwrap();
         int.nbits_read += bytes.len() * 8;
+        if int.last_was_write {
+            int.nrounds += 1;
+            int.last_was_write = false;
+        }
         int.channel.read_bytes(&mut bytes)
     }
 
@@ -91,3 +160,29 @@ impl<R: Read, W: Write> AbstractChannel for TrackChannel<R, W> {
         Self(self.0.clone())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{track_memory_channel_pair, AbstractChannel};
+
+    #[test]
+    fn stats() {
+        let (mut sender, mut receiver) = track_memory_channel_pair();
+        sender.write_u64(1).unwrap();
+        sender.flush().unwrap();
+        let _ = receiver.read_u64().unwrap();
+        receiver.write_u64(2).unwrap();
+        receiver.flush().unwrap();
+        let _ = sender.read_u64().unwrap();
+        sender.write_u64(3).unwrap();
+        sender.flush().unwrap();
+        let _ = receiver.read_u64().unwrap();
+        let stats = sender.stats();
+        assert_eq!(stats.bytes_written, 16);
+        assert_eq!(stats.bytes_read, 8);
+        assert_eq!(stats.rounds, 2);
+        assert_eq!(stats.to_json(), "{\"bytes_read\":8,\"bytes_written\":16,\"rounds\":2}");
+        assert_eq!(stats.to_csv(), "8,16,2");
+        assert_eq!(receiver.stats().rounds, 1);
+    }
+}
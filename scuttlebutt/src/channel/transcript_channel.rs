@@ -0,0 +1,318 @@
+// -*- mode: rust; -*-
+//
+// This file is part of `scuttlebutt`.
+// Copyright © 2019 Galois, Inc.
+// See LICENSE for licensing information.
+
+use crate::AbstractChannel;
+use std::{
+    io::{Error, ErrorKind, Read, Result, Write},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// The direction of a message in a `Transcript`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    /// Bytes read from the other party.
+    Read,
+    /// Bytes written to the other party.
+    Write,
+}
+
+/// A single message in a `Transcript`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TranscriptEntry {
+    /// Time elapsed between the creation of the recording channel and the
+    /// message.
+    pub elapsed: Duration,
+    /// Whether the message was read or written.
+    pub direction: Direction,
+    /// The contents of the message.
+    pub bytes: Vec<u8>,
+}
+
+/// The full byte transcript of one party's view of a protocol execution.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Transcript {
+    entries: Vec<TranscriptEntry>,
+}
+
+impl Transcript {
+    /// Make a new empty transcript.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The messages in the transcript, in order.
+    pub fn entries(&self) -> &[TranscriptEntry] {
+        &self.entries
+    }
+
+    /// All the bytes in the transcript going in direction `direction`.
+    pub fn bytes(&self, direction: Direction) -> Vec<u8> {
+        self.entries
+            .iter()
+            .filter(|e| e.direction == direction)
+            .flat_map(|e| e.bytes.iter().cloned())
+            .collect()
+    }
+
+    /// Serialize the transcript to `writer`.
+    ///
+    /// Each entry is stored as a direction byte, the elapsed time in
+    /// nanoseconds as a little-endian `u64`, the message length as a
+    /// little-endian `u64`, and the message itself.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+        for entry in self.entries.iter() {
+            let dir = match entry.direction {
+                Direction::Read => 0u8,
+                Direction::Write => 1u8,
+            };
+            writer.write_all(&[dir])?;
+            writer.write_all(&(entry.elapsed.as_nanos() as u64).to_le_bytes())?;
+            writer.write_all(&(entry.bytes.len() as u64).to_le_bytes())?;
+            writer.write_all(&entry.bytes)?;
+        }
+        writer.flush()
+    }
+
+    /// Deserialize a transcript produced by `write_to` from `reader`.
+    pub fn read_from<R: Read>(reader: &mut R) -> Result<Self> {
+        let mut entries = Vec::new();
+        loop {
+            let mut dir = [0u8; 1];
+            if reader.read(&mut dir)? == 0 {
+                break;
+            }
+            let direction = match dir[0] {
+                0 => Direction::Read,
+                1 => Direction::Write,
+                d => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!("invalid transcript direction {}", d),
+                    ));
+                }
+            };
+            let mut data = [0u8; 8];
+            reader.read_exact(&mut data)?;
+            let elapsed = Duration::from_nanos(u64::from_le_bytes(data));
+            reader.read_exact(&mut data)?;
+            // Don't trust the length enough to allocate it up front: read
+            // what is there, and check that all of it was.
+            let len = u64::from_le_bytes(data);
+            let mut bytes = Vec::new();
+            (&mut *reader).take(len).read_to_end(&mut bytes)?;
+            if bytes.len() as u64 != len {
+                return Err(Error::new(
+                    ErrorKind::UnexpectedEof,
+                    format!("transcript message of {} bytes is truncated", len),
+                ));
+            }
+            entries.push(TranscriptEntry {
+                elapsed,
+                direction,
+                bytes,
+            });
+        }
+        Ok(Self { entries })
+    }
+}
+
+/// A channel which records every message read from and written to an
+/// underlying channel, alongside a timestamp.
+pub struct RecordingChannel<C> {
+    channel: C,
+    start: Instant,
+    transcript: Arc<Mutex<Transcript>>,
+}
+
+impl<C: AbstractChannel> RecordingChannel<C> {
+    /// Make a new `RecordingChannel` wrapping `channel`.
+    pub fn new(channel: C) -> Self {
+        Self {
+            channel,
+            start: Instant::now(),
+            transcript: Arc::new(Mutex::new(Transcript::new())),
+        }
+    }
+
+    /// Return a copy of the transcript recorded so far.
+    pub fn transcript(&self) -> Transcript {
+        self.transcript.lock().unwrap().clone()
+    }
+
+    fn record(&self, direction: Direction, bytes: &[u8]) {
+        self.transcript
+            .lock()
+            .unwrap()
+            .entries
+            .push(TranscriptEntry {
+                elapsed: self.start.elapsed(),
+                direction,
+                bytes: bytes.to_vec(),
+            });
+    }
+}
+
+impl<C: AbstractChannel> AbstractChannel for RecordingChannel<C> {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        self.record(Direction::Write, bytes);
+        self.channel.write_bytes(bytes)
+    }
+
+    fn read_bytes(&mut self, bytes: &mut [u8]) -> Result<()> {
+        self.channel.read_bytes(bytes)?;
+        self.record(Direction::Read, bytes);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.channel.flush()
+    }
+
+    fn clone(&self) -> Self {
+        Self {
+            channel: self.channel.clone(),
+            start: self.start,
+            transcript: self.transcript.clone(),
+        }
+    }
+}
+
+struct ReplayState {
+    read: Vec<u8>,
+    read_pos: usize,
+    written: Vec<u8>,
+    write_pos: usize,
+}
+
+/// A channel which replays a recorded `Transcript` in place of the other party.
+///
+/// Reads return the bytes that were read during the recorded execution.
+/// Writes are checked against the bytes that were written during the recorded
+/// execution, and an `InvalidData` error reporting the offset of the first
+/// differing byte is returned if the replayed party diverges.
+pub struct ReplayChannel(Arc<Mutex<ReplayState>>);
+
+impl ReplayChannel {
+    /// Make a new `ReplayChannel` from a transcript.
+    pub fn new(transcript: &Transcript) -> Self {
+        let state = ReplayState {
+            read: transcript.bytes(Direction::Read),
+            read_pos: 0,
+            written: transcript.bytes(Direction::Write),
+            write_pos: 0,
+        };
+        Self(Arc::new(Mutex::new(state)))
+    }
+
+    /// Whether every message in the transcript has been replayed.
+    pub fn is_finished(&self) -> bool {
+        let state = self.0.lock().unwrap();
+        state.read_pos == state.read.len() && state.write_pos == state.written.len()
+    }
+}
+
+impl AbstractChannel for ReplayChannel {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<()> {
+        let mut state = self.0.lock().unwrap();
+        let start = state.write_pos;
+        for (i, b) in bytes.iter().enumerate() {
+            match state.written.get(start + i) {
+                Some(c) if c == b => {}
+                Some(_) => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!("transcript diverges at written byte {}", start + i),
+                    ));
+                }
+                None => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!("transcript ends before written byte {}", start + i),
+                    ));
+                }
+            }
+        }
+        state.write_pos += bytes.len();
+        Ok(())
+    }
+
+    fn read_bytes(&mut self, bytes: &mut [u8]) -> Result<()> {
+        let mut state = self.0.lock().unwrap();
+        let start = state.read_pos;
+        if start + bytes.len() > state.read.len() {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "transcript has no more bytes to read",
+            ));
+        }
+        bytes.copy_from_slice(&state.read[start..start + bytes.len()]);
+        state.read_pos += bytes.len();
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{memory_channel_pair, Block};
+
+    #[test]
+    fn record_and_replay() {
+        let (sender, receiver) = memory_channel_pair();
+        let blocks = (0..16).map(|_| rand::random::<Block>()).collect::<Vec<_>>();
+        let blocks_ = blocks.clone();
+        let handle = std::thread::spawn(move || {
+            let mut channel = RecordingChannel::new(sender);
+            for b in blocks_.iter() {
+                channel.write_block(b).unwrap();
+            }
+            channel.flush().unwrap();
+            let _ = channel.read_u64().unwrap();
+            channel.transcript()
+        });
+        let mut receiver = receiver;
+        let _ = receiver.read_blocks(blocks.len()).unwrap();
+        receiver.write_u64(42).unwrap();
+        receiver.flush().unwrap();
+        let transcript = handle.join().unwrap();
+
+        // Round-trip the transcript through its serialized form.
+        let mut bytes = Vec::new();
+        transcript.write_to(&mut bytes).unwrap();
+        let transcript = Transcript::read_from(&mut bytes.as_slice()).unwrap();
+
+        // Replaying the same behavior succeeds.
+        let mut replay = ReplayChannel::new(&transcript);
+        for b in blocks.iter() {
+            replay.write_block(b).unwrap();
+        }
+        assert_eq!(replay.read_u64().unwrap(), 42);
+        assert!(replay.is_finished());
+
+        // Diverging behavior is caught.
+        let mut replay = ReplayChannel::new(&transcript);
+        assert!(replay
+            .write_block(&(blocks[0] ^ Block::from(1u128)))
+            .is_err());
+
+        // A length beyond the end of the input is an error, not an
+        // allocation of that size.
+        let mut bytes = vec![1u8];
+        bytes.extend_from_slice(&0u64.to_le_bytes());
+        bytes.extend_from_slice(&u64::max_value().to_le_bytes());
+        bytes.extend_from_slice(&[0u8; 4]);
+        assert!(Transcript::read_from(&mut bytes.as_slice()).is_err());
+    }
+}
@@ -11,9 +11,13 @@
 //! receiver. It then receives `seed_` from the receiver and outputs `seed ⊕
 //! seed_`. Likewise, on input `seed`, the receiver gets `r`, sends `seed` to
 //! the sender, and then receives `seed_`, checking that `PRG(seed_) = r`.
+//!
+//! The `send_rng` and `receive_rng` functions wrap a single coin toss into a
+//! shared `AesRng`, which both parties can then use to jointly sample any
+//! number of random values (e.g., permutation seeds or challenges).
 
 use crate::{AbstractChannel, AesRng, Block};
-use rand_core::{RngCore, SeedableRng};
+use rand_core::{CryptoRng, RngCore, SeedableRng};
 
 /// Errors produced by the coin tossing protocol.
 #[derive(Debug)]
@@ -87,6 +91,34 @@ pub fn receive<C: AbstractChannel>(channel: &mut C, seeds: &[Block]) -> Result<V
     Ok(out)
 }
 
+/// Coin tossing sender, outputting an `AesRng` seeded with the shared coin.
+///
+/// The sender's contribution to the coin is sampled from `rng`.
+#[inline]
+pub fn send_rng<C: AbstractChannel, RNG: CryptoRng + RngCore>(
+    channel: &mut C,
+    rng: &mut RNG,
+) -> Result<AesRng, Error> {
+    let mut seed = Block::default();
+    rng.fill_bytes(seed.as_mut());
+    let out = send(channel, &[seed])?;
+    Ok(AesRng::from_seed(out[0]))
+}
+
+/// Coin tossing receiver, outputting an `AesRng` seeded with the shared coin.
+///
+/// The receiver's contribution to the coin is sampled from `rng`.
+#[inline]
+pub fn receive_rng<C: AbstractChannel, RNG: CryptoRng + RngCore>(
+    channel: &mut C,
+    rng: &mut RNG,
+) -> Result<AesRng, Error> {
+    let mut seed = Block::default();
+    rng.fill_bytes(seed.as_mut());
+    let out = receive(channel, &[seed])?;
+    Ok(AesRng::from_seed(out[0]))
+}
+
 #[cfg(test)]
 mod tests {
     #[cfg(feature = "nightly")]
@@ -117,4 +149,16 @@ mod tests {
         assert_eq!(output_[0], seed ^ seed_);
         handle.join().unwrap();
     }
+
+    #[test]
+    fn test_shared_rng() {
+        let (mut sender, mut receiver) = crate::memory_channel_pair();
+        let handle = std::thread::spawn(move || {
+            let mut rng = send_rng(&mut sender, &mut AesRng::new()).unwrap();
+            (0..16).map(|_| rng.next_u64()).collect::<Vec<u64>>()
+        });
+        let mut rng = receive_rng(&mut receiver, &mut AesRng::new()).unwrap();
+        let values = (0..16).map(|_| rng.next_u64()).collect::<Vec<u64>>();
+        assert_eq!(handle.join().unwrap(), values);
+    }
 }
@@ -32,7 +32,18 @@ pub use crate::{
     },
     block::Block,
     block512::Block512,
-    channel::{AbstractChannel, Channel, HashChannel, SyncChannel, TrackChannel},
+    channel::{
+        memory_channel_pair,
+        track_memory_channel_pair,
+        AbstractChannel,
+        Channel,
+        ChannelStats,
+        HashChannel,
+        MemoryChannel,
+        SyncChannel,
+        TrackChannel,
+        TrackMemoryChannel,
+    },
     hash_aes::{AesHash, AES_HASH},
     rand_aes::AesRng,
 };
@@ -45,6 +56,9 @@ pub use crate::channel::{
     UnixChannel,
 };
 
+#[cfg(feature = "tls")]
+pub use crate::channel::{tls_client_channel, tls_server_channel, TlsChannel};
+
 /// A marker trait denoting that the given scheme is semi-honest secure.
 pub trait SemiHonest {}
 /// A marker trait denoting that the given scheme is maliciously secure.
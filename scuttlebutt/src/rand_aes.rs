@@ -9,6 +9,7 @@
 use crate::{Aes128, Block};
 use rand::{CryptoRng, Error, Rng, RngCore, SeedableRng};
 use rand_core::block::{BlockRng, BlockRngCore};
+use sha2::{Digest, Sha256};
 
 /// Implementation of a random number generator based on fixed-key AES.
 ///
@@ -66,6 +67,31 @@ impl AesRng {
         let seed = self.gen::<Block>();
         AesRng::from_seed(seed)
     }
+
+    /// Create a new RNG from a 32-byte seed, which is compressed into a `Block`
+    /// seed using SHA-256. This makes it easy to reproduce a run from a seed
+    /// printed by a failing test.
+    #[inline]
+    pub fn from_seed_bytes(seed: [u8; 32]) -> Self {
+        let h = Sha256::digest(&seed);
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(&h[0..16]);
+        AesRng::from_seed(Block::from(bytes))
+    }
+
+    /// Deterministically derive the `index`th child stream of this RNG, without
+    /// advancing it. Unlike `fork`, the result only depends on the seed of this
+    /// RNG and `index`, so per-thread streams are reproducible regardless of the
+    /// order the threads are spawned in.
+    ///
+    /// The child seed is the encryption of `index` with the top bit set, which
+    /// never collides with the counter values used to produce this RNG's own
+    /// output.
+    #[inline]
+    pub fn stream(&self, index: u64) -> Self {
+        let seed = self.0.core.aes.encrypt(Block::from((1u128 << 127) | index as u128));
+        AesRng::from_seed(seed)
+    }
 }
 
 impl Default for AesRng {
@@ -157,4 +183,19 @@ mod tests {
         let b = rng.gen::<[Block; 8]>();
         assert_ne!(a, b);
     }
+
+    #[test]
+    fn test_reproducible() {
+        let seed = rand::random::<[u8; 32]>();
+        let mut rng1 = AesRng::from_seed_bytes(seed);
+        let mut rng2 = AesRng::from_seed_bytes(seed);
+        assert_eq!(rng1.gen::<[Block; 8]>(), rng2.gen::<[Block; 8]>());
+
+        let mut s1 = rng1.stream(3);
+        let mut s2 = rng2.stream(3);
+        assert_eq!(s1.gen::<Block>(), s2.gen::<Block>());
+        assert_ne!(rng1.stream(3).gen::<Block>(), rng1.stream(4).gen::<Block>());
+        // Deriving a stream does not advance the parent.
+        assert_eq!(rng1.gen::<Block>(), rng2.gen::<Block>());
+    }
 }